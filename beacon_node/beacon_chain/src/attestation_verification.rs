@@ -27,9 +27,7 @@
 //! ```
 
 use crate::{
-    beacon_chain::{
-        HEAD_LOCK_TIMEOUT, MAXIMUM_GOSSIP_CLOCK_DISPARITY, VALIDATOR_PUBKEY_CACHE_LOCK_TIMEOUT,
-    },
+    beacon_chain::{HEAD_LOCK_TIMEOUT, VALIDATOR_PUBKEY_CACHE_LOCK_TIMEOUT},
     metrics,
     observed_attestations::ObserveOutcome,
     observed_attesters::Error as ObservedAttestersError,
@@ -95,7 +93,14 @@ pub enum Error {
     /// ## Peer scoring
     ///
     /// The peer has sent an invalid message.
-    InvalidSelectionProof { aggregator_index: u64 },
+    InvalidSelectionProof {
+        aggregator_index: u64,
+        /// The length of the committee the aggregate is for.
+        committee_len: usize,
+        /// The modulo derived from `committee_len`, the selection proof hash must be a multiple of
+        /// this value to elect an aggregator.
+        modulo: u64,
+    },
     /// The `selection_proof` on the aggregate attestation selects it as a validator, however the
     /// aggregator index is not in the committee for that attestation.
     ///
@@ -138,6 +143,17 @@ pub enum Error {
     /// The attestation points to a block we have not yet imported. It's unclear if the attestation
     /// is valid or not.
     UnknownHeadBlock { beacon_block_root: Hash256 },
+    /// The `attestation.data.beacon_block_root` block is known, but it is not later than our
+    /// finalized slot so it is no longer tracked by fork choice.
+    ///
+    /// ## Peer scoring
+    ///
+    /// The attestation is for an old block. It's not necessarily invalid, however there is no
+    /// point searching for the block or propagating the attestation.
+    HeadBlockFinalized {
+        beacon_block_root: Hash256,
+        finalized_slot: Slot,
+    },
     /// The `attestation.data.slot` is not from the same epoch as `data.target.epoch`.
     ///
     /// ## Peer scoring
@@ -411,12 +427,16 @@ impl<T: BeaconChainTypes> VerifiedAggregatedAttestation<T> {
 
         // Ensure the valid aggregated attestation has not already been seen locally.
         let attestation_root = attestation.tree_hash_root();
-        if chain
+        if let Some(first_seen) = chain
             .observed_attestations
             .write()
-            .is_known(attestation, attestation_root)
+            .first_seen(attestation, attestation_root)
             .map_err(|e| Error::BeaconChainError(e.into()))?
         {
+            metrics::observe_duration(
+                &metrics::AGGREGATED_ATTESTATION_DUPLICATE_AGE,
+                first_seen.elapsed(),
+            );
             return Err(Error::AttestationAlreadyKnown(attestation_root));
         }
 
@@ -480,12 +500,16 @@ impl<T: BeaconChainTypes> VerifiedAggregatedAttestation<T> {
         //
         // It's important to double check that the attestation is not already known, otherwise two
         // attestations processed at the same time could be published.
-        if let ObserveOutcome::AlreadyKnown = chain
+        if let ObserveOutcome::AlreadyKnown { first_seen } = chain
             .observed_attestations
             .write()
             .observe_attestation(attestation, Some(attestation_root))
             .map_err(|e| Error::BeaconChainError(e.into()))?
         {
+            metrics::observe_duration(
+                &metrics::AGGREGATED_ATTESTATION_DUPLICATE_AGE,
+                first_seen.elapsed(),
+            );
             return Err(Error::AttestationAlreadyKnown(attestation_root));
         }
 
@@ -530,11 +554,19 @@ impl<T: BeaconChainTypes> VerifiedAggregatedAttestation<T> {
                 let selection_proof =
                     SelectionProof::from(signed_aggregate.message.selection_proof.clone());
 
+                let committee_len = committee.committee.len();
+                let modulo = SelectionProof::modulo(committee_len, &chain.spec)
+                    .map_err(|e| Error::BeaconChainError(e.into()))?;
+
                 if !selection_proof
-                    .is_aggregator(committee.committee.len(), &chain.spec)
+                    .is_aggregator_from_modulo(modulo)
                     .map_err(|e| Error::BeaconChainError(e.into()))?
                 {
-                    return Err(Error::InvalidSelectionProof { aggregator_index });
+                    return Err(Error::InvalidSelectionProof {
+                        aggregator_index,
+                        committee_len,
+                        modulo,
+                    });
                 }
 
                 // Ensure the aggregator is a member of the committee for which it is aggregating.
@@ -810,19 +842,34 @@ impl<T: BeaconChainTypes> VerifiedUnaggregatedAttestation<T> {
 /// 1. The block has never been verified by our application.
 /// 2. The block is prior to the latest finalized block.
 ///
-/// Case (1) is the exact thing we're trying to detect. However case (2) is a little different, but
-/// it's still fine to reject here because there's no need for us to handle attestations that are
-/// already finalized.
+/// Case (1) is the exact thing we're trying to detect and results in `Error::UnknownHeadBlock`.
+/// Case (2) is a little different, but it's still fine to reject here because there's no need for
+/// us to handle attestations that are already finalized. It results in
+/// `Error::HeadBlockFinalized`, so callers know there is no point searching for the block.
 fn verify_head_block_is_known<T: BeaconChainTypes>(
     chain: &BeaconChain<T>,
     attestation: &Attestation<T::EthSpec>,
     max_skip_slots: Option<u64>,
 ) -> Result<ProtoBlock, Error> {
-    if let Some(block) = chain
-        .fork_choice
-        .read()
-        .get_block(&attestation.data.beacon_block_root)
-    {
+    let beacon_block_root = attestation.data.beacon_block_root;
+
+    let (block_opt, unpruned_block_slot_opt, finalized_slot) = {
+        let fork_choice = chain.fork_choice.read();
+        (
+            fork_choice.get_block(&beacon_block_root),
+            // Blocks prior to finalization remain in the proto array until it is pruned.
+            fork_choice
+                .proto_array()
+                .get_block(&beacon_block_root)
+                .map(|block| block.slot),
+            fork_choice
+                .finalized_checkpoint()
+                .epoch
+                .start_slot(T::EthSpec::slots_per_epoch()),
+        )
+    };
+
+    if let Some(block) = block_opt {
         // Reject any block that exceeds our limit on skipped slots.
         if let Some(max_skip_slots) = max_skip_slots {
             if attestation.data.slot > block.slot + max_skip_slots {
@@ -835,16 +882,29 @@ fn verify_head_block_is_known<T: BeaconChainTypes>(
 
         Ok(block)
     } else {
-        Err(Error::UnknownHeadBlock {
-            beacon_block_root: attestation.data.beacon_block_root,
-        })
+        // Only use fork choice here, reading the block from the database would allow any peer to
+        // trigger a disk read with an unsigned attestation. If the block has been pruned from
+        // fork choice, the attestation slot is an upper bound on its slot.
+        let block_finalized = match unpruned_block_slot_opt {
+            Some(block_slot) => block_slot <= finalized_slot,
+            None => attestation.data.slot <= finalized_slot,
+        };
+
+        if block_finalized {
+            Err(Error::HeadBlockFinalized {
+                beacon_block_root,
+                finalized_slot,
+            })
+        } else {
+            Err(Error::UnknownHeadBlock { beacon_block_root })
+        }
     }
 }
 
 /// Verify that the `attestation` is within the acceptable gossip propagation range, with reference
 /// to the current slot of the `chain`.
 ///
-/// Accounts for `MAXIMUM_GOSSIP_CLOCK_DISPARITY`.
+/// Accounts for the `maximum_gossip_clock_disparity` of the `chain`.
 pub fn verify_propagation_slot_range<T: BeaconChainTypes>(
     chain: &BeaconChain<T>,
     attestation: &Attestation<T::EthSpec>,
@@ -853,7 +913,7 @@ pub fn verify_propagation_slot_range<T: BeaconChainTypes>(
 
     let latest_permissible_slot = chain
         .slot_clock
-        .now_with_future_tolerance(MAXIMUM_GOSSIP_CLOCK_DISPARITY)
+        .now_with_future_tolerance(chain.maximum_gossip_clock_disparity())
         .ok_or(BeaconChainError::UnableToReadSlot)?;
     if attestation_slot > latest_permissible_slot {
         return Err(Error::FutureSlot {
@@ -865,9 +925,9 @@ pub fn verify_propagation_slot_range<T: BeaconChainTypes>(
     // Taking advantage of saturating subtraction on `Slot`.
     let earliest_permissible_slot = chain
         .slot_clock
-        .now_with_past_tolerance(MAXIMUM_GOSSIP_CLOCK_DISPARITY)
+        .now_with_past_tolerance(chain.maximum_gossip_clock_disparity())
         .ok_or(BeaconChainError::UnableToReadSlot)?
-        - T::EthSpec::slots_per_epoch();
+        - chain.spec.attestation_propagation_slot_range;
     if attestation_slot < earliest_permissible_slot {
         return Err(Error::PastSlot {
             attestation_slot,
@@ -919,6 +979,70 @@ pub fn verify_attestation_signature<T: BeaconChainTypes>(
     }
 }
 
+/// Verifies the signatures of all the `indexed_attestations` using BLS batch verification.
+///
+/// # Returns
+///
+/// - `Ok(validity)`: where `validity[i]` is `true` if the signature of `indexed_attestations[i]`
+///   is valid. An attestation by a validator with an unknown public key is invalid.
+/// - `Err(e)`: if there was an error preventing signature verification.
+///
+/// ## Notes
+///
+/// If the batch fails to verify then each signature is verified individually to determine which
+/// are invalid, so this function is most efficient when invalid signatures are rare.
+pub fn verify_attestation_signatures_batch<T: BeaconChainTypes>(
+    chain: &BeaconChain<T>,
+    indexed_attestations: &[IndexedAttestation<T::EthSpec>],
+) -> Result<Vec<bool>, Error> {
+    if indexed_attestations.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let signature_setup_timer =
+        metrics::start_timer(&metrics::ATTESTATION_PROCESSING_SIGNATURE_SETUP_TIMES);
+
+    let pubkey_cache = chain
+        .validator_pubkey_cache
+        .try_read_for(VALIDATOR_PUBKEY_CACHE_LOCK_TIMEOUT)
+        .ok_or(BeaconChainError::ValidatorPubkeyCacheLockTimeout)?;
+
+    let fork = chain
+        .canonical_head
+        .try_read_for(HEAD_LOCK_TIMEOUT)
+        .ok_or(BeaconChainError::CanonicalHeadLockTimeout)
+        .map(|head| head.beacon_state.fork)?;
+
+    let signature_sets = indexed_attestations
+        .iter()
+        .map(|indexed_attestation| {
+            indexed_attestation_signature_set_from_pubkeys(
+                |validator_index| pubkey_cache.get(validator_index).map(Cow::Borrowed),
+                &indexed_attestation.signature,
+                &indexed_attestation,
+                &fork,
+                chain.genesis_validators_root,
+                &chain.spec,
+            )
+            .ok()
+        })
+        .collect::<Vec<_>>();
+
+    metrics::stop_timer(signature_setup_timer);
+
+    let _signature_verification_timer =
+        metrics::start_timer(&metrics::ATTESTATION_PROCESSING_SIGNATURE_TIMES);
+
+    if verify_signature_sets(signature_sets.iter().flatten()) {
+        Ok(signature_sets.iter().map(Option::is_some).collect())
+    } else {
+        Ok(signature_sets
+            .iter()
+            .map(|signature_set| signature_set.as_ref().map_or(false, |set| set.verify()))
+            .collect())
+    }
+}
+
 /// Verifies that the `attestation.data.target.root` is indeed the target root of the block at
 /// `attestation.data.beacon_block_root`.
 pub fn verify_attestation_target_root<T: EthSpec>(
@@ -1034,20 +1158,157 @@ pub fn verify_signed_aggregate_signatures<T: BeaconChainTypes>(
     Ok(verify_signature_sets(signature_sets.iter()))
 }
 
+/// Verifies the signatures of many `SignedAggregateAndProof` objects with a single BLS batch
+/// verification, rather than one batch per aggregate.
+///
+/// # Returns
+///
+/// - `Ok(validity)`: where `validity[i]` is `true` if all three signatures of
+///   `signed_aggregates[i]` are valid (see `verify_signed_aggregate_signatures`).
+/// - `Err(e)`: if there was an error preventing signature verification.
+///
+/// ## Notes
+///
+/// An aggregate whose signatures cannot be checked (e.g., an unknown aggregator or target root) is
+/// reported as invalid, rather than failing the entire batch.
+///
+/// If the batch fails to verify then each aggregate is verified individually to determine which
+/// are invalid, so this function is most efficient when invalid signatures are rare.
+pub fn verify_signed_aggregate_signatures_batch<T: BeaconChainTypes>(
+    chain: &BeaconChain<T>,
+    signed_aggregates: &[SignedAggregateAndProof<T::EthSpec>],
+) -> Result<Vec<bool>, Error> {
+    if signed_aggregates.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Resolve the committees before acquiring the pubkey cache lock, to avoid holding it whilst
+    // potentially reading a state from disk.
+    let indexed_attestations = signed_aggregates
+        .iter()
+        .map(|signed_aggregate| {
+            obtain_indexed_attestation_and_committees_per_slot(
+                chain,
+                &signed_aggregate.message.aggregate,
+            )
+            .ok()
+            .map(|(indexed_attestation, _)| indexed_attestation)
+        })
+        .collect::<Vec<_>>();
+
+    let signature_setup_timer =
+        metrics::start_timer(&metrics::ATTESTATION_PROCESSING_SIGNATURE_SETUP_TIMES);
+
+    let pubkey_cache = chain
+        .validator_pubkey_cache
+        .try_read_for(VALIDATOR_PUBKEY_CACHE_LOCK_TIMEOUT)
+        .ok_or(BeaconChainError::ValidatorPubkeyCacheLockTimeout)?;
+
+    let fork = chain
+        .canonical_head
+        .try_read_for(HEAD_LOCK_TIMEOUT)
+        .ok_or(BeaconChainError::CanonicalHeadLockTimeout)
+        .map(|head| head.beacon_state.fork)?;
+
+    let get_pubkey = |validator_index| pubkey_cache.get(validator_index).map(Cow::Borrowed);
+
+    let signature_sets = signed_aggregates
+        .iter()
+        .zip(indexed_attestations.iter())
+        .map(|(signed_aggregate, indexed_attestation)| {
+            let indexed_attestation = indexed_attestation.as_ref()?;
+
+            if signed_aggregate.message.aggregator_index >= pubkey_cache.len() as u64 {
+                return None;
+            }
+
+            Some(vec![
+                signed_aggregate_selection_proof_signature_set(
+                    get_pubkey,
+                    &signed_aggregate,
+                    &fork,
+                    chain.genesis_validators_root,
+                    &chain.spec,
+                )
+                .ok()?,
+                signed_aggregate_signature_set(
+                    get_pubkey,
+                    &signed_aggregate,
+                    &fork,
+                    chain.genesis_validators_root,
+                    &chain.spec,
+                )
+                .ok()?,
+                indexed_attestation_signature_set_from_pubkeys(
+                    get_pubkey,
+                    &indexed_attestation.signature,
+                    &indexed_attestation,
+                    &fork,
+                    chain.genesis_validators_root,
+                    &chain.spec,
+                )
+                .ok()?,
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    metrics::stop_timer(signature_setup_timer);
+
+    let _signature_verification_timer =
+        metrics::start_timer(&metrics::ATTESTATION_PROCESSING_SIGNATURE_TIMES);
+
+    let all_signature_sets = signature_sets
+        .iter()
+        .flatten()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    if verify_signature_sets(all_signature_sets.into_iter()) {
+        Ok(signature_sets.iter().map(Option::is_some).collect())
+    } else {
+        Ok(signature_sets
+            .iter()
+            .map(|sets| {
+                sets.as_ref()
+                    .map_or(false, |sets| verify_signature_sets(sets.iter()))
+            })
+            .collect())
+    }
+}
+
 /// Assists in readability.
 type CommitteesPerSlot = u64;
 
 /// Returns the `indexed_attestation` and committee count per slot for the `attestation` using the
 /// public keys cached in the `chain`.
+///
+/// The attesting indices are read from `chain.indexed_attestation_cache` where possible, to avoid
+/// resolving the committee for the same attestation more than once.
+///
+/// Entries are only inserted once the target block has been found in fork choice and the cache is
+/// cleared whenever fork choice is pruned, so a cache hit implies the target block is known.
 fn obtain_indexed_attestation_and_committees_per_slot<T: BeaconChainTypes>(
     chain: &BeaconChain<T>,
     attestation: &Attestation<T::EthSpec>,
 ) -> Result<(IndexedAttestation<T::EthSpec>, CommitteesPerSlot), Error> {
-    map_attestation_committee(chain, attestation, |(committee, committees_per_slot)| {
-        get_indexed_attestation(committee.committee, &attestation)
-            .map(|attestation| (attestation, committees_per_slot))
-            .map_err(Error::Invalid)
-    })
+    if let Some(cached) = chain.indexed_attestation_cache.read().get(attestation) {
+        return Ok(cached);
+    }
+
+    let (indexed_attestation, committees_per_slot) =
+        map_attestation_committee(chain, attestation, |(committee, committees_per_slot)| {
+            get_indexed_attestation(committee.committee, &attestation)
+                .map(|attestation| (attestation, committees_per_slot))
+                .map_err(Error::Invalid)
+        })?;
+
+    chain.indexed_attestation_cache.write().insert(
+        attestation,
+        &indexed_attestation,
+        committees_per_slot,
+    );
+
+    Ok((indexed_attestation, committees_per_slot))
 }
 
 /// Runs the `map_fn` with the committee and committee count per slot for the given `attestation`.
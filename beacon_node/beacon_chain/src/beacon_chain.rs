@@ -1,6 +1,6 @@
 use crate::attestation_verification::{
-    Error as AttestationError, SignatureVerifiedAttestation, VerifiedAggregatedAttestation,
-    VerifiedUnaggregatedAttestation,
+    verify_signed_aggregate_signatures_batch, Error as AttestationError,
+    SignatureVerifiedAttestation, VerifiedAggregatedAttestation, VerifiedUnaggregatedAttestation,
 };
 use crate::beacon_proposer_cache::BeaconProposerCache;
 use crate::block_verification::{
@@ -13,6 +13,7 @@ use crate::errors::{BeaconChainError as Error, BlockProductionError};
 use crate::eth1_chain::{Eth1Chain, Eth1ChainBackend};
 use crate::events::ServerSentEventHandler;
 use crate::head_tracker::HeadTracker;
+use crate::indexed_attestation_cache::IndexedAttestationCache;
 use crate::migrate::BackgroundMigrator;
 use crate::naive_aggregation_pool::{Error as NaiveAggregationError, NaiveAggregationPool};
 use crate::observed_attestations::{Error as AttestationObservationError, ObservedAttestations};
@@ -32,13 +33,16 @@ use crate::validator_pubkey_cache::ValidatorPubkeyCache;
 use crate::BeaconForkChoiceStore;
 use crate::BeaconSnapshot;
 use crate::{metrics, BeaconChainError};
-use eth2::types::{EventKind, SseBlock, SseFinalizedCheckpoint, SseHead};
-use fork_choice::ForkChoice;
+use eth2::types::{
+    EventKind, SseBlock, SseChainReorg, SseFinalizedCheckpoint, SseHead, SseJustifiedCheckpoint,
+};
+use fork_choice::{ForkChoice, ForkChoiceEvent};
 use futures::channel::mpsc::Sender;
 use itertools::process_results;
 use itertools::Itertools;
 use operation_pool::{OperationPool, PersistedOperationPool};
 use parking_lot::{Mutex, RwLock};
+use proto_array::Block as ProtoBlock;
 use slasher::Slasher;
 use slog::{crit, debug, error, info, trace, warn, Logger};
 use slot_clock::SlotClock;
@@ -110,7 +114,7 @@ pub enum ChainSegmentResult<T: EthSpec> {
     },
 }
 
-/// The accepted clock drift for nodes gossiping blocks and attestations. See:
+/// The default accepted clock drift for nodes gossiping blocks and attestations. See:
 ///
 /// https://github.com/ethereum/eth2.0-specs/blob/v0.12.1/specs/phase0/p2p-interface.md#configuration
 pub const MAXIMUM_GOSSIP_CLOCK_DISPARITY: Duration = Duration::from_millis(500);
@@ -221,7 +225,7 @@ pub struct BeaconChain<T: BeaconChainTypes> {
     ///
     /// This pool accepts `Attestation` objects that only have one aggregation bit set and provides
     /// a method to get an aggregated `Attestation` for some `AttestationData`.
-    pub naive_aggregation_pool: RwLock<NaiveAggregationPool<T::EthSpec>>,
+    pub naive_aggregation_pool: NaiveAggregationPool<T::EthSpec>,
     /// Contains a store of attestations which have been observed by the beacon chain.
     pub(crate) observed_attestations: RwLock<ObservedAttestations<T::EthSpec>>,
     /// Maintains a record of which validators have been seen to attest in recent epochs.
@@ -262,6 +266,9 @@ pub struct BeaconChain<T: BeaconChainTypes> {
     pub(crate) shuffling_cache: TimeoutRwLock<ShufflingCache>,
     /// Caches the beacon block proposer shuffling for a given epoch and shuffling key root.
     pub beacon_proposer_cache: Mutex<BeaconProposerCache>,
+    /// Caches the attesting indices of attestations, keyed by attestation data and aggregation
+    /// bits.
+    pub indexed_attestation_cache: RwLock<IndexedAttestationCache<T::EthSpec>>,
     /// Caches a map of `validator_index -> validator_pubkey`.
     pub(crate) validator_pubkey_cache: TimeoutRwLock<ValidatorPubkeyCache<T>>,
     /// A list of any hard-coded forks that have been disabled.
@@ -390,6 +397,12 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
             .map(|slot| slot.epoch(T::EthSpec::slots_per_epoch()))
     }
 
+    /// Returns the accepted clock drift for nodes gossiping blocks and attestations, as defined by
+    /// `self.config`.
+    pub fn maximum_gossip_clock_disparity(&self) -> Duration {
+        Duration::from_millis(self.config.maximum_gossip_clock_disparity_millis)
+    }
+
     /// Iterates across all `(block_root, slot)` pairs from the head of the chain (inclusive) to
     /// the earliest reachable ancestor (may or may not be genesis).
     ///
@@ -989,14 +1002,15 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         })
     }
 
-    /// Returns an aggregated `Attestation`, if any, that has a matching `attestation.data`.
+    /// Returns the aggregated `Attestation` with the most aggregation bits set, if any, that has a
+    /// matching `attestation.data`.
     ///
     /// The attestation will be obtained from `self.naive_aggregation_pool`.
     pub fn get_aggregated_attestation(
         &self,
         data: &AttestationData,
     ) -> Option<Attestation<T::EthSpec>> {
-        self.naive_aggregation_pool.read().get(data)
+        self.naive_aggregation_pool.get_best_aggregate(data)
     }
 
     /// Returns an aggregated `Attestation`, if any, that has a matching
@@ -1009,7 +1023,6 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         attestation_data_root: &Hash256,
     ) -> Option<Attestation<T::EthSpec>> {
         self.naive_aggregation_pool
-            .read()
             .get_by_slot_and_root(slot, attestation_data_root)
     }
 
@@ -1167,6 +1180,20 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         })
     }
 
+    /// Verifies the signatures of many `SignedAggregateAndProof` objects in a single BLS batch,
+    /// returning the validity of each aggregate.
+    ///
+    /// This only checks signatures, it does not perform the other gossip checks performed by
+    /// `Self::verify_aggregated_attestation_for_gossip`.
+    ///
+    /// See `attestation_verification::verify_signed_aggregate_signatures_batch` for details.
+    pub fn verify_aggregates_batch(
+        &self,
+        signed_aggregates: &[SignedAggregateAndProof<T::EthSpec>],
+    ) -> Result<Vec<bool>, AttestationError> {
+        verify_signed_aggregate_signatures_batch(self, signed_aggregates)
+    }
+
     /// Accepts some attestation-type object and attempts to verify it in the context of fork
     /// choice. If it is valid it is applied to `self.fork_choice`.
     ///
@@ -1202,7 +1229,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         let attestation = unaggregated_attestation.attestation();
 
-        match self.naive_aggregation_pool.write().insert(attestation) {
+        match self.naive_aggregation_pool.insert(attestation) {
             Ok(outcome) => trace!(
                 self.log,
                 "Stored unaggregated attestation";
@@ -1563,7 +1590,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
             // Import the blocks into the chain.
             for signature_verified_block in signature_verified_blocks {
-                match self.process_block(signature_verified_block) {
+                match self.process_block(signature_verified_block, timestamp_now()) {
                     Ok(_) => imported_blocks += 1,
                     Err(error) => {
                         return ChainSegmentResult::Failed {
@@ -1633,9 +1660,15 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
     ///
     /// Returns an `Err` if the given block was invalid, or an error was encountered during
     /// verification.
+    ///
+    /// ## Notes
+    ///
+    /// The `seen_timestamp` is the time at which the block was first received, which determines
+    /// whether the block was timely enough to receive the proposer boost.
     pub fn process_block<B: IntoFullyVerifiedBlock<T>>(
         &self,
         unverified_block: B,
+        seen_timestamp: Duration,
     ) -> Result<Hash256, BlockError<T::EthSpec>> {
         // Start the Prometheus timer.
         let _full_timer = metrics::start_timer(&metrics::BLOCK_PROCESSING_TIMES);
@@ -1649,7 +1682,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         // A small closure to group the verification and import errors.
         let import_block = |unverified_block: B| -> Result<Hash256, BlockError<T::EthSpec>> {
             let fully_verified = unverified_block.into_fully_verified_block(self)?;
-            self.import_block(fully_verified)
+            self.import_block(fully_verified, seen_timestamp)
         };
 
         // Verify and import the block.
@@ -1698,6 +1731,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
     fn import_block(
         &self,
         fully_verified_block: FullyVerifiedBlock<T>,
+        seen_timestamp: Duration,
     ) -> Result<Hash256, BlockError<T::EthSpec>> {
         let signed_block = fully_verified_block.block;
         let block_root = fully_verified_block.block_root;
@@ -1816,8 +1850,16 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         {
             let _fork_choice_block_timer =
                 metrics::start_timer(&metrics::FORK_CHOICE_PROCESS_BLOCK_TIMES);
+            let block_delay = get_block_delay_ms(seen_timestamp, block, &self.slot_clock);
             fork_choice
-                .on_block(current_slot, block, block_root, &state)
+                .on_block(
+                    current_slot,
+                    block,
+                    block_root,
+                    block_delay,
+                    &state,
+                    &self.spec,
+                )
                 .map_err(|e| BlockError::BeaconChainError(e.into()))?;
         }
 
@@ -1900,7 +1942,9 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
                 "error" => ?e,
             );
             match Self::load_fork_choice(self.store.clone())? {
-                Some(persisted_fork_choice) => {
+                Some(mut persisted_fork_choice) => {
+                    persisted_fork_choice
+                        .set_prune_retain_slots(self.config.fork_choice_prune_retain_slots);
                     *fork_choice = persisted_fork_choice;
                 }
                 None => {
@@ -1930,6 +1974,7 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         let parent_root = block.parent_root;
         let slot = block.slot;
+        let proposer_index = block.proposer_index;
 
         self.snapshot_cache
             .try_write_for(BLOCK_PROCESSING_CACHE_LOCK_TIMEOUT)
@@ -1962,6 +2007,8 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
                 event_handler.register(EventKind::Block(SseBlock {
                     slot,
                     block: block_root,
+                    proposer_index,
+                    execution_optimistic: false,
                 }));
             }
         }
@@ -2104,9 +2151,9 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         // are included in the operation pool.
         let unagg_import_timer =
             metrics::start_timer(&metrics::BLOCK_PRODUCTION_UNAGGREGATED_TIMES);
-        for attestation in self.naive_aggregation_pool.read().iter() {
+        for attestation in self.naive_aggregation_pool.iter() {
             if let Err(e) = self.op_pool.insert_attestation(
-                attestation.clone(),
+                attestation,
                 &state.fork,
                 state.genesis_validators_root,
                 &self.spec,
@@ -2217,7 +2264,24 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
     fn fork_choice_internal(&self) -> Result<(), Error> {
         // Determine the root of the block that is the head of the chain.
-        let beacon_block_root = self.fork_choice.write().get_head(self.slot()?)?;
+        let current_slot = self.slot()?;
+        let (beacon_block_root, fork_choice_events) = {
+            let mut fork_choice = self.fork_choice.write();
+            // Drain the events even if `get_head` fails, the checkpoints were still updated.
+            let beacon_block_root = fork_choice.get_head(current_slot);
+            (beacon_block_root, fork_choice.drain_events())
+        };
+
+        for event in fork_choice_events {
+            debug!(
+                self.log,
+                "Fork choice checkpoint updated on tick";
+                "event" => ?event,
+            );
+            self.register_fork_choice_event(event);
+        }
+
+        let beacon_block_root = beacon_block_root?;
 
         let current_head = self.head_info()?;
         let old_finalized_checkpoint = current_head.finalized_checkpoint;
@@ -2325,17 +2389,37 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
 
         let update_head_timer = metrics::start_timer(&metrics::UPDATE_HEAD_TIMES);
 
-        // These fields are used for server-sent events
-        let state_root = new_head.beacon_state_root();
         let head_slot = new_head.beacon_state.slot;
-        let target_epoch_start_slot = new_head
-            .beacon_state
-            .current_epoch()
-            .start_slot(T::EthSpec::slots_per_epoch());
-        let prev_target_epoch_start_slot = new_head
-            .beacon_state
-            .previous_epoch()
-            .start_slot(T::EthSpec::slots_per_epoch());
+
+        // Build the server-sent event before the new head is moved into the canonical head.
+        let head_event = self
+            .event_handler
+            .as_ref()
+            .filter(|event_handler| event_handler.has_head_subscribers())
+            .map(|_| {
+                Self::sse_head(
+                    &new_head.beacon_state,
+                    beacon_block_root,
+                    new_head.beacon_state_root(),
+                    is_epoch_transition,
+                )
+            });
+
+        // Build the re-org event whilst the previous head is still the canonical head.
+        let reorg_event = if is_reorg {
+            self.event_handler
+                .as_ref()
+                .filter(|event_handler| event_handler.has_reorg_subscribers())
+                .map(|_| {
+                    self.canonical_head
+                        .try_read_for(HEAD_LOCK_TIMEOUT)
+                        .ok_or(Error::CanonicalHeadLockTimeout)
+                        .map(|old_head| Self::sse_chain_reorg(&old_head, &new_head))
+                })
+                .transpose()?
+        } else {
+            None
+        };
 
         // Update the snapshot that stores the head of the chain at the time it received the
         // block.
@@ -2423,40 +2507,125 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         }
 
         // Register a server-sent event if necessary
-        if let Some(event_handler) = self.event_handler.as_ref() {
-            if event_handler.has_head_subscribers() {
-                if let Ok(Some(current_duty_dependent_root)) =
-                    self.block_root_at_slot(target_epoch_start_slot - 1, WhenSlotSkipped::Prev)
-                {
-                    if let Ok(Some(previous_duty_dependent_root)) = self
-                        .block_root_at_slot(prev_target_epoch_start_slot - 1, WhenSlotSkipped::Prev)
-                    {
-                        event_handler.register(EventKind::Head(SseHead {
-                            slot: head_slot,
-                            block: beacon_block_root,
-                            state: state_root,
-                            current_duty_dependent_root,
-                            previous_duty_dependent_root,
-                            epoch_transition: is_epoch_transition,
-                        }));
-                    } else {
-                        warn!(
-                            self.log,
-                            "Unable to find previous target root, cannot register head event"
-                        );
-                    }
-                } else {
-                    warn!(
-                        self.log,
-                        "Unable to find current target root, cannot register head event"
-                    );
-                }
+        if let (Some(event_handler), Some(head_event)) = (self.event_handler.as_ref(), head_event) {
+            match head_event {
+                Ok(head_event) => event_handler.register(EventKind::Head(head_event)),
+                Err(e) => warn!(
+                    self.log,
+                    "Unable to find dependent roots, cannot register head event";
+                    "error" => ?e
+                ),
             }
         }
 
+        if let (Some(event_handler), Some(reorg_event)) = (self.event_handler.as_ref(), reorg_event)
+        {
+            event_handler.register(EventKind::ChainReorg(reorg_event));
+        }
+
         Ok(())
     }
 
+    /// Returns the `SseHead` event describing the block with `head_block_root` as the head of the
+    /// chain.
+    ///
+    /// `epoch_transition` is `true` if the block is the first block of its epoch, i.e. its parent
+    /// is from an earlier epoch.
+    pub fn head_event(&self, head_block_root: Hash256) -> Result<SseHead, Error> {
+        let block = self
+            .get_block(&head_block_root)?
+            .ok_or(Error::MissingBeaconBlock(head_block_root))?;
+        let state_root = block.state_root();
+        let state = self
+            .get_state(&state_root, Some(block.slot()))?
+            .ok_or(Error::MissingBeaconState(state_root))?;
+
+        // The root which decided the current epoch's duties is the last block of a prior epoch.
+        // This is the parent block only if the head is the first block of its epoch.
+        let current_duty_dependent_root =
+            state.attester_shuffling_decision_root(head_block_root, RelativeEpoch::Next)?;
+        let epoch_transition = current_duty_dependent_root == block.parent_root();
+
+        Self::sse_head(&state, head_block_root, state_root, epoch_transition)
+    }
+
+    /// Builds the `SseHead` event for `head_state`, the post-state of the block with
+    /// `head_block_root`.
+    ///
+    /// The dependent roots are the roots of the last blocks of the epochs prior to the current
+    /// and previous epochs of `head_state`, respectively.
+    fn sse_head(
+        head_state: &BeaconState<T::EthSpec>,
+        head_block_root: Hash256,
+        head_state_root: Hash256,
+        epoch_transition: bool,
+    ) -> Result<SseHead, Error> {
+        Ok(SseHead {
+            slot: head_state.slot,
+            block: head_block_root,
+            state: head_state_root,
+            current_duty_dependent_root: head_state
+                .attester_shuffling_decision_root(head_block_root, RelativeEpoch::Next)?,
+            previous_duty_dependent_root: head_state
+                .attester_shuffling_decision_root(head_block_root, RelativeEpoch::Current)?,
+            epoch_transition,
+        })
+    }
+
+    /// Builds the `SseChainReorg` event for a re-org from `old_head` to `new_head`.
+    ///
+    /// The depth is the number of slots between `old_head` and the latest common ancestor of the
+    /// two heads. If the common ancestor is beyond the reach of the `block_roots` of either state,
+    /// the finalized checkpoint of `new_head` is assumed to be the common ancestor.
+    fn sse_chain_reorg(
+        old_head: &BeaconSnapshot<T::EthSpec>,
+        new_head: &BeaconSnapshot<T::EthSpec>,
+    ) -> SseChainReorg {
+        let old_slot = old_head.beacon_block.slot();
+        let new_slot = new_head.beacon_block.slot();
+
+        let block_root_at = |head: &BeaconSnapshot<T::EthSpec>, slot: Slot| {
+            if slot == head.beacon_block.slot() {
+                Some(head.beacon_block_root)
+            } else {
+                head.beacon_state.get_block_root(slot).ok().copied()
+            }
+        };
+
+        let common_ancestor_slot = (0..=std::cmp::min(old_slot, new_slot).as_u64())
+            .rev()
+            .map(Slot::new)
+            .map(|slot| {
+                (
+                    slot,
+                    block_root_at(old_head, slot),
+                    block_root_at(new_head, slot),
+                )
+            })
+            .take_while(|(_, old_root, new_root)| old_root.is_some() && new_root.is_some())
+            .find(|(_, old_root, new_root)| old_root == new_root)
+            .map(|(slot, _, _)| slot)
+            .unwrap_or_else(|| {
+                new_head
+                    .beacon_state
+                    .finalized_checkpoint
+                    .epoch
+                    .start_slot(T::EthSpec::slots_per_epoch())
+            });
+
+        SseChainReorg {
+            slot: new_slot,
+            depth: old_slot
+                .as_u64()
+                .saturating_sub(common_ancestor_slot.as_u64()),
+            old_head_block: old_head.beacon_block_root,
+            old_head_state: old_head.beacon_state_root(),
+            new_head_block: new_head.beacon_block_root,
+            new_head_state: new_head.beacon_state_root(),
+            epoch: new_slot.epoch(T::EthSpec::slots_per_epoch()),
+        }
+    }
+
     /// This function takes a configured weak subjectivity `Checkpoint` and the latest finalized `Checkpoint`.
     /// If the weak subjectivity checkpoint and finalized checkpoint share the same epoch, we compare
     /// roots. If we the weak subjectivity checkpoint is from an older epoch, we iterate back through
@@ -2517,7 +2686,8 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
     pub fn per_slot_task(&self) {
         trace!(self.log, "Running beacon chain per slot tasks");
         if let Some(slot) = self.slot_clock.now() {
-            self.naive_aggregation_pool.write().prune(slot);
+            self.naive_aggregation_pool.prune(slot);
+            self.observed_attestations.write().prune(slot);
         }
     }
 
@@ -2530,6 +2700,8 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         new_finalized_state_root: Hash256,
     ) -> Result<(), Error> {
         self.fork_choice.write().prune()?;
+        // Cached attesting indices may refer to target blocks which have just been pruned.
+        self.indexed_attestation_cache.write().clear();
         let new_finalized_checkpoint = head_state.finalized_checkpoint;
 
         self.observed_block_producers.write().prune(
@@ -2573,6 +2745,55 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         Ok(())
     }
 
+    /// Forwards a checkpoint update reported by fork choice to the server-sent event handler, if
+    /// anyone is listening for it.
+    fn register_fork_choice_event(&self, event: ForkChoiceEvent) {
+        let event_handler = match self.event_handler.as_ref() {
+            Some(event_handler) => event_handler,
+            None => return,
+        };
+
+        match event {
+            ForkChoiceEvent::JustifiedCheckpointUpdated { new, .. } => {
+                if event_handler.has_justified_subscribers() {
+                    event_handler.register(EventKind::JustifiedCheckpoint(
+                        SseJustifiedCheckpoint {
+                            block: new.root,
+                            epoch: new.epoch,
+                        },
+                    ));
+                }
+            }
+            ForkChoiceEvent::FinalizedCheckpointUpdated { new, .. } => {
+                if event_handler.has_finalized_subscribers() {
+                    let slot = new.epoch.start_slot(T::EthSpec::slots_per_epoch());
+                    match self.state_root_at_slot(slot) {
+                        Ok(Some(state)) => {
+                            event_handler.register(EventKind::FinalizedCheckpoint(
+                                SseFinalizedCheckpoint {
+                                    epoch: new.epoch,
+                                    block: new.root,
+                                    state,
+                                },
+                            ));
+                        }
+                        Ok(None) => warn!(
+                            self.log,
+                            "Unknown state root for finalized checkpoint event";
+                            "epoch" => new.epoch,
+                        ),
+                        Err(e) => warn!(
+                            self.log,
+                            "Failed to read state root for finalized checkpoint event";
+                            "epoch" => new.epoch,
+                            "error" => ?e,
+                        ),
+                    }
+                }
+            }
+        }
+    }
+
     /// Runs the `map_fn` with the committee cache for `shuffling_epoch` from the chain with head
     /// `head_block_root`. The `map_fn` will be supplied two values:
     ///
@@ -2610,22 +2831,8 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
     where
         F: Fn(&CommitteeCache, Hash256) -> Result<R, Error>,
     {
-        let head_block = self
-            .fork_choice
-            .read()
-            .get_block(&head_block_root)
-            .ok_or(Error::MissingBeaconBlock(head_block_root))?;
-
-        let shuffling_id = BlockShufflingIds {
-            current: head_block.current_epoch_shuffling_id.clone(),
-            next: head_block.next_epoch_shuffling_id.clone(),
-            block_root: head_block.root,
-        }
-        .id_for_epoch(shuffling_epoch)
-        .ok_or_else(|| Error::InvalidShufflingId {
-            shuffling_epoch,
-            head_block_epoch: head_block.slot.epoch(T::EthSpec::slots_per_epoch()),
-        })?;
+        let (head_block, shuffling_id) =
+            self.attestation_shuffling_id(head_block_root, shuffling_epoch)?;
 
         // Obtain the shuffling cache, timing how long we wait.
         let cache_wait_timer =
@@ -2747,6 +2954,64 @@ impl<T: BeaconChainTypes> BeaconChain<T> {
         }
     }
 
+    /// Returns the block with `head_block_root` from fork choice, along with the
+    /// `AttestationShufflingId` which identifies the committees for `shuffling_epoch` on the chain
+    /// of that block.
+    fn attestation_shuffling_id(
+        &self,
+        head_block_root: Hash256,
+        shuffling_epoch: Epoch,
+    ) -> Result<(ProtoBlock, AttestationShufflingId), Error> {
+        let head_block = self
+            .fork_choice
+            .read()
+            .get_block(&head_block_root)
+            .ok_or(Error::MissingBeaconBlock(head_block_root))?;
+
+        let shuffling_id = BlockShufflingIds {
+            current: head_block.current_epoch_shuffling_id.clone(),
+            next: head_block.next_epoch_shuffling_id.clone(),
+            block_root: head_block.root,
+        }
+        .id_for_epoch(shuffling_epoch)
+        .ok_or_else(|| Error::InvalidShufflingId {
+            shuffling_epoch,
+            head_block_epoch: head_block.slot.epoch(T::EthSpec::slots_per_epoch()),
+        })?;
+
+        Ok((head_block, shuffling_id))
+    }
+
+    /// Returns `true` if the committees for `shuffling_epoch` on the chain of `head_block_root`
+    /// are present in the `shuffling_cache`.
+    ///
+    /// When this returns `true`, verifying an attestation with a `target.root` of
+    /// `head_block_root` for `shuffling_epoch` will not need to read or advance a state.
+    pub fn shuffling_is_cached(
+        &self,
+        head_block_root: Hash256,
+        shuffling_epoch: Epoch,
+    ) -> Result<bool, Error> {
+        let (_, shuffling_id) = self.attestation_shuffling_id(head_block_root, shuffling_epoch)?;
+
+        Ok(self
+            .shuffling_cache
+            .try_read_for(ATTESTATION_CACHE_LOCK_TIMEOUT)
+            .ok_or(Error::AttestationCacheLockTimeout)?
+            .contains(&shuffling_id))
+    }
+
+    /// Ensures that the committees for `epoch` on the chain of the current head are present in the
+    /// `shuffling_cache`.
+    ///
+    /// This is intended to be called shortly before `epoch` starts, so that the first attestations
+    /// for `epoch` do not each need to load and advance a state to compute the shuffling. It is a
+    /// no-op if the committees are already cached.
+    pub fn prime_shuffling_cache(&self, epoch: Epoch) -> Result<(), Error> {
+        let head_block_root = self.head_info()?.block_root;
+        self.with_committee_cache(head_block_root, epoch, |_, _| Ok(()))
+    }
+
     /// Returns `true` if the given block root has not been processed.
     pub fn is_new_block_root(&self, beacon_block_root: &Hash256) -> Result<bool, Error> {
         Ok(!self
@@ -7,12 +7,13 @@
 use crate::{metrics, BeaconSnapshot};
 use fork_choice::ForkChoiceStore;
 use ssz_derive::{Decode, Encode};
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use store::{Error as StoreError, HotColdDB, ItemStore};
 use types::{
-    BeaconBlock, BeaconState, BeaconStateError, Checkpoint, EthSpec, Hash256, SignedBeaconBlock,
-    Slot,
+    BeaconBlock, BeaconState, BeaconStateError, Checkpoint, Epoch, EthSpec, Hash256,
+    SignedBeaconBlock, Slot,
 };
 
 #[derive(Debug)]
@@ -166,6 +167,11 @@ pub struct BeaconForkChoiceStore<E: EthSpec, Hot: ItemStore<E>, Cold: ItemStore<
     justified_checkpoint: Checkpoint,
     justified_balances: Vec<u64>,
     best_justified_checkpoint: Checkpoint,
+    /// This is not persisted to disk since it is reset at the start of every slot.
+    proposer_boost_root: Option<Hash256>,
+    /// The latest target epoch and block root voted for by each validator, only populated whilst
+    /// `ForkChoice` is tracking conflicting votes. This is not persisted to disk.
+    latest_votes: HashMap<u64, (Epoch, Hash256)>,
     _phantom: PhantomData<E>,
 }
 
@@ -175,7 +181,7 @@ where
     Hot: ItemStore<E>,
     Cold: ItemStore<E>,
 {
-    /// This implementation ignores the `store` and `slot_clock`.
+    /// This implementation ignores the `store`, `slot_clock` and `latest_votes`.
     fn eq(&self, other: &Self) -> bool {
         self.balances_cache == other.balances_cache
             && self.time == other.time
@@ -183,6 +189,7 @@ where
             && self.justified_checkpoint == other.justified_checkpoint
             && self.justified_balances == other.justified_balances
             && self.best_justified_checkpoint == other.best_justified_checkpoint
+            && self.proposer_boost_root == other.proposer_boost_root
     }
 }
 
@@ -228,6 +235,8 @@ where
             justified_balances: anchor_state.balances.clone().into(),
             finalized_checkpoint,
             best_justified_checkpoint: justified_checkpoint,
+            proposer_boost_root: None,
+            latest_votes: HashMap::new(),
             _phantom: PhantomData,
         }
     }
@@ -258,6 +267,8 @@ where
             justified_checkpoint: persisted.justified_checkpoint,
             justified_balances: persisted.justified_balances,
             best_justified_checkpoint: persisted.best_justified_checkpoint,
+            proposer_boost_root: None,
+            latest_votes: HashMap::new(),
             _phantom: PhantomData,
         })
     }
@@ -338,6 +349,37 @@ where
     fn set_best_justified_checkpoint(&mut self, checkpoint: Checkpoint) {
         self.best_justified_checkpoint = checkpoint
     }
+
+    fn proposer_boost_root(&self) -> Option<Hash256> {
+        self.proposer_boost_root
+    }
+
+    fn set_proposer_boost_root(&mut self, proposer_boost_root: Option<Hash256>) {
+        self.proposer_boost_root = proposer_boost_root
+    }
+
+    fn conflicting_vote(
+        &self,
+        validator_index: u64,
+        target_epoch: Epoch,
+        block_root: Hash256,
+    ) -> Option<Hash256> {
+        self.latest_votes
+            .get(&validator_index)
+            .filter(|(epoch, root)| *epoch == target_epoch && *root != block_root)
+            .map(|(_, root)| *root)
+    }
+
+    fn record_vote(&mut self, validator_index: u64, target_epoch: Epoch, block_root: Hash256) {
+        // Only the latest vote is retained, so a vote for an earlier epoch cannot be checked.
+        let latest_vote = self
+            .latest_votes
+            .entry(validator_index)
+            .or_insert((target_epoch, block_root));
+        if target_epoch > latest_vote.0 {
+            *latest_vote = (target_epoch, block_root);
+        }
+    }
 }
 
 /// A container which allows persisting the `BeaconForkChoiceStore` to the on-disk database.
@@ -18,6 +18,7 @@ use fork_choice::ForkChoice;
 use futures::channel::mpsc::Sender;
 use operation_pool::{OperationPool, PersistedOperationPool};
 use parking_lot::RwLock;
+use proto_array::DEFAULT_PRUNE_THRESHOLD;
 use slasher::Slasher;
 use slog::{crit, info, Logger};
 use slot_clock::{SlotClock, TestingSlotClock};
@@ -318,6 +319,7 @@ where
             genesis.beacon_block_root,
             &genesis.beacon_block.message,
             &genesis.beacon_state,
+            DEFAULT_PRUNE_THRESHOLD,
         )
         .map_err(|e| format!("Unable to build initialize ForkChoice: {:?}", e))?;
 
@@ -411,6 +413,7 @@ where
         let mut fork_choice = self
             .fork_choice
             .ok_or("Cannot build without fork choice.")?;
+        fork_choice.set_prune_retain_slots(self.chain_config.fork_choice_prune_retain_slots);
         let genesis_block_root = self
             .genesis_block_root
             .ok_or("Cannot build without a genesis block root")?;
@@ -531,6 +534,7 @@ where
             )),
             shuffling_cache: TimeoutRwLock::new(ShufflingCache::new()),
             beacon_proposer_cache: <_>::default(),
+            indexed_attestation_cache: <_>::default(),
             validator_pubkey_cache: TimeoutRwLock::new(validator_pubkey_cache),
             disabled_forks: self.disabled_forks,
             shutdown_sender: self
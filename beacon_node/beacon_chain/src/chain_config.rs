@@ -1,3 +1,4 @@
+use crate::beacon_chain::MAXIMUM_GOSSIP_CLOCK_DISPARITY;
 use serde_derive::{Deserialize, Serialize};
 use types::Checkpoint;
 
@@ -12,6 +13,17 @@ pub struct ChainConfig {
     ///
     /// If `None`, there is no weak subjectivity verification.
     pub weak_subjectivity_checkpoint: Option<Checkpoint>,
+    /// The accepted clock drift (in milliseconds) for nodes gossiping blocks and attestations.
+    ///
+    /// Defaults to `MAXIMUM_GOSSIP_CLOCK_DISPARITY`.
+    #[serde(default = "default_maximum_gossip_clock_disparity_millis")]
+    pub maximum_gossip_clock_disparity_millis: u64,
+    /// The number of slots before the finalized slot for which fork choice retains blocks when
+    /// pruning.
+    ///
+    /// Defaults to zero, which prunes all blocks prior to the finalized block.
+    #[serde(default)]
+    pub fork_choice_prune_retain_slots: u64,
 }
 
 impl Default for ChainConfig {
@@ -19,6 +31,12 @@ impl Default for ChainConfig {
         Self {
             import_max_skip_slots: None,
             weak_subjectivity_checkpoint: None,
+            maximum_gossip_clock_disparity_millis: default_maximum_gossip_clock_disparity_millis(),
+            fork_choice_prune_retain_slots: 0,
         }
     }
 }
+
+fn default_maximum_gossip_clock_disparity_millis() -> u64 {
+    MAXIMUM_GOSSIP_CLOCK_DISPARITY.as_millis() as u64
+}
@@ -1,4 +1,6 @@
-pub use eth2::types::{EventKind, SseBlock, SseFinalizedCheckpoint, SseHead};
+pub use eth2::types::{
+    EventKind, SseBlock, SseChainReorg, SseFinalizedCheckpoint, SseHead, SseJustifiedCheckpoint,
+};
 use slog::{trace, Logger};
 use tokio::sync::broadcast;
 use tokio::sync::broadcast::{error::SendError, Receiver, Sender};
@@ -10,8 +12,10 @@ pub struct ServerSentEventHandler<T: EthSpec> {
     attestation_tx: Sender<EventKind<T>>,
     block_tx: Sender<EventKind<T>>,
     finalized_tx: Sender<EventKind<T>>,
+    justified_tx: Sender<EventKind<T>>,
     head_tx: Sender<EventKind<T>>,
     exit_tx: Sender<EventKind<T>>,
+    chain_reorg_tx: Sender<EventKind<T>>,
     log: Logger,
 }
 
@@ -20,15 +24,19 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
         let (attestation_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
         let (block_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
         let (finalized_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        let (justified_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
         let (head_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
         let (exit_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
+        let (chain_reorg_tx, _) = broadcast::channel(DEFAULT_CHANNEL_CAPACITY);
 
         Self {
             attestation_tx,
             block_tx,
             finalized_tx,
+            justified_tx,
             head_tx,
             exit_tx,
+            chain_reorg_tx,
             log,
         }
     }
@@ -37,15 +45,19 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
         let (attestation_tx, _) = broadcast::channel(capacity);
         let (block_tx, _) = broadcast::channel(capacity);
         let (finalized_tx, _) = broadcast::channel(capacity);
+        let (justified_tx, _) = broadcast::channel(capacity);
         let (head_tx, _) = broadcast::channel(capacity);
         let (exit_tx, _) = broadcast::channel(capacity);
+        let (chain_reorg_tx, _) = broadcast::channel(capacity);
 
         Self {
             attestation_tx,
             block_tx,
             finalized_tx,
+            justified_tx,
             head_tx,
             exit_tx,
+            chain_reorg_tx,
             log,
         }
     }
@@ -61,10 +73,15 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
             EventKind::FinalizedCheckpoint(checkpoint) => self.finalized_tx
                 .send(EventKind::FinalizedCheckpoint(checkpoint))
                 .map(|count| trace!(self.log, "Registering server-sent finalized checkpoint event"; "receiver_count" => count)),
+            EventKind::JustifiedCheckpoint(checkpoint) => self.justified_tx
+                .send(EventKind::JustifiedCheckpoint(checkpoint))
+                .map(|count| trace!(self.log, "Registering server-sent justified checkpoint event"; "receiver_count" => count)),
             EventKind::Head(head) => self.head_tx.send(EventKind::Head(head))
                 .map(|count| trace!(self.log, "Registering server-sent head event"; "receiver_count" => count)),
             EventKind::VoluntaryExit(exit) => self.exit_tx.send(EventKind::VoluntaryExit(exit))
                 .map(|count| trace!(self.log, "Registering server-sent voluntary exit event"; "receiver_count" => count)),
+            EventKind::ChainReorg(reorg) => self.chain_reorg_tx.send(EventKind::ChainReorg(reorg))
+                .map(|count| trace!(self.log, "Registering server-sent chain reorg event"; "receiver_count" => count)),
         };
         if let Err(SendError(event)) = result {
             trace!(self.log, "No receivers registered to listen for event"; "event" => ?event);
@@ -83,6 +100,10 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
         self.finalized_tx.subscribe()
     }
 
+    pub fn subscribe_justified(&self) -> Receiver<EventKind<T>> {
+        self.justified_tx.subscribe()
+    }
+
     pub fn subscribe_head(&self) -> Receiver<EventKind<T>> {
         self.head_tx.subscribe()
     }
@@ -91,6 +112,10 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
         self.exit_tx.subscribe()
     }
 
+    pub fn subscribe_reorgs(&self) -> Receiver<EventKind<T>> {
+        self.chain_reorg_tx.subscribe()
+    }
+
     pub fn has_attestation_subscribers(&self) -> bool {
         self.attestation_tx.receiver_count() > 0
     }
@@ -103,6 +128,10 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
         self.finalized_tx.receiver_count() > 0
     }
 
+    pub fn has_justified_subscribers(&self) -> bool {
+        self.justified_tx.receiver_count() > 0
+    }
+
     pub fn has_head_subscribers(&self) -> bool {
         self.head_tx.receiver_count() > 0
     }
@@ -110,4 +139,8 @@ impl<T: EthSpec> ServerSentEventHandler<T> {
     pub fn has_exit_subscribers(&self) -> bool {
         self.exit_tx.receiver_count() > 0
     }
+
+    pub fn has_reorg_subscribers(&self) -> bool {
+        self.chain_reorg_tx.receiver_count() > 0
+    }
 }
@@ -0,0 +1,127 @@
+//! The `IndexedAttestationCache` memoizes the attesting indices of attestations, so that the
+//! committee for an attestation need not be resolved more than once.
+//!
+//! This cache is keyed by `(data_root, aggregation_bits_root)`, the tree hash roots of the
+//! `AttestationData` and aggregation bits of an attestation. The `AttestationData` includes the
+//! target root, slot and committee index, which are sufficient to determine the committee. The
+//! aggregation bits then determine which members of that committee are attesting.
+//!
+//! The signature is *not* part of the key, so only the attesting indices are stored. An
+//! `IndexedAttestation` is built from the cached indices and the signature of the attestation
+//! provided at lookup time.
+//!
+//! Lookups do not modify the cache, so they may be performed concurrently under a read lock. As a
+//! consequence, entries are evicted in the order they were inserted rather than by recency of use.
+//!
+//! Entries are only inserted once the target block is known to fork choice, so the cache must be
+//! cleared whenever fork choice is pruned.
+
+use crate::metrics;
+use lru::LruCache;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tree_hash::TreeHash;
+use types::{Attestation, EthSpec, Hash256, IndexedAttestation, VariableList};
+
+/// The number of sets of attesting indices that should be cached.
+///
+/// With 128 validators per committee, each entry is around 1 KB, so this cache should be
+/// approximately 1 MB.
+const CACHE_SIZE: usize = 1_024;
+
+/// The key used to look up attesting indices: `(data_root, aggregation_bits_root)`.
+type CacheKey = (Hash256, Hash256);
+
+/// The attesting indices for some attestation, alongside the committee count per slot at the
+/// attestation's epoch.
+#[derive(Clone)]
+struct CacheItem<T: EthSpec> {
+    attesting_indices: VariableList<u64, T::MaxValidatorsPerCommittee>,
+    committees_per_slot: u64,
+}
+
+/// A cache to store the attesting indices of attestations.
+///
+/// See the module-level documentation for more information.
+pub struct IndexedAttestationCache<T: EthSpec> {
+    cache: LruCache<CacheKey, CacheItem<T>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<T: EthSpec> Default for IndexedAttestationCache<T> {
+    fn default() -> Self {
+        Self {
+            cache: LruCache::new(CACHE_SIZE),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T: EthSpec> IndexedAttestationCache<T> {
+    /// Returns the key under which the attesting indices of `attestation` are stored.
+    fn key(attestation: &Attestation<T>) -> CacheKey {
+        (
+            attestation.data.tree_hash_root(),
+            attestation.aggregation_bits.tree_hash_root(),
+        )
+    }
+
+    /// If it is cached, returns the `IndexedAttestation` and committee count per slot for
+    /// `attestation`.
+    pub fn get(&self, attestation: &Attestation<T>) -> Option<(IndexedAttestation<T>, u64)> {
+        let item = self.cache.peek(&Self::key(attestation)).cloned();
+
+        if item.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            metrics::inc_counter(&metrics::INDEXED_ATTESTATION_CACHE_HITS);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            metrics::inc_counter(&metrics::INDEXED_ATTESTATION_CACHE_MISSES);
+        }
+
+        item.map(|item| {
+            let indexed_attestation = IndexedAttestation {
+                attesting_indices: item.attesting_indices,
+                data: attestation.data.clone(),
+                signature: attestation.signature.clone(),
+            };
+            (indexed_attestation, item.committees_per_slot)
+        })
+    }
+
+    /// Store the attesting indices of `indexed_attestation`, which is the indexed form of
+    /// `attestation`.
+    pub fn insert(
+        &mut self,
+        attestation: &Attestation<T>,
+        indexed_attestation: &IndexedAttestation<T>,
+        committees_per_slot: u64,
+    ) {
+        let key = Self::key(attestation);
+        if !self.cache.contains(&key) {
+            self.cache.put(
+                key,
+                CacheItem {
+                    attesting_indices: indexed_attestation.attesting_indices.clone(),
+                    committees_per_slot,
+                },
+            );
+        }
+    }
+
+    /// Remove all entries, e.g. after blocks have been pruned from fork choice.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Returns the number of lookups which have been fulfilled by the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of lookups which have not been fulfilled by the cache.
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
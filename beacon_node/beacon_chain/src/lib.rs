@@ -11,6 +11,7 @@ mod errors;
 pub mod eth1_chain;
 pub mod events;
 mod head_tracker;
+mod indexed_attestation_cache;
 mod metrics;
 pub mod migrate;
 mod naive_aggregation_pool;
@@ -132,6 +132,10 @@ lazy_static! {
         "beacon_aggregated_attestation_gossip_verification_seconds",
         "Full runtime of aggregated attestation gossip verification"
     );
+    pub static ref AGGREGATED_ATTESTATION_DUPLICATE_AGE: Result<Histogram> = try_create_histogram(
+        "beacon_aggregated_attestation_duplicate_age_seconds",
+        "Time since a duplicate aggregated attestation was first observed"
+    );
 
     /*
      * General Attestation Processing
@@ -164,6 +168,34 @@ lazy_static! {
         "beacon_attestation_processing_agg_pool_create_map",
         "Time spent for creating a map for a new slot"
     );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_NUM_ATTESTATIONS: Result<IntGauge> = try_create_int_gauge(
+        "beacon_attestation_processing_agg_pool_num_attestations",
+        "Total number of attestations stored in the agg pool"
+    );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_NUM_SLOTS: Result<IntGauge> = try_create_int_gauge(
+        "beacon_attestation_processing_agg_pool_num_slots",
+        "Number of slots retained in the agg pool"
+    );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_MAX_ATTESTATIONS_REACHED_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_attestation_processing_agg_pool_max_attestations_reached_total",
+        "Count of attestations dropped by the agg pool due to reaching the max attestations per slot"
+    );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_CAPACITY: Result<IntGauge> = try_create_int_gauge(
+        "beacon_attestation_processing_agg_pool_capacity",
+        "Total number of attestations the agg pool can store without re-allocating"
+    );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_INITIAL_CAPACITY: Result<IntGauge> = try_create_int_gauge(
+        "beacon_attestation_processing_agg_pool_initial_capacity",
+        "Estimated initial capacity of the most recently created agg pool slot"
+    );
+    pub static ref ATTESTATION_PROCESSING_AGG_POOL_MAP_REALLOCATIONS_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_attestation_processing_agg_pool_map_reallocations_total",
+        "Count of times a slot in the agg pool grew beyond its initial capacity"
+    );
+    pub static ref OBSERVED_ATTESTATIONS_NUM_RETAINED: Result<IntGauge> = try_create_int_gauge(
+        "beacon_observed_attestations_num_retained",
+        "Number of aggregated attestation roots retained for duplicate detection"
+    );
     pub static ref ATTESTATION_PROCESSING_APPLY_TO_OP_POOL: Result<Histogram> = try_create_histogram(
         "beacon_attestation_processing_apply_to_op_pool",
         "Time spent applying an attestation to the block inclusion pool"
@@ -205,6 +237,14 @@ lazy_static! {
     pub static ref SHUFFLING_CACHE_MISSES: Result<IntCounter> =
         try_create_int_counter("beacon_shuffling_cache_misses_total", "Count of times shuffling cache fulfils request");
 
+    /*
+     * Indexed attestation cache
+     */
+    pub static ref INDEXED_ATTESTATION_CACHE_HITS: Result<IntCounter> =
+        try_create_int_counter("beacon_indexed_attestation_cache_hits_total", "Count of times indexed attestation cache fulfils request");
+    pub static ref INDEXED_ATTESTATION_CACHE_MISSES: Result<IntCounter> =
+        try_create_int_counter("beacon_indexed_attestation_cache_misses_total", "Count of times indexed attestation cache does not fulfil request");
+
     /*
      * Attestation Production
      */
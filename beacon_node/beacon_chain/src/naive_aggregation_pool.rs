@@ -1,20 +1,21 @@
 use crate::metrics;
+use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::sync::Arc;
 use tree_hash::TreeHash;
 use types::{Attestation, AttestationData, EthSpec, Hash256, Slot};
 
-type AttestationDataRoot = Hash256;
-/// The number of slots that will be stored in the pool.
+/// The default number of slots that will be stored in the pool.
 ///
-/// For example, if `SLOTS_RETAINED == 3` and the pool is pruned at slot `6`, then all attestations
+/// For example, if `slots_retained == 3` and the pool is pruned at slot `6`, then all attestations
 /// at slots less than `4` will be dropped and any future attestation with a slot less than `4`
 /// will be refused.
-const SLOTS_RETAINED: usize = 3;
+pub const DEFAULT_SLOTS_RETAINED: usize = 3;
 
-/// The maximum number of distinct `AttestationData` that will be stored in each slot.
+/// The default maximum number of distinct `AttestationData` that will be stored in each slot.
 ///
 /// This is a DoS protection measure.
-const MAX_ATTESTATIONS_PER_SLOT: usize = 16_384;
+pub const DEFAULT_MAX_ATTESTATIONS_PER_SLOT: usize = 16_384;
 
 /// Returned upon successfully inserting an attestation into the pool.
 #[derive(Debug, PartialEq)]
@@ -52,27 +53,41 @@ pub enum Error {
     IncorrectSlot { expected: Slot, attestation: Slot },
 }
 
-/// A collection of `Attestation` objects, keyed by their `attestation.data`. Enforces that all
-/// `attestation` are from the same slot.
-struct AggregatedAttestationMap<E: EthSpec> {
-    map: HashMap<AttestationDataRoot, Attestation<E>>,
+/// A message which can be naively aggregated by the `GenericNaiveAggregationPool`.
+///
+/// Messages are grouped by `slot` and then by `data_root`. Messages with the same data root are
+/// aggregated into a single message by merging their aggregation bits and signatures.
+pub trait AggregatableMessage: Clone {
+    /// The slot that the message is for.
+    fn slot(&self) -> Slot;
+
+    /// The root of the data which is signed by the message. Only messages with the same data
+    /// root may be aggregated.
+    fn data_root(&self) -> Hash256;
+
+    /// Returns the index of the single aggregation bit which is set on `self`.
+    ///
+    /// Returns an error if there is not exactly one bit set.
+    fn aggregation_bit(&self) -> Result<usize, Error>;
+
+    /// Returns `true` if the aggregation bit at `index` is set on `self`.
+    fn has_aggregation_bit(&self, index: usize) -> Result<bool, Error>;
+
+    /// Aggregate `other` into `self`.
+    fn aggregate(&mut self, other: &Self);
 }
 
-impl<E: EthSpec> AggregatedAttestationMap<E> {
-    /// Create an empty collection with the given `initial_capacity`.
-    pub fn new(initial_capacity: usize) -> Self {
-        Self {
-            map: HashMap::with_capacity(initial_capacity),
-        }
+impl<E: EthSpec> AggregatableMessage for Attestation<E> {
+    fn slot(&self) -> Slot {
+        self.data.slot
     }
 
-    /// Insert an attestation into `self`, aggregating it into the pool.
-    ///
-    /// The given attestation (`a`) must only have one signature.
-    pub fn insert(&mut self, a: &Attestation<E>) -> Result<InsertOutcome, Error> {
-        let _timer = metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_CORE_INSERT);
+    fn data_root(&self) -> Hash256 {
+        self.data.tree_hash_root()
+    }
 
-        let set_bits = a
+    fn aggregation_bit(&self) -> Result<usize, Error> {
+        let set_bits = self
             .aggregation_bits
             .iter()
             .enumerate()
@@ -89,211 +104,408 @@ impl<E: EthSpec> AggregatedAttestationMap<E> {
             return Err(Error::MoreThanOneAggregationBitSet(set_bits.len()));
         }
 
-        let attestation_data_root = a.data.tree_hash_root();
+        Ok(committee_index)
+    }
+
+    fn has_aggregation_bit(&self, index: usize) -> Result<bool, Error> {
+        self.aggregation_bits
+            .get(index)
+            .map_err(|_| Error::InconsistentBitfieldLengths)
+    }
 
-        if let Some(existing_attestation) = self.map.get_mut(&attestation_data_root) {
-            if existing_attestation
-                .aggregation_bits
-                .get(committee_index)
-                .map_err(|_| Error::InconsistentBitfieldLengths)?
-            {
+    fn aggregate(&mut self, other: &Self) {
+        Attestation::aggregate(self, other)
+    }
+}
+
+/// A collection of messages, keyed by their data root. Enforces that all messages are from the
+/// same slot.
+struct AggregatedMessageMap<T: AggregatableMessage> {
+    map: HashMap<Hash256, T>,
+    max_messages: usize,
+    /// The capacity that was requested when `self` was created.
+    initial_capacity: usize,
+    /// The number of times `map` has had to grow beyond its allocated capacity.
+    reallocations: usize,
+}
+
+impl<T: AggregatableMessage> AggregatedMessageMap<T> {
+    /// Create an empty collection with the given `initial_capacity`, which will store at most
+    /// `max_messages` distinct data roots.
+    pub fn new(initial_capacity: usize, max_messages: usize) -> Self {
+        Self {
+            map: HashMap::with_capacity(initial_capacity),
+            max_messages,
+            initial_capacity,
+            reallocations: 0,
+        }
+    }
+
+    /// Insert a message into `self`, aggregating it into the pool.
+    ///
+    /// The given message (`a`) must only have one signature.
+    pub fn insert(&mut self, a: &T) -> Result<InsertOutcome, Error> {
+        let _timer = metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_CORE_INSERT);
+
+        let committee_index = a.aggregation_bit()?;
+        let data_root = a.data_root();
+
+        if let Some(existing_message) = self.map.get_mut(&data_root) {
+            if existing_message.has_aggregation_bit(committee_index)? {
                 Ok(InsertOutcome::SignatureAlreadyKnown { committee_index })
             } else {
                 let _timer =
                     metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_AGGREGATION);
-                existing_attestation.aggregate(a);
+                existing_message.aggregate(a);
                 Ok(InsertOutcome::SignatureAggregated { committee_index })
             }
         } else {
-            if self.map.len() >= MAX_ATTESTATIONS_PER_SLOT {
-                return Err(Error::ReachedMaxAttestationsPerSlot(
-                    MAX_ATTESTATIONS_PER_SLOT,
-                ));
+            if self.map.len() >= self.max_messages {
+                metrics::inc_counter(
+                    &metrics::ATTESTATION_PROCESSING_AGG_POOL_MAX_ATTESTATIONS_REACHED_TOTAL,
+                );
+                return Err(Error::ReachedMaxAttestationsPerSlot(self.max_messages));
+            }
+
+            if self.map.len() == self.map.capacity() {
+                // The map is full, so it must re-allocate to store the new message.
+                self.reallocations += 1;
+                metrics::inc_counter(
+                    &metrics::ATTESTATION_PROCESSING_AGG_POOL_MAP_REALLOCATIONS_TOTAL,
+                );
             }
 
-            self.map.insert(attestation_data_root, a.clone());
+            self.map.insert(data_root, a.clone());
             Ok(InsertOutcome::NewAttestationData { committee_index })
         }
     }
 
-    /// Returns an aggregated `Attestation` with the given `data`, if any.
-    ///
-    /// The given `a.data.slot` must match the slot that `self` was initialized with.
-    pub fn get(&self, data: &AttestationData) -> Option<Attestation<E>> {
-        self.map.get(&data.tree_hash_root()).cloned()
-    }
-
-    /// Returns an aggregated `Attestation` with the given `root`, if any.
-    pub fn get_by_root(&self, root: &AttestationDataRoot) -> Option<&Attestation<E>> {
+    /// Returns an aggregated message with the given `root`, if any.
+    pub fn get_by_root(&self, root: &Hash256) -> Option<&T> {
         self.map.get(root)
     }
 
-    /// Iterate all attestations in `self`.
-    pub fn iter(&self) -> impl Iterator<Item = &Attestation<E>> {
-        self.map.iter().map(|(_key, attestation)| attestation)
+    /// Iterate all messages in `self`.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.map.iter().map(|(_key, message)| message)
     }
 
     pub fn len(&self) -> usize {
         self.map.len()
     }
+
+    /// The number of messages `self` can hold without re-allocating.
+    pub fn capacity(&self) -> usize {
+        self.map.capacity()
+    }
 }
 
 /// A pool of `Attestation` that is specially designed to store "unaggregated" attestations from
 /// the native aggregation scheme.
+pub type NaiveAggregationPool<E> = GenericNaiveAggregationPool<Attestation<E>>;
+
+/// A pool of messages that is specially designed to store "unaggregated" messages from the native
+/// aggregation scheme, such as `Attestation`.
 ///
-/// **The `NaiveAggregationPool` does not do any signature or attestation verification. It assumes
-/// that all `Attestation` objects provided are valid.**
+/// **The pool does not do any signature or message verification. It assumes that all messages
+/// provided are valid.**
 ///
 /// ## Details
 ///
-/// The pool sorts the `Attestation` by `attestation.data.slot`, then by `attestation.data`.
+/// The pool sorts the messages by `message.slot()`, then by `message.data_root()`.
+///
+/// As each unaggregated message is added it is aggregated with any existing message with the same
+/// data root. Considering that the pool only accepts messages with a single signature, there
+/// should only ever be a single aggregated message for any given data root.
 ///
-/// As each unaggregated attestation is added it is aggregated with any existing `attestation` with
-/// the same `AttestationData`. Considering that the pool only accepts attestations with a single
-/// signature, there should only ever be a single aggregated `Attestation` for any given
-/// `AttestationData`.
+/// The pool has a capacity for `slots_retained` slots, when a new `message.slot()` is provided,
+/// the oldest slot is dropped and replaced with the new slot. The pool can also be pruned by
+/// supplying a `current_slot`; all existing messages with a slot lower than
+/// `current_slot - slots_retained` will be removed and any future message with a slot lower than
+/// that will also be refused. Pruning is done automatically based upon the messages it receives
+/// and it can be triggered manually.
 ///
-/// The pool has a capacity for `SLOTS_RETAINED` slots, when a new `attestation.data.slot` is
-/// provided, the oldest slot is dropped and replaced with the new slot. The pool can also be
-/// pruned by supplying a `current_slot`; all existing attestations with a slot lower than
-/// `current_slot - SLOTS_RETAINED` will be removed and any future attestation with a slot lower
-/// than that will also be refused. Pruning is done automatically based upon the attestations it
-/// receives and it can be triggered manually.
-pub struct NaiveAggregationPool<E: EthSpec> {
-    lowest_permissible_slot: Slot,
-    maps: HashMap<Slot, AggregatedAttestationMap<E>>,
+/// Each slot stores at most `max_attestations_per_slot` distinct data roots.
+///
+/// ## Concurrency
+///
+/// The pool is safe to share between threads. Each slot has its own `RwLock`, so reading the
+/// messages of one slot does not block inserting into another. The outer lock on `maps` is only
+/// held for writing whilst a new slot is being added or the pool is being pruned.
+///
+/// To avoid deadlocks, the `maps` lock must always be obtained before the
+/// `lowest_permissible_slot` lock.
+pub struct GenericNaiveAggregationPool<T: AggregatableMessage> {
+    lowest_permissible_slot: RwLock<Slot>,
+    maps: RwLock<HashMap<Slot, Arc<RwLock<AggregatedMessageMap<T>>>>>,
+    slots_retained: usize,
+    max_attestations_per_slot: usize,
 }
 
-impl<E: EthSpec> Default for NaiveAggregationPool<E> {
+impl<T: AggregatableMessage> Default for GenericNaiveAggregationPool<T> {
     fn default() -> Self {
+        Self::new(DEFAULT_SLOTS_RETAINED)
+    }
+}
+
+impl<T: AggregatableMessage> GenericNaiveAggregationPool<T> {
+    /// Create an empty pool which stores messages for `slots_retained` slots.
+    ///
+    /// Each slot stores at most `DEFAULT_MAX_ATTESTATIONS_PER_SLOT` distinct data roots, see
+    /// `Self::with_max_attestations_per_slot`.
+    pub fn new(slots_retained: usize) -> Self {
         Self {
-            lowest_permissible_slot: Slot::new(0),
-            maps: HashMap::new(),
+            lowest_permissible_slot: RwLock::new(Slot::new(0)),
+            maps: RwLock::new(HashMap::new()),
+            slots_retained,
+            max_attestations_per_slot: DEFAULT_MAX_ATTESTATIONS_PER_SLOT,
         }
     }
-}
 
-impl<E: EthSpec> NaiveAggregationPool<E> {
-    /// Insert an attestation into `self`, aggregating it into the pool.
+    /// Set the maximum number of distinct data roots that will be stored in each slot.
+    pub fn with_max_attestations_per_slot(mut self, max_attestations_per_slot: usize) -> Self {
+        self.max_attestations_per_slot = max_attestations_per_slot;
+        self
+    }
+
+    /// Insert a message into `self`, aggregating it into the pool.
     ///
-    /// The given attestation (`a`) must only have one signature and have an
-    /// `attestation.data.slot` that is not lower than `self.lowest_permissible_slot`.
+    /// The given message must only have one signature and have a `message.slot()` that is not
+    /// lower than `self.lowest_permissible_slot`.
     ///
-    /// The pool may be pruned if the given `attestation.data` has a slot higher than any
-    /// previously seen.
-    pub fn insert(&mut self, attestation: &Attestation<E>) -> Result<InsertOutcome, Error> {
+    /// The pool may be pruned if the given message has a slot higher than any previously seen.
+    pub fn insert(&self, message: &T) -> Result<InsertOutcome, Error> {
         let _timer = metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_INSERT);
-        let slot = attestation.data.slot;
-        let lowest_permissible_slot = self.lowest_permissible_slot;
+        let slot = message.slot();
 
-        // Reject any attestations that are too old.
-        if slot < lowest_permissible_slot {
-            return Err(Error::SlotTooLow {
-                slot,
-                lowest_permissible_slot,
-            });
-        }
+        // Reject any messages that are too old.
+        Self::check_slot(slot, *self.lowest_permissible_slot.read())?;
 
-        let lock_timer =
-            metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_MAPS_WRITE_LOCK);
-        drop(lock_timer);
+        let existing_map = self.maps.read().get(&slot).cloned();
 
-        let outcome = if let Some(map) = self.maps.get_mut(&slot) {
-            map.insert(attestation)
+        let map = if let Some(map) = existing_map {
+            map
         } else {
-            let _timer = metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_CREATE_MAP);
-            // To avoid re-allocations, try and determine a rough initial capacity for the new item
-            // by obtaining the mean size of all items in earlier epoch.
-            let (count, sum) = self
-                .maps
-                .iter()
-                // Only include epochs that are less than the given slot in the average. This should
-                // generally avoid including recent epochs that are still "filling up".
-                .filter(|(map_slot, _item)| **map_slot < slot)
-                .map(|(_slot, map)| map.len())
-                .fold((0, 0), |(count, sum), len| (count + 1, sum + len));
+            let lock_timer =
+                metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_MAPS_WRITE_LOCK);
+            let mut maps = self.maps.write();
+            drop(lock_timer);
 
-            // Use the mainnet default committee size if we can't determine an average.
-            let initial_capacity = sum.checked_div(count).unwrap_or(128);
+            // The pool may have been pruned whilst we were waiting for the write lock.
+            Self::check_slot(slot, *self.lowest_permissible_slot.read())?;
 
-            let mut item = AggregatedAttestationMap::new(initial_capacity);
-            let outcome = item.insert(attestation);
-            self.maps.insert(slot, item);
+            if let Some(map) = maps.get(&slot) {
+                // Another thread created the map whilst we were waiting for the write lock.
+                map.clone()
+            } else {
+                let _timer =
+                    metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_CREATE_MAP);
+                let initial_capacity = self.estimate_initial_capacity(&maps, slot);
+                metrics::set_gauge(
+                    &metrics::ATTESTATION_PROCESSING_AGG_POOL_INITIAL_CAPACITY,
+                    initial_capacity as i64,
+                );
 
-            outcome
+                let map = Arc::new(RwLock::new(AggregatedMessageMap::new(
+                    initial_capacity,
+                    self.max_attestations_per_slot,
+                )));
+                maps.insert(slot, map.clone());
+                map
+            }
         };
 
+        // Only the lock for this slot is held whilst inserting, allowing concurrent access to
+        // the other slots.
+        let outcome = map.write().insert(message);
+
         self.prune(slot);
 
         outcome
     }
 
-    /// Returns the total number of attestations stored in `self`.
-    pub fn num_attestations(&self) -> usize {
-        self.maps.iter().map(|(_, map)| map.len()).sum()
+    /// Returns the initial capacity for a new map at `slot`.
+    ///
+    /// To avoid re-allocations, try and determine a rough initial capacity for the new map by
+    /// obtaining the mean size of all maps at earlier slots. The capacity never exceeds the
+    /// number of messages that the map may store.
+    fn estimate_initial_capacity(
+        &self,
+        maps: &HashMap<Slot, Arc<RwLock<AggregatedMessageMap<T>>>>,
+        slot: Slot,
+    ) -> usize {
+        let (count, sum) = maps
+            .iter()
+            // Only include slots that are less than the given slot in the average. This should
+            // generally avoid including recent slots that are still "filling up".
+            .filter(|(map_slot, _item)| **map_slot < slot)
+            .map(|(_slot, map)| map.read().len())
+            .fold((0, 0), |(count, sum), len| (count + 1, sum + len));
+
+        // Use the mainnet default committee size if we can't determine an average.
+        let estimate = sum.checked_div(count).unwrap_or(128);
+
+        std::cmp::min(estimate, self.max_attestations_per_slot)
     }
 
-    /// Returns an aggregated `Attestation` with the given `data`, if any.
-    pub fn get(&self, data: &AttestationData) -> Option<Attestation<E>> {
-        self.maps.get(&data.slot).and_then(|map| map.get(data))
+    /// Returns an error if `slot` is lower than `lowest_permissible_slot`.
+    fn check_slot(slot: Slot, lowest_permissible_slot: Slot) -> Result<(), Error> {
+        if slot < lowest_permissible_slot {
+            Err(Error::SlotTooLow {
+                slot,
+                lowest_permissible_slot,
+            })
+        } else {
+            Ok(())
+        }
     }
 
-    /// Returns an aggregated `Attestation` with the given `data`, if any.
-    pub fn get_by_slot_and_root(
-        &self,
-        slot: Slot,
-        root: &AttestationDataRoot,
-    ) -> Option<Attestation<E>> {
+    /// Returns the total number of messages stored in `self`.
+    pub fn num_attestations(&self) -> usize {
         self.maps
-            .get(&slot)
-            .and_then(|map| map.get_by_root(root).cloned())
+            .read()
+            .iter()
+            .map(|(_, map)| map.read().len())
+            .sum()
     }
 
-    /// Iterate all attestations in all slots of `self`.
-    pub fn iter(&self) -> impl Iterator<Item = &Attestation<E>> {
-        self.maps.iter().map(|(_slot, map)| map.iter()).flatten()
+    /// Returns the total number of messages that `self` can store without re-allocating any of
+    /// its per-slot maps.
+    pub fn capacity(&self) -> usize {
+        self.maps
+            .read()
+            .iter()
+            .map(|(_, map)| map.read().capacity())
+            .sum()
+    }
+
+    /// Returns an aggregated message with the given `slot` and data `root`, if any.
+    pub fn get_by_slot_and_root(&self, slot: Slot, root: &Hash256) -> Option<T> {
+        self.get_map(slot)
+            .and_then(|map| map.read().get_by_root(root).cloned())
     }
 
-    /// Removes any attestations with a slot lower than `current_slot` and bars any future
-    /// attestations with a slot lower than `current_slot - SLOTS_RETAINED`.
-    pub fn prune(&mut self, current_slot: Slot) {
+    /// Returns the map for the given `slot`, if any.
+    ///
+    /// The lock on `self.maps` is released before returning, so holding the returned map does not
+    /// block other slots from being added or pruned.
+    fn get_map(&self, slot: Slot) -> Option<Arc<RwLock<AggregatedMessageMap<T>>>> {
+        self.maps.read().get(&slot).cloned()
+    }
+
+    /// Returns a copy of all messages in all slots of `self`.
+    ///
+    /// The messages are cloned so that no locks are held whilst the caller iterates.
+    pub fn iter(&self) -> impl Iterator<Item = T> {
+        self.maps
+            .read()
+            .values()
+            .flat_map(|map| map.read().iter().cloned().collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Removes any messages with a slot lower than `current_slot` and bars any future
+    /// messages with a slot lower than `current_slot - self.slots_retained`.
+    pub fn prune(&self, current_slot: Slot) {
         let _timer = metrics::start_timer(&metrics::ATTESTATION_PROCESSING_AGG_POOL_PRUNE);
 
         // Taking advantage of saturating subtraction on `Slot`.
-        let lowest_permissible_slot = current_slot - Slot::from(SLOTS_RETAINED);
+        let slots_retained = self.slots_retained;
+        let lowest_permissible_slot = current_slot - Slot::from(slots_retained);
 
         // No need to prune if the lowest permissible slot has not changed and the queue length is
-        // less than the maximum
-        if self.lowest_permissible_slot == lowest_permissible_slot
-            && self.maps.len() <= SLOTS_RETAINED
+        // less than the maximum. This check only requires read locks, which avoids contention in
+        // the common case.
+        let needs_pruning = |maps_len: usize, current_lowest_permissible_slot: Slot| {
+            current_lowest_permissible_slot < lowest_permissible_slot || maps_len > slots_retained
+        };
         {
+            let maps = self.maps.read();
+            if !needs_pruning(maps.len(), *self.lowest_permissible_slot.read()) {
+                return;
+            }
+        }
+
+        let mut maps = self.maps.write();
+        let mut current_lowest_permissible_slot = self.lowest_permissible_slot.write();
+
+        // Another thread may have pruned the pool whilst we were waiting for the write locks.
+        if !needs_pruning(maps.len(), *current_lowest_permissible_slot) {
             return;
         }
 
-        self.lowest_permissible_slot = lowest_permissible_slot;
+        // Never allow the lowest permissible slot to go backwards, since another thread may have
+        // pruned at a later slot.
+        let lowest_permissible_slot =
+            std::cmp::max(*current_lowest_permissible_slot, lowest_permissible_slot);
+        *current_lowest_permissible_slot = lowest_permissible_slot;
+        drop(current_lowest_permissible_slot);
 
         // Remove any maps that are definitely expired.
-        self.maps
-            .retain(|slot, _map| *slot >= lowest_permissible_slot);
+        maps.retain(|slot, _map| *slot >= lowest_permissible_slot);
 
         // If we have too many maps, remove the lowest amount to ensure we only have
-        // `SLOTS_RETAINED` left.
-        if self.maps.len() > SLOTS_RETAINED {
-            let mut slots = self
-                .maps
-                .iter()
-                .map(|(slot, _map)| *slot)
-                .collect::<Vec<_>>();
-            // Sort is generally pretty slow, however `SLOTS_RETAINED` is quite low so it should be
+        // `slots_retained` left.
+        if maps.len() > slots_retained {
+            let mut slots = maps.iter().map(|(slot, _map)| *slot).collect::<Vec<_>>();
+            // Sort is generally pretty slow, however `slots_retained` is quite low so it should be
             // negligible.
             slots.sort_unstable();
-            slots
-                .into_iter()
-                .take(self.maps.len().saturating_sub(SLOTS_RETAINED))
-                .for_each(|slot| {
-                    self.maps.remove(&slot);
-                })
+            let num_to_remove = maps.len().saturating_sub(slots_retained);
+            slots.into_iter().take(num_to_remove).for_each(|slot| {
+                maps.remove(&slot);
+            })
         }
+
+        // Pruning happens at most once per slot, so update the occupancy gauges here rather than
+        // on every insert.
+        metrics::set_gauge(
+            &metrics::ATTESTATION_PROCESSING_AGG_POOL_NUM_SLOTS,
+            maps.len() as i64,
+        );
+        metrics::set_gauge(
+            &metrics::ATTESTATION_PROCESSING_AGG_POOL_NUM_ATTESTATIONS,
+            maps.values().map(|map| map.read().len()).sum::<usize>() as i64,
+        );
+    }
+}
+
+impl<E: EthSpec> NaiveAggregationPool<E> {
+    /// Returns an aggregated `Attestation` with the given `data`, if any.
+    pub fn get(&self, data: &AttestationData) -> Option<Attestation<E>> {
+        self.get_by_slot_and_root(data.slot, &data.tree_hash_root())
+    }
+
+    /// Returns the aggregated `Attestation` with the most aggregation bits set out of all
+    /// attestations with the given `data`, if any.
+    ///
+    /// This is the attestation that should be used when producing an `AggregateAndProof`.
+    ///
+    /// ## Notes
+    ///
+    /// Every attestation inserted with the same `data` is aggregated into a single entry (keyed by
+    /// the data root), so that entry is always the densest and no search is required.
+    pub fn get_best_aggregate(&self, data: &AttestationData) -> Option<Attestation<E>> {
+        self.get(data)
+    }
+
+    /// Returns all aggregated `Attestation`s at `slot` that vote for `beacon_block_root`, across
+    /// all committee indices.
+    ///
+    /// This allows a block proposer to choose a set of aggregates for a specific head block.
+    pub fn aggregates_for_block(
+        &self,
+        slot: Slot,
+        beacon_block_root: Hash256,
+    ) -> Vec<Attestation<E>> {
+        self.get_map(slot).map_or_else(Vec::new, |map| {
+            map.read()
+                .iter()
+                .filter(|attestation| attestation.data.beacon_block_root == beacon_block_root)
+                .cloned()
+                .collect()
+        })
     }
 }
 
@@ -336,7 +548,7 @@ mod tests {
     fn single_attestation() {
         let mut a = get_attestation(Slot::new(0));
 
-        let mut pool = NaiveAggregationPool::default();
+        let pool = NaiveAggregationPool::default();
 
         assert_eq!(
             pool.insert(&a),
@@ -383,7 +595,7 @@ mod tests {
         sign(&mut a_0, 0, genesis_validators_root);
         sign(&mut a_1, 1, genesis_validators_root);
 
-        let mut pool = NaiveAggregationPool::default();
+        let pool = NaiveAggregationPool::default();
 
         assert_eq!(
             pool.insert(&a_0),
@@ -433,14 +645,13 @@ mod tests {
         );
     }
 
-    #[test]
-    fn auto_pruning() {
+    fn test_auto_pruning(slots_retained: usize) {
         let mut base = get_attestation(Slot::new(0));
         sign(&mut base, 0, Hash256::random());
 
-        let mut pool = NaiveAggregationPool::default();
+        let pool = NaiveAggregationPool::new(slots_retained);
 
-        for i in 0..SLOTS_RETAINED * 2 {
+        for i in 0..slots_retained * 2 {
             let slot = Slot::from(i);
             let mut a = base.clone();
             a.data.slot = slot;
@@ -451,18 +662,24 @@ mod tests {
                 "should accept new attestation"
             );
 
-            if i < SLOTS_RETAINED {
+            if i < slots_retained {
                 let len = i + 1;
-                assert_eq!(pool.maps.len(), len, "the pool should have length {}", len);
+                assert_eq!(
+                    pool.maps.read().len(),
+                    len,
+                    "the pool should have length {}",
+                    len
+                );
             } else {
                 assert_eq!(
-                    pool.maps.len(),
-                    SLOTS_RETAINED,
-                    "the pool should have length SLOTS_RETAINED"
+                    pool.maps.read().len(),
+                    slots_retained,
+                    "the pool should have length slots_retained"
                 );
 
                 let mut pool_slots = pool
                     .maps
+                    .read()
                     .iter()
                     .map(|(slot, _map)| *slot)
                     .collect::<Vec<_>>();
@@ -470,7 +687,7 @@ mod tests {
                 pool_slots.sort_unstable();
 
                 for (j, pool_slot) in pool_slots.iter().enumerate() {
-                    let expected_slot = slot - (SLOTS_RETAINED - 1 - j) as u64;
+                    let expected_slot = slot - (slots_retained - 1 - j) as u64;
                     assert_eq!(
                         *pool_slot, expected_slot,
                         "the slot of the map should be {}",
@@ -482,17 +699,29 @@ mod tests {
     }
 
     #[test]
-    fn max_attestations() {
+    fn auto_pruning() {
+        test_auto_pruning(DEFAULT_SLOTS_RETAINED);
+    }
+
+    #[test]
+    fn auto_pruning_single_slot() {
+        test_auto_pruning(1);
+    }
+
+    #[test]
+    fn auto_pruning_wide_window() {
+        test_auto_pruning(8);
+    }
+
+    fn test_max_attestations(pool: NaiveAggregationPool<E>, max_attestations_per_slot: usize) {
         let mut base = get_attestation(Slot::new(0));
         sign(&mut base, 0, Hash256::random());
 
-        let mut pool = NaiveAggregationPool::default();
-
-        for i in 0..=MAX_ATTESTATIONS_PER_SLOT {
+        for i in 0..=max_attestations_per_slot {
             let mut a = base.clone();
             a.data.beacon_block_root = Hash256::from_low_u64_be(i as u64);
 
-            if i < MAX_ATTESTATIONS_PER_SLOT {
+            if i < max_attestations_per_slot {
                 assert_eq!(
                     pool.insert(&a),
                     Ok(InsertOutcome::NewAttestationData { committee_index: 0 }),
@@ -502,11 +731,411 @@ mod tests {
                 assert_eq!(
                     pool.insert(&a),
                     Err(Error::ReachedMaxAttestationsPerSlot(
-                        MAX_ATTESTATIONS_PER_SLOT
+                        max_attestations_per_slot
                     )),
                     "should not accept attestation above limit"
                 );
             }
         }
     }
+
+    #[test]
+    fn max_attestations() {
+        test_max_attestations(
+            NaiveAggregationPool::default(),
+            DEFAULT_MAX_ATTESTATIONS_PER_SLOT,
+        );
+    }
+
+    #[test]
+    fn max_attestations_metric() {
+        let max_attestations_reached = || {
+            metrics::ATTESTATION_PROCESSING_AGG_POOL_MAX_ATTESTATIONS_REACHED_TOTAL
+                .as_ref()
+                .expect("metric should exist")
+                .get()
+        };
+        let initial = max_attestations_reached();
+
+        test_max_attestations(
+            NaiveAggregationPool::default().with_max_attestations_per_slot(1),
+            1,
+        );
+
+        // Other tests may also increment the counter, so only check that it has increased.
+        assert!(
+            max_attestations_reached() > initial,
+            "should count the attestation dropped at the limit"
+        );
+    }
+
+    #[test]
+    fn configured_max_attestations() {
+        test_max_attestations(
+            NaiveAggregationPool::default().with_max_attestations_per_slot(16),
+            16,
+        );
+    }
+
+    #[test]
+    fn concurrent_insert_and_read() {
+        const ATTESTATIONS_PER_SLOT: usize = 64;
+
+        let mut base = get_attestation(Slot::new(0));
+        sign(&mut base, 0, Hash256::random());
+
+        let pool = Arc::new(NaiveAggregationPool::default());
+
+        let attestations_for_slot = |slot: usize| {
+            (0..ATTESTATIONS_PER_SLOT)
+                .map(|i| {
+                    let mut a = base.clone();
+                    a.data.slot = Slot::from(slot);
+                    a.data.beacon_block_root = Hash256::from_low_u64_be(i as u64);
+                    a
+                })
+                .collect::<Vec<_>>()
+        };
+
+        // Spawn one writer and one reader per slot so that all slots are accessed concurrently.
+        let handles = (0..DEFAULT_SLOTS_RETAINED)
+            .flat_map(|slot| {
+                let attestations = attestations_for_slot(slot);
+
+                let writer_pool = pool.clone();
+                let writer_attestations = attestations.clone();
+                let writer = std::thread::spawn(move || {
+                    for a in &writer_attestations {
+                        assert_eq!(
+                            writer_pool.insert(a),
+                            Ok(InsertOutcome::NewAttestationData { committee_index: 0 }),
+                            "should accept new attestation"
+                        );
+                    }
+                });
+
+                let reader_pool = pool.clone();
+                let reader = std::thread::spawn(move || {
+                    for a in &attestations {
+                        // The attestation may or may not have been inserted yet, but if it has
+                        // then it must be complete.
+                        if let Some(retrieved) = reader_pool.get(&a.data) {
+                            assert_eq!(&retrieved, a, "should read the inserted attestation");
+                        }
+                        reader_pool.num_attestations();
+                        reader_pool.iter().count();
+                    }
+                });
+
+                vec![writer, reader]
+            })
+            .collect::<Vec<_>>();
+
+        for handle in handles {
+            handle.join().expect("thread should not panic");
+        }
+
+        assert_eq!(
+            pool.num_attestations(),
+            DEFAULT_SLOTS_RETAINED * ATTESTATIONS_PER_SLOT,
+            "all attestations should be stored"
+        );
+        for slot in 0..DEFAULT_SLOTS_RETAINED {
+            for a in attestations_for_slot(slot) {
+                assert_eq!(
+                    pool.get(&a.data),
+                    Some(a),
+                    "should retrieve every inserted attestation"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn best_aggregate() {
+        let mut a_0 = get_attestation(Slot::new(0));
+        let mut a_1 = a_0.clone();
+        let mut a_2 = a_0.clone();
+
+        let genesis_validators_root = Hash256::random();
+        sign(&mut a_0, 0, genesis_validators_root);
+        sign(&mut a_1, 1, genesis_validators_root);
+        sign(&mut a_2, 2, genesis_validators_root);
+
+        let pool = NaiveAggregationPool::default();
+
+        assert_eq!(
+            pool.get_best_aggregate(&a_0.data),
+            None,
+            "should not return an aggregate from an empty pool"
+        );
+
+        // Insert disjoint signatures, one of them twice. The pool only accepts unaggregated
+        // attestations.
+        for a in &[&a_0, &a_1, &a_0, &a_2] {
+            pool.insert(a).expect("should insert attestation");
+        }
+
+        // Insert a different attestation data at the same slot, which must not be selected.
+        let mut a_different = get_attestation(Slot::new(0));
+        sign(&mut a_different, 3, genesis_validators_root);
+        assert_ne!(a_different.data, a_0.data);
+        pool.insert(&a_different)
+            .expect("should insert different attestation");
+
+        let mut expected = a_0.clone();
+        expected.aggregate(&a_1);
+        expected.aggregate(&a_2);
+
+        let best = pool
+            .get_best_aggregate(&a_0.data)
+            .expect("should return an aggregate");
+        assert_eq!(
+            best.aggregation_bits.num_set_bits(),
+            3,
+            "should return the densest aggregate"
+        );
+        assert_eq!(best, expected, "should aggregate all signatures");
+
+        assert_eq!(
+            pool.get_best_aggregate(&a_different.data),
+            Some(a_different),
+            "should return the aggregate for the other data"
+        );
+    }
+
+    #[test]
+    fn aggregates_for_block() {
+        let slot = Slot::new(0);
+        let block_a = Hash256::from_low_u64_be(1);
+        let block_b = Hash256::from_low_u64_be(2);
+        let genesis_validators_root = Hash256::random();
+
+        let attestation = |index: u64, beacon_block_root: Hash256, validator: usize| {
+            let mut a = get_attestation(slot);
+            a.data.index = index;
+            a.data.beacon_block_root = beacon_block_root;
+            sign(&mut a, validator, genesis_validators_root);
+            a
+        };
+
+        let a_0 = attestation(0, block_a, 0);
+        let a_1 = attestation(1, block_a, 1);
+        let b_0 = attestation(0, block_b, 2);
+
+        let pool = NaiveAggregationPool::default();
+        assert!(
+            pool.aggregates_for_block(slot, block_a).is_empty(),
+            "should not return aggregates from an empty pool"
+        );
+
+        for a in &[&a_0, &a_1, &b_0] {
+            pool.insert(a).expect("should insert attestation");
+        }
+
+        let mut for_a = pool.aggregates_for_block(slot, block_a);
+        for_a.sort_by_key(|a| a.data.index);
+        assert_eq!(
+            for_a,
+            vec![a_0, a_1],
+            "should return aggregates for all committees"
+        );
+        assert_eq!(pool.aggregates_for_block(slot, block_b), vec![b_0]);
+        assert!(
+            pool.aggregates_for_block(slot + 1, block_a).is_empty(),
+            "should only return aggregates from the given slot"
+        );
+        assert!(pool
+            .aggregates_for_block(slot, Hash256::from_low_u64_be(3))
+            .is_empty());
+    }
+
+    /// A minimal message used to test the machinery shared by all `AggregatableMessage` types.
+    #[derive(Clone, Debug, PartialEq)]
+    struct MockMessage {
+        slot: Slot,
+        root: Hash256,
+        bits: Vec<bool>,
+    }
+
+    impl MockMessage {
+        fn new(slot: u64, root: u64, set_bits: &[usize]) -> Self {
+            let mut bits = vec![false; 4];
+            for i in set_bits {
+                bits[*i] = true;
+            }
+            Self {
+                slot: Slot::new(slot),
+                root: Hash256::from_low_u64_be(root),
+                bits,
+            }
+        }
+    }
+
+    impl AggregatableMessage for MockMessage {
+        fn slot(&self) -> Slot {
+            self.slot
+        }
+
+        fn data_root(&self) -> Hash256 {
+            self.root
+        }
+
+        fn aggregation_bit(&self) -> Result<usize, Error> {
+            let set_bits = self
+                .bits
+                .iter()
+                .enumerate()
+                .filter(|(_i, bit)| **bit)
+                .map(|(i, _bit)| i)
+                .collect::<Vec<_>>();
+            match set_bits.as_slice() {
+                [] => Err(Error::NoAggregationBitsSet),
+                [i] => Ok(*i),
+                _ => Err(Error::MoreThanOneAggregationBitSet(set_bits.len())),
+            }
+        }
+
+        fn has_aggregation_bit(&self, index: usize) -> Result<bool, Error> {
+            self.bits
+                .get(index)
+                .copied()
+                .ok_or(Error::InconsistentBitfieldLengths)
+        }
+
+        fn aggregate(&mut self, other: &Self) {
+            for (bit, other_bit) in self.bits.iter_mut().zip(other.bits.iter()) {
+                *bit |= *other_bit;
+            }
+        }
+    }
+
+    #[test]
+    fn generic_message_aggregation() {
+        let pool = GenericNaiveAggregationPool::<MockMessage>::default();
+
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[])),
+            Err(Error::NoAggregationBitsSet),
+            "should not accept message without any signatures"
+        );
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[0, 1])),
+            Err(Error::MoreThanOneAggregationBitSet(2)),
+            "should not accept message with multiple signatures"
+        );
+
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[0])),
+            Ok(InsertOutcome::NewAttestationData { committee_index: 0 }),
+            "should accept new message"
+        );
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[1])),
+            Ok(InsertOutcome::SignatureAggregated { committee_index: 1 }),
+            "should aggregate message"
+        );
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[1])),
+            Ok(InsertOutcome::SignatureAlreadyKnown { committee_index: 1 }),
+            "should acknowledge duplicate signature"
+        );
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 1, &[2])),
+            Ok(InsertOutcome::NewAttestationData { committee_index: 2 }),
+            "should accept message with a different root"
+        );
+
+        let root = Hash256::from_low_u64_be(0);
+        let expected = MockMessage::new(0, 0, &[0, 1]);
+        assert_eq!(
+            pool.get_by_slot_and_root(Slot::new(0), &root),
+            Some(expected),
+            "should retrieve the aggregated message"
+        );
+        assert_eq!(pool.num_attestations(), 2);
+    }
+
+    #[test]
+    fn generic_message_pruning_and_limits() {
+        let pool =
+            GenericNaiveAggregationPool::<MockMessage>::new(2).with_max_attestations_per_slot(1);
+
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[0])),
+            Ok(InsertOutcome::NewAttestationData { committee_index: 0 })
+        );
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 1, &[0])),
+            Err(Error::ReachedMaxAttestationsPerSlot(1)),
+            "should not accept message above limit"
+        );
+
+        for slot in 1..=3 {
+            assert_eq!(
+                pool.insert(&MockMessage::new(slot, 0, &[0])),
+                Ok(InsertOutcome::NewAttestationData { committee_index: 0 })
+            );
+        }
+
+        assert_eq!(
+            pool.insert(&MockMessage::new(0, 0, &[1])),
+            Err(Error::SlotTooLow {
+                slot: Slot::new(0),
+                lowest_permissible_slot: Slot::new(1),
+            }),
+            "should refuse message from pruned slot"
+        );
+        assert_eq!(
+            pool.iter().map(|message| message.slot).min(),
+            Some(Slot::new(2)),
+            "should have pruned the oldest slots"
+        );
+    }
+
+    #[test]
+    fn initial_capacity_estimate() {
+        let pool = GenericNaiveAggregationPool::<MockMessage>::default();
+        let map_at = |slot: u64| pool.get_map(Slot::new(slot)).expect("map should exist");
+
+        pool.insert(&MockMessage::new(0, 0, &[0])).unwrap();
+        assert_eq!(
+            map_at(0).read().initial_capacity,
+            128,
+            "should use the default without earlier slots"
+        );
+
+        for root in 1..3 {
+            pool.insert(&MockMessage::new(0, root, &[0])).unwrap();
+        }
+        pool.insert(&MockMessage::new(1, 0, &[0])).unwrap();
+
+        // The mean size of slots 0 and 1 is (3 + 1) / 2.
+        pool.insert(&MockMessage::new(2, 0, &[0])).unwrap();
+        let map = map_at(2);
+        assert_eq!(map.read().initial_capacity, 2);
+        assert!(map.read().capacity() >= 2, "should reserve the estimate");
+        assert_eq!(map.read().reallocations, 0);
+
+        // Exceeding the reserved capacity causes a re-allocation.
+        let reserved = map.read().capacity() as u64;
+        for root in 1..=reserved {
+            pool.insert(&MockMessage::new(2, root, &[0])).unwrap();
+        }
+        assert_eq!(map.read().reallocations, 1);
+        assert!(pool.capacity() >= pool.num_attestations());
+
+        let limited =
+            GenericNaiveAggregationPool::<MockMessage>::default().with_max_attestations_per_slot(4);
+        limited.insert(&MockMessage::new(0, 0, &[0])).unwrap();
+        assert_eq!(
+            limited
+                .get_map(Slot::new(0))
+                .unwrap()
+                .read()
+                .initial_capacity,
+            4,
+            "should not reserve more than the maximum attestations per slot"
+        );
+    }
 }
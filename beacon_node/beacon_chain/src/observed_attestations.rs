@@ -1,18 +1,20 @@
 //! Provides an `ObservedAttestations` struct which allows us to reject aggregated attestations if
 //! we've already seen the aggregated attestation.
 
-use std::collections::HashSet;
+use crate::metrics;
+use std::collections::HashMap;
 use std::marker::PhantomData;
+use std::time::Instant;
 use tree_hash::TreeHash;
 use types::{Attestation, EthSpec, Hash256, Slot};
 
 /// As a DoS protection measure, the maximum number of distinct `Attestations` that will be
 /// recorded for each slot.
 ///
-/// Currently this is set to ~524k. If we say that each entry is 40 bytes (Hash256 (32 bytes) + an
-/// 8 byte hash) then this comes to about 20mb per slot. If we're storing 34 of these slots, then
-/// we're at 680mb. This is a lot of memory usage, but probably not a show-stopper for most
-/// reasonable hardware.
+/// Currently this is set to ~524k. If we say that each entry is 56 bytes (Hash256 (32 bytes) + an
+/// `Instant` (16 bytes) + an 8 byte hash) then this comes to about 30mb per slot. If we're storing
+/// 34 of these slots, then we're at 1gb. This is a lot of memory usage, but probably not a
+/// show-stopper for most reasonable hardware.
 ///
 /// Upstream conditions should strongly restrict the amount of attestations that can show up in
 /// this pool. The maximum size with respect to upstream restrictions is more likely on the order
@@ -22,7 +24,10 @@ const MAX_OBSERVATIONS_PER_SLOT: usize = 1 << 19; // 524,288
 #[derive(Debug, PartialEq)]
 pub enum ObserveOutcome {
     /// This attestation was already known.
-    AlreadyKnown,
+    AlreadyKnown {
+        /// The moment at which the attestation was first observed.
+        first_seen: Instant,
+    },
     /// This was the first time this attestation was observed.
     New,
 }
@@ -44,9 +49,9 @@ pub enum Error {
     },
 }
 
-/// A `HashSet` that contains entries related to some `Slot`.
+/// A set of attestation roots related to some `Slot`, each with the moment it was first observed.
 struct SlotHashSet {
-    set: HashSet<Hash256>,
+    set: HashMap<Hash256, Instant>,
     slot: Slot,
 }
 
@@ -54,7 +59,7 @@ impl SlotHashSet {
     pub fn new(slot: Slot, initial_capacity: usize) -> Self {
         Self {
             slot,
-            set: HashSet::with_capacity(initial_capacity),
+            set: HashMap::with_capacity(initial_capacity),
         }
     }
 
@@ -71,8 +76,10 @@ impl SlotHashSet {
             });
         }
 
-        if self.set.contains(&root) {
-            Ok(ObserveOutcome::AlreadyKnown)
+        if let Some(first_seen) = self.set.get(&root) {
+            Ok(ObserveOutcome::AlreadyKnown {
+                first_seen: *first_seen,
+            })
         } else {
             // Here we check to see if this slot has reached the maximum observation count.
             //
@@ -88,14 +95,18 @@ impl SlotHashSet {
                 ));
             }
 
-            self.set.insert(root);
+            self.set.insert(root, Instant::now());
 
             Ok(ObserveOutcome::New)
         }
     }
 
-    /// Indicates if `a` has been observed before.
-    pub fn is_known<E: EthSpec>(&self, a: &Attestation<E>, root: Hash256) -> Result<bool, Error> {
+    /// Returns the moment at which `a` was first observed, if it has been observed before.
+    pub fn first_seen<E: EthSpec>(
+        &self,
+        a: &Attestation<E>,
+        root: Hash256,
+    ) -> Result<Option<Instant>, Error> {
         if a.data.slot != self.slot {
             return Err(Error::IncorrectSlot {
                 expected: self.slot,
@@ -103,7 +114,7 @@ impl SlotHashSet {
             });
         }
 
-        Ok(self.set.contains(&root))
+        Ok(self.set.get(&root).copied())
     }
 
     /// The number of observed attestations in `self`.
@@ -117,20 +128,31 @@ impl SlotHashSet {
 pub struct ObservedAttestations<E: EthSpec> {
     lowest_permissible_slot: Slot,
     sets: Vec<SlotHashSet>,
+    slots_retained: u64,
     _phantom: PhantomData<E>,
 }
 
 impl<E: EthSpec> Default for ObservedAttestations<E> {
+    /// Retains attestations for an epoch. We add `2` in order to account for one slot either side
+    /// of the range due to `MAXIMUM_GOSSIP_CLOCK_DISPARITY`.
     fn default() -> Self {
+        Self::new(E::slots_per_epoch() + 2)
+    }
+}
+
+impl<E: EthSpec> ObservedAttestations<E> {
+    /// Create an empty store which retains attestations for `slots_retained` slots.
+    ///
+    /// At least one slot is always retained.
+    pub fn new(slots_retained: u64) -> Self {
         Self {
             lowest_permissible_slot: Slot::new(0),
             sets: vec![],
+            slots_retained: std::cmp::max(slots_retained, 1),
             _phantom: PhantomData,
         }
     }
-}
 
-impl<E: EthSpec> ObservedAttestations<E> {
     /// Store the root of `a` in `self`.
     ///
     /// `root` must equal `a.tree_hash_root()`.
@@ -152,30 +174,56 @@ impl<E: EthSpec> ObservedAttestations<E> {
     ///
     /// `root` must equal `a.tree_hash_root()`.
     pub fn is_known(&mut self, a: &Attestation<E>, root: Hash256) -> Result<bool, Error> {
+        self.first_seen(a, root)
+            .map(|first_seen| first_seen.is_some())
+    }
+
+    /// Returns the moment at which the `root` of `a` was first observed, if it is in self.
+    ///
+    /// `root` must equal `a.tree_hash_root()`.
+    pub fn first_seen(
+        &mut self,
+        a: &Attestation<E>,
+        root: Hash256,
+    ) -> Result<Option<Instant>, Error> {
         let index = self.get_set_index(a.data.slot)?;
 
         self.sets
             .get(index)
             .ok_or(Error::InvalidSetIndex(index))
-            .and_then(|set| set.is_known(a, root))
+            .and_then(|set| set.first_seen(a, root))
+    }
+
+    /// Returns the total number of attestation roots stored in `self`.
+    pub fn num_observed(&self) -> usize {
+        self.sets.iter().map(SlotHashSet::len).sum()
     }
 
     /// The maximum number of slots that attestations are stored for.
     fn max_capacity(&self) -> u64 {
-        // We add `2` in order to account for one slot either side of the range due to
-        // `MAXIMUM_GOSSIP_CLOCK_DISPARITY`.
-        E::slots_per_epoch() + 2
+        self.slots_retained
     }
 
-    /// Removes any attestations with a slot lower than `current_slot` and bars any future
-    /// attestations with a slot lower than `current_slot - SLOTS_RETAINED`.
+    /// Removes any attestations with a slot lower than `current_slot - slots_retained + 1` and
+    /// bars any future attestations with a slot lower than that.
+    ///
+    /// The lowest permissible slot never decreases, so pruning with an earlier `current_slot` is
+    /// a no-op.
     pub fn prune(&mut self, current_slot: Slot) {
         // Taking advantage of saturating subtraction on `Slot`.
-        let lowest_permissible_slot = current_slot - (self.max_capacity() - 1);
+        let lowest_permissible_slot = std::cmp::max(
+            current_slot - (self.max_capacity() - 1),
+            self.lowest_permissible_slot,
+        );
 
         self.sets.retain(|set| set.slot >= lowest_permissible_slot);
 
         self.lowest_permissible_slot = lowest_permissible_slot;
+
+        metrics::set_gauge(
+            &metrics::OBSERVED_ATTESTATIONS_NUM_RETAINED,
+            self.num_observed() as i64,
+        );
     }
 
     /// Returns the index of `self.set` that matches `slot`.
@@ -278,9 +326,11 @@ mod tests {
                 Ok(true),
                 "should indicate a known attestation is known"
             );
-            assert_eq!(
-                store.observe_attestation(a, Some(a.tree_hash_root())),
-                Ok(ObserveOutcome::AlreadyKnown),
+            assert!(
+                matches!(
+                    store.observe_attestation(a, Some(a.tree_hash_root())),
+                    Ok(ObserveOutcome::AlreadyKnown { .. })
+                ),
                 "should acknowledge an existing attestation"
             );
         }
@@ -421,4 +471,80 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn prune_drops_old_slots() {
+        let slots_retained = 4;
+        let mut store = ObservedAttestations::<E>::new(slots_retained);
+
+        let attestations = (0..slots_retained * 3)
+            .map(|i| get_attestation(Slot::new(i), i))
+            .collect::<Vec<_>>();
+
+        for a in &attestations {
+            store.prune(a.data.slot);
+            assert_eq!(store.observe_attestation(a, None), Ok(ObserveOutcome::New));
+        }
+
+        let current_slot = attestations.last().unwrap().data.slot;
+        let lowest_permissible_slot = current_slot - (slots_retained - 1);
+        assert_eq!(store.lowest_permissible_slot, lowest_permissible_slot);
+        assert_eq!(store.num_observed(), slots_retained as usize);
+
+        for a in &attestations {
+            if a.data.slot < lowest_permissible_slot {
+                assert_eq!(
+                    store.observe_attestation(a, None),
+                    Err(Error::SlotTooLow {
+                        slot: a.data.slot,
+                        lowest_permissible_slot,
+                    }),
+                    "should have dropped old attestations"
+                );
+            } else {
+                assert!(
+                    matches!(
+                        store.observe_attestation(a, None),
+                        Ok(ObserveOutcome::AlreadyKnown { .. })
+                    ),
+                    "should retain recent attestations"
+                );
+            }
+        }
+
+        // Pruning at an earlier slot does not re-admit old attestations.
+        store.prune(Slot::new(0));
+        assert_eq!(store.lowest_permissible_slot, lowest_permissible_slot);
+        assert_eq!(store.num_observed(), slots_retained as usize);
+    }
+
+    #[test]
+    fn first_seen() {
+        let mut store = ObservedAttestations::default();
+        let a = get_attestation(Slot::new(0), 0);
+        let root = a.tree_hash_root();
+
+        assert_eq!(store.first_seen(&a, root), Ok(None));
+
+        let before = Instant::now();
+        assert_eq!(store.observe_attestation(&a, None), Ok(ObserveOutcome::New));
+        let after = Instant::now();
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+
+        let first_seen = match store.observe_attestation(&a, Some(root)) {
+            Ok(ObserveOutcome::AlreadyKnown { first_seen }) => first_seen,
+            other => panic!(
+                "should acknowledge an existing attestation, got {:?}",
+                other
+            ),
+        };
+        assert!(before <= first_seen && first_seen <= after);
+        assert!(first_seen.elapsed() >= std::time::Duration::from_millis(10));
+        assert_eq!(
+            store.first_seen(&a, root),
+            Ok(Some(first_seen)),
+            "re-observing should not update the first seen time"
+        );
+    }
 }
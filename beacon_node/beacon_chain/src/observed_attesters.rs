@@ -166,6 +166,17 @@ impl<T: Item, E: EthSpec> AutoPruningContainer<T, E> {
     /// Observe that `validator_index` has produced attestation `a`. Returns `Ok(true)` if `a` has
     /// previously been observed for `validator_index`.
     ///
+    /// This is both the check and the observation, so when `self` is behind a lock it is only
+    /// necessary to hold the write lock for this single call to know whether this caller was the
+    /// first to observe `validator_index`.
+    ///
+    /// ## Notes
+    ///
+    /// Gossip verification additionally calls `Self::validator_has_been_observed` under a read
+    /// lock before signature verification, as a cheap way to drop duplicates early. The
+    /// observation itself must wait until after the signature has been verified, otherwise an
+    /// attestation with an invalid signature could prevent a valid one from being accepted.
+    ///
     /// ## Errors
     ///
     /// - `validator_index` is higher than `VALIDATOR_REGISTRY_LIMIT`.
@@ -332,6 +343,45 @@ mod tests {
                     }
                 }
 
+                #[test]
+                fn concurrent_observations() {
+                    use parking_lot::RwLock;
+                    use std::sync::{Arc, Barrier};
+                    use std::thread;
+
+                    let num_threads = 16;
+                    let store = Arc::new(RwLock::new($type::default()));
+                    let barrier = Arc::new(Barrier::new(num_threads));
+                    let a = Arc::new(get_attestation(Epoch::new(0)));
+
+                    let handles = (0..num_threads)
+                        .map(|_| {
+                            let store = store.clone();
+                            let barrier = barrier.clone();
+                            let a = a.clone();
+                            thread::spawn(move || {
+                                barrier.wait();
+                                (0..100)
+                                    .filter(|_| {
+                                        store.write().observe_validator(&a, 42) == Ok(false)
+                                    })
+                                    .count()
+                            })
+                        })
+                        .collect::<Vec<_>>();
+
+                    let first_observations: usize = handles
+                        .into_iter()
+                        .map(|handle| handle.join().expect("thread should not panic"))
+                        .sum();
+
+                    assert_eq!(
+                        first_observations, 1,
+                        "exactly one observation should be the first"
+                    );
+                    assert_eq!(store.read().validator_has_been_observed(&a, 42), Ok(true));
+                }
+
                 #[test]
                 fn single_epoch() {
                     let mut store = $type::default();
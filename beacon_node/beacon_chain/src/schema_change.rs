@@ -1,6 +1,8 @@
 //! Utilities for managing database schema changes.
-use crate::beacon_chain::BeaconChainTypes;
+use crate::beacon_chain::{BeaconChainTypes, FORK_CHOICE_DB_KEY};
+use crate::persisted_fork_choice::PersistedForkChoice;
 use crate::validator_pubkey_cache::ValidatorPubkeyCache;
+use proto_array::ProtoArrayForkChoice;
 use std::fs;
 use std::path::Path;
 use std::sync::Arc;
@@ -54,6 +56,24 @@ pub fn migrate_schema<T: BeaconChainTypes>(
 
             Ok(())
         }
+        // Migration for adding the invalid block flag to the fork choice nodes.
+        (SchemaVersion(3), SchemaVersion(4)) => {
+            if let Some(mut persisted_fork_choice) =
+                db.get_item::<PersistedForkChoice>(&FORK_CHOICE_DB_KEY)?
+            {
+                let proto_array = ProtoArrayForkChoice::from_legacy_bytes(
+                    &persisted_fork_choice.fork_choice.proto_array_bytes,
+                )
+                .map_err(StoreError::SchemaMigrationError)?;
+                persisted_fork_choice.fork_choice.proto_array_bytes = proto_array.as_bytes();
+
+                db.put_item(&FORK_CHOICE_DB_KEY, &persisted_fork_choice)?;
+            }
+
+            db.store_schema_version(to)?;
+
+            Ok(())
+        }
         // Anything else is an error.
         (_, _) => Err(HotColdDBError::UnsupportedSchemaVersion {
             target_version: to,
@@ -143,6 +143,14 @@ async fn state_advance_timer<T: BeaconChainTypes>(
                         ),
                     };
 
+                    if let Err(e) = prime_next_epoch_shuffling(&beacon_chain) {
+                        warn!(
+                            log,
+                            "Failed to prime shuffling cache";
+                            "error" => ?e
+                        );
+                    }
+
                     // Permit this blocking task to spawn again, next time the timer fires.
                     is_running.unlock();
                 },
@@ -158,6 +166,32 @@ async fn state_advance_timer<T: BeaconChainTypes>(
     }
 }
 
+/// If the next slot is the first slot of an epoch, ensure the attester shuffling for that epoch is
+/// in the `shuffling_cache`.
+///
+/// When `advance_head` succeeds across an epoch boundary it has already inserted the shuffling, so
+/// this is a cache hit. It is useful when the head could not be advanced (e.g., the head is
+/// several slots behind due to skipped slots), since otherwise every attestation in the first
+/// slots of the epoch would race to load and advance a state.
+fn prime_next_epoch_shuffling<T: BeaconChainTypes>(
+    beacon_chain: &BeaconChain<T>,
+) -> Result<(), BeaconChainError> {
+    let slots_per_epoch = T::EthSpec::slots_per_epoch();
+    let next_slot = beacon_chain.slot()? + 1;
+
+    if next_slot % slots_per_epoch != 0 {
+        return Ok(());
+    }
+
+    // Don't prime whilst syncing, the shuffling is unlikely to be useful.
+    let head_slot = beacon_chain.head_info()?.slot;
+    if head_slot + slots_per_epoch < next_slot {
+        return Ok(());
+    }
+
+    beacon_chain.prime_shuffling_cache(next_slot.epoch(slots_per_epoch))
+}
+
 /// Reads the `snapshot_cache` from the `beacon_chain` and attempts to take a clone of the
 /// `BeaconState` of the head block. If it obtains this clone, the state will be advanced a single
 /// slot then placed back in the `snapshot_cache` to be used for block verification.
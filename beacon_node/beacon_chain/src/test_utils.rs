@@ -7,6 +7,7 @@ pub use crate::{
 use crate::{
     builder::{BeaconChainBuilder, Witness},
     eth1_chain::CachingEth1Backend,
+    validator_monitor::timestamp_now,
     BeaconChain, BeaconChainTypes, BlockError, ChainConfig, ServerSentEventHandler,
     StateSkipConfig,
 };
@@ -700,7 +701,8 @@ where
         block: SignedBeaconBlock<E>,
     ) -> Result<SignedBeaconBlockHash, BlockError<E>> {
         self.set_current_slot(slot);
-        let block_hash: SignedBeaconBlockHash = self.chain.process_block(block)?.into();
+        let block_hash: SignedBeaconBlockHash =
+            self.chain.process_block(block, timestamp_now())?.into();
         self.chain.fork_choice()?;
         Ok(block_hash)
     }
@@ -709,7 +711,8 @@ where
         &self,
         block: SignedBeaconBlock<E>,
     ) -> Result<SignedBeaconBlockHash, BlockError<E>> {
-        let block_hash: SignedBeaconBlockHash = self.chain.process_block(block)?.into();
+        let block_hash: SignedBeaconBlockHash =
+            self.chain.process_block(block, timestamp_now())?.into();
         self.chain.fork_choice().unwrap();
         Ok(block_hash)
     }
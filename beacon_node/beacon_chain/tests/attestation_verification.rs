@@ -4,20 +4,25 @@
 extern crate lazy_static;
 
 use beacon_chain::{
-    attestation_verification::Error as AttnError,
+    attestation_verification::{
+        verify_attestation_signatures_batch, verify_propagation_slot_range, Error as AttnError,
+    },
     test_utils::{AttestationStrategy, BeaconChainHarness, BlockStrategy, EphemeralHarnessType},
-    BeaconChain, BeaconChainTypes, WhenSlotSkipped,
+    BeaconChain, BeaconChainTypes, ChainConfig, WhenSlotSkipped, MAXIMUM_GOSSIP_CLOCK_DISPARITY,
 };
 use int_to_bytes::int_to_bytes32;
+use slot_clock::SlotClock;
 use state_processing::{
-    per_block_processing::errors::AttestationValidationError, per_slot_processing,
+    common::get_indexed_attestation, per_block_processing::errors::AttestationValidationError,
+    per_slot_processing,
 };
+use std::time::Duration;
 use store::config::StoreConfig;
 use tree_hash::TreeHash;
 use types::{
     test_utils::generate_deterministic_keypair, AggregateSignature, Attestation, BeaconStateError,
     BitList, EthSpec, Hash256, Keypair, MainnetEthSpec, SecretKey, SelectionProof,
-    SignedAggregateAndProof, SignedBeaconBlock, SubnetId, Unsigned,
+    SignedAggregateAndProof, SignedBeaconBlock, Slot, SubnetId, Unsigned,
 };
 
 pub type E = MainnetEthSpec;
@@ -256,7 +261,7 @@ fn aggregated_gossip_verification() {
 
     let early_slot = current_slot
         .as_u64()
-        .checked_sub(E::slots_per_epoch() + 2)
+        .checked_sub(harness.spec.attestation_propagation_slot_range + 2)
         .expect("chain is not sufficiently deep for test")
         .into();
     assert_invalid!(
@@ -273,7 +278,7 @@ fn aggregated_gossip_verification() {
             earliest_permissible_slot
         }
         if attestation_slot == early_slot
-            && earliest_permissible_slot == current_slot - E::slots_per_epoch() - 1
+            && earliest_permissible_slot == current_slot - harness.spec.attestation_propagation_slot_range - 1
     );
 
     /*
@@ -495,6 +500,20 @@ fn aggregated_gossip_verification() {
 
     let (non_aggregator_index, non_aggregator_sk) =
         get_non_aggregator(&harness.chain, &valid_aggregate.message.aggregate);
+    let expected_committee_len = harness
+        .chain
+        .head()
+        .expect("should get head")
+        .beacon_state
+        .get_beacon_committee(
+            valid_aggregate.message.aggregate.data.slot,
+            valid_aggregate.message.aggregate.data.index,
+        )
+        .expect("should get committee")
+        .committee
+        .len();
+    let expected_modulo = SelectionProof::modulo(expected_committee_len, &harness.chain.spec)
+        .expect("should compute modulo");
     assert_invalid!(
         "aggregate from non-aggregator",
         {
@@ -509,9 +528,13 @@ fn aggregated_gossip_verification() {
             )
         },
         AttnError::InvalidSelectionProof {
-            aggregator_index: index
+            aggregator_index: index,
+            committee_len,
+            modulo,
         }
         if index == non_aggregator_index as u64
+            && committee_len == expected_committee_len
+            && modulo == expected_modulo
     );
 
     // NOTE: from here on, the tests are stateful, and rely on the valid attestation having been
@@ -691,7 +714,7 @@ fn unaggregated_gossip_verification() {
 
     let early_slot = current_slot
         .as_u64()
-        .checked_sub(E::slots_per_epoch() + 2)
+        .checked_sub(harness.spec.attestation_propagation_slot_range + 2)
         .expect("chain is not sufficiently deep for test")
         .into();
     assert_invalid!(
@@ -709,7 +732,7 @@ fn unaggregated_gossip_verification() {
             // slot and the propagation tolerance will allow an extra slot.
             earliest_permissible_slot,
         }
-        if attestation_slot == early_slot && earliest_permissible_slot == current_slot - E::slots_per_epoch() - 1
+        if attestation_slot == early_slot && earliest_permissible_slot == current_slot - harness.spec.attestation_propagation_slot_range - 1
     );
 
     /*
@@ -962,3 +985,564 @@ fn attestation_that_skips_epochs() {
         .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id))
         .expect("should gossip verify attestation that skips slots");
 }
+
+/// Ensures that the committees for an attestation which skips epochs are only computed once, with
+/// the second attestation for the same target being served from the shuffling cache.
+#[test]
+fn attestation_shuffling_is_cached() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 3 + 1,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::SomeValidators(vec![]),
+    );
+
+    let head = harness.chain.head().expect("should get head");
+    let head_root = head.beacon_block_root;
+
+    // Skip two epochs without producing blocks, so the shuffling for the current epoch is not
+    // known from block import.
+    for _ in 0..MainnetEthSpec::slots_per_epoch() * 2 {
+        harness.advance_slot();
+    }
+
+    let current_slot = harness.chain.slot().expect("should get slot");
+    let current_epoch = harness.chain.epoch().expect("should get epoch");
+
+    let mut state = head.beacon_state;
+    while state.slot < current_slot {
+        per_slot_processing(&mut state, None, &harness.spec).expect("should process slot");
+    }
+    let state_root = state.update_tree_hash_cache().unwrap();
+
+    let attestations: Vec<_> = harness
+        .get_unaggregated_attestations(
+            &AttestationStrategy::AllValidators,
+            &state,
+            state_root,
+            head_root,
+            current_slot,
+        )
+        .into_iter()
+        .flatten()
+        .take(2)
+        .collect();
+    assert_eq!(attestations.len(), 2, "should have two attestations");
+
+    for (attestation, _) in &attestations {
+        assert_eq!(attestation.data.target.root, head_root);
+    }
+
+    assert!(
+        !harness
+            .chain
+            .shuffling_is_cached(head_root, current_epoch)
+            .unwrap(),
+        "shuffling should not be cached before the first attestation"
+    );
+
+    let mut attestations = attestations.into_iter();
+
+    let (attestation, subnet_id) = attestations.next().unwrap();
+    harness
+        .chain
+        .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id))
+        .expect("should verify first attestation");
+
+    assert!(
+        harness
+            .chain
+            .shuffling_is_cached(head_root, current_epoch)
+            .unwrap(),
+        "the second attestation should hit the shuffling cache"
+    );
+
+    let (attestation, subnet_id) = attestations.next().unwrap();
+    harness
+        .chain
+        .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id))
+        .expect("should verify second attestation");
+
+    // Priming the cache for the next epoch makes its shuffling available ahead of time.
+    let next_epoch = current_epoch + 1;
+    assert!(!harness
+        .chain
+        .shuffling_is_cached(head_root, next_epoch)
+        .unwrap());
+    harness
+        .chain
+        .prime_shuffling_cache(next_epoch)
+        .expect("should prime shuffling cache");
+    assert!(harness
+        .chain
+        .shuffling_is_cached(head_root, next_epoch)
+        .unwrap());
+}
+
+/// Ensures that an attestation for a block prior to finalization is distinguished from an
+/// attestation for a block which is genuinely unknown.
+#[test]
+fn attestation_for_finalized_head_block() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 5,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+    harness.advance_slot();
+
+    let finalized_checkpoint = harness
+        .chain
+        .head_info()
+        .expect("should get head info")
+        .finalized_checkpoint;
+    assert!(
+        finalized_checkpoint.epoch > 0,
+        "the chain should have finalized"
+    );
+    let finalized_slot = finalized_checkpoint
+        .epoch
+        .start_slot(MainnetEthSpec::slots_per_epoch());
+
+    let old_block_root = harness
+        .chain
+        .block_at_slot(Slot::new(1), WhenSlotSkipped::None)
+        .expect("should not error getting block at slot")
+        .expect("should find block at slot")
+        .canonical_root();
+
+    let (valid_attestation, _, _, _, subnet_id) =
+        get_valid_unaggregated_attestation(&harness.chain);
+
+    let mut attestation = valid_attestation.clone();
+    attestation.data.beacon_block_root = old_block_root;
+    assert!(
+        matches!(
+            harness
+                .chain
+                .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id)),
+            Err(AttnError::HeadBlockFinalized {
+                beacon_block_root,
+                finalized_slot: slot,
+            }) if beacon_block_root == old_block_root && slot == finalized_slot
+        ),
+        "should reject attestation for finalized block"
+    );
+
+    let unknown_root = Hash256::from_low_u64_le(424242);
+    let mut attestation = valid_attestation;
+    attestation.data.beacon_block_root = unknown_root;
+    assert!(
+        matches!(
+            harness
+                .chain
+                .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id)),
+            Err(AttnError::UnknownHeadBlock { beacon_block_root })
+                if beacon_block_root == unknown_root
+        ),
+        "should reject attestation for unknown block"
+    );
+}
+
+/// Ensures that the earliest permissible attestation slot is derived from
+/// `ATTESTATION_PROPAGATION_SLOT_RANGE`, with the range being inclusive of its lower bound.
+#[test]
+fn attestation_propagation_slot_range_boundaries() {
+    let harness = get_harness(VALIDATOR_COUNT);
+    let range = harness.spec.attestation_propagation_slot_range;
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::SomeValidators(vec![]),
+    );
+
+    let (mut attestation, _, _, _, _) = get_valid_unaggregated_attestation(&harness.chain);
+    let current_slot = harness.chain.slot().expect("should get slot");
+    assert!(
+        current_slot > range,
+        "chain is not sufficiently deep for test"
+    );
+
+    // Position the clock in the middle of the slot, so the clock disparity does not extend the
+    // range into the previous slot.
+    let slot_clock = &harness.chain.slot_clock;
+    slot_clock.set_current_time(
+        slot_clock
+            .start_of(current_slot)
+            .expect("should get slot start")
+            + slot_clock.slot_duration() / 2,
+    );
+
+    let earliest_permissible_slot = current_slot - range;
+
+    // `attestation.data.slot + ATTESTATION_PROPAGATION_SLOT_RANGE >= current_slot`.
+    attestation.data.slot = earliest_permissible_slot;
+    assert!(
+        verify_propagation_slot_range(&harness.chain, &attestation).is_ok(),
+        "the earliest permissible slot should be accepted"
+    );
+
+    attestation.data.slot = earliest_permissible_slot - 1;
+    assert!(
+        matches!(
+            verify_propagation_slot_range(&harness.chain, &attestation),
+            Err(AttnError::PastSlot {
+                attestation_slot,
+                earliest_permissible_slot: earliest,
+            }) if attestation_slot == earliest_permissible_slot - 1
+                && earliest == earliest_permissible_slot
+        ),
+        "the slot prior to the earliest permissible slot should be rejected"
+    );
+
+    // `current_slot >= attestation.data.slot`.
+    attestation.data.slot = current_slot;
+    assert!(
+        verify_propagation_slot_range(&harness.chain, &attestation).is_ok(),
+        "the current slot should be accepted"
+    );
+
+    attestation.data.slot = current_slot + 1;
+    assert!(
+        matches!(
+            verify_propagation_slot_range(&harness.chain, &attestation),
+            Err(AttnError::FutureSlot {
+                attestation_slot,
+                latest_permissible_slot,
+            }) if attestation_slot == current_slot + 1 && latest_permissible_slot == current_slot
+        ),
+        "the next slot should be rejected"
+    );
+}
+
+/// Ensures that batch signature verification reports the validity of each attestation
+/// individually when the batch contains a mix of valid and invalid signatures.
+#[test]
+fn batch_signature_verification_mixed_validity() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::SomeValidators(vec![]),
+    );
+
+    let head = harness.chain.head().expect("should get head");
+
+    let mut indexed_attestations = harness
+        .get_unaggregated_attestations(
+            &AttestationStrategy::AllValidators,
+            &head.beacon_state,
+            head.beacon_state_root(),
+            head.beacon_block_root,
+            head.beacon_state.slot,
+        )
+        .into_iter()
+        .flatten()
+        .take(8)
+        .map(|(attestation, _subnet_id)| {
+            let committee = head
+                .beacon_state
+                .get_beacon_committee(attestation.data.slot, attestation.data.index)
+                .expect("should get committee");
+            get_indexed_attestation(committee.committee, &attestation)
+                .expect("should get indexed attestation")
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(indexed_attestations.len(), 8, "should have 8 attestations");
+
+    assert_eq!(
+        verify_attestation_signatures_batch(&harness.chain, &indexed_attestations)
+            .expect("should verify batch"),
+        vec![true; 8],
+        "all signatures should be valid"
+    );
+
+    // Swap the signatures of two attestations by different validators, invalidating both.
+    let signature = indexed_attestations[1].signature.clone();
+    indexed_attestations[1].signature = indexed_attestations[4].signature.clone();
+    indexed_attestations[4].signature = signature;
+
+    assert_eq!(
+        verify_attestation_signatures_batch(&harness.chain, &indexed_attestations)
+            .expect("should verify batch"),
+        vec![true, false, true, true, false, true, true, true],
+        "only the swapped signatures should be invalid"
+    );
+
+    // Restore the signature of one attestation and attribute the other to an unknown validator,
+    // which should invalidate only that attestation.
+    indexed_attestations[4].signature = indexed_attestations[1].signature.clone();
+    indexed_attestations[1].attesting_indices = vec![VALIDATOR_COUNT as u64 + 1].into();
+
+    assert_eq!(
+        verify_attestation_signatures_batch(&harness.chain, &indexed_attestations)
+            .expect("should verify batch"),
+        vec![true, false, true, true, true, true, true, true],
+        "an unknown validator should not fail the whole batch"
+    );
+
+    assert!(
+        verify_attestation_signatures_batch(&harness.chain, &[])
+            .expect("should verify empty batch")
+            .is_empty(),
+        "an empty batch should have no results"
+    );
+}
+
+/// Ensures that batch verification of aggregates reports the validity of each aggregate
+/// individually when the batch contains a mix of valid and tampered aggregates.
+#[test]
+fn aggregate_batch_verification_mixed_validity() {
+    let harness = get_harness(VALIDATOR_COUNT);
+    let all_validators = (0..VALIDATOR_COUNT).collect::<Vec<_>>();
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+
+    // Collect one aggregate from each of the next few slots.
+    let mut signed_aggregates = vec![];
+    for _ in 0..5 {
+        harness.extend_chain(
+            1,
+            BlockStrategy::OnCanonicalHead,
+            AttestationStrategy::SomeValidators(vec![]),
+        );
+        let head = harness.chain.head().expect("should get head");
+        signed_aggregates.extend(
+            harness
+                .make_attestations(
+                    &all_validators,
+                    &head.beacon_state,
+                    head.beacon_state_root(),
+                    head.beacon_block_root.into(),
+                    head.beacon_state.slot,
+                )
+                .into_iter()
+                .filter_map(|(_, signed_aggregate)| signed_aggregate)
+                .take(1),
+        );
+    }
+    assert_eq!(signed_aggregates.len(), 5, "should have 5 aggregates");
+
+    assert_eq!(
+        harness
+            .chain
+            .verify_aggregates_batch(&signed_aggregates)
+            .expect("should verify batch"),
+        vec![true; 5],
+        "all aggregates should be valid"
+    );
+
+    // Tamper with the outer signature of one aggregate, the selection proof of another and the
+    // aggregator index of a third.
+    signed_aggregates[1].signature = signed_aggregates[0].signature.clone();
+    signed_aggregates[3].message.selection_proof =
+        signed_aggregates[2].message.selection_proof.clone();
+    signed_aggregates[4].message.aggregator_index = VALIDATOR_COUNT as u64;
+
+    assert_eq!(
+        harness
+            .chain
+            .verify_aggregates_batch(&signed_aggregates)
+            .expect("should verify batch"),
+        vec![true, false, true, false, false],
+        "only the tampered aggregates should be invalid"
+    );
+
+    assert!(
+        harness
+            .chain
+            .verify_aggregates_batch(&[])
+            .expect("should verify empty batch")
+            .is_empty(),
+        "an empty batch should have no results"
+    );
+}
+
+/// Ensures that the attesting indices of an attestation are cached, so that verifying an identical
+/// attestation does not require the committee to be resolved again.
+#[test]
+fn indexed_attestation_cache_hit_on_identical_attestation() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::SomeValidators(vec![]),
+    );
+
+    let (attestation, validator_index, _, _, subnet_id) =
+        get_valid_unaggregated_attestation(&harness.chain);
+
+    let cache_stats = || {
+        let cache = harness.chain.indexed_attestation_cache.read();
+        (cache.hits(), cache.misses())
+    };
+
+    let (initial_hits, initial_misses) = cache_stats();
+
+    let verified = harness
+        .chain
+        .verify_unaggregated_attestation_for_gossip(attestation.clone(), Some(subnet_id))
+        .expect("should verify attestation");
+
+    assert_eq!(
+        cache_stats(),
+        (initial_hits, initial_misses + 1),
+        "the first verification should miss the cache"
+    );
+
+    let result = harness
+        .chain
+        .verify_unaggregated_attestation_for_gossip(attestation.clone(), Some(subnet_id));
+    assert!(
+        matches!(result, Err(AttnError::PriorAttestationKnown { .. })),
+        "the second verification should fail after the committee is resolved"
+    );
+
+    assert_eq!(
+        cache_stats(),
+        (initial_hits + 1, initial_misses + 1),
+        "the second verification should hit the cache"
+    );
+
+    let (cached, _) = harness
+        .chain
+        .indexed_attestation_cache
+        .read()
+        .get(&attestation)
+        .expect("attestation should be cached");
+
+    assert_eq!(
+        &cached,
+        verified.indexed_attestation(),
+        "cached result should match the original"
+    );
+    assert_eq!(
+        cached.attesting_indices.to_vec(),
+        vec![validator_index as u64],
+        "cached result should contain the attester"
+    );
+}
+
+/// Ensures that cached attesting indices are discarded once fork choice has been pruned, since
+/// their target blocks may no longer be known.
+#[test]
+fn indexed_attestation_cache_cleared_on_finalization() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 2,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::SomeValidators(vec![]),
+    );
+
+    let (attestation, _, _, _, subnet_id) = get_valid_unaggregated_attestation(&harness.chain);
+
+    harness
+        .chain
+        .verify_unaggregated_attestation_for_gossip(attestation.clone(), Some(subnet_id))
+        .expect("should verify attestation");
+    assert!(
+        harness
+            .chain
+            .indexed_attestation_cache
+            .read()
+            .get(&attestation)
+            .is_some(),
+        "attestation should be cached"
+    );
+
+    harness.extend_chain(
+        MainnetEthSpec::slots_per_epoch() as usize * 5,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+    assert!(
+        harness
+            .chain
+            .head_info()
+            .expect("should get head info")
+            .finalized_checkpoint
+            .epoch
+            > 0,
+        "the chain should have finalized"
+    );
+
+    assert!(
+        harness
+            .chain
+            .indexed_attestation_cache
+            .read()
+            .get(&attestation)
+            .is_none(),
+        "attestation should be removed from the cache"
+    );
+}
+
+/// Ensures that the gossip clock disparity is read from the `ChainConfig`, such that a chain with
+/// an enlarged disparity accepts an attestation which would otherwise be from a future slot.
+#[test]
+fn attestation_within_configured_clock_disparity() {
+    let disparity = Duration::from_millis(1_000);
+
+    let default_harness = get_harness(VALIDATOR_COUNT);
+    let enlarged_harness = BeaconChainHarness::new_with_chain_config(
+        MainnetEthSpec,
+        KEYPAIRS[0..VALIDATOR_COUNT].to_vec(),
+        4,
+        StoreConfig::default(),
+        ChainConfig {
+            maximum_gossip_clock_disparity_millis: disparity.as_millis() as u64,
+            ..ChainConfig::default()
+        },
+    );
+    enlarged_harness.advance_slot();
+
+    for harness in &[&default_harness, &enlarged_harness] {
+        harness.extend_chain(
+            MainnetEthSpec::slots_per_epoch() as usize,
+            BlockStrategy::OnCanonicalHead,
+            AttestationStrategy::SomeValidators(vec![]),
+        );
+    }
+
+    let (mut attestation, _, _, _, _) = get_valid_unaggregated_attestation(&default_harness.chain);
+    let future_slot = default_harness.chain.slot().expect("should get slot") + 1;
+    attestation.data.slot = future_slot;
+
+    // Position both clocks such that the attestation is beyond the default disparity, but within
+    // the enlarged one.
+    let borderline_time = |chain: &BeaconChain<EphemeralHarnessType<E>>| {
+        chain
+            .slot_clock
+            .start_of(future_slot)
+            .expect("should get slot start")
+            - (MAXIMUM_GOSSIP_CLOCK_DISPARITY + disparity) / 2
+    };
+    for harness in &[&default_harness, &enlarged_harness] {
+        harness
+            .chain
+            .slot_clock
+            .set_current_time(borderline_time(&harness.chain));
+    }
+
+    assert!(
+        matches!(
+            verify_propagation_slot_range(&default_harness.chain, &attestation),
+            Err(AttnError::FutureSlot { attestation_slot, .. }) if attestation_slot == future_slot
+        ),
+        "default disparity should reject the attestation"
+    );
+    assert!(
+        verify_propagation_slot_range(&enlarged_harness.chain, &attestation).is_ok(),
+        "enlarged disparity should accept the attestation"
+    );
+}
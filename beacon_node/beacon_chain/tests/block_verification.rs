@@ -5,6 +5,7 @@ extern crate lazy_static;
 
 use beacon_chain::{
     test_utils::{AttestationStrategy, BeaconChainHarness, BlockStrategy, EphemeralHarnessType},
+    validator_monitor::timestamp_now,
     BeaconSnapshot, BlockError,
 };
 use slasher::{Config as SlasherConfig, Slasher};
@@ -313,7 +314,7 @@ fn assert_invalid_signature(
         matches!(
             harness
                 .chain
-                .process_block(snapshots[block_index].beacon_block.clone()),
+                .process_block(snapshots[block_index].beacon_block.clone(), timestamp_now(),),
             Err(BlockError::InvalidSignature)
         ),
         "should not import individual block with an invalid {} signature",
@@ -358,7 +359,7 @@ fn invalid_signature_gossip_block() {
             matches!(
                 harness
                     .chain
-                    .process_block(snapshots[block_index].beacon_block.clone()),
+                    .process_block(snapshots[block_index].beacon_block.clone(), timestamp_now(),),
                 Err(BlockError::InvalidSignature)
             ),
             "should not import individual block with an invalid gossip signature",
@@ -594,7 +595,7 @@ fn block_gossip_verification() {
 
         harness
             .chain
-            .process_block(gossip_verified)
+            .process_block(gossip_verified, timestamp_now())
             .expect("should import valid gossip verified block");
     }
 
@@ -817,7 +818,10 @@ fn verify_block_for_gossip_slashing_detection() {
     let (block2, _) = harness.make_block(state, Slot::new(1));
 
     let verified_block = harness.chain.verify_block_for_gossip(block1).unwrap();
-    harness.chain.process_block(verified_block).unwrap();
+    harness
+        .chain
+        .process_block(verified_block, timestamp_now())
+        .unwrap();
     unwrap_err(harness.chain.verify_block_for_gossip(block2));
 
     // Slasher should have been handed the two conflicting blocks and crafted a slashing.
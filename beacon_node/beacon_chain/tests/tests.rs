@@ -5,10 +5,12 @@ extern crate lazy_static;
 
 use beacon_chain::{
     attestation_verification::Error as AttnError,
+    events::EventKind,
     test_utils::{
         AttestationStrategy, BeaconChainHarness, BlockStrategy, EphemeralHarnessType,
         OP_POOL_DB_KEY,
     },
+    validator_monitor::timestamp_now,
     WhenSlotSkipped,
 };
 use operation_pool::PersistedOperationPool;
@@ -188,6 +190,67 @@ fn chooses_fork() {
     );
 }
 
+#[test]
+fn emits_chain_reorg_event() {
+    let harness = get_harness(VALIDATOR_COUNT);
+
+    let two_thirds = (VALIDATOR_COUNT / 3) * 2;
+    let delay = MinimalEthSpec::default_spec().min_attestation_inclusion_delay as usize;
+
+    let majority_validators: Vec<usize> = (0..two_thirds).collect();
+    let minority_validators: Vec<usize> = (two_thirds..VALIDATOR_COUNT).collect();
+
+    let initial_blocks = delay + 1;
+    let minority_fork_blocks = delay + 1;
+    let majority_fork_blocks = delay + 2;
+
+    harness.extend_chain(
+        initial_blocks,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+
+    let mut reorgs = harness
+        .chain
+        .event_handler
+        .as_ref()
+        .expect("should have event handler")
+        .subscribe_reorgs();
+
+    // Build the minority fork first so that it becomes the head, then re-org to the majority fork.
+    let (minority_head, majority_head) = harness.generate_two_forks_by_skipping_a_block(
+        &minority_validators,
+        &majority_validators,
+        minority_fork_blocks,
+        majority_fork_blocks,
+    );
+
+    assert_eq!(
+        harness
+            .chain
+            .head()
+            .expect("should get head")
+            .beacon_block_root,
+        majority_head,
+        "the majority fork should be the canonical chain"
+    );
+
+    let reorg = match reorgs.try_recv().expect("should emit a re-org event") {
+        EventKind::ChainReorg(reorg) => reorg,
+        other => panic!("unexpected event {:?}", other),
+    };
+
+    assert_eq!(reorg.old_head_block, minority_head);
+    assert_eq!(
+        reorg.depth, minority_fork_blocks as u64,
+        "the re-org should revert the whole minority fork"
+    );
+    assert!(
+        reorgs.try_recv().is_err(),
+        "there should only be one re-org event"
+    );
+}
+
 #[test]
 fn finalizes_with_full_participation() {
     let num_blocks_produced = MinimalEthSpec::slots_per_epoch() * 5;
@@ -579,6 +642,7 @@ fn run_skip_slot_test(skip_slots: u64) {
                     .expect("should get head")
                     .beacon_block
                     .clone(),
+                timestamp_now(),
             )
             .unwrap(),
         harness_a
@@ -754,3 +818,64 @@ fn block_roots_skip_slot_behaviour() {
         "WhenSlotSkipped::Prev should return None on a future slot"
     );
 }
+
+#[test]
+fn head_event_at_epoch_boundary() {
+    let slots_per_epoch = MinimalEthSpec::slots_per_epoch();
+    let harness = get_harness(VALIDATOR_COUNT);
+    let root_at_slot = |slot: u64| {
+        harness
+            .chain
+            .block_root_at_slot(Slot::new(slot), WhenSlotSkipped::Prev)
+            .unwrap()
+            .unwrap()
+    };
+
+    // Build a chain with the head at the first slot of epoch 2.
+    let boundary_slot = slots_per_epoch * 2;
+    let head_root = harness.extend_chain(
+        boundary_slot as usize,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+
+    let head = harness.chain.head_event(head_root).unwrap();
+    assert_eq!(head.slot, boundary_slot);
+    assert_eq!(head.block, head_root);
+    assert_eq!(
+        head.state,
+        harness.chain.head().unwrap().beacon_state_root()
+    );
+    assert!(head.epoch_transition);
+    assert_eq!(
+        head.current_duty_dependent_root,
+        root_at_slot(boundary_slot - 1)
+    );
+    assert_eq!(
+        head.previous_duty_dependent_root,
+        root_at_slot(boundary_slot - slots_per_epoch - 1)
+    );
+    assert_ne!(
+        head.current_duty_dependent_root,
+        head.previous_duty_dependent_root
+    );
+
+    // The next block in the same epoch is not an epoch transition and shares dependent roots.
+    harness.advance_slot();
+    let next_root = harness.extend_chain(
+        1,
+        BlockStrategy::OnCanonicalHead,
+        AttestationStrategy::AllValidators,
+    );
+    let next = harness.chain.head_event(next_root).unwrap();
+    assert_eq!(next.slot, boundary_slot + 1);
+    assert!(!next.epoch_transition);
+    assert_eq!(
+        next.current_duty_dependent_root,
+        head.current_duty_dependent_root
+    );
+    assert_eq!(
+        next.previous_duty_dependent_root,
+        head.previous_duty_dependent_root
+    );
+}
@@ -68,6 +68,7 @@ impl<TSpec: EthSpec> PeerScoreSettings<TSpec> {
         active_validators: usize,
         thresholds: &PeerScoreThresholds,
         enr_fork_id: &EnrForkId,
+        gossip_encodings: &HashMap<GossipKind, GossipEncoding>,
         current_slot: Slot,
     ) -> error::Result<PeerScoreParams> {
         let mut params = PeerScoreParams {
@@ -93,8 +94,8 @@ impl<TSpec: EthSpec> PeerScoreSettings<TSpec> {
         params.topics = HashMap::new();
 
         let get_hash = |kind: GossipKind| -> TopicHash {
-            let topic: Topic =
-                GossipTopic::new(kind, GossipEncoding::default(), enr_fork_id.fork_digest).into();
+            let encoding = gossip_encodings.get(&kind).cloned().unwrap_or_default();
+            let topic: Topic = GossipTopic::new(kind, encoding, enr_fork_id.fork_digest).into();
             topic.hash()
         };
 
@@ -17,12 +17,13 @@ use libp2p::{
     core::{
         connection::{ConnectedPoint, ConnectionId, ListenerId},
         identity::Keypair,
+        multiaddr::Protocol as MProtocol,
         Multiaddr,
     },
     gossipsub::{
         subscription_filter::{MaxCountSubscriptionFilter, WhitelistSubscriptionFilter},
         Gossipsub as BaseGossipsub, GossipsubEvent, IdentTopic as Topic, MessageAcceptance,
-        MessageAuthenticity, MessageId, PeerScoreThresholds,
+        MessageAuthenticity, MessageId, PeerScoreThresholds, PublishError,
     },
     identify::{Identify, IdentifyEvent},
     swarm::{
@@ -33,7 +34,7 @@ use libp2p::{
 };
 use slog::{crit, debug, o, trace, warn};
 use ssz::Encode;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
@@ -48,7 +49,6 @@ use types::{ChainSpec, EnrForkId, EthSpec, SignedBeaconBlock, Slot, SubnetId};
 mod gossipsub_scoring_parameters;
 mod handler;
 
-const MAX_IDENTIFY_ADDRESSES: usize = 10;
 pub const GOSSIPSUB_GREYLIST_THRESHOLD: f64 = -16000.0;
 
 /// Identifier of requests sent by a peer.
@@ -72,6 +72,8 @@ pub enum BehaviourEvent<TSpec: EthSpec> {
         id: RequestId,
         /// The peer to which this request was sent.
         peer_id: PeerId,
+        /// The reason the request failed.
+        error: RPCError,
     },
     RequestReceived {
         /// The peer that sent the request.
@@ -103,6 +105,23 @@ pub enum BehaviourEvent<TSpec: EthSpec> {
     StatusPeer(PeerId),
 }
 
+/// The result of publishing a message on a single gossipsub topic.
+#[derive(Debug)]
+pub struct PublishOutcome {
+    /// The topic the message was published on.
+    pub topic: GossipTopic,
+    /// The id of the published message, or the reason it could not be published.
+    pub result: Result<MessageId, PublishError>,
+}
+
+impl PublishOutcome {
+    /// Returns `true` if the message could not be published because there were no peers to send
+    /// it to.
+    pub fn has_no_recipients(&self) -> bool {
+        matches!(self.result, Err(PublishError::InsufficientPeers))
+    }
+}
+
 /// Builds the network behaviour that manages the core protocols of eth2.
 /// This core behaviour is managed by `Behaviour` which adds peer management to all core
 /// behaviours.
@@ -127,10 +146,17 @@ pub struct Behaviour<TSpec: EthSpec> {
     // NOTE: This can be accessed via the network_globals ENR. However we keep it here for quick
     // lookups for every gossipsub message send.
     enr_fork_id: EnrForkId,
+    /// The gossipsub encodings configured for specific topic kinds. Kinds without an entry use
+    /// `GossipEncoding::default()`.
+    gossip_encodings: HashMap<GossipKind, GossipEncoding>,
     /// The waker for the current thread.
     waker: Option<std::task::Waker>,
     /// Directory where metadata is stored
     network_dir: PathBuf,
+    /// The maximum number of listen addresses accepted from a peer's identify message.
+    max_identify_addresses: usize,
+    /// Accept private and loopback listen addresses from a peer's identify message.
+    allow_private_addresses: bool,
     /// Logger for behaviour actions.
     log: slog::Logger,
 
@@ -151,28 +177,24 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
     ) -> error::Result<Self> {
         let behaviour_log = log.new(o!());
 
-        let identify = if net_conf.private {
-            Identify::new(
-                "".into(),
-                "".into(),
-                local_key.public(), // Still send legitimate public key
-            )
-        } else {
-            Identify::new(
-                "lighthouse/libp2p".into(),
-                lighthouse_version::version_with_platform(),
-                local_key.public(),
-            )
-        };
+        let (protocol_version, agent_version) = identify_versions(net_conf);
+        let identify = Identify::new(
+            protocol_version,
+            agent_version,
+            local_key.public(), // Always send legitimate public key
+        );
 
         let enr_fork_id = network_globals
             .local_enr()
             .eth2()
             .expect("Local ENR must have a fork id");
 
+        let gossip_encodings: HashMap<GossipKind, GossipEncoding> =
+            net_conf.gossip_encodings.iter().cloned().collect();
+
         let possible_fork_digests = vec![enr_fork_id.fork_digest];
         let filter = MaxCountSubscriptionFilter {
-            filter: Self::create_whitelist_filter(possible_fork_digests, 64), //TODO change this to a constant
+            filter: Self::create_whitelist_filter(possible_fork_digests, 64, &gossip_encodings), //TODO change this to a constant
             max_subscribed_topics: 200, //TODO change this to a constant
             max_subscriptions_per_request: 100, //this is according to the current go implementation
         };
@@ -207,6 +229,7 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
             active_validators,
             &thresholds,
             &enr_fork_id,
+            &gossip_encodings,
             current_slot,
         )?;
 
@@ -219,7 +242,8 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
             .expect("Valid score params and thresholds");
 
         Ok(Behaviour {
-            eth2_rpc: RPC::new(log.clone()),
+            eth2_rpc: RPC::new(&net_conf.rpc_rate_limits, log.clone())
+                .map_err(|e| format!("Invalid RPC rate limits: {}", e))?,
             gossipsub,
             identify,
             peer_manager: PeerManager::new(local_key, net_conf, network_globals.clone(), log)
@@ -228,8 +252,11 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
             peers_to_dc: VecDeque::new(),
             network_globals,
             enr_fork_id,
+            gossip_encodings,
             waker: None,
             network_dir: net_conf.network_dir.clone(),
+            max_identify_addresses: net_conf.max_identify_addresses,
+            allow_private_addresses: net_conf.allow_private_addresses,
             log: behaviour_log,
             score_settings,
             update_gossipsub_scores,
@@ -246,8 +273,10 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
                 .get_dynamic_topic_params(active_validators, current_slot)?;
 
         let fork_digest = self.enr_fork_id.fork_digest;
+        let gossip_encodings = &self.gossip_encodings;
         let get_topic = |kind: GossipKind| -> Topic {
-            GossipTopic::new(kind, GossipEncoding::default(), fork_digest).into()
+            let encoding = gossip_encodings.get(&kind).cloned().unwrap_or_default();
+            GossipTopic::new(kind, encoding, fork_digest).into()
         };
 
         debug!(self.log, "Updating gossipsub score parameters";
@@ -296,16 +325,28 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
         &self.gossipsub
     }
 
+    /// Returns the number of mesh peers for each subscribed gossip kind.
+    ///
+    /// If we are subscribed to a kind on multiple topics (e.g. across a fork boundary) the mesh
+    /// peers of each topic are summed.
+    pub fn mesh_peers_per_topic(&self) -> HashMap<GossipKind, usize> {
+        let mut mesh_peers = HashMap::new();
+        for topic in self.network_globals.gossipsub_subscriptions.read().iter() {
+            let gs_topic: Topic = topic.clone().into();
+            let topic_hash = gs_topic.hash();
+            *mesh_peers.entry(topic.kind().clone()).or_insert(0) +=
+                self.gossipsub.mesh_peers(&topic_hash).count();
+        }
+        mesh_peers
+    }
+
     /* Pubsub behaviour functions */
 
     /// Subscribes to a gossipsub topic kind, letting the network service determine the
     /// encoding and fork version.
     pub fn subscribe_kind(&mut self, kind: GossipKind) -> bool {
-        let gossip_topic = GossipTopic::new(
-            kind,
-            GossipEncoding::default(),
-            self.enr_fork_id.fork_digest,
-        );
+        let encoding = self.gossip_encoding(&kind);
+        let gossip_topic = GossipTopic::new(kind, encoding, self.enr_fork_id.fork_digest);
 
         self.subscribe(gossip_topic)
     }
@@ -313,34 +354,53 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
     /// Unsubscribes from a gossipsub topic kind, letting the network service determine the
     /// encoding and fork version.
     pub fn unsubscribe_kind(&mut self, kind: GossipKind) -> bool {
-        let gossip_topic = GossipTopic::new(
-            kind,
-            GossipEncoding::default(),
-            self.enr_fork_id.fork_digest,
-        );
+        let encoding = self.gossip_encoding(&kind);
+        let gossip_topic = GossipTopic::new(kind, encoding, self.enr_fork_id.fork_digest);
         self.unsubscribe(gossip_topic)
     }
 
     /// Subscribes to a specific subnet id;
     pub fn subscribe_to_subnet(&mut self, subnet_id: SubnetId) -> bool {
-        let topic = GossipTopic::new(
-            subnet_id.into(),
-            GossipEncoding::default(),
-            self.enr_fork_id.fork_digest,
-        );
+        let kind = GossipKind::from(subnet_id);
+        let encoding = self.gossip_encoding(&kind);
+        let topic = GossipTopic::new(kind, encoding, self.enr_fork_id.fork_digest);
         self.subscribe(topic)
     }
 
     /// Un-Subscribes from a specific subnet id;
     pub fn unsubscribe_from_subnet(&mut self, subnet_id: SubnetId) -> bool {
-        let topic = GossipTopic::new(
-            subnet_id.into(),
-            GossipEncoding::default(),
-            self.enr_fork_id.fork_digest,
-        );
+        let kind = GossipKind::from(subnet_id);
+        let encoding = self.gossip_encoding(&kind);
+        let topic = GossipTopic::new(kind, encoding, self.enr_fork_id.fork_digest);
         self.unsubscribe(topic)
     }
 
+    /// Subscribes to every attestation subnet, setting all subnets in the local ENR bitfield.
+    ///
+    /// The ENR and metadata are updated once, rather than once per subnet. Returns the number of
+    /// subnets which were newly subscribed to.
+    pub fn subscribe_to_all_subnets(&mut self) -> usize {
+        let subnet_count = self.score_settings.attestation_subnet_count();
+        let subscribed = (0..subnet_count)
+            .filter(|&id| self.subscribe_to_subnet(SubnetId::new(id)))
+            .count();
+        self.update_enr_all_subnets(true);
+        subscribed
+    }
+
+    /// Unsubscribes from every attestation subnet, clearing all subnets in the local ENR bitfield.
+    ///
+    /// The ENR and metadata are updated once, rather than once per subnet. Returns the number of
+    /// subnets which were unsubscribed from.
+    pub fn unsubscribe_from_all_subnets(&mut self) -> usize {
+        let subnet_count = self.score_settings.attestation_subnet_count();
+        let unsubscribed = (0..subnet_count)
+            .filter(|&id| self.unsubscribe_from_subnet(SubnetId::new(id)))
+            .count();
+        self.update_enr_all_subnets(false);
+        unsubscribed
+    }
+
     /// Subscribes to a gossipsub topic.
     fn subscribe(&mut self, topic: GossipTopic) -> bool {
         // update the network globals
@@ -387,11 +447,16 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
     }
 
     /// Publishes a list of messages on the pubsub (gossipsub) behaviour, choosing the encoding.
-    pub fn publish(&mut self, messages: Vec<PubsubMessage<TSpec>>) {
+    ///
+    /// Returns a `PublishOutcome` for each topic of each message, in the order they were published.
+    pub fn publish(&mut self, messages: Vec<PubsubMessage<TSpec>>) -> Vec<PublishOutcome> {
+        let mut outcomes = Vec::with_capacity(messages.len());
         for message in messages {
-            for topic in message.topics(GossipEncoding::default(), self.enr_fork_id.fork_digest) {
-                let message_data = message.encode(GossipEncoding::default());
-                if let Err(e) = self.gossipsub.publish(topic.clone().into(), message_data) {
+            let encoding = self.gossip_encoding(&message.kind());
+            for topic in message.topics(encoding.clone(), self.enr_fork_id.fork_digest) {
+                let message_data = message.encode(encoding.clone());
+                let result = self.gossipsub.publish(topic.clone().into(), message_data);
+                if let Err(e) = &result {
                     slog::warn!(self.log, "Could not publish message";
                                         "error" => ?e);
 
@@ -415,8 +480,10 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
                         }
                     }
                 }
+                outcomes.push(PublishOutcome { topic, result });
             }
         }
+        outcomes
     }
 
     /// Informs the gossipsub about the result of a message validation.
@@ -500,11 +567,43 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
         self.peer_manager.goodbye_peer(peer_id, reason, source);
     }
 
+    /// Sends a goodbye to every connected peer and disconnects them.
+    ///
+    /// Unlike `goodbye_peer` this does not penalise or ban the peers. It is intended for a clean
+    /// shutdown of the local node, e.g. with `GoodbyeReason::ClientShutdown`.
+    pub fn goodbye_all(&mut self, reason: GoodbyeReason) {
+        let peers = {
+            let mut peer_db = self.network_globals.peers.write();
+            let peers = peer_db.connected_peer_ids().cloned().collect::<Vec<_>>();
+            for peer_id in &peers {
+                peer_db.notify_disconnecting(peer_id);
+            }
+            peers
+        };
+
+        debug!(self.log, "Sending goodbye to all peers"; "peers" => peers.len(), "reason" => %reason);
+        self.peers_to_dc.extend(
+            peers
+                .into_iter()
+                .map(|peer_id| (peer_id, Some(reason.clone()))),
+        );
+        if let Some(waker) = &self.waker {
+            waker.wake_by_ref();
+        }
+    }
+
     /// Returns an iterator over all enr entries in the DHT.
     pub fn enr_entries(&mut self) -> Vec<Enr> {
         self.peer_manager.discovery_mut().table_entries_enr()
     }
 
+    /// Returns all enr entries in the DHT which are on the fork with the given digest.
+    pub fn enr_entries_on_fork(&mut self, fork_digest: [u8; 4]) -> Vec<Enr> {
+        self.peer_manager
+            .discovery_mut()
+            .table_entries_enr_on_fork(fork_digest)
+    }
+
     /// Add an ENR to the routing table of the discovery mechanism.
     pub fn add_enr(&mut self, enr: Enr) {
         self.peer_manager.discovery_mut().add_enr(enr);
@@ -525,6 +624,20 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
         self.update_metadata();
     }
 
+    /// Sets every subnet in the local ENR bitfield to `value`, updating the metadata if the
+    /// bitfield changed.
+    fn update_enr_all_subnets(&mut self, value: bool) {
+        match self
+            .peer_manager
+            .discovery_mut()
+            .update_enr_bitfield_all(value)
+        {
+            Ok(true) => self.update_metadata(),
+            Ok(false) => {}
+            Err(e) => crit!(self.log, "Could not update ENR bitfield"; "error" => e),
+        }
+    }
+
     /// Attempts to discover new peers for a given subnet. The `min_ttl` gives the time at which we
     /// would like to retain the peers for.
     pub fn discover_subnet_peers(&mut self, subnet_subscriptions: Vec<SubnetDiscovery>) {
@@ -564,6 +677,11 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
 
     /* Private internal functions */
 
+    /// Returns the configured gossipsub encoding for topics of the given `kind`.
+    fn gossip_encoding(&self, kind: &GossipKind) -> GossipEncoding {
+        self.gossip_encodings.get(kind).cloned().unwrap_or_default()
+    }
+
     /// Updates the current meta data of the node to match the local ENR.
     fn update_metadata(&mut self) {
         let local_attnets = self
@@ -707,6 +825,11 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
             return;
         }
 
+        if message.event.is_ok() {
+            // keep track of when we last heard from this peer
+            self.peer_manager.rpc_received(&peer_id);
+        }
+
         let handler_id = message.conn_id;
         // The METADATA and PING RPC responses are handled within the behaviour and not propagated
         match message.event {
@@ -740,7 +863,7 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
                         );
                         // inform failures of requests comming outside the behaviour
                         if !matches!(id, RequestId::Behaviour) {
-                            self.add_event(BehaviourEvent::RPCFailed { peer_id, id });
+                            self.add_event(BehaviourEvent::RPCFailed { peer_id, id, error });
                         }
                     }
                 }
@@ -907,12 +1030,20 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
                 mut info,
                 observed_addr,
             } => {
-                if info.listen_addrs.len() > MAX_IDENTIFY_ADDRESSES {
+                let identified_addresses = info.listen_addrs.len();
+                filter_identified_addresses(
+                    &mut info.listen_addrs,
+                    self.allow_private_addresses,
+                    self.max_identify_addresses,
+                );
+                if info.listen_addrs.len() < identified_addresses {
                     debug!(
                         self.log,
-                        "More than 10 addresses have been identified, truncating"
+                        "Discarded identified addresses";
+                        "peer_id" => %peer_id,
+                        "identified" => identified_addresses,
+                        "retained" => info.listen_addrs.len(),
                     );
-                    info.listen_addrs.truncate(MAX_IDENTIFY_ADDRESSES);
                 }
                 // send peer info to the peer manager.
                 self.peer_manager.identify(&peer_id, &info);
@@ -939,16 +1070,17 @@ impl<TSpec: EthSpec> Behaviour<TSpec> {
     }
 
     /// Creates a whitelist topic filter that covers all possible topics using the given set of
-    /// possible fork digests.
+    /// possible fork digests and the encoding configured for each topic kind.
     fn create_whitelist_filter(
         possible_fork_digests: Vec<[u8; 4]>,
         attestation_subnet_count: u64,
+        gossip_encodings: &HashMap<GossipKind, GossipEncoding>,
     ) -> WhitelistSubscriptionFilter {
         let mut possible_hashes = HashSet::new();
         for fork_digest in possible_fork_digests {
-            let mut add = |kind| {
-                let topic: Topic =
-                    GossipTopic::new(kind, GossipEncoding::SSZSnappy, fork_digest).into();
+            let mut add = |kind: GossipKind| {
+                let encoding = gossip_encodings.get(&kind).cloned().unwrap_or_default();
+                let topic: Topic = GossipTopic::new(kind, encoding, fork_digest).into();
                 possible_hashes.insert(topic.hash());
             };
 
@@ -1335,6 +1467,57 @@ impl<TSpec: EthSpec> std::convert::From<Response<TSpec>> for RPCCodedResponse<TS
     }
 }
 
+/// Returns the `(protocol_version, agent_version)` advertised to peers via identify.
+///
+/// These are empty in private mode. Otherwise, any overrides in `net_conf` are used instead of the
+/// lighthouse defaults.
+fn identify_versions(net_conf: &NetworkConfig) -> (String, String) {
+    if net_conf.private {
+        (String::new(), String::new())
+    } else {
+        (
+            net_conf
+                .protocol_version
+                .clone()
+                .unwrap_or_else(|| "lighthouse/libp2p".into()),
+            net_conf
+                .agent_version
+                .clone()
+                .unwrap_or_else(lighthouse_version::version_with_platform),
+        )
+    }
+}
+
+/// Removes private addresses from `listen_addrs` (unless `allow_private_addresses` is set) and then
+/// truncates it to at most `max_addresses` entries.
+fn filter_identified_addresses(
+    listen_addrs: &mut Vec<Multiaddr>,
+    allow_private_addresses: bool,
+    max_addresses: usize,
+) {
+    if !allow_private_addresses {
+        listen_addrs.retain(is_routable_address);
+    }
+    listen_addrs.truncate(max_addresses);
+}
+
+/// Returns `false` if `addr` is an IP address in a loopback, private, link-local or unspecified
+/// range, which is not reachable from the public internet.
+fn is_routable_address(addr: &Multiaddr) -> bool {
+    match addr.iter().next() {
+        Some(MProtocol::Ip4(ip)) => {
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified())
+        }
+        Some(MProtocol::Ip6(ip)) => {
+            let first_segment = ip.segments()[0];
+            let is_unique_local = first_segment & 0xfe00 == 0xfc00;
+            let is_unicast_link_local = first_segment & 0xffc0 == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || is_unique_local || is_unicast_link_local)
+        }
+        _ => true,
+    }
+}
+
 /// Persist metadata to disk
 pub fn save_metadata_to_disk<E: EthSpec>(dir: &Path, metadata: MetaData<E>, log: &slog::Logger) {
     let _ = std::fs::create_dir_all(&dir);
@@ -1354,3 +1537,132 @@ pub fn save_metadata_to_disk<E: EthSpec>(dir: &Path, metadata: MetaData<E>, log:
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(addrs: &[&str]) -> Vec<Multiaddr> {
+        addrs
+            .iter()
+            .map(|addr| addr.parse::<Multiaddr>().unwrap())
+            .filter(is_routable_address)
+            .collect()
+    }
+
+    #[test]
+    fn test_identify_versions() {
+        let mut config = NetworkConfig::default();
+        assert_eq!(
+            identify_versions(&config),
+            (
+                "lighthouse/libp2p".to_string(),
+                lighthouse_version::version_with_platform()
+            )
+        );
+
+        config.agent_version = Some("custom-client/v1.0.0".into());
+        config.protocol_version = Some("custom/libp2p".into());
+        assert_eq!(
+            identify_versions(&config),
+            (
+                "custom/libp2p".to_string(),
+                "custom-client/v1.0.0".to_string()
+            )
+        );
+
+        // Private mode takes precedence over any overrides.
+        config.private = true;
+        assert_eq!(identify_versions(&config), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn test_is_routable_address() {
+        let public = [
+            "/ip4/8.8.8.8/tcp/9000",
+            "/ip6/2001:4860:4860::8888/tcp/9000",
+            "/dns4/example.com/tcp/9000",
+        ];
+        let private = [
+            "/ip4/127.0.0.1/tcp/9000",
+            "/ip4/10.0.0.1/tcp/9000",
+            "/ip4/172.16.0.1/tcp/9000",
+            "/ip4/192.168.1.1/tcp/9000",
+            "/ip4/169.254.0.1/tcp/9000",
+            "/ip4/0.0.0.0/tcp/9000",
+            "/ip6/::1/tcp/9000",
+            "/ip6/::/tcp/9000",
+            "/ip6/fd00::1/tcp/9000",
+            "/ip6/fe80::1/tcp/9000",
+        ];
+
+        let mixed = public
+            .iter()
+            .chain(private.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+
+        assert_eq!(filter(&mixed), filter(&public));
+        assert!(filter(&private).is_empty());
+    }
+
+    #[test]
+    fn test_filter_identified_addresses() {
+        let parse = |addrs: &[&str]| {
+            addrs
+                .iter()
+                .map(|addr| addr.parse::<Multiaddr>().unwrap())
+                .collect::<Vec<_>>()
+        };
+        let identified = parse(&[
+            "/ip4/192.168.1.1/tcp/9000",
+            "/ip4/8.8.8.8/tcp/9000",
+            "/ip4/127.0.0.1/tcp/9000",
+            "/ip4/1.1.1.1/tcp/9000",
+            "/ip4/9.9.9.9/tcp/9000",
+        ]);
+
+        // Private addresses are dropped before truncating.
+        let mut addrs = identified.clone();
+        filter_identified_addresses(&mut addrs, false, 2);
+        assert_eq!(
+            addrs,
+            parse(&["/ip4/8.8.8.8/tcp/9000", "/ip4/1.1.1.1/tcp/9000"])
+        );
+
+        // Private addresses are retained when allowed.
+        let mut addrs = identified.clone();
+        filter_identified_addresses(&mut addrs, true, 2);
+        assert_eq!(
+            addrs,
+            parse(&["/ip4/192.168.1.1/tcp/9000", "/ip4/8.8.8.8/tcp/9000"])
+        );
+
+        let mut addrs = identified.clone();
+        filter_identified_addresses(&mut addrs, true, 10);
+        assert_eq!(addrs, identified);
+    }
+
+    #[test]
+    fn test_whitelist_filter_uses_configured_encodings() {
+        let fork_digest = [0; 4];
+        let gossip_encodings = vec![(GossipKind::BeaconBlock, GossipEncoding::SSZ)]
+            .into_iter()
+            .collect();
+        let whitelist = Behaviour::<types::MainnetEthSpec>::create_whitelist_filter(
+            vec![fork_digest],
+            1,
+            &gossip_encodings,
+        )
+        .0;
+
+        let hash = |kind: GossipKind, encoding: GossipEncoding| {
+            Topic::from(GossipTopic::new(kind, encoding, fork_digest)).hash()
+        };
+
+        assert!(whitelist.contains(&hash(GossipKind::BeaconBlock, GossipEncoding::SSZ)));
+        assert!(!whitelist.contains(&hash(GossipKind::BeaconBlock, GossipEncoding::SSZSnappy)));
+        assert!(whitelist.contains(&hash(GossipKind::VoluntaryExit, GossipEncoding::SSZSnappy)));
+        assert!(!whitelist.contains(&hash(GossipKind::VoluntaryExit, GossipEncoding::SSZ)));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::types::GossipKind;
+use crate::rpc::RPCRateLimiterConfig;
+use crate::types::{GossipEncoding, GossipKind};
 use crate::{Enr, PeerIdSerialized};
 use directory::{
     DEFAULT_BEACON_NODE_DIR, DEFAULT_HARDCODED_NETWORK, DEFAULT_NETWORK_DIR, DEFAULT_ROOT_DIR,
@@ -32,6 +33,10 @@ pub struct Config {
     /// IP address to listen on.
     pub listen_address: std::net::IpAddr,
 
+    /// An optional IPv6 address to listen on for libp2p connections in addition to
+    /// `listen_address`, allowing dual-stack nodes.
+    pub listen_address_v6: Option<std::net::Ipv6Addr>,
+
     /// The TCP port that libp2p listens on.
     pub libp2p_port: u16,
 
@@ -42,6 +47,10 @@ pub struct Config {
     /// that no discovery address has been set in the CLI args.
     pub enr_address: Option<std::net::IpAddr>,
 
+    /// An additional IPv6 address to broadcast to peers in the `ip6` field of our ENR. None
+    /// indicates that we do not advertise an IPv6 address.
+    pub enr_address_v6: Option<std::net::Ipv6Addr>,
+
     /// The udp port to broadcast to peers in order to reach back for discovery.
     pub enr_udp_port: Option<u16>,
 
@@ -91,8 +100,40 @@ pub struct Config {
     /// prevents sending client identifying information over identify.
     pub private: bool,
 
+    /// Overrides the agent version advertised via identify.
+    pub agent_version: Option<String>,
+
+    /// Overrides the protocol version advertised via identify.
+    pub protocol_version: Option<String>,
+
+    /// The quotas applied to inbound RPC requests from each peer.
+    pub rpc_rate_limits: RPCRateLimiterConfig,
+
     /// List of extra topics to initially subscribe to as strings.
     pub topics: Vec<GossipKind>,
+
+    /// Overrides the gossipsub encoding used for specific topic kinds. Kinds without an override
+    /// use `GossipEncoding::default()`.
+    pub gossip_encodings: Vec<(GossipKind, GossipEncoding)>,
+
+    /// The maximum number of listen addresses accepted from a peer's identify message.
+    pub max_identify_addresses: usize,
+
+    /// Accept private and loopback listen addresses from peers. Useful for local testnets.
+    pub allow_private_addresses: bool,
+
+    /// Overrides the maximum number of unaggregated attestations queued by the beacon processor.
+    pub beacon_processor_attestation_queue_len: Option<usize>,
+
+    /// Overrides the maximum number of aggregated attestations queued by the beacon processor.
+    pub beacon_processor_aggregate_queue_len: Option<usize>,
+
+    /// Overrides the number of gossip attestations and aggregates which the beacon processor
+    /// accepts from a single peer within each `beacon_processor_rate_limit_period`.
+    pub beacon_processor_attestations_per_peer: Option<u64>,
+
+    /// Overrides the period over which `beacon_processor_attestations_per_peer` applies.
+    pub beacon_processor_rate_limit_period: Option<Duration>,
 }
 
 impl Default for Config {
@@ -170,9 +211,11 @@ impl Default for Config {
         Config {
             network_dir,
             listen_address: "0.0.0.0".parse().expect("valid ip address"),
+            listen_address_v6: None,
             libp2p_port: 9000,
             discovery_port: 9000,
             enr_address: None,
+            enr_address_v6: None,
             enr_udp_port: None,
             enr_tcp_port: None,
             target_peers: 50,
@@ -186,9 +229,19 @@ impl Default for Config {
             disable_discovery: false,
             upnp_enabled: true,
             private: false,
+            agent_version: None,
+            rpc_rate_limits: RPCRateLimiterConfig::default(),
+            protocol_version: None,
             subscribe_all_subnets: false,
             import_all_attestations: false,
             topics: Vec::new(),
+            gossip_encodings: Vec::new(),
+            max_identify_addresses: 10,
+            allow_private_addresses: false,
+            beacon_processor_attestation_queue_len: None,
+            beacon_processor_aggregate_queue_len: None,
+            beacon_processor_attestations_per_peer: None,
+            beacon_processor_rate_limit_period: None,
         }
     }
 }
@@ -126,6 +126,9 @@ pub fn create_enr_builder_from_config<T: EnrKey>(
     if let Some(enr_address) = config.enr_address {
         builder.ip(enr_address);
     }
+    if let Some(enr_address_v6) = config.enr_address_v6 {
+        builder.ip(enr_address_v6.into());
+    }
     if let Some(udp_port) = config.enr_udp_port {
         builder.udp(udp_port);
     }
@@ -163,6 +166,8 @@ pub fn build_enr<T: EthSpec>(
 fn compare_enr(local_enr: &Enr, disk_enr: &Enr) -> bool {
     // take preference over disk_enr address if one is not specified
     (local_enr.ip().is_none() || local_enr.ip() == disk_enr.ip())
+        // the same applies to the ipv6 address
+        && (local_enr.ip6().is_none() || local_enr.ip6() == disk_enr.ip6())
         // tcp ports must match
         && local_enr.tcp() == disk_enr.tcp()
         // must match on the same fork
@@ -58,8 +58,8 @@ const DURATION_DIFFERENCE: Duration = Duration::from_millis(1);
 
 /// The events emitted by polling discovery.
 pub enum DiscoveryEvent {
-    /// A query has completed. This result contains a mapping of discovered peer IDs to the `min_ttl`
-    /// of the peer if it is specified.
+    /// A query has completed or a useful peer was added to the routing table. This result contains
+    /// a mapping of discovered peer IDs to the `min_ttl` of the peer if it is specified.
     QueryResult(HashMap<PeerId, Option<Instant>>),
     /// This indicates that our local UDP socketaddr has been updated and we should inform libp2p.
     SocketUpdated(SocketAddr),
@@ -347,6 +347,21 @@ impl<TSpec: EthSpec> Discovery<TSpec> {
         self.discv5.table_entries_enr()
     }
 
+    /// Returns all enr entries in the DHT whose `eth2` field has the given fork digest.
+    ///
+    /// ENRs without a valid `eth2` field are skipped.
+    pub fn table_entries_enr_on_fork(&mut self, fork_digest: [u8; 4]) -> Vec<Enr> {
+        self.discv5
+            .table_entries_enr()
+            .into_iter()
+            .filter(|enr| {
+                enr.eth2()
+                    .map(|enr_fork_id| enr_fork_id.fork_digest == fork_digest)
+                    .unwrap_or(false)
+            })
+            .collect()
+    }
+
     /// Returns the ENR of a known peer if it exists.
     pub fn enr_of_peer(&mut self, peer_id: &PeerId) -> Option<Enr> {
         // first search the local cache
@@ -487,6 +502,44 @@ impl<TSpec: EthSpec> Discovery<TSpec> {
         enr::save_enr_to_disk(Path::new(&self.enr_dir), &self.local_enr(), &self.log);
     }
 
+    /// Sets every subnet in the local ENR bitfield to `value`, updating the ENR at most once.
+    ///
+    /// Returns `true` if the bitfield was modified.
+    pub fn update_enr_bitfield_all(&mut self, value: bool) -> Result<bool, String> {
+        let local_enr = self.discv5.local_enr();
+        let mut current_bitfield = local_enr.bitfield::<TSpec>()?;
+
+        let mut modified = false;
+        for id in 0..current_bitfield.len() {
+            if current_bitfield
+                .get(id)
+                .map_err(|_| String::from("Subnet ID out of bounds"))?
+                != value
+            {
+                current_bitfield.set(id, value).map_err(|_| {
+                    String::from("Subnet ID out of bounds, could not set subnet ID")
+                })?;
+                modified = true;
+            }
+        }
+
+        if !modified {
+            return Ok(false);
+        }
+
+        // insert the bitfield into the ENR record
+        self.discv5
+            .enr_insert(BITFIELD_ENR_KEY, &current_bitfield.as_ssz_bytes())
+            .map_err(|e| format!("{:?}", e))?;
+
+        // replace the global version
+        *self.network_globals.local_enr.write() = self.discv5.local_enr();
+
+        // persist modified enr to disk
+        enr::save_enr_to_disk(Path::new(&self.enr_dir), &self.local_enr(), &self.log);
+        Ok(true)
+    }
+
     // Bans a peer and it's associated seen IP addresses.
     pub fn ban_peer(&mut self, peer_id: &PeerId, ip_addresses: Vec<IpAddr>) {
         // first try and convert the peer_id to a node_id.
@@ -861,6 +914,62 @@ impl<TSpec: EthSpec> Discovery<TSpec> {
         None
     }
 
+    /// Processes an ENR newly added to the discv5 routing table, e.g. after a bootnode responds.
+    ///
+    /// If the ENR is on our fork, has a TCP port and is not already connected or being dialed, it
+    /// is returned so the peer manager can dial it without waiting for the next query. Whilst
+    /// subnet queries are queued, the ENR must also be on one of those subnets and is mapped to the
+    /// furthest `min_ttl` of the matching queries. The peer manager applies the peer limit.
+    fn process_added_enr(&mut self, enr: Enr) -> Option<HashMap<PeerId, Option<Instant>>> {
+        let enr_fork_id = self.local_enr().eth2().ok()?;
+        if enr.eth2() != Ok(enr_fork_id) || (enr.tcp().is_none() && enr.tcp6().is_none()) {
+            return None;
+        }
+
+        let peer_id = enr.peer_id();
+        if self
+            .network_globals
+            .peers
+            .read()
+            .is_connected_or_dialing(&peer_id)
+        {
+            return None;
+        }
+
+        let subnet_queries = self
+            .queued_queries
+            .iter()
+            .filter_map(|query| match query {
+                QueryType::Subnet(subnet_query) => Some(subnet_query),
+                QueryType::FindPeers => None,
+            })
+            .collect::<Vec<_>>();
+        let matching_queries = subnet_queries
+            .iter()
+            .filter(|subnet_query| {
+                subnet_predicate::<TSpec>(vec![subnet_query.subnet_id], &self.log)(&enr)
+            })
+            .collect::<Vec<_>>();
+
+        // Whilst we are searching for peers on particular subnets, don't spend our peer slots on
+        // peers which are not on any of them.
+        if !subnet_queries.is_empty() && matching_queries.is_empty() {
+            return None;
+        }
+
+        let min_ttl = matching_queries
+            .iter()
+            .filter_map(|subnet_query| subnet_query.min_ttl)
+            .max();
+
+        debug!(self.log, "Useful peer added to routing table"; "peer_id" => %peer_id, "min_ttl" => ?min_ttl);
+        self.cached_enrs.put(peer_id, enr);
+
+        let mut results = HashMap::new();
+        results.insert(peer_id, min_ttl);
+        Some(results)
+    }
+
     /// Drives the queries returning any results from completed queries.
     fn poll_queries(&mut self, cx: &mut Context) -> Option<HashMap<PeerId, Option<Instant>>> {
         while let Poll::Ready(Some(query_result)) = self.active_queries.poll_next_unpin(cx) {
@@ -923,6 +1032,11 @@ impl<TSpec: EthSpec> Discovery<TSpec> {
                             }
                             */
                         }
+                        Discv5Event::EnrAdded { enr, .. } => {
+                            if let Some(results) = self.process_added_enr(enr) {
+                                return Poll::Ready(DiscoveryEvent::QueryResult(results));
+                            }
+                        }
                         Discv5Event::SocketUpdated(socket) => {
                             info!(self.log, "Address updated"; "ip" => %socket.ip(), "udp_port" => %socket.port());
                             metrics::inc_counter(&metrics::ADDRESS_UPDATE_COUNT);
@@ -1087,6 +1201,23 @@ mod tests {
         builder.build(&enr_key).unwrap()
     }
 
+    #[test]
+    fn test_subnet_predicate() {
+        let log = build_log(slog::Level::Debug, false);
+        let predicate = subnet_predicate::<E>(vec![SubnetId::new(1), SubnetId::new(5)], &log);
+
+        assert!(predicate(&make_enr(vec![1])));
+        assert!(predicate(&make_enr(vec![2, 5])));
+        assert!(!predicate(&make_enr(vec![2, 3])));
+        assert!(!predicate(&make_enr(vec![])));
+
+        // An ENR without an attestation subnet bitfield does not match.
+        let keypair = libp2p::identity::Keypair::generate_secp256k1();
+        let enr_key: CombinedKey = CombinedKey::from_libp2p(&keypair).unwrap();
+        let enr = EnrBuilder::new("v4").build(&enr_key).unwrap();
+        assert!(!predicate(&enr));
+    }
+
     #[tokio::test]
     async fn test_completed_subnet_queries() {
         let mut discovery = build_discovery().await;
@@ -1124,4 +1255,161 @@ mod tests {
         // when a peer belongs to multiple subnet ids, we use the highest ttl.
         assert_eq!(results.get(&enr1.peer_id()).unwrap(), &instant1);
     }
+
+    #[test]
+    fn test_dual_stack_enr() {
+        let keypair = libp2p::identity::Keypair::generate_secp256k1();
+        let enr_key: CombinedKey = CombinedKey::from_libp2p(&keypair).unwrap();
+        let ipv4: std::net::Ipv4Addr = "10.0.0.1".parse().unwrap();
+        let ipv6: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let config = NetworkConfig {
+            enr_address: Some(ipv4.into()),
+            enr_address_v6: Some(ipv6),
+            ..Default::default()
+        };
+
+        let enr: Enr = build_enr::<E>(&enr_key, &config, EnrForkId::default()).unwrap();
+
+        assert_eq!(enr.ip(), Some(ipv4));
+        assert_eq!(enr.ip6(), Some(ipv6));
+    }
+
+    #[tokio::test]
+    async fn test_table_entries_enr_on_fork() {
+        let mut discovery = build_discovery().await;
+
+        let make_fork_enr = |fork_digest: [u8; 4]| {
+            let keypair = libp2p::identity::Keypair::generate_secp256k1();
+            let enr_key: CombinedKey = CombinedKey::from_libp2p(&keypair).unwrap();
+            let config = NetworkConfig {
+                enr_address: Some("127.0.0.1".parse().unwrap()),
+                enr_udp_port: Some(unused_port()),
+                ..Default::default()
+            };
+            let enr_fork_id = EnrForkId {
+                fork_digest,
+                ..EnrForkId::default()
+            };
+            build_enr::<E>(&enr_key, &config, enr_fork_id).unwrap()
+        };
+
+        let ours = vec![make_fork_enr([1; 4]), make_fork_enr([1; 4])];
+        let theirs = make_fork_enr([2; 4]);
+
+        // An ENR without an eth2 field is never on our fork.
+        let keypair = libp2p::identity::Keypair::generate_secp256k1();
+        let enr_key: CombinedKey = CombinedKey::from_libp2p(&keypair).unwrap();
+        let no_eth2 = EnrBuilder::new("v4")
+            .ip("127.0.0.1".parse().unwrap())
+            .udp(unused_port())
+            .build(&enr_key)
+            .unwrap();
+
+        for enr in ours.iter().chain(vec![&theirs, &no_eth2]) {
+            discovery.add_enr(enr.clone());
+        }
+        assert_eq!(discovery.table_entries_enr().len(), 4);
+
+        let mut on_fork = discovery
+            .table_entries_enr_on_fork([1; 4])
+            .into_iter()
+            .map(|enr| enr.node_id())
+            .collect::<Vec<_>>();
+        on_fork.sort();
+        let mut expected = ours.iter().map(|enr| enr.node_id()).collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(on_fork, expected);
+
+        let on_other_fork = discovery.table_entries_enr_on_fork([2; 4]);
+        assert_eq!(on_other_fork.len(), 1);
+        assert_eq!(on_other_fork[0].node_id(), theirs.node_id());
+
+        assert!(discovery.table_entries_enr_on_fork([3; 4]).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enr_added_event() {
+        let mut discovery = build_discovery().await;
+        let (event_tx, event_rx) = mpsc::channel(10);
+        discovery.event_stream = EventStream::Present(event_rx);
+
+        let make_added_enr = |fork_digest: [u8; 4], subnet_ids: Vec<usize>| {
+            let keypair = libp2p::identity::Keypair::generate_secp256k1();
+            let enr_key: CombinedKey = CombinedKey::from_libp2p(&keypair).unwrap();
+            let config = NetworkConfig {
+                enr_address: Some("127.0.0.1".parse().unwrap()),
+                enr_udp_port: Some(unused_port()),
+                ..Default::default()
+            };
+            let mut builder = create_enr_builder_from_config(&config, true);
+            let enr_fork_id = EnrForkId {
+                fork_digest,
+                ..EnrForkId::default()
+            };
+            builder.add_value(ETH2_ENR_KEY, &enr_fork_id.as_ssz_bytes());
+            let mut bitfield = BitVector::<<E as EthSpec>::SubnetBitfieldLength>::new();
+            for id in subnet_ids {
+                bitfield.set(id, true).unwrap();
+            }
+            builder.add_value(BITFIELD_ENR_KEY, &bitfield.as_ssz_bytes());
+            builder.build(&enr_key).unwrap()
+        };
+        let local_fork_digest = discovery.local_enr().eth2().unwrap().fork_digest;
+        let mut other_fork_digest = local_fork_digest;
+        other_fork_digest[0] ^= 1;
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut poll_added = |discovery: &mut Discovery<E>, enr: Enr| {
+            event_tx
+                .try_send(Discv5Event::EnrAdded {
+                    enr,
+                    replaced: None,
+                })
+                .unwrap();
+            match discovery.poll(&mut cx) {
+                Poll::Ready(DiscoveryEvent::QueryResult(results)) => Some(results),
+                Poll::Ready(DiscoveryEvent::SocketUpdated(_)) => panic!("unexpected socket update"),
+                Poll::Pending => None,
+            }
+        };
+
+        // A peer on another fork is ignored.
+        let other_fork = make_added_enr(other_fork_digest, vec![]);
+        assert!(poll_added(&mut discovery, other_fork).is_none());
+
+        // A peer on our fork is returned to be dialed.
+        let ours = make_added_enr(local_fork_digest, vec![]);
+        let results = poll_added(&mut discovery, ours.clone()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results.get(&ours.peer_id()), Some(&None));
+        assert!(discovery.cached_enrs.contains(&ours.peer_id()));
+
+        // A peer on a subnet we are searching for inherits the query's `min_ttl`. This is checked
+        // directly, since polling would start the queued query.
+        let min_ttl = Some(Instant::now() + Duration::from_secs(10));
+        discovery
+            .queued_queries
+            .push_back(QueryType::Subnet(SubnetQuery {
+                subnet_id: SubnetId::new(1),
+                min_ttl,
+                retries: 0,
+            }));
+        let subnet_peer = make_added_enr(local_fork_digest, vec![1]);
+        let results = discovery.process_added_enr(subnet_peer.clone()).unwrap();
+        assert_eq!(results.get(&subnet_peer.peer_id()), Some(&min_ttl));
+
+        // Whilst subnet queries are queued, a peer on none of those subnets is ignored.
+        let other_subnet_peer = make_added_enr(local_fork_digest, vec![2]);
+        assert!(discovery.process_added_enr(other_subnet_peer).is_none());
+
+        // A peer which we are already dialing is ignored.
+        let dialing_peer = make_added_enr(local_fork_digest, vec![1]);
+        discovery
+            .network_globals
+            .peers
+            .write()
+            .dialing_peer(&dialing_peer.peer_id(), None);
+        assert!(discovery.process_added_enr(dialing_peer).is_none());
+    }
 }
@@ -60,13 +60,15 @@ impl<'de> Deserialize<'de> for PeerIdSerialized {
     }
 }
 
-pub use crate::types::{error, Enr, GossipTopic, NetworkGlobals, PubsubMessage, SubnetDiscovery};
-pub use behaviour::{BehaviourEvent, Gossipsub, PeerRequestId, Request, Response};
+pub use crate::types::{
+    error, Enr, GossipTopic, NetworkGlobals, NetworkSnapshot, PubsubMessage, SubnetDiscovery,
+};
+pub use behaviour::{BehaviourEvent, Gossipsub, PeerRequestId, PublishOutcome, Request, Response};
 pub use config::Config as NetworkConfig;
 pub use discovery::{CombinedKeyExt, EnrExt, Eth2Enr};
 pub use discv5;
 pub use libp2p::bandwidth::BandwidthSinks;
-pub use libp2p::gossipsub::{MessageAcceptance, MessageId, Topic, TopicHash};
+pub use libp2p::gossipsub::{MessageAcceptance, MessageId, PublishError, Topic, TopicHash};
 pub use libp2p::{core::ConnectedPoint, PeerId, Swarm};
 pub use libp2p::{multiaddr, Multiaddr};
 pub use metrics::scrape_discovery_metrics;
@@ -31,6 +31,7 @@ mod peer_sync_status;
 mod peerdb;
 pub(crate) mod score;
 
+use peer_info::unix_now;
 pub use peer_info::{ConnectionDirection, PeerConnectionStatus, PeerConnectionStatus::*, PeerInfo};
 pub use peer_sync_status::{PeerSyncStatus, SyncInfo};
 use score::{PeerAction, ReportSource, ScoreState};
@@ -63,6 +64,12 @@ const ALLOWED_NEGATIVE_GOSSIPSUB_FACTOR: f32 = 0.1;
 /// A fraction of `PeerManager::target_peers` that need to be outbound-only connections.
 const MIN_OUTBOUND_ONLY_FACTOR: f32 = 0.1;
 
+/// The time in seconds we wait before re-dialing a peer that we have failed to dial once. This
+/// doubles with each consecutive failure, up to `DIAL_BACKOFF_MAX`.
+const DIAL_BACKOFF_BASE: u64 = 5;
+/// The maximum time in seconds we wait before re-dialing a peer that we have failed to dial.
+const DIAL_BACKOFF_MAX: u64 = 600;
+
 /// The main struct that handles peer's reputation and connection status.
 pub struct PeerManager<TSpec: EthSpec> {
     /// Storage of network globals to access the `PeerDB`.
@@ -81,12 +88,34 @@ pub struct PeerManager<TSpec: EthSpec> {
     max_peers: usize,
     /// The discovery service.
     discovery: Discovery<TSpec>,
+    /// Peers that we have recently failed to dial, and when we may dial them again.
+    dial_backoffs: HashMap<PeerId, DialBackoff>,
     /// The heartbeat interval to perform routine maintenance.
     heartbeat: tokio::time::Interval,
     /// The logger associated with the `PeerManager`.
     log: slog::Logger,
 }
 
+/// Tracks consecutive dial failures to a peer.
+struct DialBackoff {
+    /// The number of consecutive failed dials.
+    failures: u32,
+    /// The earliest time at which the peer may be dialed again.
+    retry_at: Instant,
+}
+
+impl DialBackoff {
+    /// Returns the time to wait before re-dialing after `failures` consecutive failures.
+    fn duration(failures: u32) -> Duration {
+        let multiplier = 1_u64 << failures.saturating_sub(1).min(16);
+        Duration::from_secs(
+            DIAL_BACKOFF_BASE
+                .saturating_mul(multiplier)
+                .min(DIAL_BACKOFF_MAX),
+        )
+    }
+}
+
 /// The events that the `PeerManager` outputs (requests).
 pub enum PeerManagerEvent {
     /// Dial a PeerId.
@@ -128,6 +157,7 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
             target_peers: config.target_peers,
             max_peers: (config.target_peers as f32 * (1.0 + PEER_EXCESS_FACTOR)).ceil() as usize,
             discovery,
+            dial_backoffs: HashMap::new(),
             heartbeat,
             log: log.clone(),
         })
@@ -279,6 +309,30 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
         }
     }
 
+    /// An RPC request or response has been received from a peer.
+    pub fn rpc_received(&mut self, peer_id: &PeerId) {
+        self.rpc_received_at(peer_id, unix_now());
+    }
+
+    /// An RPC request or response has been received from a peer at the unix timestamp `now` (in
+    /// seconds).
+    fn rpc_received_at(&mut self, peer_id: &PeerId, now: u64) {
+        // The timestamp has second granularity, so only take the write lock on the peer db when
+        // the recorded value would change, rather than for every message.
+        let is_outdated = self
+            .network_globals
+            .peers
+            .read()
+            .peer_info(peer_id)
+            .map_or(false, |peer_info| peer_info.last_rpc_at() != Some(now));
+
+        if is_outdated {
+            if let Some(peer_info) = self.network_globals.peers.write().peer_info_mut(peer_id) {
+                peer_info.on_rpc_activity(now);
+            }
+        }
+    }
+
     /// A STATUS message has been received from a peer. This resets the status timer.
     pub fn peer_statusd(&mut self, peer_id: &PeerId) {
         self.status_peers.insert(*peer_id);
@@ -333,9 +387,31 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
             // set peer as disconnected in discovery DHT
             debug!(self.log, "Marking peer disconnected in DHT"; "peer_id" => %peer_id);
             self.discovery.disconnect_peer(peer_id);
+
+            // back off from re-dialing the peer
+            let now = Instant::now();
+            let backoff = self.dial_backoffs.entry(*peer_id).or_insert(DialBackoff {
+                failures: 0,
+                retry_at: now,
+            });
+            backoff.failures = backoff.failures.saturating_add(1);
+            backoff.retry_at = now + DialBackoff::duration(backoff.failures);
+            trace!(self.log, "Backing off from dialing peer"; "peer_id" => %peer_id, "failures" => backoff.failures);
         }
     }
 
+    /// Returns `true` if we may dial the peer.
+    ///
+    /// This is `false` if the peer is connected, dialing or banned, or if we are backing off after
+    /// failing to dial it.
+    pub fn should_dial(&self, peer_id: &PeerId) -> bool {
+        self.network_globals.peers.read().should_dial(peer_id)
+            && self
+                .dial_backoffs
+                .get(peer_id)
+                .map_or(true, |backoff| backoff.retry_at <= Instant::now())
+    }
+
     /// Sets a peer as connected as long as their reputation allows it
     /// Informs if the peer was accepted
     pub fn connect_ingoing(&mut self, peer_id: &PeerId, multiaddr: Multiaddr) -> bool {
@@ -368,6 +444,7 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
     /// Updates `PeerInfo` with `identify` information.
     pub fn identify(&mut self, peer_id: &PeerId, info: &IdentifyInfo) {
         if let Some(peer_info) = self.network_globals.peers.write().peer_info_mut(peer_id) {
+            peer_info.on_rpc_activity(unix_now());
             let previous_kind = peer_info.client.kind.clone();
             peer_info.client = client::Client::from_identify_info(info);
             peer_info.listening_addresses = info.listen_addrs.clone();
@@ -699,7 +776,7 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
             // we attempt a connection if this peer is a subnet peer or if the max peer count
             // is not yet filled (including dialing peers)
             if (min_ttl.is_some() || connected_or_dialing + to_dial_peers.len() < self.max_peers)
-                && self.network_globals.peers.read().should_dial(&peer_id)
+                && self.should_dial(&peer_id)
             {
                 // This should be updated with the peer dialing. In fact created once the peer is
                 // dialed
@@ -740,11 +817,13 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
                     return true;
                 }
                 ConnectingType::IngoingConnected { multiaddr } => {
+                    self.dial_backoffs.remove(peer_id);
                     peerdb.connect_ingoing(peer_id, multiaddr, enr);
                     // start a timer to ping inbound peers.
                     self.inbound_ping_peers.insert(*peer_id);
                 }
                 ConnectingType::OutgoingConnected { multiaddr } => {
+                    self.dial_backoffs.remove(peer_id);
                     peerdb.connect_outgoing(peer_id, multiaddr, enr);
                     // start a timer for to ping outbound peers.
                     self.outbound_ping_peers.insert(*peer_id);
@@ -932,6 +1011,11 @@ impl<TSpec: EthSpec> PeerManager<TSpec> {
         // Updates peer's scores.
         self.update_peer_scores();
 
+        // Forget the dial failures of peers that have not failed for a while.
+        let now = Instant::now();
+        self.dial_backoffs
+            .retain(|_, backoff| backoff.retry_at + Duration::from_secs(DIAL_BACKOFF_MAX) > now);
+
         // Keep a list of peers we are disconnecting
         let mut disconnecting_peers = Vec::new();
 
@@ -1352,4 +1436,84 @@ mod tests {
         // the number of connected peers updates and we will not remove too many peers.
         assert_eq!(peer_manager.network_globals.connected_or_dialing_peers(), 3);
     }
+
+    #[tokio::test]
+    async fn test_peer_manager_backs_off_failed_dials() {
+        let mut peer_manager = build_peer_manager(3).await;
+        let peer = PeerId::random();
+
+        assert!(peer_manager.should_dial(&peer));
+
+        // Each consecutive failure doubles the backoff and prevents an immediate re-dial.
+        let mut previous_backoff = Duration::from_secs(0);
+        for failures in 1..=4 {
+            peer_manager.dial_peer(&peer);
+            assert!(!peer_manager.should_dial(&peer), "peer is dialing");
+
+            let before_failure = Instant::now();
+            peer_manager.notify_dial_failure(&peer);
+            assert!(
+                !peer_manager.should_dial(&peer),
+                "should not re-dial immediately after a failure"
+            );
+
+            let backoff = &peer_manager.dial_backoffs[&peer];
+            assert_eq!(backoff.failures, failures);
+            let remaining = backoff.retry_at - before_failure;
+            assert!(remaining >= DialBackoff::duration(failures));
+            assert!(remaining > previous_backoff, "backoff should grow");
+            previous_backoff = remaining;
+
+            // Let the backoff expire.
+            peer_manager.dial_backoffs.get_mut(&peer).unwrap().retry_at = Instant::now();
+            assert!(peer_manager.should_dial(&peer));
+        }
+
+        // The backoff is capped.
+        assert_eq!(
+            DialBackoff::duration(u32::max_value()),
+            Duration::from_secs(DIAL_BACKOFF_MAX)
+        );
+
+        // A successful connection resets the backoff.
+        peer_manager.dial_peer(&peer);
+        peer_manager.notify_dial_failure(&peer);
+        peer_manager.connect_outgoing(&peer, "/ip4/0.0.0.0".parse().unwrap());
+        assert!(!peer_manager.dial_backoffs.contains_key(&peer));
+    }
+
+    #[tokio::test]
+    async fn test_peer_manager_records_peer_activity_times() {
+        let mut peer_manager = build_peer_manager(3).await;
+        let peer = PeerId::random();
+        let peer_times = |peer_manager: &PeerManager<E>| {
+            let peers = peer_manager.network_globals.peers.read();
+            let info = peers.peer_info(&peer).unwrap();
+            (info.connected_since(), info.last_rpc_at())
+        };
+
+        peer_manager.connect_ingoing(&peer, "/ip4/0.0.0.0".parse().unwrap());
+        let (connected_since, last_rpc_at) = peer_times(&peer_manager);
+        let connected_since = connected_since.expect("connected peer has a session start");
+        assert_eq!(last_rpc_at, None, "no messages received yet");
+
+        peer_manager.rpc_received(&peer);
+        let first_rpc_at = peer_times(&peer_manager)
+            .1
+            .expect("rpc activity is recorded");
+        assert!(first_rpc_at >= connected_since);
+
+        peer_manager.rpc_received_at(&peer, first_rpc_at + 1);
+        let (still_connected_since, second_rpc_at) = peer_times(&peer_manager);
+        assert_eq!(still_connected_since, Some(connected_since));
+        assert_eq!(
+            second_rpc_at,
+            Some(first_rpc_at + 1),
+            "last rpc time advances"
+        );
+
+        // Disconnecting ends the session but keeps the last time we heard from the peer.
+        peer_manager.notify_disconnect(&peer);
+        assert_eq!(peer_times(&peer_manager), (None, second_rpc_at));
+    }
 }
@@ -10,7 +10,7 @@ use serde::{
 };
 use std::collections::HashSet;
 use std::net::{IpAddr, SocketAddr};
-use std::time::Instant;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use strum::AsRefStr;
 use types::{EthSpec, SubnetId};
 use PeerConnectionStatus::*;
@@ -52,6 +52,11 @@ pub struct PeerInfo<T: EthSpec> {
     pub connection_direction: Option<ConnectionDirection>,
     /// The enr of the peer, if known.
     pub enr: Option<Enr>,
+    /// Unix timestamp (in seconds) of when the current connected session with this peer began.
+    /// None if the peer is not connected.
+    connected_since: Option<u64>,
+    /// Unix timestamp (in seconds) of the last RPC or identify message received from this peer.
+    last_rpc_at: Option<u64>,
 }
 
 impl<TSpec: EthSpec> Default for PeerInfo<TSpec> {
@@ -70,6 +75,8 @@ impl<TSpec: EthSpec> Default for PeerInfo<TSpec> {
             is_trusted: false,
             connection_direction: None,
             enr: None,
+            connected_since: None,
+            last_rpc_at: None,
         }
     }
 }
@@ -109,6 +116,23 @@ impl<T: EthSpec> PeerInfo<T> {
         &self.connection_status
     }
 
+    /// Returns the unix timestamp (in seconds) at which the current connected session began.
+    pub fn connected_since(&self) -> Option<u64> {
+        self.connected_since
+    }
+
+    /// Returns the unix timestamp (in seconds) of the last RPC or identify message received from
+    /// this peer.
+    pub fn last_rpc_at(&self) -> Option<u64> {
+        self.last_rpc_at
+    }
+
+    /// Records that an RPC or identify message was received from this peer at the unix timestamp
+    /// `now` (in seconds).
+    pub(crate) fn on_rpc_activity(&mut self, now: u64) {
+        self.last_rpc_at = Some(now);
+    }
+
     /// Reports if this peer has some future validator duty in which case it is valuable to keep it.
     pub fn has_future_duty(&self) -> bool {
         self.min_ttl.map_or(false, |i| i >= Instant::now())
@@ -206,6 +230,7 @@ impl<T: EthSpec> PeerInfo<T> {
             Disconnecting { to_ban } => {
                 // If we are disconnecting this peer in the process of banning, we now ban the
                 // peer.
+                self.connected_since = None;
                 if to_ban {
                     self.connection_status = Banned {
                         since: Instant::now(),
@@ -219,6 +244,7 @@ impl<T: EthSpec> PeerInfo<T> {
                 }
             }
             Connected { .. } | Dialing { .. } | Unknown => {
+                self.connected_since = None;
                 self.connection_status = Disconnected {
                     since: Instant::now(),
                 };
@@ -238,6 +264,7 @@ impl<T: EthSpec> PeerInfo<T> {
 
     /// Modifies the status to Banned
     pub fn ban(&mut self) {
+        self.connected_since = None;
         self.connection_status = Banned {
             since: Instant::now(),
         };
@@ -277,6 +304,7 @@ impl<T: EthSpec> PeerInfo<T> {
             | Unknown => {
                 self.connection_status = Connected { n_in: 1, n_out: 0 };
                 self.connection_direction = Some(ConnectionDirection::Incoming);
+                self.connected_since = Some(unix_now());
             }
         }
 
@@ -297,6 +325,7 @@ impl<T: EthSpec> PeerInfo<T> {
             | Unknown => {
                 self.connection_status = Connected { n_in: 0, n_out: 1 };
                 self.connection_direction = Some(ConnectionDirection::Outgoing);
+                self.connected_since = Some(unix_now());
             }
         }
         if let Some(ip_addr) = seen_address {
@@ -318,6 +347,14 @@ impl<T: EthSpec> PeerInfo<T> {
     }
 }
 
+/// Returns the current unix timestamp in seconds.
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
 #[derive(Clone, Debug, Serialize)]
 /// The current health status of the peer.
 pub enum PeerStatus {
@@ -28,6 +28,9 @@ use tokio_util::time::{delay_queue, DelayQueue};
 use types::EthSpec;
 
 /// The time (in seconds) before a substream that is awaiting a response from the user times out.
+///
+/// For outbound streamed requests (e.g. `BlocksByRange`) this timer is reset on every received
+/// chunk, so a peer that stalls mid-stream is timed out with `RPCError::StreamTimeout`.
 pub const RESPONSE_TIMEOUT: u64 = 10;
 
 /// The number of times to retry an outbound upgrade in the case of IO errors.
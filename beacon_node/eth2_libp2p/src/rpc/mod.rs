@@ -16,7 +16,6 @@ use rate_limiter::{RPCRateLimiter as RateLimiter, RPCRateLimiterBuilder, RateLim
 use slog::{crit, debug, o};
 use std::marker::PhantomData;
 use std::task::{Context, Poll};
-use std::time::Duration;
 use types::EthSpec;
 
 pub(crate) use handler::HandlerErr;
@@ -29,6 +28,7 @@ pub use methods::{
     RPCResponseErrorCode, RequestId, ResponseTermination, StatusMessage, MAX_REQUEST_BLOCKS,
 };
 pub use protocol::{Protocol, RPCError};
+pub use rate_limiter::{Quota, RPCRateLimiterConfig};
 
 pub(crate) mod codec;
 mod handler;
@@ -101,30 +101,14 @@ pub struct RPC<TSpec: EthSpec> {
 }
 
 impl<TSpec: EthSpec> RPC<TSpec> {
-    pub fn new(log: slog::Logger) -> Self {
+    pub fn new(config: &RPCRateLimiterConfig, log: slog::Logger) -> Result<Self, &'static str> {
         let log = log.new(o!("service" => "libp2p_rpc"));
-        let limiter = RPCRateLimiterBuilder::new()
-            .n_every(Protocol::MetaData, 2, Duration::from_secs(5))
-            .n_every(Protocol::Ping, 2, Duration::from_secs(10))
-            .n_every(Protocol::Status, 5, Duration::from_secs(15))
-            .one_every(Protocol::Goodbye, Duration::from_secs(10))
-            .n_every(
-                Protocol::BlocksByRange,
-                methods::MAX_REQUEST_BLOCKS,
-                Duration::from_secs(10),
-            )
-            .n_every(
-                Protocol::BlocksByRoot,
-                methods::MAX_REQUEST_BLOCKS,
-                Duration::from_secs(10),
-            )
-            .build()
-            .expect("Configuration parameters are valid");
-        RPC {
+        let limiter = RPCRateLimiterBuilder::from_config(config).build()?;
+        Ok(RPC {
             limiter,
             events: Vec::new(),
             log,
-        }
+        })
     }
 
     /// Sends an RPC response.
@@ -1,6 +1,7 @@
-use crate::rpc::{Protocol, RPCRequest};
+use crate::rpc::{Protocol, RPCRequest, MAX_REQUEST_BLOCKS};
 use fnv::FnvHashMap;
 use libp2p::PeerId;
+use serde_derive::{Deserialize, Serialize};
 use std::convert::TryInto;
 use std::future::Future;
 use std::hash::Hash;
@@ -47,6 +48,7 @@ type Nanosecs = u64;
 /// n*`replenish_all_every`/`max_tokens` units of time since their last request.
 ///
 /// To produce hard limits, set `max_tokens` to 1.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Quota {
     /// How often are `max_tokens` fully replenished.
     replenish_all_every: Duration,
@@ -55,6 +57,48 @@ pub struct Quota {
     max_tokens: u64,
 }
 
+impl Quota {
+    /// A hard limit of one token every `time_period`.
+    pub fn one_every(time_period: Duration) -> Self {
+        Quota {
+            replenish_all_every: time_period,
+            max_tokens: 1,
+        }
+    }
+
+    /// Allow `n` tokens to be used every `time_period`.
+    pub fn n_every(n: u64, time_period: Duration) -> Self {
+        Quota {
+            replenish_all_every: time_period,
+            max_tokens: n,
+        }
+    }
+}
+
+/// The quotas applied to inbound requests of each protocol.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCRateLimiterConfig {
+    pub ping_quota: Quota,
+    pub metadata_quota: Quota,
+    pub status_quota: Quota,
+    pub goodbye_quota: Quota,
+    pub blocks_by_range_quota: Quota,
+    pub blocks_by_root_quota: Quota,
+}
+
+impl Default for RPCRateLimiterConfig {
+    fn default() -> Self {
+        Self {
+            ping_quota: Quota::n_every(2, Duration::from_secs(10)),
+            metadata_quota: Quota::n_every(2, Duration::from_secs(5)),
+            status_quota: Quota::n_every(5, Duration::from_secs(15)),
+            goodbye_quota: Quota::one_every(Duration::from_secs(10)),
+            blocks_by_range_quota: Quota::n_every(MAX_REQUEST_BLOCKS, Duration::from_secs(10)),
+            blocks_by_root_quota: Quota::n_every(MAX_REQUEST_BLOCKS, Duration::from_secs(10)),
+        }
+    }
+}
+
 /// Manages rate limiting of requests per peer, with differentiated rates per protocol.
 pub struct RPCRateLimiter {
     /// Interval to prune peers for which their timer ran out.
@@ -106,6 +150,20 @@ impl RPCRateLimiterBuilder {
         Default::default()
     }
 
+    /// Get a `RPCRateLimiterBuilder` with all the quotas of `config`.
+    pub fn from_config(config: &RPCRateLimiterConfig) -> Self {
+        Self::new()
+            .set_quota(Protocol::Ping, config.ping_quota.clone())
+            .set_quota(Protocol::MetaData, config.metadata_quota.clone())
+            .set_quota(Protocol::Status, config.status_quota.clone())
+            .set_quota(Protocol::Goodbye, config.goodbye_quota.clone())
+            .set_quota(
+                Protocol::BlocksByRange,
+                config.blocks_by_range_quota.clone(),
+            )
+            .set_quota(Protocol::BlocksByRoot, config.blocks_by_root_quota.clone())
+    }
+
     /// Set a quota for a protocol.
     fn set_quota(mut self, protocol: Protocol, quota: Quota) -> Self {
         let q = Some(quota);
@@ -120,29 +178,6 @@ impl RPCRateLimiterBuilder {
         self
     }
 
-    /// Allow one token every `time_period` to be used for this `protocol`.
-    /// This produces a hard limit.
-    pub fn one_every(self, protocol: Protocol, time_period: Duration) -> Self {
-        self.set_quota(
-            protocol,
-            Quota {
-                replenish_all_every: time_period,
-                max_tokens: 1,
-            },
-        )
-    }
-
-    /// Allow `n` tokens to be use used every `time_period` for this `protocol`.
-    pub fn n_every(self, protocol: Protocol, n: u64, time_period: Duration) -> Self {
-        self.set_quota(
-            protocol,
-            Quota {
-                max_tokens: n,
-                replenish_all_every: time_period,
-            },
-        )
-    }
-
     pub fn build(self) -> Result<RPCRateLimiter, &'static str> {
         // get our quotas
         let ping_quota = self.ping_quota.ok_or("Ping quota not specified")?;
@@ -328,8 +363,13 @@ impl<Key: Hash + Eq + Clone> Limiter<Key> {
 
 #[cfg(test)]
 mod tests {
-    use crate::rpc::rate_limiter::{Limiter, Quota};
+    use crate::rpc::rate_limiter::{
+        Limiter, Quota, RPCRateLimiterBuilder, RPCRateLimiterConfig, RateLimitedErr,
+    };
+    use crate::rpc::{BlocksByRangeRequest, RPCRequest};
+    use libp2p::PeerId;
     use std::time::Duration;
+    use types::MinimalEthSpec;
 
     #[test]
     fn it_works_a() {
@@ -396,4 +436,32 @@ mod tests {
             .allows(Duration::from_secs_f32(0.4), &key, 1)
             .is_err());
     }
+
+    #[tokio::test]
+    async fn blocks_by_range_burst_is_rate_limited() {
+        let config = RPCRateLimiterConfig {
+            blocks_by_range_quota: Quota::n_every(64, Duration::from_secs(10)),
+            ..RPCRateLimiterConfig::default()
+        };
+        let mut limiter = RPCRateLimiterBuilder::from_config(&config).build().unwrap();
+        let request = RPCRequest::<MinimalEthSpec>::BlocksByRange(BlocksByRangeRequest {
+            start_slot: 0,
+            count: 16,
+            step: 1,
+        });
+        let peer_id = PeerId::random();
+        let other_peer_id = PeerId::random();
+
+        // The first 64 blocks worth of requests are within the budget.
+        for _ in 0..4 {
+            assert!(limiter.allows(&peer_id, &request).is_ok());
+        }
+        assert!(matches!(
+            limiter.allows(&peer_id, &request),
+            Err(RateLimitedErr::TooSoon(_))
+        ));
+
+        // Other peers have their own budget.
+        assert!(limiter.allows(&other_peer_id, &request).is_ok());
+    }
 }
@@ -22,6 +22,7 @@ use slog::{crit, debug, info, o, trace, warn, Logger};
 use ssz::Decode;
 use std::fs::File;
 use std::io::prelude::*;
+use std::net::IpAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::time::Duration;
@@ -100,7 +101,7 @@ impl<TSpec: EthSpec> Service<TSpec> {
         } else {
             config.discovery_port.to_string()
         };
-        debug!(log, "Attempting to open listening ports"; "address" => ?config.listen_address, "tcp_port" => config.libp2p_port, "udp_port" => discovery_string);
+        debug!(log, "Attempting to open listening ports"; "address" => ?config.listen_address, "address_v6" => ?config.listen_address_v6, "tcp_port" => config.libp2p_port, "udp_port" => discovery_string);
 
         let (mut swarm, bandwidth) = {
             // Set up the transport - tcp/ws with noise and mplex
@@ -144,29 +145,35 @@ impl<TSpec: EthSpec> Service<TSpec> {
             )
         };
 
-        // listen on the specified address
-        let listen_multiaddr = {
-            let mut m = Multiaddr::from(config.listen_address);
-            m.push(Protocol::Tcp(config.libp2p_port));
-            m
-        };
-
-        match Swarm::listen_on(&mut swarm, listen_multiaddr.clone()) {
-            Ok(_) => {
-                let mut log_address = listen_multiaddr;
-                log_address.push(Protocol::P2p(local_peer_id.into()));
-                info!(log, "Listening established"; "address" => %log_address);
-            }
-            Err(err) => {
-                crit!(
-                    log,
-                    "Unable to listen on libp2p address";
-                    "error" => ?err,
-                    "listen_multiaddr" => %listen_multiaddr,
-                );
-                return Err("Libp2p was unable to listen on the given listen address.".into());
-            }
-        };
+        // listen on the specified addresses, including the optional ipv6 address for dual-stack
+        // nodes
+        let listen_addresses = std::iter::once(config.listen_address)
+            .chain(config.listen_address_v6.map(IpAddr::V6))
+            .map(|address| {
+                let mut m = Multiaddr::from(address);
+                m.push(Protocol::Tcp(config.libp2p_port));
+                m
+            })
+            .collect::<Vec<_>>();
+
+        for listen_multiaddr in listen_addresses {
+            match Swarm::listen_on(&mut swarm, listen_multiaddr.clone()) {
+                Ok(_) => {
+                    let mut log_address = listen_multiaddr;
+                    log_address.push(Protocol::P2p(local_peer_id.into()));
+                    info!(log, "Listening established"; "address" => %log_address);
+                }
+                Err(err) => {
+                    crit!(
+                        log,
+                        "Unable to listen on libp2p address";
+                        "error" => ?err,
+                        "listen_multiaddr" => %listen_multiaddr,
+                    );
+                    return Err("Libp2p was unable to listen on the given listen address.".into());
+                }
+            };
+        }
 
         // helper closure for dialing peers
         let mut dial_addr = |mut multiaddr: Multiaddr| {
@@ -3,13 +3,24 @@ use crate::peer_manager::PeerDB;
 use crate::rpc::MetaData;
 use crate::types::SyncState;
 use crate::Client;
-use crate::EnrExt;
 use crate::{Enr, GossipTopic, Multiaddr, PeerId};
+use crate::{EnrExt, Eth2Enr};
 use parking_lot::RwLock;
 use std::collections::HashSet;
 use std::sync::atomic::{AtomicU16, Ordering};
 use types::EthSpec;
 
+/// A point-in-time view of the state held in `NetworkGlobals`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkSnapshot {
+    /// The number of libp2p connected peers.
+    pub connected_peers: usize,
+    /// The current gossipsub topic subscriptions.
+    pub gossipsub_subscriptions: Vec<GossipTopic>,
+    /// The fork digest advertised in the local ENR, if it has an `eth2` field.
+    pub fork_digest: Option<[u8; 4]>,
+}
+
 pub struct NetworkGlobals<TSpec: EthSpec> {
     /// The current local ENR.
     pub local_enr: RwLock<Enr>,
@@ -104,6 +115,25 @@ impl<TSpec: EthSpec> NetworkGlobals<TSpec> {
         self.sync_state.read().clone()
     }
 
+    /// Returns a consistent view of the peer count, subscriptions and local fork digest.
+    ///
+    /// All the relevant locks are held at the same time whilst reading, so the values are not
+    /// affected by concurrent updates.
+    pub fn snapshot(&self) -> NetworkSnapshot {
+        let peers = self.peers.read();
+        let gossipsub_subscriptions = self.gossipsub_subscriptions.read();
+        let local_enr = self.local_enr.read();
+
+        NetworkSnapshot {
+            connected_peers: peers.connected_peer_ids().count(),
+            gossipsub_subscriptions: gossipsub_subscriptions.iter().cloned().collect(),
+            fork_digest: local_enr
+                .eth2()
+                .ok()
+                .map(|enr_fork_id| enr_fork_id.fork_digest),
+        }
+    }
+
     /// Returns a `Client` type if one is known for the `PeerId`.
     pub fn client(&self, peer_id: &PeerId) -> Client {
         self.peers
@@ -120,3 +150,75 @@ impl<TSpec: EthSpec> NetworkGlobals<TSpec> {
         std::mem::replace(&mut *self.sync_state.write(), new_state)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::discovery::enr::build_enr;
+    use crate::discovery::CombinedKeyExt;
+    use crate::types::{GossipEncoding, GossipKind};
+    use crate::NetworkConfig;
+    use discv5::enr::CombinedKey;
+    use types::{EnrForkId, MinimalEthSpec};
+
+    type E = MinimalEthSpec;
+
+    #[test]
+    fn snapshot_reflects_state() {
+        let fork_digest = [1, 2, 3, 4];
+        let keypair = libp2p::identity::Keypair::generate_secp256k1();
+        let enr_key = CombinedKey::from_libp2p(&keypair).unwrap();
+        let enr_fork_id = EnrForkId {
+            fork_digest,
+            ..EnrForkId::default()
+        };
+        let enr = build_enr::<E>(&enr_key, &NetworkConfig::default(), enr_fork_id).unwrap();
+        let log = slog::Logger::root(slog::Discard, slog::o!());
+        let globals = NetworkGlobals::<E>::new(
+            enr,
+            9000,
+            9000,
+            MetaData {
+                seq_number: 0,
+                attnets: Default::default(),
+            },
+            vec![],
+            &log,
+        );
+
+        assert_eq!(
+            globals.snapshot(),
+            NetworkSnapshot {
+                connected_peers: 0,
+                gossipsub_subscriptions: vec![],
+                fork_digest: Some(fork_digest),
+            }
+        );
+
+        let topic = GossipTopic::new(
+            GossipKind::BeaconBlock,
+            GossipEncoding::default(),
+            fork_digest,
+        );
+        globals
+            .gossipsub_subscriptions
+            .write()
+            .insert(topic.clone());
+        globals.peers.write().connect_ingoing(
+            &PeerId::random(),
+            "/ip4/0.0.0.0".parse().unwrap(),
+            None,
+        );
+
+        let snapshot = globals.snapshot();
+        assert_eq!(
+            snapshot,
+            NetworkSnapshot {
+                connected_peers: 1,
+                gossipsub_subscriptions: vec![topic],
+                fork_digest: Some(fork_digest),
+            }
+        );
+        assert_eq!(snapshot.connected_peers, globals.connected_peers());
+    }
+}
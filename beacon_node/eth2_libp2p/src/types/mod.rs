@@ -12,7 +12,7 @@ pub type EnrBitfield<T: EthSpec> = BitVector<T::SubnetBitfieldLength>;
 
 pub type Enr = discv5::enr::Enr<discv5::enr::CombinedKey>;
 
-pub use globals::NetworkGlobals;
+pub use globals::{NetworkGlobals, NetworkSnapshot};
 pub use pubsub::{PubsubMessage, SnappyTransform};
 pub use subnet::SubnetDiscovery;
 pub use sync_state::SyncState;
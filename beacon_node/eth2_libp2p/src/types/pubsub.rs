@@ -29,7 +29,8 @@ pub enum PubsubMessage<T: EthSpec> {
     AttesterSlashing(Box<AttesterSlashing<T>>),
 }
 
-// Implements the `DataTransform` trait of gossipsub to employ snappy compression
+// Implements the `DataTransform` trait of gossipsub to employ snappy compression on topics which
+// use the `GossipEncoding::SSZSnappy` encoding.
 pub struct SnappyTransform {
     /// Sets the maximum size we allow gossipsub messages to decompress to.
     max_size_per_message: usize,
@@ -41,6 +42,14 @@ impl SnappyTransform {
             max_size_per_message,
         }
     }
+
+    /// Returns `true` if messages on `topic` are expected to be snappy compressed. Unknown topics
+    /// are assumed to use the default encoding.
+    fn is_compressed(topic: &TopicHash) -> bool {
+        GossipTopic::decode(topic.as_str())
+            .map(|topic| *topic.encoding() == GossipEncoding::SSZSnappy)
+            .unwrap_or(true)
+    }
 }
 
 impl DataTransform for SnappyTransform {
@@ -49,22 +58,32 @@ impl DataTransform for SnappyTransform {
         &self,
         raw_message: RawGossipsubMessage,
     ) -> Result<GossipsubMessage, std::io::Error> {
-        // check the length of the raw bytes
-        let len = decompress_len(&raw_message.data)?;
-        if len > self.max_size_per_message {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "ssz_snappy decoded data > GOSSIP_MAX_SIZE",
-            ));
-        }
+        let data = if Self::is_compressed(&raw_message.topic) {
+            // check the length of the raw bytes
+            let len = decompress_len(&raw_message.data)?;
+            if len > self.max_size_per_message {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "ssz_snappy decoded data > GOSSIP_MAX_SIZE",
+                ));
+            }
 
-        let mut decoder = Decoder::new();
-        let decompressed_data = decoder.decompress_vec(&raw_message.data)?;
+            let mut decoder = Decoder::new();
+            decoder.decompress_vec(&raw_message.data)?
+        } else {
+            if raw_message.data.len() > self.max_size_per_message {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "ssz data > GOSSIP_MAX_SIZE",
+                ));
+            }
+            raw_message.data
+        };
 
         // Build the GossipsubMessage struct
         Ok(GossipsubMessage {
             source: raw_message.source,
-            data: decompressed_data,
+            data,
             sequence_number: raw_message.sequence_number,
             topic: raw_message.topic,
         })
@@ -73,17 +92,19 @@ impl DataTransform for SnappyTransform {
     /// Provides the snappy compression logic to gossipsub.
     fn outbound_transform(
         &self,
-        _topic: &TopicHash,
+        topic: &TopicHash,
         data: Vec<u8>,
     ) -> Result<Vec<u8>, std::io::Error> {
-        // Currently we are not employing topic-based compression. Everything is expected to be
-        // snappy compressed.
         if data.len() > self.max_size_per_message {
             return Err(Error::new(
                 ErrorKind::InvalidData,
                 "ssz_snappy Encoded data > GOSSIP_MAX_SIZE",
             ));
         }
+        // Only topics using the `SSZSnappy` encoding are compressed.
+        if !Self::is_compressed(topic) {
+            return Ok(data);
+        }
         let mut encoder = Encoder::new();
         encoder.compress_vec(&data).map_err(Into::into)
     }
@@ -118,10 +139,10 @@ impl<T: EthSpec> PubsubMessage<T> {
         match GossipTopic::decode(topic.as_str()) {
             Err(_) => Err(format!("Unknown gossipsub topic: {:?}", topic)),
             Ok(gossip_topic) => {
-                // All topics are currently expected to be compressed and decompressed with snappy.
-                // This is done in the `SnappyTransform` struct.
-                // Therefore compression has already been handled for us by the time we are
-                // decoding the objects here.
+                // Topics using the `SSZSnappy` encoding are compressed and decompressed with
+                // snappy in the `SnappyTransform` struct. Therefore compression has already been
+                // handled for us by the time we are decoding the objects here, and all supported
+                // encodings carry plain SSZ bytes.
 
                 // the ssz decoders
                 match gossip_topic.kind() {
@@ -168,10 +189,8 @@ impl<T: EthSpec> PubsubMessage<T> {
     /// Encodes a `PubsubMessage` based on the topic encodings. The first known encoding is used. If
     /// no encoding is known, and error is returned.
     pub fn encode(&self, _encoding: GossipEncoding) -> Vec<u8> {
-        // Currently do not employ encoding strategies based on the topic. All messages are ssz
-        // encoded.
-        // Also note, that the compression is handled by the `SnappyTransform` struct. Gossipsub will compress the
-        // messages for us.
+        // All supported encodings are SSZ based. Compression for the `SSZSnappy` encoding is
+        // handled by the `SnappyTransform` struct. Gossipsub will compress the messages for us.
         match &self {
             PubsubMessage::BeaconBlock(data) => data.as_ssz_bytes(),
             PubsubMessage::AggregateAndProofAttestation(data) => data.as_ssz_bytes(),
@@ -8,6 +8,7 @@ use types::SubnetId;
 // For example /eth2/beacon_block/ssz
 pub const TOPIC_PREFIX: &str = "eth2";
 pub const SSZ_SNAPPY_ENCODING_POSTFIX: &str = "ssz_snappy";
+pub const SSZ_ENCODING_POSTFIX: &str = "ssz";
 pub const BEACON_BLOCK_TOPIC: &str = "beacon_block";
 pub const BEACON_AGGREGATE_AND_PROOF_TOPIC: &str = "beacon_aggregate_and_proof";
 pub const BEACON_ATTESTATION_PREFIX: &str = "beacon_attestation_";
@@ -69,6 +70,10 @@ impl std::fmt::Display for GossipKind {
 pub enum GossipEncoding {
     /// Messages are encoded with SSZSnappy.
     SSZSnappy,
+    /// Messages are encoded with SSZ and are not compressed.
+    ///
+    /// This is not part of the specification and is only intended for experimental networks.
+    SSZ,
 }
 
 impl Default for GossipEncoding {
@@ -119,6 +124,7 @@ impl GossipTopic {
 
             let encoding = match topic_parts[4] {
                 SSZ_SNAPPY_ENCODING_POSTFIX => GossipEncoding::SSZSnappy,
+                SSZ_ENCODING_POSTFIX => GossipEncoding::SSZ,
                 _ => return Err(format!("Unknown encoding: {}", topic)),
             };
             let kind = match topic_parts[3] {
@@ -154,6 +160,7 @@ impl Into<String> for GossipTopic {
     fn into(self) -> String {
         let encoding = match self.encoding {
             GossipEncoding::SSZSnappy => SSZ_SNAPPY_ENCODING_POSTFIX,
+            GossipEncoding::SSZ => SSZ_ENCODING_POSTFIX,
         };
 
         let kind = match self.kind {
@@ -218,7 +225,7 @@ mod tests {
     fn topics() -> Vec<String> {
         let mut topics = Vec::new();
         let fork_digest: [u8; 4] = [1, 2, 3, 4];
-        for encoding in [GossipEncoding::SSZSnappy].iter() {
+        for encoding in [GossipEncoding::SSZSnappy, GossipEncoding::SSZ].iter() {
             for kind in [
                 BeaconBlock,
                 BeaconAggregateAndProof,
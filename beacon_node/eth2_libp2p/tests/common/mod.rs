@@ -97,13 +97,26 @@ pub fn build_config(port: u16, mut boot_nodes: Vec<Enr>) -> NetworkConfig {
     config
 }
 
+#[allow(dead_code)]
 pub async fn build_libp2p_instance(
     rt: Weak<Runtime>,
     boot_nodes: Vec<Enr>,
     log: slog::Logger,
+) -> Libp2pInstance {
+    build_configured_libp2p_instance(rt, boot_nodes, log, |_| {}).await
+}
+
+// Builds a libp2p instance, applying `configure` to the default test configuration.
+#[allow(dead_code)]
+pub async fn build_configured_libp2p_instance(
+    rt: Weak<Runtime>,
+    boot_nodes: Vec<Enr>,
+    log: slog::Logger,
+    configure: fn(&mut NetworkConfig),
 ) -> Libp2pInstance {
     let port = unused_port("tcp").unwrap();
-    let config = build_config(port, boot_nodes);
+    let mut config = build_config(port, boot_nodes);
+    configure(&mut config);
     // launch libp2p service
 
     let (signal, exit) = exit_future::signal();
@@ -164,12 +177,24 @@ pub async fn build_full_mesh(
 pub async fn build_node_pair(
     rt: Weak<Runtime>,
     log: &slog::Logger,
+) -> (Libp2pInstance, Libp2pInstance) {
+    build_configured_node_pair(rt, log, |_| {}).await
+}
+
+// Constructs a pair of nodes as in `build_node_pair`, applying `configure` to the configuration of
+// both nodes.
+#[allow(dead_code)]
+pub async fn build_configured_node_pair(
+    rt: Weak<Runtime>,
+    log: &slog::Logger,
+    configure: fn(&mut NetworkConfig),
 ) -> (Libp2pInstance, Libp2pInstance) {
     let sender_log = log.new(o!("who" => "sender"));
     let receiver_log = log.new(o!("who" => "receiver"));
 
-    let mut sender = build_libp2p_instance(rt.clone(), vec![], sender_log).await;
-    let mut receiver = build_libp2p_instance(rt, vec![], receiver_log).await;
+    let mut sender =
+        build_configured_libp2p_instance(rt.clone(), vec![], sender_log, configure).await;
+    let mut receiver = build_configured_libp2p_instance(rt, vec![], receiver_log, configure).await;
 
     let receiver_multiaddr = receiver.swarm.local_enr().multiaddr()[1].clone();
 
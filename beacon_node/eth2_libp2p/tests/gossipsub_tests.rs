@@ -169,3 +169,163 @@ async fn test_gossipsub_full_mesh_publish() {
     }
 }
 */
+
+#![cfg(test)]
+use eth2_libp2p::types::{GossipEncoding, GossipKind};
+use eth2_libp2p::{BehaviourEvent, Libp2pEvent, PubsubMessage};
+use slog::Level;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tokio::time::sleep;
+use types::{BeaconBlock, EthSpec, MinimalEthSpec, Signature, SignedBeaconBlock};
+
+type E = MinimalEthSpec;
+
+mod common;
+
+// Tests that two connected nodes subscribed to the same topic graft each other into their mesh
+#[test]
+fn test_mesh_peers_per_topic() {
+    let log = common::build_log(Level::Debug, false);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+    rt.block_on(async {
+        let (mut sender, mut receiver) = common::build_node_pair(Arc::downgrade(&rt), &log).await;
+
+        assert!(sender.swarm.subscribe_kind(GossipKind::BeaconBlock));
+        assert!(receiver.swarm.subscribe_kind(GossipKind::BeaconBlock));
+        assert_eq!(
+            sender
+                .swarm
+                .mesh_peers_per_topic()
+                .get(&GossipKind::BeaconBlock),
+            Some(&0)
+        );
+
+        let in_mesh = |node: &common::Libp2pInstance| {
+            node.swarm
+                .mesh_peers_per_topic()
+                .get(&GossipKind::BeaconBlock)
+                .map_or(false, |peers| *peers > 0)
+        };
+
+        // drive both nodes until each has the other in its mesh, which happens on a heartbeat
+        let mesh_future = async {
+            while !in_mesh(&sender) || !in_mesh(&receiver) {
+                tokio::select! {
+                    _ = sender.next_event() => {}
+                    _ = receiver.next_event() => {}
+                    _ = sleep(Duration::from_millis(100)) => {}
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = mesh_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
+// Tests that publishing on a topic without any peers reports that the message had no recipients
+#[test]
+fn test_publish_without_peers() {
+    let log = common::build_log(Level::Debug, false);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+    rt.block_on(async {
+        let mut node = common::build_libp2p_instance(Arc::downgrade(&rt), vec![], log).await;
+        assert!(node.swarm.subscribe_kind(GossipKind::BeaconBlock));
+
+        let spec = E::default_spec();
+        let message = PubsubMessage::BeaconBlock(Box::new(SignedBeaconBlock {
+            message: BeaconBlock::empty(&spec),
+            signature: Signature::empty(),
+        }));
+
+        let outcomes = node.swarm.publish(vec![message]);
+        assert_eq!(outcomes.len(), 1);
+        assert_eq!(outcomes[0].topic.kind(), &GossipKind::BeaconBlock);
+        assert!(
+            outcomes[0].has_no_recipients(),
+            "should have no recipients, got {:?}",
+            outcomes[0].result
+        );
+    })
+}
+
+// Tests that nodes configured with a non-default encoding for a topic kind subscribe and publish
+// on the topic for that encoding, and that messages round-trip.
+#[test]
+fn test_publish_with_configured_encoding() {
+    let log = common::build_log(Level::Debug, false);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+    rt.block_on(async {
+        let (mut sender, mut receiver) =
+            common::build_configured_node_pair(Arc::downgrade(&rt), &log, |config| {
+                config.gossip_encodings = vec![(GossipKind::BeaconBlock, GossipEncoding::SSZ)];
+            })
+            .await;
+
+        assert!(sender.swarm.subscribe_kind(GossipKind::BeaconBlock));
+        assert!(receiver.swarm.subscribe_kind(GossipKind::BeaconBlock));
+
+        let in_mesh = |node: &common::Libp2pInstance| {
+            node.swarm
+                .mesh_peers_per_topic()
+                .get(&GossipKind::BeaconBlock)
+                .map_or(false, |peers| *peers > 0)
+        };
+
+        let spec = E::default_spec();
+        let message = PubsubMessage::BeaconBlock(Box::new(SignedBeaconBlock {
+            message: BeaconBlock::empty(&spec),
+            signature: Signature::empty(),
+        }));
+
+        let round_trip_future = async {
+            // drive both nodes until each has the other in its mesh
+            while !in_mesh(&sender) || !in_mesh(&receiver) {
+                tokio::select! {
+                    _ = sender.next_event() => {}
+                    _ = receiver.next_event() => {}
+                    _ = sleep(Duration::from_millis(100)) => {}
+                }
+            }
+
+            let outcomes = sender.swarm.publish(vec![message.clone()]);
+            assert_eq!(outcomes.len(), 1);
+            assert_eq!(outcomes[0].topic.encoding(), &GossipEncoding::SSZ);
+            assert!(outcomes[0].result.is_ok());
+
+            loop {
+                tokio::select! {
+                    _ = sender.next_event() => {}
+                    event = receiver.next_event() => {
+                        if let Libp2pEvent::Behaviour(BehaviourEvent::PubsubMessage {
+                            topic,
+                            message: received,
+                            ..
+                        }) = event
+                        {
+                            assert!(topic.as_str().ends_with("/ssz"));
+                            assert_eq!(received, message);
+                            return;
+                        }
+                    }
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = round_trip_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
@@ -1,5 +1,6 @@
 #![cfg(test)]
 use eth2_libp2p::rpc::methods::*;
+use eth2_libp2p::rpc::RPCError;
 use eth2_libp2p::{BehaviourEvent, Libp2pEvent, ReportSource, Request, Response};
 use slog::{debug, warn, Level};
 use ssz_types::VariableList;
@@ -742,6 +743,126 @@ fn test_blocks_by_root_chunked_rpc_terminates_correctly() {
     })
 }
 
+// Tests that a BlocksByRange stream which stalls mid-way is timed out and reported as failed
+#[test]
+#[allow(clippy::single_match)]
+fn test_blocks_by_range_stalled_stream_times_out() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Debug;
+    let enable_logging = false;
+
+    let messages_to_send = 10;
+    // the receiver stalls after sending this many chunks
+    let messages_before_stall = 2;
+
+    let log = common::build_log(log_level, enable_logging);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    rt.block_on(async {
+        // get sender/receiver
+        let (mut sender, mut receiver) = common::build_node_pair(Arc::downgrade(&rt), &log).await;
+
+        // BlocksByRange Request
+        let rpc_request = Request::BlocksByRange(BlocksByRangeRequest {
+            start_slot: 0,
+            count: messages_to_send,
+            step: 0,
+        });
+
+        // BlocksByRange Response
+        let spec = E::default_spec();
+        let empty_block = BeaconBlock::empty(&spec);
+        let empty_signed = SignedBeaconBlock {
+            message: empty_block,
+            signature: Signature::empty(),
+        };
+        let rpc_response = Response::BlocksByRange(Some(Box::new(empty_signed)));
+
+        // keep count of the number of messages received
+        let mut messages_received: u64 = 0;
+        // build the sender future
+        let sender_future = async {
+            loop {
+                match sender.next_event().await {
+                    Libp2pEvent::Behaviour(BehaviourEvent::PeerDialed(peer_id)) => {
+                        debug!(log, "Sending RPC");
+                        sender.swarm.send_request(
+                            peer_id,
+                            RequestId::Sync(10),
+                            rpc_request.clone(),
+                        );
+                    }
+                    Libp2pEvent::Behaviour(BehaviourEvent::ResponseReceived {
+                        peer_id: _,
+                        id: RequestId::Sync(10),
+                        response,
+                    }) => match response {
+                        Response::BlocksByRange(Some(_)) => {
+                            assert_eq!(response, rpc_response.clone());
+                            messages_received += 1;
+                        }
+                        Response::BlocksByRange(None) => {
+                            panic!("Stalled stream should not terminate successfully")
+                        }
+                        _ => panic!("Invalid RPC received"),
+                    },
+                    Libp2pEvent::Behaviour(BehaviourEvent::RPCFailed {
+                        peer_id: _,
+                        id: RequestId::Sync(10),
+                        error,
+                    }) => {
+                        // the stream timed out after the receiver stalled
+                        debug!(log, "Sender request failed"; "error" => %error);
+                        assert!(
+                            matches!(error, RPCError::StreamTimeout),
+                            "request should fail with a stream timeout"
+                        );
+                        assert_eq!(messages_received, messages_before_stall);
+                        return;
+                    }
+                    _ => {} // Ignore other behaviour events
+                }
+            }
+        };
+
+        // build the receiver future
+        let receiver_future = async {
+            loop {
+                match receiver.next_event().await {
+                    Libp2pEvent::Behaviour(BehaviourEvent::RequestReceived {
+                        peer_id,
+                        id,
+                        request,
+                    }) => {
+                        if request == rpc_request {
+                            // send some of the requested chunks, then stall without terminating
+                            // the stream
+                            warn!(log, "Receiver got request");
+                            for _ in 0..messages_before_stall {
+                                receiver.swarm.send_successful_response(
+                                    peer_id,
+                                    id,
+                                    rpc_response.clone(),
+                                );
+                            }
+                        }
+                    }
+                    _ => {} // Ignore other events
+                }
+            }
+        };
+
+        tokio::select! {
+            _ = sender_future => {}
+            _ = receiver_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
+
 // Tests a Goodbye RPC message
 #[test]
 #[allow(clippy::single_match)]
@@ -801,3 +922,61 @@ fn test_goodbye_rpc() {
         }
     })
 }
+
+// Tests that a goodbye is sent to all connected peers without banning them
+#[test]
+#[allow(clippy::single_match)]
+fn test_goodbye_all_rpc() {
+    // set up the logging. The level and enabled logging or not
+    let log_level = Level::Trace;
+    let enable_logging = false;
+
+    let log = common::build_log(log_level, enable_logging);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+    // get sender/receiver
+    rt.block_on(async {
+        let (mut sender, mut receiver) = common::build_node_pair(Arc::downgrade(&rt), &log).await;
+
+        // build the sender future
+        let sender_future = async {
+            loop {
+                match sender.next_event().await {
+                    Libp2pEvent::Behaviour(BehaviourEvent::PeerDialed(_)) => {
+                        // Send a goodbye to everyone, as on shutdown
+                        debug!(log, "Sending goodbye to all peers");
+                        sender.swarm.goodbye_all(GoodbyeReason::ClientShutdown);
+                    }
+                    Libp2pEvent::Behaviour(BehaviourEvent::PeerDisconnected(peer_id)) => {
+                        // A shutdown goodbye must not ban the peer
+                        assert!(!sender.swarm.peer_manager().is_banned(&peer_id));
+                        return;
+                    }
+                    _ => {} // Ignore other RPC messages
+                }
+            }
+        };
+
+        // build the receiver future
+        let receiver_future = async {
+            loop {
+                match receiver.next_event().await {
+                    Libp2pEvent::Behaviour(BehaviourEvent::PeerDisconnected(_)) => {
+                        // Should be disconnected by the goodbye
+                        return;
+                    }
+                    _ => {} // Ignore other events
+                }
+            }
+        };
+
+        let total_future = futures::future::join(sender_future, receiver_future);
+
+        tokio::select! {
+            _ = total_future => {}
+            _ = sleep(Duration::from_secs(30)) => {
+                panic!("Future timed out");
+            }
+        }
+    })
+}
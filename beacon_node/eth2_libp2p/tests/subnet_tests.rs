@@ -0,0 +1,62 @@
+#![cfg(test)]
+use eth2_libp2p::Service as LibP2PService;
+use slog::Level;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+use types::{ChainSpec, EnrForkId, MinimalEthSpec};
+
+mod common;
+
+type E = MinimalEthSpec;
+
+// Tests subscribing to and unsubscribing from all attestation subnets at once
+#[test]
+fn test_subscribe_to_all_subnets() {
+    // set up the logging. The level and enabled logging or not
+    let log = common::build_log(Level::Debug, false);
+
+    let rt = Arc::new(Runtime::new().unwrap());
+
+    rt.block_on(async {
+        let port = common::unused_port("tcp").unwrap();
+        let config = common::build_config(port, vec![]);
+        let spec = ChainSpec::minimal();
+
+        let (_signal, exit) = exit_future::signal();
+        let (shutdown_tx, _) = futures::channel::mpsc::channel(1);
+        let executor =
+            task_executor::TaskExecutor::new(Arc::downgrade(&rt), exit, log.clone(), shutdown_tx);
+        let (globals, mut service) =
+            LibP2PService::<E>::new(executor, &config, EnrForkId::default(), &log, &spec)
+                .await
+                .expect("should build libp2p instance");
+
+        let subnet_count = spec.attestation_subnet_count as usize;
+        let initial_seq_number = globals.local_metadata.read().seq_number;
+
+        assert_eq!(service.swarm.subscribe_to_all_subnets(), subnet_count);
+        assert_eq!(globals.gossipsub_subscriptions.read().len(), subnet_count);
+        assert!(globals.local_metadata.read().attnets.iter().all(|bit| bit));
+        assert_eq!(
+            globals.local_metadata.read().seq_number,
+            initial_seq_number + 1,
+            "metadata should only be updated once"
+        );
+
+        // Subscribing again should not change anything.
+        assert_eq!(service.swarm.subscribe_to_all_subnets(), 0);
+        assert_eq!(
+            globals.local_metadata.read().seq_number,
+            initial_seq_number + 1
+        );
+
+        assert_eq!(service.swarm.unsubscribe_from_all_subnets(), subnet_count);
+        assert!(globals.gossipsub_subscriptions.read().is_empty());
+        assert!(globals.local_metadata.read().attnets.iter().all(|bit| !bit));
+        assert_eq!(
+            globals.local_metadata.read().seq_number,
+            initial_seq_number + 2,
+            "metadata should only be updated once"
+        );
+    })
+}
@@ -1,7 +1,7 @@
 use beacon_chain::{BeaconChain, BeaconChainTypes, WhenSlotSkipped};
 use eth2::types::BlockId as CoreBlockId;
 use std::str::FromStr;
-use types::{Hash256, SignedBeaconBlock, Slot};
+use types::{EthSpec, Hash256, SignedBeaconBlock, Slot};
 
 /// Wraps `eth2::types::BlockId` and provides a simple way to obtain a block or root for a given
 /// `BlockId`.
@@ -47,6 +47,9 @@ impl BlockId {
                         ))
                     })
                 }),
+            CoreBlockId::Epoch(epoch) => {
+                Self::from_slot(epoch.start_slot(T::EthSpec::slots_per_epoch())).root(chain)
+            }
             CoreBlockId::Root(root) => Ok(*root),
         }
     }
@@ -60,6 +63,9 @@ impl BlockId {
             CoreBlockId::Head => chain
                 .head_beacon_block()
                 .map_err(warp_utils::reject::beacon_chain_error),
+            CoreBlockId::Epoch(epoch) => {
+                Self::from_slot(epoch.start_slot(T::EthSpec::slots_per_epoch())).block(chain)
+            }
             CoreBlockId::Slot(slot) => {
                 let root = self.root(chain)?;
                 chain
@@ -21,7 +21,7 @@ use beacon_chain::{
 };
 use block_id::BlockId;
 use eth2::types::{self as api_types, ValidatorId};
-use eth2_libp2p::{types::SyncState, EnrExt, NetworkGlobals, PeerId, PubsubMessage};
+use eth2_libp2p::{types::SyncState, NetworkGlobals, PeerId, PubsubMessage};
 use lighthouse_version::version_with_platform;
 use network::NetworkMessage;
 use serde::{Deserialize, Serialize};
@@ -34,6 +34,7 @@ use std::convert::TryInto;
 use std::future::Future;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio_stream::{wrappers::BroadcastStream, StreamExt};
 use types::{
@@ -41,7 +42,7 @@ use types::{
     SignedAggregateAndProof, SignedBeaconBlock, SignedVoluntaryExit, Slot, YamlConfig,
 };
 use warp::http::StatusCode;
-use warp::sse::Event;
+use warp::hyper::{body::Bytes, Body};
 use warp::Reply;
 use warp::{http::Response, Filter};
 use warp_utils::task::{blocking_json_task, blocking_task};
@@ -56,6 +57,9 @@ const API_VERSION: &str = "v1";
 /// finalized head.
 const SYNC_TOLERANCE_EPOCHS: u64 = 8;
 
+/// The interval between the comments sent on an idle server-sent event stream.
+const SSE_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
 /// A wrapper around all the items required to spawn the HTTP server.
 ///
 /// The server will gracefully handle the case where any fields are `None`.
@@ -460,7 +464,7 @@ pub fn serve<T: BeaconChainTypes>(
             },
         );
 
-    // GET beacon/states/{state_id}/validators?id,status
+    // GET beacon/states/{state_id}/validators?id,status,offset,limit
     let get_beacon_state_validators = beacon_states_path
         .clone()
         .and(warp::path("validators"))
@@ -469,57 +473,70 @@ pub fn serve<T: BeaconChainTypes>(
         .and_then(
             |state_id: StateId, chain: Arc<BeaconChain<T>>, query: api_types::ValidatorsQuery| {
                 blocking_json_task(move || {
-                    state_id
-                        .map_state(&chain, |state| {
-                            let epoch = state.current_epoch();
-                            let far_future_epoch = chain.spec.far_future_epoch;
+                    state_id.map_state(&chain, |state| {
+                        let epoch = state.current_epoch();
+                        let far_future_epoch = chain.spec.far_future_epoch;
 
-                            Ok(state
-                                .validators
-                                .iter()
-                                .zip(state.balances.iter())
-                                .enumerate()
-                                // filter by validator id(s) if provided
-                                .filter(|(index, (validator, _))| {
-                                    query.id.as_ref().map_or(true, |ids| {
-                                        ids.0.iter().any(|id| match id {
-                                            ValidatorId::PublicKey(pubkey) => {
-                                                &validator.pubkey == pubkey
-                                            }
-                                            ValidatorId::Index(param_index) => {
-                                                *param_index == *index as u64
-                                            }
-                                        })
+                        let validators = state
+                            .validators
+                            .iter()
+                            .zip(state.balances.iter())
+                            .enumerate()
+                            // filter by validator id(s) if provided
+                            .filter(|(index, (validator, _))| {
+                                query.id.as_ref().map_or(true, |ids| {
+                                    ids.0.iter().any(|id| match id {
+                                        ValidatorId::PublicKey(pubkey) => {
+                                            &validator.pubkey == pubkey
+                                        }
+                                        ValidatorId::Index(param_index) => {
+                                            *param_index == *index as u64
+                                        }
                                     })
                                 })
-                                // filter by status(es) if provided and map the result
-                                .filter_map(|(index, (validator, balance))| {
-                                    let status = api_types::ValidatorStatus::from_validator(
-                                        validator,
-                                        epoch,
-                                        far_future_epoch,
-                                    );
+                            })
+                            // filter by status(es) if provided
+                            .filter_map(|(index, (validator, balance))| {
+                                let status = api_types::ValidatorStatus::from_validator(
+                                    validator,
+                                    epoch,
+                                    far_future_epoch,
+                                );
 
-                                    let status_matches =
-                                        query.status.as_ref().map_or(true, |statuses| {
-                                            statuses.0.contains(&status)
-                                                || statuses.0.contains(&status.superstatus())
-                                        });
-
-                                    if status_matches {
-                                        Some(api_types::ValidatorData {
-                                            index: index as u64,
-                                            balance: *balance,
-                                            status,
-                                            validator: validator.clone(),
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect::<Vec<_>>())
-                        })
-                        .map(api_types::GenericResponse::from)
+                                let status_matches =
+                                    query.status.as_ref().map_or(true, |statuses| {
+                                        statuses
+                                            .0
+                                            .iter()
+                                            .any(|queried| status.matches_query(*queried))
+                                    });
+
+                                if status_matches {
+                                    Some((index, validator, balance, status))
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect::<Vec<_>>();
+
+                        // count all matching validators, but only clone those in the requested
+                        // page
+                        let total = validators.len() as u64;
+                        let data = query
+                            .paginate(validators)
+                            .into_iter()
+                            .map(
+                                |(index, validator, balance, status)| api_types::ValidatorData {
+                                    index: index as u64,
+                                    balance: *balance,
+                                    status,
+                                    validator: validator.clone(),
+                                },
+                            )
+                            .collect();
+
+                        Ok(api_types::PaginatedResponse { data, total })
+                    })
                 })
             },
         );
@@ -778,18 +795,35 @@ pub fn serve<T: BeaconChainTypes>(
         .and(warp::path("beacon"))
         .and(warp::path("blocks"))
         .and(warp::path::end())
-        .and(warp::body::json())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::body::bytes())
         .and(chain_filter.clone())
         .and(network_tx_filter.clone())
         .and(log_filter.clone())
         .and_then(
-            |block: SignedBeaconBlock<T::EthSpec>,
+            |content_type: Option<String>,
+             body: warp::hyper::body::Bytes,
              chain: Arc<BeaconChain<T>>,
              network_tx: UnboundedSender<NetworkMessage<T::EthSpec>>,
              log: Logger| {
                 blocking_json_task(move || {
                     let seen_timestamp = timestamp_now();
 
+                    // The block may be encoded as either SSZ or JSON, defaulting to JSON. Any other
+                    // content type is rejected.
+                    let content_type = content_type
+                        .map(|s| s.parse::<api_types::ContentType>())
+                        .transpose()
+                        .map_err(warp_utils::reject::unsupported_media_type)?
+                        .unwrap_or(api_types::ContentType::Json);
+                    let block: SignedBeaconBlock<T::EthSpec> =
+                        api_types::decode_body(content_type, &body).map_err(|e| {
+                            warp_utils::reject::custom_bad_request(format!(
+                                "invalid block: {:?}",
+                                e
+                            ))
+                        })?;
+
                     // Send the block, regardless of whether or not it is valid. The API
                     // specification is very clear that this is the desired behaviour.
                     publish_pubsub_message(
@@ -806,7 +840,7 @@ pub fn serve<T: BeaconChainTypes>(
                     );
 
 
-                    match chain.process_block(block.clone()) {
+                    match chain.process_block(block.clone(), seen_timestamp) {
                         Ok(root) => {
                             info!(
                                 log,
@@ -1049,7 +1083,7 @@ pub fn serve<T: BeaconChainTypes>(
             },
         );
 
-    // GET beacon/pool/attestations?committee_index,slot
+    // GET beacon/pool/attestations?committee_index,slot,min_slot,max_slot
     let get_beacon_pool_attestations = beacon_pool_path
         .clone()
         .and(warp::path("attestations"))
@@ -1058,24 +1092,16 @@ pub fn serve<T: BeaconChainTypes>(
         .and_then(
             |chain: Arc<BeaconChain<T>>, query: api_types::AttestationPoolQuery| {
                 blocking_json_task(move || {
+                    query
+                        .validate()
+                        .map_err(warp_utils::reject::custom_bad_request)?;
+
                     let query_filter = |attestation: &Attestation<T::EthSpec>| {
-                        query
-                            .slot
-                            .map_or(true, |slot| slot == attestation.data.slot)
-                            && query
-                                .committee_index
-                                .map_or(true, |index| index == attestation.data.index)
+                        query.matches(attestation.data.slot, attestation.data.index)
                     };
 
                     let mut attestations = chain.op_pool.get_filtered_attestations(query_filter);
-                    attestations.extend(
-                        chain
-                            .naive_aggregation_pool
-                            .read()
-                            .iter()
-                            .cloned()
-                            .filter(query_filter),
-                    );
+                    attestations.extend(chain.naive_aggregation_pool.iter().filter(query_filter));
                     Ok(api_types::GenericResponse::from(attestations))
                 })
             },
@@ -1375,28 +1401,27 @@ pub fn serve<T: BeaconChainTypes>(
         .and_then(|network_globals: Arc<NetworkGlobals<T::EthSpec>>| {
             blocking_json_task(move || {
                 let enr = network_globals.local_enr();
-                let p2p_addresses = enr.multiaddr_p2p_tcp();
-                let discovery_addresses = enr.multiaddr_p2p_udp();
-                Ok(api_types::GenericResponse::from(api_types::IdentityData {
-                    peer_id: network_globals.local_peer_id().to_base58(),
-                    enr,
-                    p2p_addresses,
-                    discovery_addresses,
-                    metadata: api_types::MetaData {
-                        seq_number: network_globals.local_metadata.read().seq_number,
-                        attnets: format!(
-                            "0x{}",
-                            hex::encode(
-                                network_globals
-                                    .local_metadata
-                                    .read()
-                                    .attnets
-                                    .clone()
-                                    .into_bytes()
+                let listen_addrs = network_globals.listen_multiaddrs();
+                Ok(api_types::GenericResponse::from(
+                    api_types::IdentityData::from_enr_and_listen_addrs(
+                        &enr,
+                        &listen_addrs,
+                        api_types::MetaData {
+                            seq_number: network_globals.local_metadata.read().seq_number,
+                            attnets: format!(
+                                "0x{}",
+                                hex::encode(
+                                    network_globals
+                                        .local_metadata
+                                        .read()
+                                        .attnets
+                                        .clone()
+                                        .into_bytes()
+                                ),
                             ),
-                        ),
-                    },
-                }))
+                        },
+                    ),
+                ))
             })
         });
 
@@ -1493,33 +1518,15 @@ pub fn serve<T: BeaconChainTypes>(
                         warp_utils::reject::custom_bad_request("invalid peer id.".to_string())
                     })?;
 
-                    if let Some(peer_info) = network_globals.peers.read().peer_info(&peer_id) {
-                        let address = if let Some(socket_addr) =
-                            peer_info.seen_addresses.iter().next()
-                        {
-                            let mut addr = eth2_libp2p::Multiaddr::from(socket_addr.ip());
-                            addr.push(eth2_libp2p::multiaddr::Protocol::Tcp(socket_addr.port()));
-                            addr.to_string()
-                        } else if let Some(addr) = peer_info.listening_addresses.first() {
-                            addr.to_string()
-                        } else {
-                            String::new()
-                        };
-
-                        // the eth2 API spec implies only peers we have been connected to at some point should be included.
-                        if let Some(dir) = peer_info.connection_direction.as_ref() {
-                            return Ok(api_types::GenericResponse::from(api_types::PeerData {
-                                peer_id: peer_id.to_string(),
-                                enr: peer_info.enr.as_ref().map(|enr| enr.to_base64()),
-                                last_seen_p2p_address: address,
-                                direction: api_types::PeerDirection::from_connection_direction(
-                                    &dir,
-                                ),
-                                state: api_types::PeerState::from_peer_connection_status(
-                                    &peer_info.connection_status(),
-                                ),
-                            }));
-                        }
+                    if let Some(peer_data) = network_globals
+                        .peers
+                        .read()
+                        .peer_info(&peer_id)
+                        .and_then(|peer_info| {
+                            api_types::PeerData::from_peer_info(&peer_id, peer_info)
+                        })
+                    {
+                        return Ok(api_types::GenericResponse::from(peer_data));
                     }
                     Err(warp_utils::reject::custom_not_found(
                         "peer not found.".to_string(),
@@ -1538,52 +1545,15 @@ pub fn serve<T: BeaconChainTypes>(
         .and_then(
             |query: api_types::PeersQuery, network_globals: Arc<NetworkGlobals<T::EthSpec>>| {
                 blocking_json_task(move || {
-                    let mut peers: Vec<api_types::PeerData> = Vec::new();
-                    network_globals
+                    let peers: Vec<api_types::PeerData> = network_globals
                         .peers
                         .read()
                         .peers()
-                        .for_each(|(peer_id, peer_info)| {
-                            let address =
-                                if let Some(socket_addr) = peer_info.seen_addresses.iter().next() {
-                                    let mut addr = eth2_libp2p::Multiaddr::from(socket_addr.ip());
-                                    addr.push(eth2_libp2p::multiaddr::Protocol::Tcp(
-                                        socket_addr.port(),
-                                    ));
-                                    addr.to_string()
-                                } else if let Some(addr) = peer_info.listening_addresses.first() {
-                                    addr.to_string()
-                                } else {
-                                    String::new()
-                                };
-
-                            // the eth2 API spec implies only peers we have been connected to at some point should be included.
-                            if let Some(dir) = peer_info.connection_direction.as_ref() {
-                                let direction =
-                                    api_types::PeerDirection::from_connection_direction(&dir);
-                                let state = api_types::PeerState::from_peer_connection_status(
-                                    &peer_info.connection_status(),
-                                );
-
-                                let state_matches = query.state.as_ref().map_or(true, |states| {
-                                    states.0.iter().any(|state_param| *state_param == state)
-                                });
-                                let direction_matches =
-                                    query.direction.as_ref().map_or(true, |directions| {
-                                        directions.0.iter().any(|dir_param| *dir_param == direction)
-                                    });
-
-                                if state_matches && direction_matches {
-                                    peers.push(api_types::PeerData {
-                                        peer_id: peer_id.to_string(),
-                                        enr: peer_info.enr.as_ref().map(|enr| enr.to_base64()),
-                                        last_seen_p2p_address: address,
-                                        direction,
-                                        state,
-                                    });
-                                }
-                            }
-                        });
+                        .filter_map(|(peer_id, peer_info)| {
+                            api_types::PeerData::from_peer_info(peer_id, peer_info)
+                        })
+                        .filter(|peer| query.matches(peer))
+                        .collect();
                     Ok(api_types::PeersData {
                         meta: api_types::PeersMetaData {
                             count: peers.len() as u64,
@@ -1874,12 +1844,47 @@ pub fn serve<T: BeaconChainTypes>(
         .and(warp::body::json())
         .and(network_tx_filter)
         .and(chain_filter.clone())
+        .and(log_filter.clone())
         .and_then(
             |subscriptions: Vec<api_types::BeaconCommitteeSubscription>,
              network_tx: UnboundedSender<NetworkMessage<T::EthSpec>>,
-             chain: Arc<BeaconChain<T>>| {
+             chain: Arc<BeaconChain<T>>,
+             log: Logger| {
                 blocking_json_task(move || {
-                    for subscription in &subscriptions {
+                    let earliest_current_slot = chain
+                        .slot_clock
+                        .now_with_past_tolerance(chain.maximum_gossip_clock_disparity())
+                        .ok_or(BeaconChainError::UnableToReadSlot)
+                        .map_err(warp_utils::reject::beacon_chain_error)?;
+                    let latest_current_slot = chain
+                        .slot_clock
+                        .now_with_future_tolerance(chain.maximum_gossip_clock_disparity())
+                        .ok_or(BeaconChainError::UnableToReadSlot)
+                        .map_err(warp_utils::reject::beacon_chain_error)?;
+
+                    // Invalid subscriptions are skipped so that they do not prevent the valid
+                    // subscriptions in the same request from being applied.
+                    let subscriptions = subscriptions.into_iter().filter(|subscription| {
+                        match subscription.validate::<T::EthSpec>(
+                            earliest_current_slot,
+                            latest_current_slot,
+                            &chain.spec,
+                        ) {
+                            Ok(()) => true,
+                            Err(e) => {
+                                warn!(
+                                    log,
+                                    "Ignoring invalid committee subscription";
+                                    "error" => e,
+                                    "validator_index" => subscription.validator_index,
+                                    "slot" => subscription.slot,
+                                );
+                                false
+                            }
+                        }
+                    });
+
+                    for subscription in subscriptions {
                         chain
                             .validator_monitor
                             .write()
@@ -2153,23 +2158,20 @@ pub fn serve<T: BeaconChainTypes>(
                                 api_types::EventTopic::FinalizedCheckpoint => {
                                     event_handler.subscribe_finalized()
                                 }
+                                api_types::EventTopic::JustifiedCheckpoint => {
+                                    event_handler.subscribe_justified()
+                                }
+                                api_types::EventTopic::ChainReorg => {
+                                    event_handler.subscribe_reorgs()
+                                }
                             };
 
                             receivers.push(BroadcastStream::new(receiver).map(|msg| {
-                                match msg {
-                                    Ok(data) => Event::default()
-                                        .event(data.topic_name())
-                                        .json_data(data)
-                                        .map_err(|e| {
-                                            warp_utils::reject::server_sent_event_error(format!(
-                                                "{:?}",
-                                                e
-                                            ))
-                                        }),
-                                    Err(e) => Err(warp_utils::reject::server_sent_event_error(
-                                        format!("{:?}", e),
-                                    )),
-                                }
+                                msg.map_err(|e| format!("{:?}", e))
+                                    .and_then(|data| {
+                                        data.to_sse_bytes().map_err(|e| format!("{:?}", e))
+                                    })
+                                    .map(Bytes::from)
                             }));
                         }
                     } else {
@@ -2178,9 +2180,25 @@ pub fn serve<T: BeaconChainTypes>(
                         ));
                     }
 
-                    let s = futures::stream::select_all(receivers);
-
-                    Ok::<_, warp::Rejection>(warp::sse::reply(warp::sse::keep_alive().stream(s)))
+                    // Periodically send an SSE comment so that idle connections are not dropped.
+                    let keep_alive = futures::stream::unfold((), |()| async {
+                        tokio::time::sleep(SSE_KEEP_ALIVE_INTERVAL).await;
+                        Some((Ok::<_, String>(Bytes::from_static(b":\n\n")), ()))
+                    });
+                    let s =
+                        futures::stream::select(futures::stream::select_all(receivers), keep_alive);
+
+                    Response::builder()
+                        .status(200)
+                        .header("Content-Type", "text/event-stream")
+                        .header("Cache-Control", "no-cache")
+                        .body(Body::wrap_stream(s))
+                        .map_err(|e| {
+                            warp_utils::reject::custom_server_error(format!(
+                                "failed to create response: {}",
+                                e
+                            ))
+                        })
                 })
             },
         );
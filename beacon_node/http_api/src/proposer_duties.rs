@@ -1,9 +1,7 @@
 //! Contains the handler for the `GET validator/duties/proposer/{epoch}` endpoint.
 
 use crate::state_id::StateId;
-use beacon_chain::{
-    BeaconChain, BeaconChainError, BeaconChainTypes, MAXIMUM_GOSSIP_CLOCK_DISPARITY,
-};
+use beacon_chain::{BeaconChain, BeaconChainError, BeaconChainTypes};
 use eth2::types::{self as api_types};
 use slog::{debug, Logger};
 use slot_clock::SlotClock;
@@ -25,14 +23,14 @@ pub fn proposer_duties<T: BeaconChainTypes>(
         .map_err(warp_utils::reject::beacon_chain_error)?;
 
     // Determine what the current epoch would be if we fast-forward our system clock by
-    // `MAXIMUM_GOSSIP_CLOCK_DISPARITY`.
+    // `chain.maximum_gossip_clock_disparity()`.
     //
     // Most of the time, `tolerant_current_epoch` will be equal to `current_epoch`. However, during
-    // the first `MAXIMUM_GOSSIP_CLOCK_DISPARITY` duration of the epoch `tolerant_current_epoch`
-    // will equal `current_epoch + 1`
+    // the first `chain.maximum_gossip_clock_disparity()` duration of the epoch,
+    // `tolerant_current_epoch` will equal `current_epoch + 1`
     let tolerant_current_epoch = chain
         .slot_clock
-        .now_with_future_tolerance(MAXIMUM_GOSSIP_CLOCK_DISPARITY)
+        .now_with_future_tolerance(chain.maximum_gossip_clock_disparity())
         .ok_or_else(|| warp_utils::reject::custom_server_error("unable to read slot clock".into()))?
         .epoch(T::EthSpec::slots_per_epoch());
 
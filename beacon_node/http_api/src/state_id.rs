@@ -40,6 +40,7 @@ impl StateId {
                     .start_slot(T::EthSpec::slots_per_epoch())
             }),
             CoreStateId::Slot(slot) => Ok(*slot),
+            CoreStateId::Epoch(epoch) => Ok(epoch.start_slot(T::EthSpec::slots_per_epoch())),
             CoreStateId::Root(root) => return Ok(*root),
         }
         .map_err(warp_utils::reject::beacon_chain_error)?;
@@ -72,6 +73,10 @@ impl StateId {
                     .map_err(warp_utils::reject::beacon_chain_error)
             }
             CoreStateId::Slot(slot) => (self.root(chain)?, Some(*slot)),
+            CoreStateId::Epoch(epoch) => (
+                self.root(chain)?,
+                Some(epoch.start_slot(T::EthSpec::slots_per_epoch())),
+            ),
             _ => (self.root(chain)?, None),
         };
 
@@ -20,10 +20,11 @@ use http_api::{Config, Context};
 use network::NetworkMessage;
 use sensitive_url::SensitiveUrl;
 use slot_clock::SlotClock;
+use ssz::Encode;
 use state_processing::per_slot_processing;
 use std::convert::TryInto;
 use std::iter::Iterator;
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tokio::sync::oneshot;
@@ -65,6 +66,7 @@ struct ApiTester {
     proposer_slashing: ProposerSlashing,
     voluntary_exit: SignedVoluntaryExit,
     _server_shutdown: oneshot::Sender<()>,
+    listening_socket: SocketAddr,
     validator_keypairs: Vec<Keypair>,
     network_rx: mpsc::UnboundedReceiver<NetworkMessage<E>>,
     local_enr: Enr,
@@ -218,6 +220,7 @@ impl ApiTester {
             proposer_slashing,
             voluntary_exit,
             _server_shutdown: shutdown_tx,
+            listening_socket,
             validator_keypairs: harness.validator_keypairs,
             network_rx,
             local_enr: enr_clone,
@@ -325,6 +328,7 @@ impl ApiTester {
             proposer_slashing,
             voluntary_exit,
             _server_shutdown: shutdown_tx,
+            listening_socket,
             validator_keypairs: harness.validator_keypairs,
             network_rx,
             local_enr: enr_clone,
@@ -354,6 +358,7 @@ impl ApiTester {
             StateId::Slot(Slot::from(SKIPPED_SLOTS[1])),
             StateId::Slot(Slot::from(SKIPPED_SLOTS[2])),
             StateId::Slot(Slot::from(SKIPPED_SLOTS[3])),
+            StateId::Epoch(Epoch::new(1)),
             StateId::Root(Hash256::zero()),
         ];
         ids.push(StateId::Root(self.chain.head_info().unwrap().state_root));
@@ -372,6 +377,7 @@ impl ApiTester {
             BlockId::Slot(Slot::from(SKIPPED_SLOTS[1])),
             BlockId::Slot(Slot::from(SKIPPED_SLOTS[2])),
             BlockId::Slot(Slot::from(SKIPPED_SLOTS[3])),
+            BlockId::Epoch(Epoch::new(1)),
             BlockId::Root(Hash256::zero()),
         ];
         ids.push(BlockId::Root(self.chain.head_info().unwrap().block_root));
@@ -424,6 +430,12 @@ impl ApiTester {
 
                 self.chain.get_state(&root, Some(slot)).unwrap()
             }
+            StateId::Epoch(epoch) => {
+                let slot = epoch.start_slot(E::slots_per_epoch());
+                let root = self.chain.state_root_at_slot(slot).unwrap().unwrap();
+
+                self.chain.get_state(&root, Some(slot)).unwrap()
+            }
             StateId::Root(root) => self.chain.get_state(&root, None).unwrap(),
         }
     }
@@ -478,6 +490,10 @@ impl ApiTester {
                     self.chain.state_root_at_slot(justified_slot).unwrap()
                 }
                 StateId::Slot(slot) => self.chain.state_root_at_slot(slot).unwrap(),
+                StateId::Epoch(epoch) => self
+                    .chain
+                    .state_root_at_slot(epoch.start_slot(E::slots_per_epoch()))
+                    .unwrap(),
                 StateId::Root(root) => Some(root),
             };
 
@@ -658,9 +674,10 @@ impl ApiTester {
                                 epoch,
                                 far_future_epoch,
                             );
-                            if statuses.contains(&status)
-                                || statuses.is_empty()
-                                || statuses.contains(&status.superstatus())
+                            if statuses.is_empty()
+                                || statuses
+                                    .iter()
+                                    .any(|queried| status.matches_query(*queried))
                             {
                                 validators.push(ValidatorData {
                                     index: i as u64,
@@ -683,6 +700,57 @@ impl ApiTester {
         self
     }
 
+    pub async fn test_beacon_states_validators_paginated(self) -> Self {
+        for state_id in self.interesting_state_ids() {
+            let all = self
+                .client
+                .get_beacon_states_validators(state_id, None, None)
+                .await
+                .unwrap()
+                .map(|res| res.data);
+
+            let all = match all {
+                Some(all) => all,
+                None => continue,
+            };
+            let total = all.len() as u64;
+
+            for (offset, limit) in &[
+                (None, None),
+                (Some(1), None),
+                (None, Some(3)),
+                (Some(2), Some(3)),
+                (Some(total.saturating_sub(1)), Some(3)),
+                (Some(total), Some(3)),
+                (Some(total + 10), None),
+                (None, Some(0)),
+            ] {
+                let result = self
+                    .client
+                    .get_beacon_states_validators_paginated(state_id, None, None, *offset, *limit)
+                    .await
+                    .unwrap()
+                    .expect("state should exist");
+
+                let expected = all
+                    .iter()
+                    .skip(offset.unwrap_or(0) as usize)
+                    .take(limit.map_or(usize::MAX, |limit| limit as usize))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                assert_eq!(result.total, total, "{:?}", state_id);
+                assert_eq!(
+                    result.data, expected,
+                    "{:?} offset: {:?} limit: {:?}",
+                    state_id, offset, limit
+                );
+            }
+        }
+
+        self
+    }
+
     pub async fn test_beacon_states_validator_id(self) -> Self {
         for state_id in self.interesting_state_ids() {
             let state_opt = self.get_state(state_id);
@@ -795,6 +863,13 @@ impl ApiTester {
                 .chain
                 .block_root_at_slot(slot, WhenSlotSkipped::None)
                 .unwrap(),
+            BlockId::Epoch(epoch) => self
+                .chain
+                .block_root_at_slot(
+                    epoch.start_slot(E::slots_per_epoch()),
+                    WhenSlotSkipped::None,
+                )
+                .unwrap(),
             BlockId::Root(root) => Some(root),
         }
     }
@@ -969,6 +1044,39 @@ impl ApiTester {
         self
     }
 
+    pub async fn test_post_beacon_blocks_ssz_valid(mut self) -> Self {
+        let next_block = &self.next_block;
+
+        self.client
+            .post_beacon_blocks_ssz(next_block)
+            .await
+            .unwrap();
+
+        assert!(
+            self.network_rx.recv().await.is_some(),
+            "valid blocks should be sent to network"
+        );
+
+        self
+    }
+
+    pub async fn test_post_beacon_blocks_unsupported_content_type(self) -> Self {
+        let response = eth2::reqwest::Client::new()
+            .post(&format!(
+                "http://{}/eth/v1/beacon/blocks",
+                self.listening_socket
+            ))
+            .header(CONTENT_TYPE, "text/plain")
+            .body(self.next_block.as_ssz_bytes())
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        self
+    }
+
     pub async fn test_post_beacon_blocks_invalid(mut self) -> Self {
         let mut next_block = self.next_block.clone();
         next_block.message.proposer_index += 1;
@@ -1097,7 +1205,7 @@ impl ApiTester {
             .data;
 
         let mut expected = self.chain.op_pool.get_all_attestations();
-        expected.extend(self.chain.naive_aggregation_pool.read().iter().cloned());
+        expected.extend(self.chain.naive_aggregation_pool.iter());
 
         assert_eq!(result, expected);
 
@@ -1348,8 +1456,14 @@ impl ApiTester {
             last_seen_p2p_address: EXTERNAL_ADDR.to_string(),
             state: PeerState::Connected,
             direction: PeerDirection::Inbound,
+            agent_version: None,
+            score: 0.0,
+            banned: false,
+            connected_since: result.connected_since,
+            last_rpc_at: None,
         };
 
+        assert!(result.connected_since.is_some());
         assert_eq!(result, expected);
 
         self
@@ -1380,6 +1494,11 @@ impl ApiTester {
                     last_seen_p2p_address: EXTERNAL_ADDR.to_string(),
                     state: PeerState::Connected,
                     direction: PeerDirection::Inbound,
+                    agent_version: None,
+                    score: 0.0,
+                    banned: false,
+                    connected_since: result.data.first().and_then(|peer| peer.connected_since),
+                    last_rpc_at: None,
                 };
 
                 let state_match =
@@ -2019,17 +2138,31 @@ impl ApiTester {
             validator_index: 0,
             committee_index: 0,
             committees_at_slot: 1,
-            slot: Slot::new(1),
+            slot: self.chain.slot().unwrap(),
             is_aggregator: true,
         };
 
         self.client
-            .post_validator_beacon_committee_subscriptions(&[subscription])
+            .post_validator_beacon_committee_subscriptions(&[subscription.clone()])
             .await
             .unwrap();
 
         self.network_rx.recv().now_or_never().unwrap();
 
+        // An invalid subscription is skipped without affecting the rest of the batch.
+        let invalid_subscription = BeaconCommitteeSubscription {
+            committees_at_slot: 0,
+            ..subscription.clone()
+        };
+
+        self.client
+            .post_validator_beacon_committee_subscriptions(&[invalid_subscription, subscription])
+            .await
+            .unwrap();
+
+        self.network_rx.recv().now_or_never().unwrap();
+        assert!(self.network_rx.recv().now_or_never().is_none());
+
         self
     }
 
@@ -2193,6 +2326,8 @@ impl ApiTester {
         let expected_block = EventKind::Block(SseBlock {
             block: block_root,
             slot: next_slot,
+            proposer_index: self.next_block.message.proposer_index,
+            execution_optimistic: false,
         });
 
         let expected_head = EventKind::Head(SseHead {
@@ -2250,6 +2385,8 @@ impl ApiTester {
         let expected_block = EventKind::Block(SseBlock {
             block: block_root,
             slot: next_slot,
+            proposer_index: self.next_block.message.proposer_index,
+            execution_optimistic: false,
         });
 
         let expected_head = EventKind::Head(SseHead {
@@ -2322,6 +2459,8 @@ async fn beacon_get() {
         .await
         .test_beacon_states_validators()
         .await
+        .test_beacon_states_validators_paginated()
+        .await
         .test_beacon_states_validator_balances()
         .await
         .test_beacon_states_committees()
@@ -2355,6 +2494,18 @@ async fn post_beacon_blocks_valid() {
     ApiTester::new().test_post_beacon_blocks_valid().await;
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn post_beacon_blocks_ssz_valid() {
+    ApiTester::new().test_post_beacon_blocks_ssz_valid().await;
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn post_beacon_blocks_unsupported_content_type() {
+    ApiTester::new()
+        .test_post_beacon_blocks_unsupported_content_type()
+        .await;
+}
+
 #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
 async fn post_beacon_blocks_invalid() {
     ApiTester::new().test_post_beacon_blocks_invalid().await;
@@ -0,0 +1,339 @@
+//! Provides a mechanism which queues work for later processing when it arrives too early.
+//!
+//! When the `beacon_processor::Worker` finds that some work (i.e., a block or an aggregate) is
+//! from a slot which has not yet arrived, but which is less than one slot away, it will send it to
+//! this queue where it will be placed in a `DelayQueue` until the slot arrives. Once the work has
+//! been determined to be ready, it will be sent back out on a channel to be processed by the
+//! `BeaconProcessor` again.
+//!
+//! There is the edge-case where the slot arrives before this queue manages to process it. In that
+//! case, the work will be sent off for immediate processing (skipping the `DelayQueue`).
+//!
+//! Any gossip message which is dropped by this queue is reported to gossipsub as `Ignore`, so that
+//! gossipsub does not wait on a validation result which will never arrive.
+use crate::service::NetworkMessage;
+use beacon_chain::{BeaconChainTypes, GossipVerifiedBlock};
+use eth2_libp2p::{MessageAcceptance, MessageId, PeerId};
+use futures::stream::{Stream, StreamExt};
+use futures::task::Poll;
+use slog::{crit, debug, error, Logger};
+use slot_clock::SlotClock;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::Context;
+use std::time::{Duration, Instant};
+use task_executor::TaskExecutor;
+use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedSender};
+use tokio::time::error::Error as TimeError;
+use tokio_util::time::DelayQueue;
+use types::{EthSpec, Hash256, SignedAggregateAndProof, Slot};
+
+/// Queue work for re-processing with an `ADDITIONAL_DELAY` after the slot starts. This is to
+/// account for any slight drift in the system clock.
+const ADDITIONAL_DELAY: Duration = Duration::from_millis(5);
+
+/// Some work which may be held in a delay queue until its slot arrives.
+pub trait DelayedWork: Send + 'static {
+    /// Uniquely identifies an item so that it is not added to the queue twice.
+    type Key: Hash + Eq + Debug + Send;
+
+    /// The name of the task which runs the queue.
+    const TASK_NAME: &'static str;
+    /// The kind of work, for logging.
+    const WORK_TYPE: &'static str;
+    /// An upper-bound on the number of queued items, to avoid DoS attacks.
+    const MAXIMUM_QUEUED: usize;
+
+    /// The slot at which the work should be processed.
+    fn slot(&self) -> Slot;
+
+    /// The key which uniquely identifies this item.
+    fn key(&self) -> Self::Key;
+
+    /// The gossip message and the peer which sent it, if a validation result is still owed to
+    /// gossipsub for this work.
+    fn gossip_source(&self) -> Option<(&MessageId, &PeerId)>;
+}
+
+/// A block that arrived early and has been queued for later import.
+pub struct QueuedBlock<T: BeaconChainTypes> {
+    pub peer_id: PeerId,
+    pub block: GossipVerifiedBlock<T>,
+    pub seen_timestamp: Duration,
+    /// The time at which the work was first received by the `BeaconProcessor`.
+    pub received: Instant,
+}
+
+impl<T: BeaconChainTypes> DelayedWork for QueuedBlock<T> {
+    type Key = Hash256;
+
+    const TASK_NAME: &'static str = "beacon_processor_block_delay_queue";
+    const WORK_TYPE: &'static str = "block";
+    /// The fact that we signature-verify blocks before putting them in the queue *should* protect
+    /// against DoS, but it's nice to have extra protection.
+    const MAXIMUM_QUEUED: usize = 16;
+
+    fn slot(&self) -> Slot {
+        self.block.block.slot()
+    }
+
+    fn key(&self) -> Hash256 {
+        self.block.block_root
+    }
+
+    /// The block has already been propagated on gossip before it is queued.
+    fn gossip_source(&self) -> Option<(&MessageId, &PeerId)> {
+        None
+    }
+}
+
+/// An aggregate that arrived early and has been queued for later processing.
+pub struct QueuedAggregate<T: BeaconChainTypes> {
+    pub message_id: MessageId,
+    pub peer_id: PeerId,
+    pub aggregate: Box<SignedAggregateAndProof<T::EthSpec>>,
+    pub seen_timestamp: Duration,
+    /// The time at which the work was first received by the `BeaconProcessor`.
+    pub received: Instant,
+}
+
+impl<T: BeaconChainTypes> DelayedWork for QueuedAggregate<T> {
+    type Key = MessageId;
+
+    const TASK_NAME: &'static str = "beacon_processor_aggregate_delay_queue";
+    const WORK_TYPE: &'static str = "aggregate";
+    /// The aggregates in this queue have not had their signatures verified, so allow a generous
+    /// number but not an unbounded one.
+    const MAXIMUM_QUEUED: usize = 1_024;
+
+    fn slot(&self) -> Slot {
+        self.aggregate.message.aggregate.data.slot
+    }
+
+    fn key(&self) -> MessageId {
+        self.message_id.clone()
+    }
+
+    fn gossip_source(&self) -> Option<(&MessageId, &PeerId)> {
+        Some((&self.message_id, &self.peer_id))
+    }
+}
+
+/// Unifies the different messages processed by the delay queue.
+enum InboundEvent<W> {
+    /// Work that has been received early that we should queue for later processing.
+    EarlyWork(W),
+    /// Work that was queued for later processing and is ready to be processed.
+    ReadyWork(W),
+    /// The `DelayQueue` returned an error.
+    DelayQueueError(TimeError),
+}
+
+/// Combines the `DelayQueue` and `Receiver` streams into a single stream.
+///
+/// This struct has a similar purpose to `tokio::select!`, however it allows for more fine-grained
+/// control (specifically in the ordering of event processing).
+struct InboundEvents<W> {
+    pub delay_queue: DelayQueue<W>,
+    early_work_rx: Receiver<W>,
+}
+
+impl<W> Stream for InboundEvents<W> {
+    type Item = InboundEvent<W>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Poll for expired work *before* we try to process new work.
+        //
+        // The sequential nature of blockchains means it is generally better to try and import all
+        // existing blocks before new ones. Similarly, expired aggregates have been waiting for
+        // longer.
+        match self.delay_queue.poll_expired(cx) {
+            Poll::Ready(Some(Ok(queued))) => {
+                return Poll::Ready(Some(InboundEvent::ReadyWork(queued.into_inner())));
+            }
+            Poll::Ready(Some(Err(e))) => {
+                return Poll::Ready(Some(InboundEvent::DelayQueueError(e)));
+            }
+            // `Poll::Ready(None)` means that there are no more entries in the delay queue and we
+            // will continue to get this result until something else is added into the queue.
+            Poll::Ready(None) | Poll::Pending => (),
+        }
+
+        match self.early_work_rx.poll_recv(cx) {
+            Poll::Ready(Some(queued)) => {
+                return Poll::Ready(Some(InboundEvent::EarlyWork(queued)));
+            }
+            Poll::Ready(None) => {
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {}
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Spawn a queue which will accept work via the returned `Sender`, potentially queue it until its
+/// slot arrives, then send it back out via `ready_work_tx`.
+///
+/// Gossip messages which are dropped by the queue are reported as `Ignore` via `network_tx`.
+pub fn spawn_delay_queue<W: DelayedWork, S: SlotClock + 'static, E: EthSpec>(
+    ready_work_tx: Sender<W>,
+    network_tx: UnboundedSender<NetworkMessage<E>>,
+    channel_len: usize,
+    executor: &TaskExecutor,
+    slot_clock: S,
+    log: Logger,
+) -> Sender<W> {
+    let (early_work_tx, early_work_rx): (_, Receiver<W>) = mpsc::channel(channel_len);
+
+    let queue_future = async move {
+        let mut queued_keys = HashSet::new();
+
+        let mut inbound_events = InboundEvents {
+            early_work_rx,
+            delay_queue: DelayQueue::new(),
+        };
+
+        // Stop gossipsub from waiting on a validation result for work which will not be processed.
+        let ignore = |dropped: W| {
+            if let Some((message_id, peer_id)) = dropped.gossip_source() {
+                if network_tx
+                    .send(NetworkMessage::ValidationResult {
+                        propagation_source: *peer_id,
+                        message_id: message_id.clone(),
+                        validation_result: MessageAcceptance::Ignore,
+                    })
+                    .is_err()
+                {
+                    error!(
+                        log,
+                        "Failed to report dropped work";
+                        "work_type" => W::WORK_TYPE,
+                    );
+                }
+            }
+        };
+
+        loop {
+            match inbound_events.next().await {
+                // Some work has been indicated as "early" and should be processed when the
+                // appropriate slot arrives.
+                Some(InboundEvent::EarlyWork(early_work)) => {
+                    let slot = early_work.slot();
+                    let key = early_work.key();
+
+                    // Don't add the same work to the queue twice. This prevents DoS attacks.
+                    if queued_keys.contains(&key) {
+                        ignore(early_work);
+                        continue;
+                    }
+
+                    if let Some(duration_till_slot) = slot_clock.duration_to_slot(slot) {
+                        // Check to ensure this won't over-fill the queue.
+                        if queued_keys.len() >= W::MAXIMUM_QUEUED {
+                            error!(
+                                log,
+                                "Early work queue is full";
+                                "work_type" => W::WORK_TYPE,
+                                "queue_size" => W::MAXIMUM_QUEUED,
+                                "msg" => "check system clock"
+                            );
+                            ignore(early_work);
+                            continue;
+                        }
+
+                        // Only hold work that is less than one slot early.
+                        if duration_till_slot >= slot_clock.slot_duration() {
+                            debug!(
+                                log,
+                                "Dropping early work";
+                                "msg" => "more than one slot early",
+                                "work_type" => W::WORK_TYPE,
+                                "slot" => slot,
+                            );
+                            ignore(early_work);
+                            continue;
+                        }
+
+                        queued_keys.insert(key);
+                        // Queue the work until the start of the appropriate slot, plus
+                        // `ADDITIONAL_DELAY`.
+                        inbound_events
+                            .delay_queue
+                            .insert(early_work, duration_till_slot + ADDITIONAL_DELAY);
+                    } else {
+                        // If there is no duration till the slot, check to see if the slot has
+                        // already arrived. If it has already arrived, send it out for immediate
+                        // processing.
+                        //
+                        // If we can't read the slot or the slot hasn't arrived, simply drop the
+                        // work.
+                        //
+                        // This logic is slightly awkward since `SlotClock::duration_to_slot`
+                        // doesn't distinguish between a slot that has already arrived and an
+                        // error reading the slot clock.
+                        match slot_clock.now() {
+                            Some(now) if slot <= now => {
+                                if let Err(e) = ready_work_tx.try_send(early_work) {
+                                    error!(
+                                        log,
+                                        "Failed to send work";
+                                        "work_type" => W::WORK_TYPE,
+                                    );
+                                    ignore(e.into_inner());
+                                }
+                            }
+                            _ => ignore(early_work),
+                        }
+                    }
+                }
+                // Work that was queued for later processing is now ready to be processed.
+                Some(InboundEvent::ReadyWork(ready_work)) => {
+                    let key = ready_work.key();
+
+                    if !queued_keys.remove(&key) {
+                        // Log an error to alert that we've made a bad assumption about how this
+                        // program works, but still process the work anyway.
+                        error!(
+                            log,
+                            "Unknown work in delay queue";
+                            "work_type" => W::WORK_TYPE,
+                            "key" => ?key
+                        );
+                    }
+
+                    if let Err(e) = ready_work_tx.try_send(ready_work) {
+                        error!(
+                            log,
+                            "Failed to pop queued work";
+                            "work_type" => W::WORK_TYPE,
+                        );
+                        ignore(e.into_inner());
+                    }
+                }
+                Some(InboundEvent::DelayQueueError(e)) => crit!(
+                    log,
+                    "Failed to poll delay queue";
+                    "work_type" => W::WORK_TYPE,
+                    "e" => ?e
+                ),
+                None => {
+                    debug!(
+                        log,
+                        "Delay queue stopped";
+                        "work_type" => W::WORK_TYPE,
+                        "msg" => "shutting down"
+                    );
+                    break;
+                }
+            }
+        }
+    };
+
+    executor.spawn(queue_future, W::TASK_NAME);
+
+    early_work_tx
+}
@@ -37,15 +37,21 @@
 
 use crate::{metrics, service::NetworkMessage, sync::SyncMessage};
 use beacon_chain::{BeaconChain, BeaconChainTypes, BlockError, GossipVerifiedBlock};
-use block_delay_queue::{spawn_block_delay_queue, QueuedBlock};
+use delay_queue::{spawn_delay_queue, QueuedAggregate, QueuedBlock};
 use eth2_libp2p::{
     rpc::{BlocksByRangeRequest, BlocksByRootRequest, StatusMessage},
-    MessageId, NetworkGlobals, PeerId, PeerRequestId,
+    MessageAcceptance, MessageId, NetworkConfig, NetworkGlobals, PeerAction, PeerId, PeerRequestId,
+    ReportSource,
 };
+use futures::future::FutureExt;
 use futures::stream::{Stream, StreamExt};
 use futures::task::Poll;
-use slog::{debug, error, trace, warn, Logger};
-use std::collections::VecDeque;
+use peer_rate_limiter::PeerRateLimiter;
+use reprocess_queue::{
+    spawn_reprocess_queue, QueuedUnaggregate, ReprocessQueueMessage, QUEUED_ATTESTATION_TTL,
+};
+use slog::{debug, error, info, trace, warn, Logger};
+use std::collections::{BTreeMap, VecDeque};
 use std::fmt;
 use std::pin::Pin;
 use std::sync::{Arc, Weak};
@@ -54,19 +60,21 @@ use std::time::{Duration, Instant};
 use task_executor::TaskExecutor;
 use tokio::sync::{mpsc, oneshot};
 use types::{
-    Attestation, AttesterSlashing, Hash256, ProposerSlashing, SignedAggregateAndProof,
-    SignedBeaconBlock, SignedVoluntaryExit, SubnetId,
+    Attestation, AttestationData, AttesterSlashing, Hash256, ProposerSlashing,
+    SignedAggregateAndProof, SignedBeaconBlock, SignedVoluntaryExit, Slot, SubnetId,
 };
 
 use worker::{Toolbox, Worker};
 
-mod block_delay_queue;
+mod delay_queue;
+mod peer_rate_limiter;
+mod reprocess_queue;
 mod tests;
 mod worker;
 
 pub use worker::ProcessId;
 
-/// The maximum size of the channel for work events to the `BeaconProcessor`.
+/// The default maximum size of the channel for work events to the `BeaconProcessor`.
 ///
 /// Setting this too low will cause consensus messages to be dropped.
 pub const MAX_WORK_EVENT_QUEUE_LEN: usize = 16_384;
@@ -77,14 +85,25 @@ pub const MAX_WORK_EVENT_QUEUE_LEN: usize = 16_384;
 /// set to the CPU count, but we set it high to be safe.
 const MAX_IDLE_QUEUE_LEN: usize = 16_384;
 
-/// The maximum number of queued `Attestation` objects that will be stored before we start dropping
-/// them.
+/// The default maximum number of queued `Attestation` objects that will be stored before we start
+/// dropping them.
 const MAX_UNAGGREGATED_ATTESTATION_QUEUE_LEN: usize = 16_384;
 
-/// The maximum number of queued `SignedAggregateAndProof` objects that will be stored before we
-/// start dropping them.
+/// The default maximum number of queued `SignedAggregateAndProof` objects that will be stored
+/// before we start dropping them.
 const MAX_AGGREGATED_ATTESTATION_QUEUE_LEN: usize = 1_024;
 
+/// The default number of gossip attestations and aggregates that a single peer may send within
+/// each `GOSSIP_RATE_LIMIT_PERIOD` before further work from that peer is dropped.
+const MAX_GOSSIP_ATTESTATIONS_PER_PEER: u64 = 16_384;
+
+/// The default period over which `MAX_GOSSIP_ATTESTATIONS_PER_PEER` applies (one slot on mainnet).
+const GOSSIP_RATE_LIMIT_PERIOD: Duration = Duration::from_secs(12);
+
+/// The maximum number of queued `SignedAggregateAndProof` objects received prior to their slot
+/// (but less than one slot early) that will be queued before we start dropping them.
+const MAX_DELAYED_AGGREGATE_QUEUE_LEN: usize = 1_024;
+
 /// The maximum number of queued `SignedBeaconBlock` objects received on gossip that will be stored
 /// before we start dropping them.
 const MAX_GOSSIP_BLOCK_QUEUE_LEN: usize = 1_024;
@@ -93,6 +112,10 @@ const MAX_GOSSIP_BLOCK_QUEUE_LEN: usize = 1_024;
 /// within acceptable clock disparity) that will be queued before we start dropping them.
 const MAX_DELAYED_BLOCK_QUEUE_LEN: usize = 1_024;
 
+/// The maximum number of queued `Attestation` objects which referenced an unknown block and are
+/// ready to be re-processed that will be stored before we start dropping them.
+const MAX_UNKNOWN_BLOCK_ATTESTATION_QUEUE_LEN: usize = 8_192;
+
 /// The maximum number of queued `SignedVoluntaryExit` objects received on gossip that will be stored
 /// before we start dropping them.
 const MAX_GOSSIP_EXIT_QUEUE_LEN: usize = 4_096;
@@ -133,13 +156,18 @@ const WORKER_TASK_NAME: &str = "beacon_processor_worker";
 /// The minimum interval between log messages indicating that a queue is full.
 const LOG_DEBOUNCE_INTERVAL: Duration = Duration::from_secs(30);
 
+/// The maximum time the manager will spend completing queued work after shutdown begins.
+const MAX_DRAIN_DURATION: Duration = Duration::from_secs(10);
+
 /// Unique IDs used for metrics and testing.
 pub const WORKER_FREED: &str = "worker_freed";
 pub const NOTHING_TO_DO: &str = "nothing_to_do";
 pub const GOSSIP_ATTESTATION: &str = "gossip_attestation";
 pub const GOSSIP_AGGREGATE: &str = "gossip_aggregate";
+pub const DELAYED_AGGREGATE: &str = "delayed_aggregate";
 pub const GOSSIP_BLOCK: &str = "gossip_block";
 pub const DELAYED_IMPORT_BLOCK: &str = "delayed_import_block";
+pub const UNKNOWN_BLOCK_ATTESTATION: &str = "unknown_block_attestation";
 pub const GOSSIP_VOLUNTARY_EXIT: &str = "gossip_voluntary_exit";
 pub const GOSSIP_PROPOSER_SLASHING: &str = "gossip_proposer_slashing";
 pub const GOSSIP_ATTESTER_SLASHING: &str = "gossip_attester_slashing";
@@ -149,6 +177,61 @@ pub const STATUS_PROCESSING: &str = "status_processing";
 pub const BLOCKS_BY_RANGE_REQUEST: &str = "blocks_by_range_request";
 pub const BLOCKS_BY_ROOTS_REQUEST: &str = "blocks_by_roots_request";
 
+/// Configuration for the lengths of the `BeaconProcessor` queues.
+///
+/// Nodes subscribed to many subnets may wish to increase these, whilst memory-constrained nodes may
+/// wish to decrease them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeaconProcessorConfig {
+    /// The maximum size of the channel for work events to the `BeaconProcessor`.
+    pub max_work_event_queue_len: usize,
+    /// The maximum number of queued `Attestation` objects.
+    pub max_unaggregated_attestation_queue_len: usize,
+    /// The maximum number of queued `SignedAggregateAndProof` objects.
+    pub max_aggregated_attestation_queue_len: usize,
+    /// The number of gossip attestations and aggregates that a single peer may send within each
+    /// `gossip_rate_limit_period`.
+    pub max_gossip_attestations_per_peer: u64,
+    /// The period over which `max_gossip_attestations_per_peer` applies.
+    pub gossip_rate_limit_period: Duration,
+    /// The maximum time an attestation to an unknown block is held whilst waiting for the block.
+    pub unknown_block_attestation_ttl: Duration,
+}
+
+impl Default for BeaconProcessorConfig {
+    fn default() -> Self {
+        Self {
+            max_work_event_queue_len: MAX_WORK_EVENT_QUEUE_LEN,
+            max_unaggregated_attestation_queue_len: MAX_UNAGGREGATED_ATTESTATION_QUEUE_LEN,
+            max_aggregated_attestation_queue_len: MAX_AGGREGATED_ATTESTATION_QUEUE_LEN,
+            max_gossip_attestations_per_peer: MAX_GOSSIP_ATTESTATIONS_PER_PEER,
+            gossip_rate_limit_period: GOSSIP_RATE_LIMIT_PERIOD,
+            unknown_block_attestation_ttl: QUEUED_ATTESTATION_TTL,
+        }
+    }
+}
+
+impl From<&NetworkConfig> for BeaconProcessorConfig {
+    fn from(config: &NetworkConfig) -> Self {
+        let default = Self::default();
+        Self {
+            max_unaggregated_attestation_queue_len: config
+                .beacon_processor_attestation_queue_len
+                .unwrap_or(default.max_unaggregated_attestation_queue_len),
+            max_aggregated_attestation_queue_len: config
+                .beacon_processor_aggregate_queue_len
+                .unwrap_or(default.max_aggregated_attestation_queue_len),
+            max_gossip_attestations_per_peer: config
+                .beacon_processor_attestations_per_peer
+                .unwrap_or(default.max_gossip_attestations_per_peer),
+            gossip_rate_limit_period: config
+                .beacon_processor_rate_limit_period
+                .unwrap_or(default.gossip_rate_limit_period),
+            ..default
+        }
+    }
+}
+
 /// Used to send/receive results from a rpc block import in a blocking task.
 pub type BlockResultSender<E> = oneshot::Sender<Result<Hash256, BlockError<E>>>;
 pub type BlockResultReceiver<E> = oneshot::Receiver<Result<Hash256, BlockError<E>>>;
@@ -196,34 +279,98 @@ impl<T> FifoQueue<T> {
     }
 }
 
-/// A simple last-in-first-out queue with a maximum length.
-struct LifoQueue<T> {
-    queue: VecDeque<T>,
+/// The priority of some attestation work, used to order the attestation queues.
+///
+/// Attestations from later slots are more valuable to us, as are attestations which reference a
+/// block that is already known to us (since they can be imported immediately). When an attestation
+/// queue is full, the attestations with the lowest priority are dropped first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AttestationPriority {
+    slot: Slot,
+    head_block_known: bool,
+}
+
+/// The block roots which the manager considers known when computing an `AttestationPriority`.
+///
+/// The roots are read from the head of the `BeaconChain` at most once per slot, so that the
+/// manager does not take a lock for each queued attestation. Blocks imported during the slot are
+/// not included until the next slot, which only affects the order of attestations within a slot.
+#[derive(Default)]
+struct KnownRootsCache {
+    slot: Option<Slot>,
+    roots: Vec<Hash256>,
+}
+
+impl KnownRootsCache {
+    /// Returns the priority with which an attestation with the given `data` should be queued,
+    /// refreshing the known roots from `chain` if the slot has changed.
+    fn attestation_priority<T: BeaconChainTypes>(
+        &mut self,
+        chain: &Weak<BeaconChain<T>>,
+        data: &AttestationData,
+    ) -> AttestationPriority {
+        if let Some(chain) = chain.upgrade() {
+            let current_slot = chain.slot().ok();
+            if current_slot != self.slot {
+                self.slot = current_slot;
+                self.roots = chain
+                    .head_info()
+                    .map(|head| {
+                        vec![
+                            head.block_root,
+                            head.current_justified_checkpoint.root,
+                            head.finalized_checkpoint.root,
+                        ]
+                    })
+                    .unwrap_or_default();
+            }
+        }
+
+        AttestationPriority {
+            slot: data.slot,
+            head_block_known: self.roots.contains(&data.beacon_block_root),
+        }
+    }
+}
+
+/// A queue with a maximum length which always yields its highest-priority item first.
+///
+/// Items of equal priority are yielded in last-in-first-out order.
+struct PriorityQueue<P, T> {
+    /// Items keyed by their priority and then the order in which they were added.
+    queue: BTreeMap<(P, u64), T>,
+    next_seq: u64,
     max_length: usize,
 }
 
-impl<T> LifoQueue<T> {
+impl<P: Ord + Copy, T> PriorityQueue<P, T> {
     /// Create a new, empty queue with the given length.
     pub fn new(max_length: usize) -> Self {
         Self {
-            queue: VecDeque::default(),
+            queue: BTreeMap::new(),
+            next_seq: 0,
             max_length,
         }
     }
 
-    /// Add a new item to the front of the queue.
+    /// Add a new item to the queue with the given `priority`.
     ///
-    /// If the queue is full, the item at the back of the queue is dropped.
-    pub fn push(&mut self, item: T) {
-        if self.queue.len() == self.max_length {
-            self.queue.pop_back();
+    /// If the queue is full, the lowest-priority item is dropped (which may be `item`).
+    pub fn push(&mut self, priority: P, item: T) {
+        self.queue.insert((priority, self.next_seq), item);
+        self.next_seq += 1;
+
+        if self.queue.len() > self.max_length {
+            if let Some(lowest) = self.queue.keys().next().copied() {
+                self.queue.remove(&lowest);
+            }
         }
-        self.queue.push_front(item);
     }
 
-    /// Remove the next item from the queue.
+    /// Remove the highest-priority item from the queue.
     pub fn pop(&mut self) -> Option<T> {
-        self.queue.pop_front()
+        let highest = self.queue.keys().next_back().copied()?;
+        self.queue.remove(&highest)
     }
 
     /// Returns `true` if the queue is full.
@@ -240,6 +387,9 @@ impl<T> LifoQueue<T> {
 /// An event to be processed by the manager task.
 pub struct WorkEvent<T: BeaconChainTypes> {
     drop_during_sync: bool,
+    /// The time at which this event was created, used to measure the latency between receiving
+    /// some work and a worker finishing it.
+    received: Instant,
     work: Work<T>,
 }
 
@@ -261,6 +411,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: true,
+            received: Instant::now(),
             work: Work::GossipAttestation {
                 message_id,
                 peer_id,
@@ -281,6 +432,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: true,
+            received: Instant::now(),
             work: Work::GossipAggregate {
                 message_id,
                 peer_id,
@@ -290,6 +442,21 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
         }
     }
 
+    /// Create a new `Work` event for some aggregated attestation that was delayed for later
+    /// processing.
+    pub fn delayed_aggregated_attestation(queued: QueuedAggregate<T>) -> Self {
+        Self {
+            drop_during_sync: true,
+            received: queued.received,
+            work: Work::DelayedAggregate {
+                message_id: queued.message_id,
+                peer_id: queued.peer_id,
+                aggregate: queued.aggregate,
+                seen_timestamp: queued.seen_timestamp,
+            },
+        }
+    }
+
     /// Create a new `Work` event for some block.
     pub fn gossip_beacon_block(
         message_id: MessageId,
@@ -299,6 +466,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::GossipBlock {
                 message_id,
                 peer_id,
@@ -309,17 +477,31 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     }
 
     /// Create a new `Work` event for some block that was delayed for later processing.
-    pub fn delayed_import_beacon_block(
-        peer_id: PeerId,
-        block: Box<GossipVerifiedBlock<T>>,
-        seen_timestamp: Duration,
-    ) -> Self {
+    pub fn delayed_import_beacon_block(queued: QueuedBlock<T>) -> Self {
         Self {
             drop_during_sync: false,
+            received: queued.received,
             work: Work::DelayedImportBlock {
-                peer_id,
-                block,
-                seen_timestamp,
+                peer_id: queued.peer_id,
+                block: Box::new(queued.block),
+                seen_timestamp: queued.seen_timestamp,
+            },
+        }
+    }
+
+    /// Create a new `Work` event for some unaggregated attestation which referenced an unknown
+    /// block and has since become ready for re-processing.
+    pub fn unknown_block_attestation(queued: QueuedUnaggregate<T>) -> Self {
+        Self {
+            drop_during_sync: true,
+            received: queued.received,
+            work: Work::UnknownBlockAttestation {
+                message_id: queued.message_id,
+                peer_id: queued.peer_id,
+                attestation: queued.attestation,
+                subnet_id: queued.subnet_id,
+                should_import: queued.should_import,
+                seen_timestamp: queued.seen_timestamp,
             },
         }
     }
@@ -332,6 +514,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::GossipVoluntaryExit {
                 message_id,
                 peer_id,
@@ -348,6 +531,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::GossipProposerSlashing {
                 message_id,
                 peer_id,
@@ -364,6 +548,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::GossipAttesterSlashing {
                 message_id,
                 peer_id,
@@ -380,6 +565,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
         let (result_tx, result_rx) = oneshot::channel();
         let event = Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::RpcBlock { block, result_tx },
         };
         (event, result_rx)
@@ -392,6 +578,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::ChainSegment { process_id, blocks },
         }
     }
@@ -400,6 +587,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     pub fn status_message(peer_id: PeerId, message: StatusMessage) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::Status { peer_id, message },
         }
     }
@@ -412,6 +600,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::BlocksByRangeRequest {
                 peer_id,
                 request_id,
@@ -428,6 +617,7 @@ impl<T: BeaconChainTypes> WorkEvent<T> {
     ) -> Self {
         Self {
             drop_during_sync: false,
+            received: Instant::now(),
             work: Work::BlocksByRootsRequest {
                 peer_id,
                 request_id,
@@ -459,6 +649,12 @@ pub enum Work<T: BeaconChainTypes> {
         aggregate: Box<SignedAggregateAndProof<T::EthSpec>>,
         seen_timestamp: Duration,
     },
+    DelayedAggregate {
+        message_id: MessageId,
+        peer_id: PeerId,
+        aggregate: Box<SignedAggregateAndProof<T::EthSpec>>,
+        seen_timestamp: Duration,
+    },
     GossipBlock {
         message_id: MessageId,
         peer_id: PeerId,
@@ -470,6 +666,14 @@ pub enum Work<T: BeaconChainTypes> {
         block: Box<GossipVerifiedBlock<T>>,
         seen_timestamp: Duration,
     },
+    UnknownBlockAttestation {
+        message_id: MessageId,
+        peer_id: PeerId,
+        attestation: Box<Attestation<T::EthSpec>>,
+        subnet_id: SubnetId,
+        should_import: bool,
+        seen_timestamp: Duration,
+    },
     GossipVoluntaryExit {
         message_id: MessageId,
         peer_id: PeerId,
@@ -515,8 +719,10 @@ impl<T: BeaconChainTypes> Work<T> {
         match self {
             Work::GossipAttestation { .. } => GOSSIP_ATTESTATION,
             Work::GossipAggregate { .. } => GOSSIP_AGGREGATE,
+            Work::DelayedAggregate { .. } => DELAYED_AGGREGATE,
             Work::GossipBlock { .. } => GOSSIP_BLOCK,
             Work::DelayedImportBlock { .. } => DELAYED_IMPORT_BLOCK,
+            Work::UnknownBlockAttestation { .. } => UNKNOWN_BLOCK_ATTESTATION,
             Work::GossipVoluntaryExit { .. } => GOSSIP_VOLUNTARY_EXIT,
             Work::GossipProposerSlashing { .. } => GOSSIP_PROPOSER_SLASHING,
             Work::GossipAttesterSlashing { .. } => GOSSIP_ATTESTER_SLASHING,
@@ -527,6 +733,24 @@ impl<T: BeaconChainTypes> Work<T> {
             Work::BlocksByRootsRequest { .. } => BLOCKS_BY_ROOTS_REQUEST,
         }
     }
+
+    /// Returns the gossip message id and the peer which sent this work, if it is subject to
+    /// per-peer rate limiting.
+    fn rate_limited_source(&self) -> Option<(&MessageId, &PeerId)> {
+        match self {
+            Work::GossipAttestation {
+                message_id,
+                peer_id,
+                ..
+            }
+            | Work::GossipAggregate {
+                message_id,
+                peer_id,
+                ..
+            } => Some((message_id, peer_id)),
+            _ => None,
+        }
+    }
 }
 
 /// Provides de-bounce functionality for logging.
@@ -556,6 +780,13 @@ enum InboundEvent<T: BeaconChainTypes> {
     WorkEvent(WorkEvent<T>),
     /// A block that was delayed for import at a later slot has become ready.
     QueuedBlock(Box<QueuedBlock<T>>),
+    /// An aggregate that was delayed for processing at a later slot has become ready.
+    QueuedAggregate(Box<QueuedAggregate<T>>),
+    /// An attestation which referenced an unknown block has become ready for re-processing.
+    QueuedUnaggregate(Box<QueuedUnaggregate<T>>),
+    /// The exit signal has fired or the channel for new work has closed, so the queued work
+    /// should be drained.
+    Shutdown,
 }
 
 /// Combines the various incoming event streams for the `BeaconProcessor` into a single stream.
@@ -569,12 +800,30 @@ struct InboundEvents<T: BeaconChainTypes> {
     event_rx: mpsc::Receiver<WorkEvent<T>>,
     /// Used internally for queuing blocks for processing once their slot arrives.
     post_delay_block_queue_rx: mpsc::Receiver<QueuedBlock<T>>,
+    /// Used internally for queuing aggregates for processing once their slot arrives.
+    post_delay_aggregate_queue_rx: mpsc::Receiver<QueuedAggregate<T>>,
+    /// Used internally for re-queuing attestations once the block they reference is imported.
+    ready_attestation_queue_rx: mpsc::Receiver<QueuedUnaggregate<T>>,
+    /// Fires when the node is shutting down.
+    exit: exit_future::Exit,
+    /// Set once `exit` fires or `event_rx` has closed. After this, `event_rx` is no longer polled
+    /// and the closure of any other channel is ignored so that the remaining work can be drained.
+    shutting_down: bool,
 }
 
 impl<T: BeaconChainTypes> Stream for InboundEvents<T> {
     type Item = InboundEvent<T>;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Check for the exit signal first, since the other tasks which send to the delay queues
+        // are cancelled when it fires and their channels will close.
+        if !self.shutting_down {
+            if let Poll::Ready(()) = self.exit.poll_unpin(cx) {
+                self.shutting_down = true;
+                return Poll::Ready(Some(InboundEvent::Shutdown));
+            }
+        }
+
         // Always check for idle workers before anything else. This allows us to ensure that a big
         // stream of new events doesn't suppress the processing of existing events.
         match self.idle_rx.poll_recv(cx) {
@@ -594,21 +843,52 @@ impl<T: BeaconChainTypes> Stream for InboundEvents<T> {
                 return Poll::Ready(Some(InboundEvent::QueuedBlock(Box::new(queued_block))));
             }
             Poll::Ready(None) => {
-                return Poll::Ready(None);
+                if !self.shutting_down {
+                    return Poll::Ready(None);
+                }
             }
             Poll::Pending => {}
         }
 
-        match self.event_rx.poll_recv(cx) {
-            Poll::Ready(Some(event)) => {
-                return Poll::Ready(Some(InboundEvent::WorkEvent(event)));
+        // Poll for delayed aggregates and re-queued attestations before polling for new work,
+        // since they have already been waiting for some time.
+        match self.post_delay_aggregate_queue_rx.poll_recv(cx) {
+            Poll::Ready(Some(queued)) => {
+                return Poll::Ready(Some(InboundEvent::QueuedAggregate(Box::new(queued))));
             }
             Poll::Ready(None) => {
-                return Poll::Ready(None);
+                if !self.shutting_down {
+                    return Poll::Ready(None);
+                }
+            }
+            Poll::Pending => {}
+        }
+
+        match self.ready_attestation_queue_rx.poll_recv(cx) {
+            Poll::Ready(Some(queued)) => {
+                return Poll::Ready(Some(InboundEvent::QueuedUnaggregate(Box::new(queued))));
+            }
+            Poll::Ready(None) => {
+                if !self.shutting_down {
+                    return Poll::Ready(None);
+                }
             }
             Poll::Pending => {}
         }
 
+        if !self.shutting_down {
+            match self.event_rx.poll_recv(cx) {
+                Poll::Ready(Some(event)) => {
+                    return Poll::Ready(Some(InboundEvent::WorkEvent(event)));
+                }
+                Poll::Ready(None) => {
+                    self.shutting_down = true;
+                    return Poll::Ready(Some(InboundEvent::Shutdown));
+                }
+                Poll::Pending => {}
+            }
+        }
+
         Poll::Pending
     }
 }
@@ -625,6 +905,7 @@ pub struct BeaconProcessor<T: BeaconChainTypes> {
     pub executor: TaskExecutor,
     pub max_workers: usize,
     pub current_workers: usize,
+    pub config: BeaconProcessorConfig,
     pub log: Logger,
 }
 
@@ -648,13 +929,20 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
         // Used by workers to communicate that they are finished a task.
         let (idle_tx, idle_rx) = mpsc::channel::<()>(MAX_IDLE_QUEUE_LEN);
 
-        // Using LIFO queues for attestations since validator profits rely upon getting fresh
+        // Using priority queues for attestations since validator profits rely upon getting fresh
         // attestations into blocks. Additionally, later attestations contain more information than
-        // earlier ones, so we consider them more valuable.
-        let mut aggregate_queue = LifoQueue::new(MAX_AGGREGATED_ATTESTATION_QUEUE_LEN);
+        // earlier ones, so we consider them more valuable. Stale attestations (from earlier slots
+        // or for unknown blocks) are processed last and dropped first when the queue is full.
+        let mut aggregate_queue =
+            PriorityQueue::new(self.config.max_aggregated_attestation_queue_len);
+        // Using a FIFO queue for delayed aggregates, since they all became ready at the start of
+        // the same slot.
+        let mut delayed_aggregate_queue = FifoQueue::new(MAX_DELAYED_AGGREGATE_QUEUE_LEN);
         let mut aggregate_debounce = TimeLatch::default();
-        let mut attestation_queue = LifoQueue::new(MAX_UNAGGREGATED_ATTESTATION_QUEUE_LEN);
+        let mut attestation_queue =
+            PriorityQueue::new(self.config.max_unaggregated_attestation_queue_len);
         let mut attestation_debounce = TimeLatch::default();
+        let mut known_roots = KnownRootsCache::default();
 
         // Using a FIFO queue for voluntary exits since it prevents exit censoring. I don't have
         // a strong feeling about queue type for exits.
@@ -673,18 +961,32 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
         let mut gossip_block_queue = FifoQueue::new(MAX_GOSSIP_BLOCK_QUEUE_LEN);
         let mut delayed_block_queue = FifoQueue::new(MAX_DELAYED_BLOCK_QUEUE_LEN);
 
+        // Using a FIFO queue for attestations which referenced an unknown block, since they have
+        // already waited in the reprocess queue and are processed in the order they became ready.
+        let mut unknown_block_attestation_queue =
+            FifoQueue::new(MAX_UNKNOWN_BLOCK_ATTESTATION_QUEUE_LEN);
+
         let mut status_queue = FifoQueue::new(MAX_STATUS_QUEUE_LEN);
         let mut bbrange_queue = FifoQueue::new(MAX_BLOCKS_BY_RANGE_QUEUE_LEN);
         let mut bbroots_queue = FifoQueue::new(MAX_BLOCKS_BY_ROOTS_QUEUE_LEN);
 
+        // Prevents a single peer from filling the attestation queues.
+        let mut peer_rate_limiter = PeerRateLimiter::new(
+            self.config.max_gossip_attestations_per_peer,
+            self.config.gossip_rate_limit_period,
+            Instant::now(),
+        );
+
         // The delayed block queues are used to re-queue blocks for processing at a later time if
         // they're received early.
         let (post_delay_block_queue_tx, post_delay_block_queue_rx) =
             mpsc::channel(MAX_DELAYED_BLOCK_QUEUE_LEN);
         let pre_delay_block_queue_tx = {
             if let Some(chain) = self.beacon_chain.upgrade() {
-                spawn_block_delay_queue(
+                spawn_delay_queue(
                     post_delay_block_queue_tx,
+                    self.network_tx.clone(),
+                    MAX_DELAYED_BLOCK_QUEUE_LEN,
                     &self.executor,
                     chain.slot_clock.clone(),
                     self.log.clone(),
@@ -696,7 +998,41 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
             }
         };
 
+        // The delayed aggregate queues are used to re-queue aggregates for processing at a later
+        // time if they're received early.
+        let (post_delay_aggregate_queue_tx, post_delay_aggregate_queue_rx) =
+            mpsc::channel(MAX_DELAYED_AGGREGATE_QUEUE_LEN);
+        let pre_delay_aggregate_queue_tx = {
+            if let Some(chain) = self.beacon_chain.upgrade() {
+                spawn_delay_queue(
+                    post_delay_aggregate_queue_tx,
+                    self.network_tx.clone(),
+                    MAX_DELAYED_AGGREGATE_QUEUE_LEN,
+                    &self.executor,
+                    chain.slot_clock.clone(),
+                    self.log.clone(),
+                )
+            } else {
+                // No need to proceed any further if the beacon chain has been dropped, the client
+                // is shutting down.
+                return;
+            }
+        };
+
+        // The reprocess queue holds attestations which reference an unknown block until that block
+        // is imported.
+        let (ready_attestation_queue_tx, ready_attestation_queue_rx) =
+            mpsc::channel(MAX_UNKNOWN_BLOCK_ATTESTATION_QUEUE_LEN);
+        let reprocess_queue_tx = spawn_reprocess_queue(
+            ready_attestation_queue_tx,
+            self.network_tx.clone(),
+            self.config.unknown_block_attestation_ttl,
+            &self.executor,
+            self.log.clone(),
+        );
+
         let executor = self.executor.clone();
+        let exit = executor.exit();
 
         // The manager future will run on the core executor and delegate tasks to worker
         // threads on the blocking executor.
@@ -705,21 +1041,69 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                 idle_rx,
                 event_rx,
                 post_delay_block_queue_rx,
+                post_delay_aggregate_queue_rx,
+                ready_attestation_queue_rx,
+                exit,
+                shutting_down: false,
             };
 
+            // Once the exit signal fires or `event_rx` closes no new work is accepted, but the work
+            // which is already queued is still processed (for up to `MAX_DRAIN_DURATION`) before
+            // the manager stops.
+            let mut draining = false;
+            let mut drain_deadline = None;
+            let mut drained = 0_usize;
+            let mut dropped = 0_usize;
+
             loop {
-                let work_event = match inbound_events.next().await {
+                let next_event = match drain_deadline {
+                    Some(deadline) => {
+                        match tokio::time::timeout_at(deadline, inbound_events.next()).await {
+                            Ok(next_event) => next_event,
+                            Err(_) => {
+                                warn!(
+                                    self.log,
+                                    "Gossip processor drain timed out";
+                                    "drained" => drained,
+                                    "active_workers" => self.current_workers,
+                                );
+                                break;
+                            }
+                        }
+                    }
+                    None => inbound_events.next().await,
+                };
+
+                let work_event = match next_event {
                     Some(InboundEvent::WorkerIdle) => {
                         self.current_workers = self.current_workers.saturating_sub(1);
                         None
                     }
                     Some(InboundEvent::WorkEvent(event)) => Some(event),
                     Some(InboundEvent::QueuedBlock(queued_block)) => {
-                        Some(WorkEvent::delayed_import_beacon_block(
-                            queued_block.peer_id,
-                            Box::new(queued_block.block),
-                            queued_block.seen_timestamp,
-                        ))
+                        Some(WorkEvent::delayed_import_beacon_block(*queued_block))
+                    }
+                    Some(InboundEvent::QueuedAggregate(queued)) => {
+                        Some(WorkEvent::delayed_aggregated_attestation(*queued))
+                    }
+                    Some(InboundEvent::QueuedUnaggregate(queued)) => {
+                        Some(WorkEvent::unknown_block_attestation(*queued))
+                    }
+                    Some(InboundEvent::Shutdown) => {
+                        debug!(
+                            self.log,
+                            "Gossip processor draining";
+                            "msg" => "shutting down",
+                            "active_workers" => self.current_workers,
+                        );
+                        draining = true;
+                        drain_deadline = Some(tokio::time::Instant::now() + MAX_DRAIN_DURATION);
+                        // Work is only queued whilst all workers are busy, so there is nothing
+                        // left to drain if no workers are active.
+                        if self.current_workers == 0 {
+                            break;
+                        }
+                        continue;
                     }
                     None => {
                         debug!(
@@ -731,6 +1115,12 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                     }
                 };
 
+                // Whilst draining, delayed work which becomes ready is not accepted.
+                if draining && work_event.is_some() {
+                    dropped += 1;
+                    continue;
+                }
+
                 let _event_timer =
                     metrics::start_timer(&metrics::BEACON_PROCESSOR_EVENT_HANDLING_SECONDS);
                 if let Some(event) = &work_event {
@@ -757,6 +1147,7 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                 let drop_during_sync = work_event
                     .as_ref()
                     .map_or(false, |event| event.drop_during_sync);
+                let mut nothing_to_do = false;
 
                 match work_event {
                     // There is no new work event, but we are able to spawn a new worker.
@@ -767,6 +1158,8 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         let toolbox = Toolbox {
                             idle_tx: idle_tx.clone(),
                             delayed_block_tx: pre_delay_block_queue_tx.clone(),
+                            delayed_aggregate_tx: pre_delay_aggregate_queue_tx.clone(),
+                            reprocess_tx: reprocess_queue_tx.clone(),
                         };
 
                         // Check for chain segments first, they're the most efficient way to get
@@ -788,10 +1181,17 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         // Check the aggregates, *then* the unaggregates since we assume that
                         // aggregates are more valuable to local validators and effectively give us
                         // more information with less signature verification time.
+                        //
+                        // Delayed aggregates are checked first since they are for the current
+                        // slot.
+                        } else if let Some(item) = delayed_aggregate_queue.pop() {
+                            self.spawn_worker(item, toolbox);
                         } else if let Some(item) = aggregate_queue.pop() {
                             self.spawn_worker(item, toolbox);
                         } else if let Some(item) = attestation_queue.pop() {
                             self.spawn_worker(item, toolbox);
+                        } else if let Some(item) = unknown_block_attestation_queue.pop() {
+                            self.spawn_worker(item, toolbox);
                         // Check RPC methods next. Status messages are needed for sync so
                         // prioritize them over syncing requests from other peers (BlocksByRange
                         // and BlocksByRoot)
@@ -815,6 +1215,7 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                             self.spawn_worker(item, toolbox);
                         // This statement should always be the final else statement.
                         } else {
+                            nothing_to_do = true;
                             // Let the journal know that a worker is freed and there's nothing else
                             // for it to do.
                             if let Some(work_journal_tx) = &work_journal_tx {
@@ -851,44 +1252,103 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                             "work_id" => work_id
                         );
                     }
+                    // The peer which sent this work has exceeded its rate limit.
+                    Some(work_event)
+                        if work_event
+                            .work
+                            .rate_limited_source()
+                            .map_or(false, |(_, peer_id)| {
+                                !peer_rate_limiter.allows(peer_id, Instant::now())
+                            }) =>
+                    {
+                        let work_id = work_event.work.str_id();
+                        if let Some((message_id, peer_id)) = work_event.work.rate_limited_source() {
+                            metrics::inc_counter(
+                                &metrics::BEACON_PROCESSOR_GOSSIP_RATE_LIMITED_TOTAL,
+                            );
+                            trace!(
+                                self.log,
+                                "Gossip processor skipping work";
+                                "msg" => "peer is rate limited",
+                                "work_id" => work_id,
+                                "peer_id" => %peer_id,
+                            );
+                            // Stop gossipsub from waiting on a validation result for the message and
+                            // penalize the peer for flooding us.
+                            self.send_network_message(NetworkMessage::ValidationResult {
+                                propagation_source: *peer_id,
+                                message_id: message_id.clone(),
+                                validation_result: MessageAcceptance::Ignore,
+                            });
+                            self.send_network_message(NetworkMessage::ReportPeer {
+                                peer_id: *peer_id,
+                                action: PeerAction::HighToleranceError,
+                                source: ReportSource::Gossipsub,
+                            });
+                        }
+                    }
                     // There is a new work event and the chain is not syncing. Process it or queue
                     // it.
-                    Some(WorkEvent { work, .. }) => {
-                        let work_id = work.str_id();
+                    Some(work_event) => {
+                        let work_id = work_event.work.str_id();
                         let toolbox = Toolbox {
                             idle_tx: idle_tx.clone(),
                             delayed_block_tx: pre_delay_block_queue_tx.clone(),
+                            delayed_aggregate_tx: pre_delay_aggregate_queue_tx.clone(),
+                            reprocess_tx: reprocess_queue_tx.clone(),
                         };
 
-                        match work {
-                            _ if can_spawn => self.spawn_worker(work, toolbox),
-                            Work::GossipAttestation { .. } => attestation_queue.push(work),
-                            Work::GossipAggregate { .. } => aggregate_queue.push(work),
+                        match work_event.work {
+                            _ if can_spawn => self.spawn_worker(work_event, toolbox),
+                            Work::GossipAttestation {
+                                ref attestation, ..
+                            } => {
+                                let priority = known_roots
+                                    .attestation_priority(&self.beacon_chain, &attestation.data);
+                                attestation_queue.push(priority, work_event)
+                            }
+                            Work::GossipAggregate { ref aggregate, .. } => {
+                                let priority = known_roots.attestation_priority(
+                                    &self.beacon_chain,
+                                    &aggregate.message.aggregate.data,
+                                );
+                                aggregate_queue.push(priority, work_event)
+                            }
+                            Work::DelayedAggregate { .. } => {
+                                delayed_aggregate_queue.push(work_event, work_id, &self.log)
+                            }
                             Work::GossipBlock { .. } => {
-                                gossip_block_queue.push(work, work_id, &self.log)
+                                gossip_block_queue.push(work_event, work_id, &self.log)
                             }
                             Work::DelayedImportBlock { .. } => {
-                                delayed_block_queue.push(work, work_id, &self.log)
+                                delayed_block_queue.push(work_event, work_id, &self.log)
+                            }
+                            Work::UnknownBlockAttestation { .. } => {
+                                unknown_block_attestation_queue.push(work_event, work_id, &self.log)
                             }
                             Work::GossipVoluntaryExit { .. } => {
-                                gossip_voluntary_exit_queue.push(work, work_id, &self.log)
+                                gossip_voluntary_exit_queue.push(work_event, work_id, &self.log)
                             }
                             Work::GossipProposerSlashing { .. } => {
-                                gossip_proposer_slashing_queue.push(work, work_id, &self.log)
+                                gossip_proposer_slashing_queue.push(work_event, work_id, &self.log)
                             }
                             Work::GossipAttesterSlashing { .. } => {
-                                gossip_attester_slashing_queue.push(work, work_id, &self.log)
+                                gossip_attester_slashing_queue.push(work_event, work_id, &self.log)
+                            }
+                            Work::RpcBlock { .. } => {
+                                rpc_block_queue.push(work_event, work_id, &self.log)
                             }
-                            Work::RpcBlock { .. } => rpc_block_queue.push(work, work_id, &self.log),
                             Work::ChainSegment { .. } => {
-                                chain_segment_queue.push(work, work_id, &self.log)
+                                chain_segment_queue.push(work_event, work_id, &self.log)
+                            }
+                            Work::Status { .. } => {
+                                status_queue.push(work_event, work_id, &self.log)
                             }
-                            Work::Status { .. } => status_queue.push(work, work_id, &self.log),
                             Work::BlocksByRangeRequest { .. } => {
-                                bbrange_queue.push(work, work_id, &self.log)
+                                bbrange_queue.push(work_event, work_id, &self.log)
                             }
                             Work::BlocksByRootsRequest { .. } => {
-                                bbroots_queue.push(work, work_id, &self.log)
+                                bbroots_queue.push(work_event, work_id, &self.log)
                             }
                         }
                     }
@@ -898,10 +1358,18 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                     &metrics::BEACON_PROCESSOR_WORKERS_ACTIVE_TOTAL,
                     self.current_workers as i64,
                 );
+                metrics::set_gauge(
+                    &metrics::BEACON_PROCESSOR_GOSSIP_RATE_LIMITED_PEERS_TOTAL,
+                    peer_rate_limiter.num_peers() as i64,
+                );
                 metrics::set_gauge(
                     &metrics::BEACON_PROCESSOR_UNAGGREGATED_ATTESTATION_QUEUE_TOTAL,
                     attestation_queue.len() as i64,
                 );
+                metrics::set_gauge(
+                    &metrics::BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_QUEUE_TOTAL,
+                    unknown_block_attestation_queue.len() as i64,
+                );
                 metrics::set_gauge(
                     &metrics::BEACON_PROCESSOR_AGGREGATED_ATTESTATION_QUEUE_TOTAL,
                     aggregate_queue.len() as i64,
@@ -948,19 +1416,51 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         "queue_len" => attestation_queue.max_length,
                     )
                 }
+
+                if draining {
+                    if !nothing_to_do {
+                        drained += 1;
+                    } else if self.current_workers == 0 {
+                        break;
+                    }
+                }
+            }
+
+            if draining {
+                info!(
+                    self.log,
+                    "Gossip processor stopped";
+                    "msg" => "shutting down",
+                    "drained" => drained,
+                    "dropped" => dropped,
+                );
             }
         };
 
-        // Spawn on the core executor.
-        executor.spawn(manager_future, MANAGER_TASK_NAME);
+        // Spawn on the core executor without wrapping it in the exit future, since the manager
+        // watches the exit signal itself so that it can drain the queued work.
+        executor.spawn_without_exit(manager_future, MANAGER_TASK_NAME);
+    }
+
+    /// Send a message to `network_tx`.
+    ///
+    /// Creates a log if there is an internal error.
+    fn send_network_message(&self, message: NetworkMessage<T::EthSpec>) {
+        self.network_tx.send(message).unwrap_or_else(|e| {
+            error!(self.log, "Could not send message to the network service";
+                "error" => %e)
+        });
     }
 
     /// Spawns a blocking worker thread to process some `Work`.
     ///
     /// Sends an message on `idle_tx` when the work is complete and the task is stopping.
-    fn spawn_worker(&mut self, work: Work<T>, toolbox: Toolbox<T>) {
+    fn spawn_worker(&mut self, work_event: WorkEvent<T>, toolbox: Toolbox<T>) {
+        let WorkEvent { work, received, .. } = work_event;
         let idle_tx = toolbox.idle_tx;
         let delayed_block_tx = toolbox.delayed_block_tx;
+        let delayed_aggregate_tx = toolbox.delayed_aggregate_tx;
+        let reprocess_tx = toolbox.reprocess_tx;
 
         // Wrap the `idle_tx` in a struct that will fire the idle message whenever it is dropped.
         //
@@ -1031,7 +1531,32 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         *attestation,
                         subnet_id,
                         should_import,
+                        Some(reprocess_tx),
+                        seen_timestamp,
+                        received,
+                    ),
+                    /*
+                     * Unaggregated attestations which referenced an unknown block that has since
+                     * been imported.
+                     *
+                     * These are not sent to the reprocess queue a second time.
+                     */
+                    Work::UnknownBlockAttestation {
+                        message_id,
+                        peer_id,
+                        attestation,
+                        subnet_id,
+                        should_import,
+                        seen_timestamp,
+                    } => worker.process_gossip_attestation(
+                        message_id,
+                        peer_id,
+                        *attestation,
+                        subnet_id,
+                        should_import,
+                        None,
                         seen_timestamp,
+                        received,
                     ),
                     /*
                      * Aggregated attestation verification.
@@ -1045,7 +1570,28 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         message_id,
                         peer_id,
                         *aggregate,
+                        Some(delayed_aggregate_tx),
+                        seen_timestamp,
+                        received,
+                    ),
+                    /*
+                     * Aggregated attestations that arrived early and are now ready for
+                     * verification.
+                     *
+                     * These are not sent to the delay queue a second time.
+                     */
+                    Work::DelayedAggregate {
+                        message_id,
+                        peer_id,
+                        aggregate,
+                        seen_timestamp,
+                    } => worker.process_gossip_aggregate(
+                        message_id,
+                        peer_id,
+                        *aggregate,
+                        None,
                         seen_timestamp,
+                        received,
                     ),
                     /*
                      * Verification for beacon blocks received on gossip.
@@ -1060,7 +1606,9 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         peer_id,
                         *block,
                         delayed_block_tx,
+                        reprocess_tx,
                         seen_timestamp,
+                        received,
                     ),
                     /*
                      * Import for blocks that we received earlier than their intended slot.
@@ -1069,7 +1617,12 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                         peer_id,
                         block,
                         seen_timestamp,
-                    } => worker.process_gossip_verified_block(peer_id, *block, seen_timestamp),
+                    } => worker.process_gossip_verified_block(
+                        peer_id,
+                        *block,
+                        reprocess_tx,
+                        seen_timestamp,
+                    ),
                     /*
                      * Voluntary exits received on gossip.
                      */
@@ -1106,7 +1659,7 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                      * Verification for beacon blocks received during syncing via RPC.
                      */
                     Work::RpcBlock { block, result_tx } => {
-                        worker.process_rpc_block(*block, result_tx)
+                        worker.process_rpc_block(*block, result_tx, reprocess_tx)
                     }
                     /*
                      * Verification for a chain segment (multiple blocks).
@@ -1136,6 +1689,12 @@ impl<T: BeaconChainTypes> BeaconProcessor<T> {
                     } => worker.handle_blocks_by_root_request(peer_id, request_id, request),
                 };
 
+                metrics::observe_timer_vec(
+                    &metrics::BEACON_PROCESSOR_WORK_LATENCY_SECONDS,
+                    &[work_id],
+                    received.elapsed(),
+                );
+
                 trace!(
                     log,
                     "Beacon processor worker done";
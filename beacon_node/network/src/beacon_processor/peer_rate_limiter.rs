@@ -0,0 +1,110 @@
+//! Provides a per-peer token bucket which is used to prevent a single peer from filling the
+//! `BeaconProcessor` queues with gossip work.
+//!
+//! Each peer is given a bucket of `max_tokens` tokens, which is fully replenished every
+//! `replenish_all_every`. Each item of rate-limited work sent by a peer consumes one token and work
+//! is dropped whilst the peer has no tokens remaining.
+//!
+//! The bucket of a peer which has not sent any work for `replenish_all_every` is indistinguishable
+//! from a new bucket, so such buckets are pruned to stop the map from growing forever.
+use eth2_libp2p::PeerId;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+/// The token bucket of a single peer.
+struct Bucket {
+    /// The number of tokens available to the peer at `last_update`.
+    tokens: u64,
+    /// The time at which `tokens` was last replenished.
+    last_update: Instant,
+}
+
+/// Rate limits work per-peer with a token bucket.
+///
+/// See the module-level documentation for more information.
+pub struct PeerRateLimiter {
+    buckets: HashMap<PeerId, Bucket>,
+    max_tokens: u64,
+    replenish_all_every: Duration,
+    last_prune: Instant,
+}
+
+impl PeerRateLimiter {
+    /// Create a new rate limiter which allows each peer `max_tokens` items of work every
+    /// `replenish_all_every`.
+    pub fn new(max_tokens: u64, replenish_all_every: Duration, now: Instant) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            max_tokens,
+            replenish_all_every,
+            last_prune: now,
+        }
+    }
+
+    /// The time it takes to replenish a single token, in nanoseconds.
+    ///
+    /// Nanoseconds are used since `max_tokens` may be too large to divide a `Duration` by.
+    fn nanos_per_token(&self) -> u128 {
+        self.replenish_all_every.as_nanos() / u128::from(self.max_tokens.max(1))
+    }
+
+    /// Returns `true` if `peer_id` is permitted to send another item of work at `now`, consuming
+    /// one of its tokens.
+    pub fn allows(&mut self, peer_id: &PeerId, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last_prune) >= self.replenish_all_every {
+            self.prune(now);
+        }
+
+        let max_tokens = self.max_tokens;
+        let nanos_per_token = self.nanos_per_token();
+        let bucket = self.buckets.entry(*peer_id).or_insert(Bucket {
+            tokens: max_tokens,
+            last_update: now,
+        });
+
+        // Replenish any tokens which have become available since the last update. Only whole
+        // tokens are added so that partial progress towards the next token is not lost.
+        let elapsed = now.saturating_duration_since(bucket.last_update);
+        let new_tokens = if nanos_per_token == 0 {
+            max_tokens
+        } else {
+            u64::try_from(elapsed.as_nanos() / nanos_per_token)
+                .unwrap_or(u64::max_value())
+                .min(max_tokens)
+        };
+        if new_tokens > 0 {
+            bucket.tokens = bucket.tokens.saturating_add(new_tokens).min(max_tokens);
+            bucket.last_update = if bucket.tokens == max_tokens {
+                now
+            } else {
+                // `new_tokens * nanos_per_token <= elapsed`, so this cannot pass `now`.
+                u64::try_from(nanos_per_token * u128::from(new_tokens))
+                    .ok()
+                    .and_then(|nanos| bucket.last_update.checked_add(Duration::from_nanos(nanos)))
+                    .unwrap_or(now)
+            };
+        }
+
+        if bucket.tokens > 0 {
+            bucket.tokens -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove the buckets of all peers which would have been fully replenished by `now`.
+    pub fn prune(&mut self, now: Instant) {
+        let replenish_all_every = self.replenish_all_every;
+        self.buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.last_update) < replenish_all_every
+        });
+        self.last_prune = now;
+    }
+
+    /// Returns the number of peers which are currently being tracked.
+    pub fn num_peers(&self) -> usize {
+        self.buckets.len()
+    }
+}
@@ -0,0 +1,301 @@
+//! Provides a mechanism which queues attestations for later processing when they reference a block
+//! which is not yet known.
+//!
+//! When the `beacon_processor::Worker` fails to verify an unaggregated attestation because its
+//! `beacon_block_root` is unknown, it will send it to this queue (and ask sync to find the block).
+//! The attestation is held in a `ReprocessQueue` until either:
+//!
+//! - A block with a matching root is imported, in which case the attestation is sent back out on a
+//!   channel to be processed by the `BeaconProcessor` again.
+//! - The attestation has been queued for longer than its TTL (`QUEUED_ATTESTATION_TTL` by
+//!   default), in which case it is dropped.
+//!
+//! Any attestation which is dropped by this queue is reported to gossipsub as `Ignore`, so that
+//! gossipsub does not wait on a validation result which will never arrive.
+use super::MAX_UNKNOWN_BLOCK_ATTESTATION_QUEUE_LEN;
+use crate::metrics;
+use crate::service::NetworkMessage;
+use beacon_chain::BeaconChainTypes;
+use eth2_libp2p::{MessageAcceptance, MessageId, PeerId};
+use futures::stream::{Stream, StreamExt};
+use futures::task::Poll;
+use slog::{crit, debug, error, Logger};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::task::Context;
+use std::time::{Duration, Instant};
+use task_executor::TaskExecutor;
+use tokio::sync::mpsc::{self, Receiver, Sender, UnboundedSender};
+use tokio::time::error::Error as TimeError;
+use tokio_util::time::delay_queue::{DelayQueue, Key};
+use types::{Attestation, Hash256, SubnetId};
+
+const TASK_NAME: &str = "beacon_processor_reprocess_queue";
+
+/// The maximum time an attestation will be held whilst waiting for its block to be imported. This
+/// is one slot on mainnet, after which the attestation is of little value to us.
+pub const QUEUED_ATTESTATION_TTL: Duration = Duration::from_secs(12);
+
+/// Set an arbitrary upper-bound on the number of queued attestations to avoid DoS attacks, since
+/// the attestations in this queue have not had their signatures verified.
+const MAXIMUM_QUEUED_ATTESTATIONS: usize = 16_384;
+
+/// An unaggregated attestation that references an unknown block and has been queued for later
+/// processing.
+pub struct QueuedUnaggregate<T: BeaconChainTypes> {
+    pub message_id: MessageId,
+    pub peer_id: PeerId,
+    pub attestation: Box<Attestation<T::EthSpec>>,
+    pub subnet_id: SubnetId,
+    pub should_import: bool,
+    pub seen_timestamp: Duration,
+    /// The time at which the work was first received by the `BeaconProcessor`.
+    pub received: Instant,
+}
+
+/// Messages that can be sent to the queue spawned by `spawn_reprocess_queue`.
+pub enum ReprocessQueueMessage<T: BeaconChainTypes> {
+    /// An unaggregated attestation that references an unknown block.
+    UnknownBlockUnaggregate(QueuedUnaggregate<T>),
+    /// A block with the given root has been imported.
+    BlockImported(Hash256),
+}
+
+/// Holds attestations which reference an unknown block until that block is imported or the
+/// attestation expires.
+pub struct ReprocessQueue<T: BeaconChainTypes> {
+    /// The queued attestations, keyed by an identifier which is unique within `self`.
+    attestations: HashMap<usize, (QueuedUnaggregate<T>, Key)>,
+    /// The identifiers of the queued attestations which reference each unknown block root.
+    awaiting_block: HashMap<Hash256, Vec<usize>>,
+    /// Yields the identifiers of attestations once they have been queued for `self.ttl`.
+    expiry_queue: DelayQueue<usize>,
+    next_id: usize,
+    max_length: usize,
+    ttl: Duration,
+}
+
+impl<T: BeaconChainTypes> ReprocessQueue<T> {
+    /// Create a new, empty queue which will hold at most `max_length` attestations, each for no
+    /// longer than `ttl`.
+    pub fn new(max_length: usize, ttl: Duration) -> Self {
+        Self {
+            attestations: HashMap::new(),
+            awaiting_block: HashMap::new(),
+            expiry_queue: DelayQueue::new(),
+            next_id: 0,
+            max_length,
+            ttl,
+        }
+    }
+
+    /// Add `item` to the queue, returning it in an `Err` if the queue is full.
+    pub fn push(&mut self, item: QueuedUnaggregate<T>) -> Result<(), QueuedUnaggregate<T>> {
+        if self.attestations.len() >= self.max_length {
+            return Err(item);
+        }
+
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        let expiry_key = self.expiry_queue.insert(id, self.ttl);
+        self.awaiting_block
+            .entry(item.attestation.data.beacon_block_root)
+            .or_default()
+            .push(id);
+        self.attestations.insert(id, (item, expiry_key));
+
+        Ok(())
+    }
+
+    /// Remove and return all the attestations which reference `block_root`.
+    pub fn block_imported(&mut self, block_root: Hash256) -> Vec<QueuedUnaggregate<T>> {
+        let ids = self.awaiting_block.remove(&block_root).unwrap_or_default();
+
+        let mut ready = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some((item, expiry_key)) = self.attestations.remove(&id) {
+                self.expiry_queue.remove(&expiry_key);
+                ready.push(item);
+            }
+        }
+        ready
+    }
+}
+
+/// Yields attestations which have expired, removing them from the queue.
+impl<T: BeaconChainTypes> Stream for ReprocessQueue<T> {
+    type Item = Result<QueuedUnaggregate<T>, TimeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match self.expiry_queue.poll_expired(cx) {
+                Poll::Ready(Some(Ok(expired))) => {
+                    let id = expired.into_inner();
+
+                    if let Some((item, _)) = self.attestations.remove(&id) {
+                        let block_root = item.attestation.data.beacon_block_root;
+                        if let Some(ids) = self.awaiting_block.get_mut(&block_root) {
+                            ids.retain(|queued_id| *queued_id != id);
+                            if ids.is_empty() {
+                                self.awaiting_block.remove(&block_root);
+                            }
+                        }
+
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                // `Poll::Ready(None)` means that there are no more entries in the delay queue, but
+                // more may be added later so we must not end the stream.
+                Poll::Ready(None) | Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Unifies the different messages processed by the reprocess queue.
+enum InboundEvent<T: BeaconChainTypes> {
+    /// A message from a worker.
+    Message(ReprocessQueueMessage<T>),
+    /// An attestation has been queued for longer than its TTL.
+    ExpiredAttestation(QueuedUnaggregate<T>),
+    /// The `DelayQueue` returned an error.
+    DelayQueueError(TimeError),
+}
+
+/// Combines the `ReprocessQueue` and `Receiver` streams into a single stream.
+///
+/// This struct has a similar purpose to `tokio::select!`, however it allows for more fine-grained
+/// control (specifically in the ordering of event processing).
+struct InboundEvents<T: BeaconChainTypes> {
+    queue: ReprocessQueue<T>,
+    messages_rx: Receiver<ReprocessQueueMessage<T>>,
+}
+
+impl<T: BeaconChainTypes> Stream for InboundEvents<T> {
+    type Item = InboundEvent<T>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        // Poll for expired attestations *before* we process new messages, so that we do not
+        // re-process an attestation that has already expired.
+        match self.queue.poll_next_unpin(cx) {
+            Poll::Ready(Some(Ok(expired))) => {
+                return Poll::Ready(Some(InboundEvent::ExpiredAttestation(expired)));
+            }
+            Poll::Ready(Some(Err(e))) => {
+                return Poll::Ready(Some(InboundEvent::DelayQueueError(e)));
+            }
+            Poll::Ready(None) | Poll::Pending => (),
+        }
+
+        match self.messages_rx.poll_recv(cx) {
+            Poll::Ready(Some(message)) => {
+                return Poll::Ready(Some(InboundEvent::Message(message)));
+            }
+            Poll::Ready(None) => {
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {}
+        }
+
+        Poll::Pending
+    }
+}
+
+/// Spawn a queue which will accept `ReprocessQueueMessage`s via the returned `Sender`, holding
+/// attestations for unknown blocks until the block is imported, then sending them back out via
+/// `ready_attestations_tx`. Each attestation is held for no longer than `ttl`.
+///
+/// Attestations which are dropped by the queue are reported as `Ignore` via `network_tx`.
+pub fn spawn_reprocess_queue<T: BeaconChainTypes>(
+    ready_attestations_tx: Sender<QueuedUnaggregate<T>>,
+    network_tx: UnboundedSender<NetworkMessage<T::EthSpec>>,
+    ttl: Duration,
+    executor: &TaskExecutor,
+    log: Logger,
+) -> Sender<ReprocessQueueMessage<T>> {
+    let (messages_tx, messages_rx) = mpsc::channel(MAX_UNKNOWN_BLOCK_ATTESTATION_QUEUE_LEN);
+
+    let queue_future = async move {
+        let mut inbound_events = InboundEvents {
+            queue: ReprocessQueue::new(MAXIMUM_QUEUED_ATTESTATIONS, ttl),
+            messages_rx,
+        };
+
+        // Stop gossipsub from waiting on a validation result for an attestation which will not be
+        // processed.
+        let ignore = |dropped: QueuedUnaggregate<T>| {
+            if network_tx
+                .send(NetworkMessage::ValidationResult {
+                    propagation_source: dropped.peer_id,
+                    message_id: dropped.message_id,
+                    validation_result: MessageAcceptance::Ignore,
+                })
+                .is_err()
+            {
+                error!(log, "Failed to report dropped attestation");
+            }
+        };
+
+        loop {
+            match inbound_events.next().await {
+                Some(InboundEvent::Message(ReprocessQueueMessage::UnknownBlockUnaggregate(
+                    queued,
+                ))) => {
+                    if let Err(dropped) = inbound_events.queue.push(queued) {
+                        error!(
+                            log,
+                            "Attestation reprocess queue is full";
+                            "queue_size" => MAXIMUM_QUEUED_ATTESTATIONS,
+                            "beacon_block_root" => ?dropped.attestation.data.beacon_block_root,
+                        );
+                        ignore(dropped);
+                    }
+                }
+                Some(InboundEvent::Message(ReprocessQueueMessage::BlockImported(block_root))) => {
+                    for ready in inbound_events.queue.block_imported(block_root) {
+                        if let Err(e) = ready_attestations_tx.try_send(ready) {
+                            error!(
+                                log,
+                                "Failed to send attestation for re-processing";
+                                "beacon_block_root" => ?block_root,
+                            );
+                            ignore(e.into_inner());
+                        }
+                    }
+                }
+                Some(InboundEvent::ExpiredAttestation(expired)) => {
+                    metrics::inc_counter(
+                        &metrics::BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_EXPIRED_TOTAL,
+                    );
+                    debug!(
+                        log,
+                        "Dropping attestation for unknown block";
+                        "msg" => "block was not imported in time",
+                        "beacon_block_root" => ?expired.attestation.data.beacon_block_root,
+                        "peer_id" => %expired.peer_id,
+                    );
+                    ignore(expired);
+                }
+                Some(InboundEvent::DelayQueueError(e)) => crit!(
+                    log,
+                    "Failed to poll attestation reprocess queue";
+                    "e" => ?e
+                ),
+                None => {
+                    debug!(
+                        log,
+                        "Attestation reprocess queue stopped";
+                        "msg" => "shutting down"
+                    );
+                    break;
+                }
+            }
+        }
+    };
+
+    executor.spawn(queue_future, TASK_NAME);
+
+    messages_tx
+}
@@ -1,6 +1,7 @@
 #![cfg(not(debug_assertions))] // Tests are too slow in debug.
 #![cfg(test)]
 
+use crate::beacon_processor::peer_rate_limiter::PeerRateLimiter;
 use crate::beacon_processor::*;
 use crate::{service::NetworkMessage, sync::SyncMessage};
 use beacon_chain::{
@@ -9,17 +10,21 @@ use beacon_chain::{
 };
 use discv5::enr::{CombinedKey, EnrBuilder};
 use environment::{null_logger, Environment, EnvironmentBuilder};
-use eth2_libp2p::{rpc::methods::MetaData, types::EnrBitfield, MessageId, NetworkGlobals, PeerId};
+use eth2_libp2p::{
+    rpc::methods::MetaData, types::EnrBitfield, MessageAcceptance, MessageId, NetworkGlobals,
+    PeerId,
+};
 use slot_clock::SlotClock;
 use std::cmp;
 use std::iter::Iterator;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
 use tokio::sync::mpsc;
 use types::{
     test_utils::generate_deterministic_keypairs, Attestation, AttesterSlashing, MainnetEthSpec,
-    ProposerSlashing, SignedBeaconBlock, SignedVoluntaryExit, SubnetId,
+    ProposerSlashing, SignedAggregateAndProof, SignedBeaconBlock, SignedVoluntaryExit, Slot,
+    SubnetId,
 };
 
 type E = MainnetEthSpec;
@@ -42,12 +47,14 @@ struct TestRig {
     chain: Arc<BeaconChain<T>>,
     next_block: SignedBeaconBlock<E>,
     attestations: Vec<(Attestation<E>, SubnetId)>,
+    next_block_attestations: Vec<(Attestation<E>, SubnetId)>,
+    aggregate: SignedAggregateAndProof<E>,
     attester_slashing: AttesterSlashing<E>,
     proposer_slashing: ProposerSlashing,
     voluntary_exit: SignedVoluntaryExit,
     beacon_processor_tx: mpsc::Sender<WorkEvent<T>>,
     work_journal_rx: mpsc::Receiver<String>,
-    _network_rx: mpsc::UnboundedReceiver<NetworkMessage<E>>,
+    network_rx: mpsc::UnboundedReceiver<NetworkMessage<E>>,
     _sync_rx: mpsc::UnboundedReceiver<SyncMessage<E>>,
     environment: Option<Environment<E>>,
 }
@@ -57,13 +64,25 @@ struct TestRig {
 impl Drop for TestRig {
     fn drop(&mut self) {
         // Causes the beacon processor to shutdown.
-        self.beacon_processor_tx = mpsc::channel(MAX_WORK_EVENT_QUEUE_LEN).0;
+        self.close_work_channel();
         self.environment.take().unwrap().shutdown_on_idle();
     }
 }
 
 impl TestRig {
     pub fn new(chain_length: u64) -> Self {
+        Self::new_with_config(
+            chain_length,
+            BeaconProcessorConfig::default(),
+            cmp::max(1, num_cpus::get()),
+        )
+    }
+
+    pub fn new_with_config(
+        chain_length: u64,
+        config: BeaconProcessorConfig,
+        max_workers: usize,
+    ) -> Self {
         let mut harness = BeaconChainHarness::new(
             MainnetEthSpec,
             generate_deterministic_keypairs(VALIDATOR_COUNT),
@@ -89,7 +108,7 @@ impl TestRig {
             "precondition: current slot is one after head"
         );
 
-        let (next_block, _next_state) =
+        let (next_block, next_state) =
             harness.make_block(head.beacon_state.clone(), harness.chain.slot().unwrap());
 
         let head_state_root = head.beacon_state_root();
@@ -110,6 +129,37 @@ impl TestRig {
             "precondition: attestations for testing"
         );
 
+        let next_block_attestations = harness
+            .get_unaggregated_attestations(
+                &AttestationStrategy::AllValidators,
+                &next_state,
+                next_block.state_root(),
+                next_block.canonical_root(),
+                next_block.slot(),
+            )
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        assert!(
+            !next_block_attestations.is_empty(),
+            "precondition: attestations to the next block for testing"
+        );
+
+        // Produce an aggregate at the current slot for the head block, using the next state since
+        // the selection proof is signed over the slot of the state.
+        let aggregate = harness
+            .make_attestations(
+                &harness.get_all_validators(),
+                &next_state,
+                next_block.state_root(),
+                head.beacon_block_root.into(),
+                harness.chain.slot().unwrap(),
+            )
+            .into_iter()
+            .find_map(|(_, aggregate)| aggregate)
+            .expect("precondition: aggregate for testing");
+
         let attester_slashing = harness.make_attester_slashing(vec![0, 1]);
         let proposer_slashing = harness.make_proposer_slashing(2);
         let voluntary_exit = harness.make_voluntary_exit(3, harness.chain.epoch().unwrap());
@@ -122,11 +172,12 @@ impl TestRig {
 
         let chain = Arc::new(harness.chain);
 
-        let (network_tx, _network_rx) = mpsc::unbounded_channel();
+        let (network_tx, network_rx) = mpsc::unbounded_channel();
 
         let log = null_logger().unwrap();
 
-        let (beacon_processor_tx, beacon_processor_rx) = mpsc::channel(MAX_WORK_EVENT_QUEUE_LEN);
+        let (beacon_processor_tx, beacon_processor_rx) =
+            mpsc::channel(config.max_work_event_queue_len);
         let (sync_tx, _sync_rx) = mpsc::unbounded_channel();
 
         // Default metadata
@@ -163,8 +214,9 @@ impl TestRig {
             sync_tx,
             network_globals,
             executor,
-            max_workers: cmp::max(1, num_cpus::get()),
+            max_workers,
             current_workers: 0,
+            config,
             log: log.clone(),
         }
         .spawn_manager(beacon_processor_rx, Some(work_journal_tx));
@@ -173,12 +225,14 @@ impl TestRig {
             chain,
             next_block,
             attestations,
+            next_block_attestations,
+            aggregate,
             attester_slashing,
             proposer_slashing,
             voluntary_exit,
             beacon_processor_tx,
             work_journal_rx,
-            _network_rx,
+            network_rx,
             _sync_rx,
             environment: Some(environment),
         }
@@ -209,6 +263,31 @@ impl TestRig {
             .unwrap();
     }
 
+    pub fn enqueue_next_block_unaggregated_attestation(&self) {
+        let (attestation, subnet_id) = self.next_block_attestations.first().unwrap().clone();
+        self.beacon_processor_tx
+            .try_send(WorkEvent::unaggregated_attestation(
+                junk_message_id(),
+                junk_peer_id(),
+                attestation,
+                subnet_id,
+                true,
+                Duration::from_secs(0),
+            ))
+            .unwrap();
+    }
+
+    pub fn enqueue_gossip_aggregate(&self) {
+        self.beacon_processor_tx
+            .try_send(WorkEvent::aggregated_attestation(
+                junk_message_id(),
+                junk_peer_id(),
+                self.aggregate.clone(),
+                Duration::from_secs(0),
+            ))
+            .unwrap();
+    }
+
     pub fn enqueue_gossip_attester_slashing(&self) {
         self.beacon_processor_tx
             .try_send(WorkEvent::gossip_attester_slashing(
@@ -239,6 +318,16 @@ impl TestRig {
             .unwrap();
     }
 
+    /// Drop the sender of work events, which causes the `BeaconProcessor` to shutdown.
+    pub fn close_work_channel(&mut self) {
+        self.beacon_processor_tx = mpsc::channel(MAX_WORK_EVENT_QUEUE_LEN).0;
+    }
+
+    /// Fires the exit signal, as happens when the node shuts down.
+    pub fn fire_exit_signal(&mut self) {
+        self.environment.as_mut().unwrap().fire_signal();
+    }
+
     fn runtime(&mut self) -> Arc<Runtime> {
         self.environment
             .as_mut()
@@ -264,6 +353,32 @@ impl TestRig {
         })
     }
 
+    /// Wait for the next gossip validation result sent to the network, panicking if none is received
+    /// within `STANDARD_TIMEOUT`.
+    pub fn next_validation_result(&mut self) -> MessageAcceptance {
+        self.runtime().block_on(async {
+            let recv_future = async {
+                loop {
+                    match self.network_rx.recv().await {
+                        Some(NetworkMessage::ValidationResult {
+                            validation_result, ..
+                        }) => break validation_result,
+                        Some(_) => continue,
+                        None => panic!("network channel closed"),
+                    }
+                }
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(STANDARD_TIMEOUT) => panic!(
+                    "timeout ({:?}) expired waiting for a validation result",
+                    STANDARD_TIMEOUT,
+                ),
+                validation_result = recv_future => validation_result,
+            }
+        })
+    }
+
     /// Assert that the `BeaconProcessor` event journal is as `expected`.
     ///
     /// ## Note
@@ -271,7 +386,90 @@ impl TestRig {
     /// We won't attempt to listen for any more than `expected.len()` events. As such, it makes sense
     /// to use the `NOTHING_TO_DO` event to ensure that execution has completed.
     pub fn assert_event_journal(&mut self, expected: &[&str]) {
-        let events = self.runtime().block_on(async {
+        let events = self.drain_event_journal(expected);
+
+        assert_eq!(
+            events,
+            expected
+                .into_iter()
+                .map(|s| s.to_string())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Assert that the `BeaconProcessor` event journal contains the `expected` events, in any
+    /// order.
+    ///
+    /// This is useful when events are produced concurrently (e.g., by multiple workers).
+    pub fn assert_event_journal_unordered(&mut self, expected: &[&str]) {
+        let mut events = self.drain_event_journal(expected);
+        let mut expected = expected.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        events.sort();
+        expected.sort();
+
+        assert_eq!(events, expected);
+    }
+
+    /// Collect all events from the `BeaconProcessor` event journal until the manager stops,
+    /// panicking if it does not stop within `STANDARD_TIMEOUT`.
+    pub fn event_journal_until_closed(&mut self) -> Vec<String> {
+        self.runtime().block_on(async {
+            let mut events = vec![];
+
+            let drain_future = async {
+                while let Some(event) = self.work_journal_rx.recv().await {
+                    events.push(event);
+                }
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(STANDARD_TIMEOUT) => panic!(
+                    "timeout ({:?}) expired waiting for the journal to close. got {:?}",
+                    STANDARD_TIMEOUT,
+                    events
+                ),
+                _ = drain_future => {},
+            }
+
+            events
+        })
+    }
+
+    /// Collect events from the `BeaconProcessor` event journal until `count` events with the given
+    /// `id` have been received, panicking if they are not received within `STANDARD_TIMEOUT`.
+    pub fn event_journal_until_count(&mut self, id: &str, count: usize) -> Vec<String> {
+        self.runtime().block_on(async {
+            let mut events = vec![];
+
+            let drain_future = async {
+                while events.iter().filter(|event| *event == id).count() < count {
+                    match self.work_journal_rx.recv().await {
+                        Some(event) => events.push(event),
+                        None => break,
+                    }
+                }
+            };
+
+            tokio::select! {
+                _ = tokio::time::sleep(STANDARD_TIMEOUT) => panic!(
+                    "timeout ({:?}) expired waiting for {} {} events. got {:?}",
+                    STANDARD_TIMEOUT,
+                    count,
+                    id,
+                    events
+                ),
+                _ = drain_future => {},
+            }
+
+            events
+        })
+    }
+
+    /// Collect `expected.len()` events from the `BeaconProcessor` event journal, panicking if they
+    /// are not received within `STANDARD_TIMEOUT`.
+    fn drain_event_journal(&mut self, expected: &[&str]) -> Vec<String> {
+        self.runtime().block_on(async {
             let mut events = vec![];
 
             let drain_future = async {
@@ -302,15 +500,7 @@ impl TestRig {
             }
 
             events
-        });
-
-        assert_eq!(
-            events,
-            expected
-                .into_iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>()
-        );
+        })
     }
 }
 
@@ -424,6 +614,10 @@ fn import_gossip_block_at_current_slot() {
         rig.next_block.canonical_root(),
         "block should be imported and become head"
     );
+    assert!(
+        matches!(rig.next_validation_result(), MessageAcceptance::Accept),
+        "block should be propagated"
+    );
 }
 
 /// Ensure a valid attestation can be imported.
@@ -431,19 +625,111 @@ fn import_gossip_block_at_current_slot() {
 fn import_gossip_attestation() {
     let mut rig = TestRig::new(SMALL_CHAIN);
 
-    let initial_attns = rig.chain.naive_aggregation_pool.read().num_attestations();
+    let initial_attns = rig.chain.naive_aggregation_pool.num_attestations();
 
     rig.enqueue_unaggregated_attestation();
 
     rig.assert_event_journal(&[GOSSIP_ATTESTATION, WORKER_FREED, NOTHING_TO_DO]);
 
     assert_eq!(
-        rig.chain.naive_aggregation_pool.read().num_attestations(),
+        rig.chain.naive_aggregation_pool.num_attestations(),
         initial_attns + 1,
         "op pool should have one more attestation"
     );
 }
 
+/// Aggregates that arrive less than a slot early should be queued for later processing.
+#[test]
+fn import_gossip_aggregate_acceptably_early() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+
+    let aggregate_slot = rig.aggregate.message.aggregate.data.slot;
+    let slot_start = rig.chain.slot_clock.start_of(aggregate_slot).unwrap();
+
+    // Move the clock back so the aggregate is beyond the gossip clock disparity, but less than one
+    // slot early.
+    rig.chain
+        .slot_clock
+        .set_current_time(slot_start - MAXIMUM_GOSSIP_CLOCK_DISPARITY * 2);
+
+    assert_eq!(
+        rig.chain.slot().unwrap(),
+        aggregate_slot - 1,
+        "chain should be at the correct slot"
+    );
+
+    let initial_attns = rig.chain.op_pool.num_attestations();
+
+    rig.enqueue_gossip_aggregate();
+
+    rig.assert_event_journal(&[GOSSIP_AGGREGATE, WORKER_FREED, NOTHING_TO_DO]);
+
+    assert_eq!(
+        rig.chain.op_pool.num_attestations(),
+        initial_attns,
+        "aggregate should be queued rather than imported"
+    );
+
+    // Note: similar to `import_gossip_block_acceptably_early`, this assumes that we can set the
+    // slot clock before the aggregate is due for processing.
+    rig.chain.slot_clock.set_slot(aggregate_slot.into());
+
+    rig.assert_event_journal(&[DELAYED_AGGREGATE, WORKER_FREED, NOTHING_TO_DO]);
+
+    assert_eq!(
+        rig.chain.op_pool.num_attestations(),
+        initial_attns + 1,
+        "op pool should have one more attestation"
+    );
+}
+
+/// An early aggregate which the delay queue declines to hold should be ignored on gossip, rather
+/// than leaving gossipsub waiting on a validation result.
+#[test]
+fn ignore_aggregate_dropped_by_delay_queue() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+    let executor = rig.core_context().executor;
+    let (ready_tx, _ready_rx) = mpsc::channel::<QueuedAggregate<T>>(1);
+    let (network_tx, mut network_rx) = mpsc::unbounded_channel();
+
+    let early_aggregate_tx = spawn_delay_queue(
+        ready_tx,
+        network_tx,
+        1,
+        &executor,
+        rig.chain.slot_clock.clone(),
+        null_logger().unwrap(),
+    );
+
+    // The queue only holds work which is less than one slot early.
+    let mut aggregate = rig.aggregate.clone();
+    aggregate.message.aggregate.data.slot = rig.chain.slot().unwrap() + 2;
+    let message_id = junk_message_id();
+
+    early_aggregate_tx
+        .try_send(QueuedAggregate {
+            message_id: message_id.clone(),
+            peer_id: junk_peer_id(),
+            aggregate: Box::new(aggregate),
+            seen_timestamp: Duration::from_secs(0),
+            received: Instant::now(),
+        })
+        .unwrap();
+
+    let result = rig
+        .runtime()
+        .block_on(async { tokio::time::timeout(STANDARD_TIMEOUT, network_rx.recv()).await });
+
+    assert!(matches!(
+        result,
+        Ok(Some(NetworkMessage::ValidationResult {
+            message_id: ref id,
+            validation_result: MessageAcceptance::Ignore,
+            ..
+        })) if *id == message_id
+    ));
+}
+
 /// Ensure a bunch of valid operations can be imported.
 #[test]
 fn import_misc_gossip_ops() {
@@ -498,3 +784,395 @@ fn import_misc_gossip_ops() {
         "op pool should have one more exit"
     );
 }
+
+/// The work latency should include the time that work spent before reaching a worker.
+#[test]
+fn work_latency_includes_time_before_processing() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+    let delay = Duration::from_secs(2);
+
+    let histogram = || {
+        crate::metrics::get_histogram(
+            &crate::metrics::BEACON_PROCESSOR_WORK_LATENCY_SECONDS,
+            &[GOSSIP_ATTESTER_SLASHING],
+        )
+        .expect("histogram should exist")
+    };
+    let initial_count = histogram().get_sample_count();
+    let initial_sum = histogram().get_sample_sum();
+
+    // Pretend the slashing was received `delay` ago.
+    let mut event = WorkEvent::gossip_attester_slashing(
+        junk_message_id(),
+        junk_peer_id(),
+        Box::new(rig.attester_slashing.clone()),
+    );
+    event.received = Instant::now()
+        .checked_sub(delay)
+        .expect("test clock should be later than the delay");
+    rig.beacon_processor_tx.try_send(event).unwrap();
+
+    rig.assert_event_journal(&[GOSSIP_ATTESTER_SLASHING, WORKER_FREED, NOTHING_TO_DO]);
+
+    assert_eq!(histogram().get_sample_count(), initial_count + 1);
+    let observed = histogram().get_sample_sum() - initial_sum;
+    assert!(
+        observed >= delay.as_secs_f64() && observed < (delay + STANDARD_TIMEOUT).as_secs_f64(),
+        "observed latency {} should be approximately {:?}",
+        observed,
+        delay
+    );
+}
+
+/// Work which is re-submitted from a delay queue should keep the time it was first received.
+#[test]
+fn delayed_aggregate_keeps_original_receipt_time() {
+    let rig = TestRig::new(SMALL_CHAIN);
+    let received = Instant::now()
+        .checked_sub(Duration::from_secs(2))
+        .expect("test clock should be later than the delay");
+
+    let event = WorkEvent::delayed_aggregated_attestation(QueuedAggregate {
+        message_id: junk_message_id(),
+        peer_id: junk_peer_id(),
+        aggregate: Box::new(rig.aggregate.clone()),
+        seen_timestamp: Duration::from_secs(0),
+        received,
+    });
+
+    assert_eq!(event.received, received);
+}
+
+/// Work which is queued when the work channel closes should be completed before the manager stops.
+#[test]
+fn drain_queued_work_on_shutdown() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+
+    // Enqueue more work than there are workers so that some of it is queued.
+    let num_items = cmp::max(1, num_cpus::get()) * 2 + 1;
+    for _ in 0..num_items {
+        rig.enqueue_gossip_attester_slashing();
+    }
+    rig.close_work_channel();
+
+    let events = rig.event_journal_until_closed();
+    let count = |id: &str| events.iter().filter(|event| *event == id).count();
+
+    assert_eq!(count(GOSSIP_ATTESTER_SLASHING), num_items);
+    assert_eq!(
+        count(WORKER_FREED),
+        num_items,
+        "every queued item should be completed before the manager stops"
+    );
+}
+
+/// Work which is queued when the exit signal fires should be completed before the manager stops,
+/// even though the work channel remains open.
+#[test]
+fn drain_queued_work_on_exit_signal() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+
+    // Enqueue more work than there are workers so that some of it is queued.
+    let num_items = cmp::max(1, num_cpus::get()) * 2 + 1;
+    for _ in 0..num_items {
+        rig.enqueue_gossip_attester_slashing();
+    }
+
+    // Work which is still in the channel when the exit signal fires is not accepted, so wait for
+    // the manager to receive all of it first.
+    let mut events = rig.event_journal_until_count(GOSSIP_ATTESTER_SLASHING, num_items);
+    rig.fire_exit_signal();
+    events.extend(rig.event_journal_until_closed());
+    let count = |id: &str| events.iter().filter(|event| *event == id).count();
+
+    assert_eq!(count(GOSSIP_ATTESTER_SLASHING), num_items);
+    assert_eq!(
+        count(WORKER_FREED),
+        num_items,
+        "every queued item should be completed before the manager stops"
+    );
+}
+
+/// Attestations to an unknown block should be queued and processed once the block is imported.
+#[test]
+fn import_attestation_after_unknown_block() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+
+    let initial_attns = rig.chain.naive_aggregation_pool.num_attestations();
+
+    rig.enqueue_next_block_unaggregated_attestation();
+
+    rig.assert_event_journal(&[GOSSIP_ATTESTATION, WORKER_FREED, NOTHING_TO_DO]);
+
+    assert_eq!(
+        rig.chain.naive_aggregation_pool.num_attestations(),
+        initial_attns,
+        "attestation should be queued rather than imported"
+    );
+
+    rig.enqueue_gossip_block();
+
+    // The re-processed attestation may arrive before or after the block worker is freed.
+    rig.assert_event_journal_unordered(&[
+        GOSSIP_BLOCK,
+        UNKNOWN_BLOCK_ATTESTATION,
+        WORKER_FREED,
+        NOTHING_TO_DO,
+        WORKER_FREED,
+        NOTHING_TO_DO,
+    ]);
+
+    assert_eq!(
+        rig.chain.head().unwrap().beacon_block_root,
+        rig.next_block.canonical_root(),
+        "block should be imported and become head"
+    );
+    assert_eq!(
+        rig.chain.naive_aggregation_pool.num_attestations(),
+        initial_attns + 1,
+        "op pool should have one more attestation"
+    );
+}
+
+/// Attestations to an unknown block should be ignored once they expire from the queue.
+#[test]
+fn ignore_attestation_expired_from_unknown_block_queue() {
+    let config = BeaconProcessorConfig {
+        unknown_block_attestation_ttl: Duration::from_millis(100),
+        ..BeaconProcessorConfig::default()
+    };
+    let mut rig = TestRig::new_with_config(SMALL_CHAIN, config, cmp::max(1, num_cpus::get()));
+
+    rig.enqueue_next_block_unaggregated_attestation();
+
+    rig.assert_event_journal(&[GOSSIP_ATTESTATION, WORKER_FREED, NOTHING_TO_DO]);
+
+    assert!(
+        matches!(rig.next_validation_result(), MessageAcceptance::Ignore),
+        "expired attestation should be ignored"
+    );
+}
+
+/// Attestations which fail verification after being re-processed should not be counted as errors a
+/// second time.
+#[test]
+fn reprocessed_attestation_errors_are_not_counted() {
+    let mut rig = TestRig::new(SMALL_CHAIN);
+
+    let prior_attestation_known = || {
+        crate::metrics::get_int_counter(
+            &crate::metrics::GOSSIP_ATTESTATION_ERRORS_PER_TYPE,
+            &["PriorAttestationKnown"],
+        )
+        .expect("counter should exist")
+        .get()
+    };
+    let initial_errors = prior_attestation_known();
+
+    // Both copies of the attestation are queued until the block arrives.
+    rig.enqueue_next_block_unaggregated_attestation();
+    rig.enqueue_next_block_unaggregated_attestation();
+    rig.enqueue_gossip_block();
+
+    // The block and the first copy of the attestation are accepted, the duplicate is ignored.
+    let results = (0..3)
+        .map(|_| rig.next_validation_result())
+        .collect::<Vec<_>>();
+    let accepted = results
+        .iter()
+        .filter(|result| matches!(result, MessageAcceptance::Accept))
+        .count();
+    let ignored = results
+        .iter()
+        .filter(|result| matches!(result, MessageAcceptance::Ignore))
+        .count();
+    assert_eq!(accepted, 2);
+    assert_eq!(ignored, 1);
+
+    assert_eq!(
+        prior_attestation_known(),
+        initial_errors,
+        "errors from re-processed attestations should not be counted"
+    );
+}
+
+fn attestation_priority(slot: u64, head_block_known: bool) -> AttestationPriority {
+    AttestationPriority {
+        slot: Slot::new(slot),
+        head_block_known,
+    }
+}
+
+/// Attestations from the current slot should be dispatched before those from older slots,
+/// regardless of the order in which they arrived.
+#[test]
+fn attestation_queue_prioritizes_current_slot() {
+    let mut queue = PriorityQueue::new(8);
+
+    queue.push(attestation_priority(1, true), "old_1");
+    queue.push(attestation_priority(2, true), "current_1");
+    queue.push(attestation_priority(1, true), "old_2");
+    queue.push(attestation_priority(2, false), "current_unknown_head");
+    queue.push(attestation_priority(2, true), "current_2");
+    queue.push(attestation_priority(0, true), "older");
+
+    let dispatched = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+
+    assert_eq!(
+        dispatched,
+        vec![
+            "current_2",
+            "current_1",
+            "current_unknown_head",
+            "old_2",
+            "old_1",
+            "older"
+        ]
+    );
+}
+
+/// When the attestation queue is full, the stalest attestations should be dropped first.
+#[test]
+fn attestation_queue_sheds_stale_work_first() {
+    let mut queue = PriorityQueue::new(3);
+
+    queue.push(attestation_priority(2, true), "current_1");
+    queue.push(attestation_priority(1, true), "old");
+    queue.push(attestation_priority(2, false), "current_unknown_head");
+    assert!(queue.is_full());
+
+    // The old attestation should be dropped in favour of the new one.
+    queue.push(attestation_priority(2, true), "current_2");
+    assert_eq!(queue.len(), 3);
+
+    // An attestation which is staler than everything in the queue should itself be dropped.
+    queue.push(attestation_priority(0, true), "older");
+    assert_eq!(queue.len(), 3);
+
+    let dispatched = std::iter::from_fn(|| queue.pop()).collect::<Vec<_>>();
+
+    assert_eq!(
+        dispatched,
+        vec!["current_2", "current_1", "current_unknown_head"]
+    );
+}
+
+/// Aggregates beyond the configured queue length should be dropped, rather than queued up to the
+/// default length.
+#[test]
+fn aggregate_queue_full_at_configured_length() {
+    let config = BeaconProcessorConfig {
+        max_aggregated_attestation_queue_len: 1,
+        ..BeaconProcessorConfig::default()
+    };
+    let mut rig = TestRig::new_with_config(SMALL_CHAIN, config, 1);
+
+    // Hold the fork choice lock so that the only worker is stuck on the first aggregate whilst the
+    // remaining aggregates are queued.
+    let fork_choice = rig.chain.fork_choice.write();
+
+    for _ in 0..4 {
+        rig.enqueue_gossip_aggregate();
+    }
+    rig.assert_event_journal(&[
+        GOSSIP_AGGREGATE,
+        GOSSIP_AGGREGATE,
+        GOSSIP_AGGREGATE,
+        GOSSIP_AGGREGATE,
+    ]);
+
+    drop(fork_choice);
+
+    // Only one of the three queued aggregates should have been retained.
+    rig.assert_event_journal(&[WORKER_FREED, WORKER_FREED, NOTHING_TO_DO]);
+    rig.assert_no_events_for(Duration::from_millis(100));
+}
+
+/// Gossip from a peer which has exceeded its rate limit should be ignored without reaching a worker.
+#[test]
+fn ignore_gossip_attestation_over_rate_limit() {
+    let config = BeaconProcessorConfig {
+        max_gossip_attestations_per_peer: 1,
+        ..BeaconProcessorConfig::default()
+    };
+    let mut rig = TestRig::new_with_config(SMALL_CHAIN, config, cmp::max(1, num_cpus::get()));
+
+    let peer_id = junk_peer_id();
+    let (attestation, subnet_id) = rig.attestations.first().unwrap().clone();
+    let send_attestation = |rig: &TestRig| {
+        rig.beacon_processor_tx
+            .try_send(WorkEvent::unaggregated_attestation(
+                junk_message_id(),
+                peer_id,
+                attestation.clone(),
+                subnet_id,
+                true,
+                Duration::from_secs(0),
+            ))
+            .unwrap();
+    };
+
+    send_attestation(&rig);
+    rig.assert_event_journal(&[GOSSIP_ATTESTATION, WORKER_FREED, NOTHING_TO_DO]);
+    assert!(
+        matches!(rig.next_validation_result(), MessageAcceptance::Accept),
+        "first attestation should be propagated"
+    );
+
+    send_attestation(&rig);
+    rig.assert_event_journal(&[GOSSIP_ATTESTATION]);
+    assert!(
+        matches!(rig.next_validation_result(), MessageAcceptance::Ignore),
+        "second attestation should be ignored"
+    );
+    rig.assert_no_events_for(Duration::from_millis(100));
+}
+
+/// A peer which exceeds its budget should have its work dropped, without affecting other peers.
+#[test]
+fn peer_rate_limiter_drops_work_over_budget() {
+    let now = Instant::now();
+    let mut limiter = PeerRateLimiter::new(4, Duration::from_secs(12), now);
+    let spammer = junk_peer_id();
+    let honest = junk_peer_id();
+
+    let allowed = (0..16).filter(|_| limiter.allows(&spammer, now)).count();
+    assert_eq!(allowed, 4, "drops should begin after the configured budget");
+
+    assert!(
+        (0..4).all(|_| limiter.allows(&honest, now)),
+        "second peer should be unaffected"
+    );
+
+    // A single token is replenished after a quarter of the period.
+    let later = now + Duration::from_secs(3);
+    assert!(limiter.allows(&spammer, later));
+    assert!(!limiter.allows(&spammer, later));
+}
+
+/// The buckets of peers which go quiet should be pruned.
+#[test]
+fn peer_rate_limiter_prunes_quiet_peers() {
+    let now = Instant::now();
+    let period = Duration::from_secs(12);
+    let mut limiter = PeerRateLimiter::new(4, period, now);
+
+    assert!(limiter.allows(&junk_peer_id(), now));
+    assert!(limiter.allows(&junk_peer_id(), now));
+    assert_eq!(limiter.num_peers(), 2);
+
+    let active = junk_peer_id();
+    assert!(limiter.allows(&active, now + period));
+    assert_eq!(limiter.num_peers(), 1, "only the active peer should remain");
+}
+
+/// Budgets which do not fit in a `u32` should not be truncated.
+#[test]
+fn peer_rate_limiter_large_budget() {
+    let now = Instant::now();
+    let mut limiter = PeerRateLimiter::new(1 << 32, Duration::from_secs(12), now);
+    let peer = junk_peer_id();
+
+    assert!((0..1_024).all(|_| limiter.allows(&peer, now)));
+    assert!(limiter.allows(&peer, now + Duration::from_secs(1)));
+}
@@ -10,14 +10,20 @@ use eth2_libp2p::{MessageAcceptance, MessageId, PeerAction, PeerId, ReportSource
 use slog::{debug, error, info, trace, warn};
 use slot_clock::SlotClock;
 use ssz::Encode;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
 use types::{
     Attestation, AttesterSlashing, Hash256, ProposerSlashing, SignedAggregateAndProof,
-    SignedBeaconBlock, SignedVoluntaryExit, SubnetId,
+    SignedBeaconBlock, SignedVoluntaryExit, Slot, SubnetId,
 };
 
-use super::{super::block_delay_queue::QueuedBlock, Worker};
+use super::{
+    super::{
+        delay_queue::{QueuedAggregate, QueuedBlock},
+        reprocess_queue::{QueuedUnaggregate, ReprocessQueueMessage},
+    },
+    Worker,
+};
 
 impl<T: BeaconChainTypes> Worker<T> {
     /* Auxiliary functions */
@@ -58,7 +64,13 @@ impl<T: BeaconChainTypes> Worker<T> {
     /// - Attempt to apply it to fork choice.
     /// - Attempt to add it to the naive aggregation pool.
     ///
+    /// If the attestation references an unknown block and `reprocess_tx` is `Some`, the attestation
+    /// is sent to the reprocess queue to be processed again once the block is imported. When the
+    /// attestation is re-processed `reprocess_tx` is `None`, and verification errors are not
+    /// counted a second time.
+    ///
     /// Raises a log if there are errors.
+    #[allow(clippy::too_many_arguments)]
     pub fn process_gossip_attestation(
         self,
         message_id: MessageId,
@@ -66,23 +78,48 @@ impl<T: BeaconChainTypes> Worker<T> {
         attestation: Attestation<T::EthSpec>,
         subnet_id: SubnetId,
         should_import: bool,
+        reprocess_tx: Option<mpsc::Sender<ReprocessQueueMessage<T>>>,
         seen_timestamp: Duration,
+        received: Instant,
     ) {
         let beacon_block_root = attestation.data.beacon_block_root;
 
+        // Only hold a copy of the attestation if it might need to be queued for re-processing.
+        let attestation_for_reprocess = reprocess_tx.as_ref().map(|_| attestation.clone());
+
         let attestation = match self
             .chain
             .verify_unaggregated_attestation_for_gossip(attestation, Some(subnet_id))
         {
             Ok(attestation) => attestation,
             Err(e) => {
-                self.handle_attestation_verification_failure(
-                    peer_id,
-                    message_id,
-                    beacon_block_root,
-                    "unaggregated",
-                    e,
-                );
+                // Only count the error on the first receipt of the attestation. An attestation
+                // which is being re-processed had its error counted before it was queued.
+                if reprocess_tx.is_some() {
+                    metrics::register_attestation_error(&e);
+                }
+
+                match (e, reprocess_tx, attestation_for_reprocess) {
+                    (AttnError::UnknownHeadBlock { .. }, Some(reprocess_tx), Some(attestation)) => {
+                        let queued = QueuedUnaggregate {
+                            message_id,
+                            peer_id,
+                            attestation: Box::new(attestation),
+                            subnet_id,
+                            should_import,
+                            seen_timestamp,
+                            received,
+                        };
+                        self.queue_unknown_block_attestation(reprocess_tx, queued)
+                    }
+                    (e, _, _) => self.handle_attestation_verification_failure(
+                        peer_id,
+                        message_id,
+                        beacon_block_root,
+                        "unaggregated",
+                        e,
+                    ),
+                }
                 return;
             }
         };
@@ -141,36 +178,156 @@ impl<T: BeaconChainTypes> Worker<T> {
         metrics::inc_counter(&metrics::BEACON_PROCESSOR_UNAGGREGATED_ATTESTATION_IMPORTED_TOTAL);
     }
 
+    /// Send an unaggregated attestation which references an unknown block to the reprocess queue
+    /// and ask sync to find the block.
+    ///
+    /// The validation result is not propagated to gossipsub here. It is determined if and when the
+    /// attestation is re-processed, otherwise the message times out in gossipsub.
+    fn queue_unknown_block_attestation(
+        &self,
+        reprocess_tx: mpsc::Sender<ReprocessQueueMessage<T>>,
+        queued: QueuedUnaggregate<T>,
+    ) {
+        let peer_id = queued.peer_id;
+        let beacon_block_root = queued.attestation.data.beacon_block_root;
+
+        trace!(
+            self.log,
+            "Queuing attestation for unknown block";
+            "peer_id" => %peer_id,
+            "block" => %beacon_block_root
+        );
+
+        // We don't know the block, get the sync manager to handle the block lookup.
+        self.send_sync_message(SyncMessage::UnknownBlockHash(peer_id, beacon_block_root));
+
+        if let Err(e) =
+            reprocess_tx.try_send(ReprocessQueueMessage::UnknownBlockUnaggregate(queued))
+        {
+            error!(
+                self.log,
+                "Failed to queue attestation for re-processing";
+                "block" => %beacon_block_root,
+            );
+            if let ReprocessQueueMessage::UnknownBlockUnaggregate(queued) = e.into_inner() {
+                self.propagate_validation_result(
+                    queued.message_id,
+                    peer_id,
+                    MessageAcceptance::Ignore,
+                );
+            }
+        } else {
+            metrics::inc_counter(&metrics::BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_QUEUED_TOTAL);
+        }
+    }
+
+    /// Returns `true` if `slot` is in the future, but starts less than one slot from now.
+    fn is_less_than_one_slot_early(&self, slot: Slot) -> bool {
+        let slot_clock = &self.chain.slot_clock;
+        slot_clock
+            .duration_to_slot(slot)
+            .map_or(false, |duration| duration < slot_clock.slot_duration())
+    }
+
+    /// Send an aggregate which is from a future slot to the delay queue, to be processed once its
+    /// slot arrives.
+    ///
+    /// The validation result is not propagated to gossipsub here. It is determined when the
+    /// aggregate is re-processed, or is `Ignore` if the delay queue drops the aggregate.
+    fn queue_early_aggregate(
+        &self,
+        delayed_aggregate_tx: mpsc::Sender<QueuedAggregate<T>>,
+        queued: QueuedAggregate<T>,
+    ) {
+        let peer_id = queued.peer_id;
+        let aggregate_slot = queued.aggregate.message.aggregate.data.slot;
+
+        debug!(
+            self.log,
+            "Aggregate arrived early";
+            "aggregate_slot" => %aggregate_slot,
+            "peer_id" => %peer_id,
+            "msg" => "if this happens consistently, check system clock"
+        );
+
+        if let Err(e) = delayed_aggregate_tx.try_send(queued) {
+            error!(
+                self.log,
+                "Failed to defer aggregate processing";
+                "aggregate_slot" => %aggregate_slot,
+            );
+            self.propagate_validation_result(
+                e.into_inner().message_id,
+                peer_id,
+                MessageAcceptance::Ignore,
+            );
+        } else {
+            metrics::inc_counter(&metrics::BEACON_PROCESSOR_AGGREGATED_ATTESTATION_REQUEUED_TOTAL);
+        }
+    }
+
     /// Process the aggregated attestation received from the gossip network and:
     ///
     /// - If it passes gossip propagation criteria, tell the network thread to forward it.
     /// - Attempt to apply it to fork choice.
     /// - Attempt to add it to the block inclusion pool.
     ///
+    /// If the aggregate is from a future slot that is less than one slot away and
+    /// `delayed_aggregate_tx` is `Some`, the aggregate is sent to the delay queue to be processed
+    /// again once its slot arrives. When the aggregate is re-processed `delayed_aggregate_tx` is
+    /// `None`, and verification errors are not counted a second time.
+    ///
     /// Raises a log if there are errors.
     pub fn process_gossip_aggregate(
         self,
         message_id: MessageId,
         peer_id: PeerId,
         aggregate: SignedAggregateAndProof<T::EthSpec>,
+        delayed_aggregate_tx: Option<mpsc::Sender<QueuedAggregate<T>>>,
         seen_timestamp: Duration,
+        received: Instant,
     ) {
         let beacon_block_root = aggregate.message.aggregate.data.beacon_block_root;
 
+        // Only hold a copy of the aggregate if it is less than one slot early, in which case it
+        // might need to be queued for later processing.
+        let aggregate_for_delay = delayed_aggregate_tx
+            .as_ref()
+            .filter(|_| self.is_less_than_one_slot_early(aggregate.message.aggregate.data.slot))
+            .map(|_| aggregate.clone());
+
         let aggregate = match self
             .chain
             .verify_aggregated_attestation_for_gossip(aggregate)
         {
             Ok(aggregate) => aggregate,
             Err(e) => {
-                // Report the failure to gossipsub
-                self.handle_attestation_verification_failure(
-                    peer_id,
-                    message_id,
-                    beacon_block_root,
-                    "aggregated",
-                    e,
-                );
+                // Only count the error on the first receipt of the aggregate. An aggregate which is
+                // being re-processed had its error counted before it was queued.
+                if delayed_aggregate_tx.is_some() {
+                    metrics::register_attestation_error(&e);
+                }
+
+                match (e, delayed_aggregate_tx, aggregate_for_delay) {
+                    (AttnError::FutureSlot { .. }, Some(delayed_aggregate_tx), Some(aggregate)) => {
+                        let queued = QueuedAggregate {
+                            message_id,
+                            peer_id,
+                            aggregate: Box::new(aggregate),
+                            seen_timestamp,
+                            received,
+                        };
+                        self.queue_early_aggregate(delayed_aggregate_tx, queued)
+                    }
+                    // Report the failure to gossipsub
+                    (e, _, _) => self.handle_attestation_verification_failure(
+                        peer_id,
+                        message_id,
+                        beacon_block_root,
+                        "aggregated",
+                        e,
+                    ),
+                }
                 return;
             }
         };
@@ -233,13 +390,16 @@ impl<T: BeaconChainTypes> Worker<T> {
     ///   be downloaded.
     ///
     /// Raises a log if there are errors.
+    #[allow(clippy::too_many_arguments)]
     pub fn process_gossip_block(
         self,
         message_id: MessageId,
         peer_id: PeerId,
         block: SignedBeaconBlock<T::EthSpec>,
         delayed_import_tx: mpsc::Sender<QueuedBlock<T>>,
+        reprocess_tx: mpsc::Sender<ReprocessQueueMessage<T>>,
         seen_duration: Duration,
+        received: Instant,
     ) {
         // Log metrics to track delay from other nodes on the network.
         metrics::observe_duration(
@@ -365,6 +525,7 @@ impl<T: BeaconChainTypes> Worker<T> {
                         peer_id,
                         block: verified_block,
                         seen_timestamp: seen_duration,
+                        received,
                     })
                     .is_err()
                 {
@@ -377,7 +538,12 @@ impl<T: BeaconChainTypes> Worker<T> {
                     )
                 }
             }
-            Ok(_) => self.process_gossip_verified_block(peer_id, verified_block, seen_duration),
+            Ok(_) => self.process_gossip_verified_block(
+                peer_id,
+                verified_block,
+                reprocess_tx,
+                seen_duration,
+            ),
             Err(e) => {
                 error!(
                     self.log,
@@ -398,15 +564,17 @@ impl<T: BeaconChainTypes> Worker<T> {
         self,
         peer_id: PeerId,
         verified_block: GossipVerifiedBlock<T>,
-        // This value is not used presently, but it might come in handy for debugging.
-        _seen_duration: Duration,
+        reprocess_tx: mpsc::Sender<ReprocessQueueMessage<T>>,
+        seen_duration: Duration,
     ) {
         let block = Box::new(verified_block.block.clone());
 
-        match self.chain.process_block(verified_block) {
-            Ok(_block_root) => {
+        match self.chain.process_block(verified_block, seen_duration) {
+            Ok(block_root) => {
                 metrics::inc_counter(&metrics::BEACON_PROCESSOR_GOSSIP_BLOCK_IMPORTED_TOTAL);
 
+                self.send_block_imported(&reprocess_tx, block_root);
+
                 trace!(
                     self.log,
                     "Gossipsub block processed";
@@ -634,7 +802,6 @@ impl<T: BeaconChainTypes> Worker<T> {
         attestation_type: &str,
         error: AttnError,
     ) {
-        metrics::register_attestation_error(&error);
         match &error {
             AttnError::FutureEpoch { .. }
             | AttnError::PastEpoch { .. }
@@ -661,7 +828,29 @@ impl<T: BeaconChainTypes> Worker<T> {
                 // Do not propagate these messages.
                 self.propagate_validation_result(message_id, peer_id, MessageAcceptance::Ignore);
             }
-            AttnError::InvalidSelectionProof { .. } | AttnError::InvalidSignature => {
+            AttnError::InvalidSelectionProof {
+                aggregator_index,
+                committee_len,
+                modulo,
+            } => {
+                /*
+                 * The selection proof does not elect the aggregator.
+                 *
+                 * The peer has published an invalid consensus message.
+                 */
+                debug!(
+                    self.log,
+                    "Selection proof does not elect aggregator";
+                    "peer_id" => %peer_id,
+                    "block" => %beacon_block_root,
+                    "aggregator_index" => aggregator_index,
+                    "committee_len" => committee_len,
+                    "modulo" => modulo,
+                );
+                self.propagate_validation_result(message_id, peer_id, MessageAcceptance::Reject);
+                self.gossip_penalize_peer(peer_id, PeerAction::LowToleranceError);
+            }
+            AttnError::InvalidSignature => {
                 /*
                  * These errors are caused by invalid signatures.
                  *
@@ -795,7 +984,9 @@ impl<T: BeaconChainTypes> Worker<T> {
                 //
                 // https://github.com/sigp/lighthouse/issues/1039
 
-                // TODO: Maintain this attestation and re-process once sync completes
+                // Unaggregated attestations from gossip are queued for re-processing before reaching
+                // this point, see `Self::queue_unknown_block_attestation`.
+                //
                 // TODO: We then score based on whether we can download the block and re-process.
                 trace!(
                     self.log,
@@ -816,6 +1007,26 @@ impl<T: BeaconChainTypes> Worker<T> {
                 self.propagate_validation_result(message_id, peer_id, MessageAcceptance::Ignore);
                 return;
             }
+            AttnError::HeadBlockFinalized {
+                beacon_block_root,
+                finalized_slot,
+            } => {
+                /*
+                 * The attestation is for a block prior to our finalized slot.
+                 *
+                 * The peer may be lagging behind, but it is not necessarily faulty. There is no
+                 * need to request the block from sync since we will not import it.
+                 */
+                trace!(
+                    self.log,
+                    "Attestation for finalized block";
+                    "peer_id" => %peer_id,
+                    "block" => %beacon_block_root,
+                    "finalized_slot" => finalized_slot,
+                );
+                self.propagate_validation_result(message_id, peer_id, MessageAcceptance::Ignore);
+                return;
+            }
             AttnError::UnknownTargetRoot(_) => {
                 /*
                  * The block indicated by the target root is not known to us.
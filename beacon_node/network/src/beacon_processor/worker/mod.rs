@@ -1,9 +1,10 @@
-use super::QueuedBlock;
+use super::{QueuedAggregate, QueuedBlock, ReprocessQueueMessage};
 use crate::{service::NetworkMessage, sync::SyncMessage};
 use beacon_chain::{BeaconChain, BeaconChainTypes};
 use slog::{error, Logger};
 use std::sync::Arc;
 use tokio::sync::mpsc;
+use types::Hash256;
 
 mod gossip_methods;
 mod rpc_methods;
@@ -41,10 +42,32 @@ impl<T: BeaconChainTypes> Worker<T> {
                 "error" => %e)
         });
     }
+
+    /// Notify the reprocess queue that a block has been imported, so that any attestations
+    /// waiting on it can be processed.
+    fn send_block_imported(
+        &self,
+        reprocess_tx: &mpsc::Sender<ReprocessQueueMessage<T>>,
+        block_root: Hash256,
+    ) {
+        if reprocess_tx
+            .try_send(ReprocessQueueMessage::BlockImported(block_root))
+            .is_err()
+        {
+            error!(
+                self.log,
+                "Failed to inform block import";
+                "source" => "reprocess_queue",
+                "block_root" => ?block_root,
+            )
+        }
+    }
 }
 
 /// Contains the necessary items for a worker to do their job.
 pub struct Toolbox<T: BeaconChainTypes> {
     pub idle_tx: mpsc::Sender<()>,
     pub delayed_block_tx: mpsc::Sender<QueuedBlock<T>>,
+    pub delayed_aggregate_tx: mpsc::Sender<QueuedAggregate<T>>,
+    pub reprocess_tx: mpsc::Sender<ReprocessQueueMessage<T>>,
 }
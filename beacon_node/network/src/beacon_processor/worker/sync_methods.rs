@@ -1,12 +1,15 @@
-use super::Worker;
+use super::{super::ReprocessQueueMessage, Worker};
 use crate::beacon_processor::worker::FUTURE_SLOT_TOLERANCE;
 use crate::beacon_processor::BlockResultSender;
 use crate::metrics;
 use crate::sync::manager::SyncMessage;
 use crate::sync::{BatchProcessResult, ChainId};
-use beacon_chain::{BeaconChainTypes, BlockError, ChainSegmentResult};
+use beacon_chain::{
+    validator_monitor::timestamp_now, BeaconChainTypes, BlockError, ChainSegmentResult,
+};
 use eth2_libp2p::PeerId;
 use slog::{crit, debug, error, info, trace, warn};
+use tokio::sync::mpsc;
 use types::{Epoch, Hash256, SignedBeaconBlock};
 
 /// Id associated to a block processing request, either a batch or a single block.
@@ -27,9 +30,10 @@ impl<T: BeaconChainTypes> Worker<T> {
         self,
         block: SignedBeaconBlock<T::EthSpec>,
         result_tx: BlockResultSender<T::EthSpec>,
+        reprocess_tx: mpsc::Sender<ReprocessQueueMessage<T>>,
     ) {
         let slot = block.slot();
-        let block_result = self.chain.process_block(block);
+        let block_result = self.chain.process_block(block, timestamp_now());
 
         metrics::inc_counter(&metrics::BEACON_PROCESSOR_RPC_BLOCK_IMPORTED_TOTAL);
 
@@ -40,6 +44,8 @@ impl<T: BeaconChainTypes> Worker<T> {
                 "slot" => slot,
                 "hash" => %root
             );
+
+            self.send_block_imported(&reprocess_tx, *root);
         }
 
         if result_tx.send(block_result).is_err() {
@@ -19,3 +19,4 @@ mod sync;
 
 pub use eth2_libp2p::NetworkConfig;
 pub use service::{NetworkMessage, NetworkService};
+pub use status::{HeadStatus, ToStatusMessage};
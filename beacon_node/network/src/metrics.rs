@@ -186,6 +186,11 @@ lazy_static! {
         "Time taken for a worker to fully process some parcel of work.",
         &["type"]
     );
+    pub static ref BEACON_PROCESSOR_WORK_LATENCY_SECONDS: Result<HistogramVec> = try_create_histogram_vec(
+        "beacon_processor_work_latency_seconds",
+        "Time from a work event being received to a worker finishing it, including time queued.",
+        &["type"]
+    );
     pub static ref BEACON_PROCESSOR_WORKERS_SPAWNED_TOTAL: Result<IntCounter> = try_create_int_counter(
         "beacon_processor_workers_spawned_total",
         "The number of workers ever spawned by the gossip processing pool."
@@ -198,6 +203,14 @@ lazy_static! {
         "beacon_processor_idle_events_total",
         "Count of idle events processed by the gossip processor manager."
     );
+    pub static ref BEACON_PROCESSOR_GOSSIP_RATE_LIMITED_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_processor_gossip_rate_limited_total",
+        "Count of gossip work events dropped because the sending peer exceeded its rate limit."
+    );
+    pub static ref BEACON_PROCESSOR_GOSSIP_RATE_LIMITED_PEERS_TOTAL: Result<IntGauge> = try_create_int_gauge(
+        "beacon_processor_gossip_rate_limited_peers_total",
+        "Count of peers currently tracked by the gossip processor rate limiter."
+    );
     pub static ref BEACON_PROCESSOR_EVENT_HANDLING_SECONDS: Result<Histogram> = try_create_histogram(
         "beacon_processor_event_handling_seconds",
         "Time spent handling a new message and allocating it to a queue or worker."
@@ -301,6 +314,19 @@ lazy_static! {
         "beacon_processor_unaggregated_attestation_imported_total",
         "Total number of unaggregated attestations imported to fork choice, etc."
     );
+    // Unaggregated attestations for unknown blocks.
+    pub static ref BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_QUEUE_TOTAL: Result<IntGauge> = try_create_int_gauge(
+        "beacon_processor_unknown_block_attestation_queue_total",
+        "Count of unagg. attestations for previously unknown blocks waiting to be re-processed."
+    );
+    pub static ref BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_QUEUED_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_processor_unknown_block_attestation_queued_total",
+        "Total number of unagg. attestations sent to the reprocess queue due to an unknown block."
+    );
+    pub static ref BEACON_PROCESSOR_UNKNOWN_BLOCK_ATTESTATION_EXPIRED_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_processor_unknown_block_attestation_expired_total",
+        "Total number of unagg. attestations dropped from the reprocess queue before their block was imported."
+    );
     // Aggregated attestations.
     pub static ref BEACON_PROCESSOR_AGGREGATED_ATTESTATION_QUEUE_TOTAL: Result<IntGauge> = try_create_int_gauge(
         "beacon_processor_aggregated_attestation_queue_total",
@@ -314,6 +340,10 @@ lazy_static! {
         "beacon_processor_aggregated_attestation_imported_total",
         "Total number of aggregated attestations imported to fork choice, etc."
     );
+    pub static ref BEACON_PROCESSOR_AGGREGATED_ATTESTATION_REQUEUED_TOTAL: Result<IntCounter> = try_create_int_counter(
+        "beacon_processor_aggregated_attestation_requeued_total",
+        "Total number of aggregated attestations that arrived early and were re-queued for later processing."
+    );
 }
 
 lazy_static! {
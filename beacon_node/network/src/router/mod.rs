@@ -7,6 +7,7 @@
 
 mod processor;
 
+use crate::beacon_processor::BeaconProcessorConfig;
 use crate::error;
 use crate::service::NetworkMessage;
 use beacon_chain::{BeaconChain, BeaconChainTypes};
@@ -75,6 +76,7 @@ impl<T: BeaconChainTypes> Router<T> {
         network_globals: Arc<NetworkGlobals<T::EthSpec>>,
         network_send: mpsc::UnboundedSender<NetworkMessage<T::EthSpec>>,
         executor: task_executor::TaskExecutor,
+        beacon_processor_config: BeaconProcessorConfig,
         log: slog::Logger,
     ) -> error::Result<mpsc::UnboundedSender<RouterMessage<T::EthSpec>>> {
         let message_handler_log = log.new(o!("service"=> "router"));
@@ -88,6 +90,7 @@ impl<T: BeaconChainTypes> Router<T> {
             beacon_chain,
             network_globals.clone(),
             network_send,
+            beacon_processor_config,
             &log,
         );
 
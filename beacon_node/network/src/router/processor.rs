@@ -1,5 +1,5 @@
 use crate::beacon_processor::{
-    BeaconProcessor, WorkEvent as BeaconWorkEvent, MAX_WORK_EVENT_QUEUE_LEN,
+    BeaconProcessor, BeaconProcessorConfig, WorkEvent as BeaconWorkEvent,
 };
 use crate::service::NetworkMessage;
 use crate::sync::SyncMessage;
@@ -38,11 +38,12 @@ impl<T: BeaconChainTypes> Processor<T> {
         beacon_chain: Arc<BeaconChain<T>>,
         network_globals: Arc<NetworkGlobals<T::EthSpec>>,
         network_send: mpsc::UnboundedSender<NetworkMessage<T::EthSpec>>,
+        beacon_processor_config: BeaconProcessorConfig,
         log: &slog::Logger,
     ) -> Self {
         let sync_logger = log.new(o!("service"=> "sync"));
         let (beacon_processor_send, beacon_processor_receive) =
-            mpsc::channel(MAX_WORK_EVENT_QUEUE_LEN);
+            mpsc::channel(beacon_processor_config.max_work_event_queue_len);
 
         // spawn the sync thread
         let sync_send = crate::sync::manager::spawn(
@@ -62,6 +63,7 @@ impl<T: BeaconChainTypes> Processor<T> {
             executor,
             max_workers: cmp::max(1, num_cpus::get()),
             current_workers: 0,
+            config: beacon_processor_config,
             log: log.clone(),
         }
         .spawn_manager(beacon_processor_receive, None);
@@ -1,3 +1,4 @@
+use crate::beacon_processor::BeaconProcessorConfig;
 use crate::persisted_dht::{load_dht, persist_dht};
 use crate::router::{Router, RouterMessage};
 use crate::{
@@ -19,7 +20,7 @@ use store::HotColdDB;
 use task_executor::ShutdownReason;
 use tokio::sync::mpsc;
 use tokio::time::Sleep;
-use types::{EthSpec, RelativeEpoch, SubnetId, Unsigned, ValidatorSubscription};
+use types::{EthSpec, RelativeEpoch, ValidatorSubscription};
 
 mod tests;
 
@@ -190,6 +191,7 @@ impl<T: BeaconChainTypes> NetworkService<T> {
             network_globals.clone(),
             network_send.clone(),
             executor.clone(),
+            BeaconProcessorConfig::from(config),
             network_log.clone(),
         )?;
 
@@ -359,7 +361,22 @@ fn spawn_service<T: BeaconChainTypes>(
                                     "topics" => ?topic_kinds
                                 );
                                 metrics::expose_publish_metrics(&messages);
-                                service.libp2p.swarm.publish(messages);
+                                let unsent_topics = service
+                                    .libp2p
+                                    .swarm
+                                    .publish(messages)
+                                    .into_iter()
+                                    .filter(|outcome| outcome.has_no_recipients())
+                                    .map(|outcome| outcome.topic.kind().clone())
+                                    .collect::<Vec<_>>();
+                                if !unsent_topics.is_empty() {
+                                    debug!(
+                                        service.log,
+                                        "Published messages had no recipients";
+                                        "count" => unsent_topics.len(),
+                                        "topics" => ?unsent_topics
+                                    );
+                                }
                         }
                         NetworkMessage::ReportPeer { peer_id, action, source } => service.libp2p.report_peer(&peer_id, action, source),
                         NetworkMessage::GoodbyePeer { peer_id, reason, source } => service.libp2p.goodbye_peer(&peer_id, reason, source),
@@ -382,24 +399,17 @@ fn spawn_service<T: BeaconChainTypes>(
                                 }
                             }
 
+                            if !subscribed_topics.is_empty() {
+                                info!(service.log, "Subscribed to topics"; "topics" => ?subscribed_topics);
+                            }
+
                             // if we are to subscribe to all subnets we do it here
                             if service.subscribe_all_subnets {
-                                for subnet_id in 0..<<T as BeaconChainTypes>::EthSpec as EthSpec>::SubnetBitfieldLength::to_u64() {
-                                    let subnet_id = SubnetId::new(subnet_id);
-                                    let topic_kind = eth2_libp2p::types::GossipKind::Attestation(subnet_id);
-                                if service.libp2p.swarm.subscribe_kind(topic_kind.clone()) {
-                                    // Update the ENR bitfield.
-                                    service.libp2p.swarm.update_enr_subnet(subnet_id, true);
-                                    subscribed_topics.push(topic_kind.clone());
-                                } else {
-                                    warn!(service.log, "Could not subscribe to topic"; "topic" => %topic_kind);
-                                }
+                                let subnet_count = service.libp2p.swarm.subscribe_to_all_subnets();
+                                if subnet_count > 0 {
+                                    info!(service.log, "Subscribed to all subnets"; "new_subscriptions" => subnet_count);
                                 }
                             }
-
-                            if !subscribed_topics.is_empty() {
-                                info!(service.log, "Subscribed to topics"; "topics" => ?subscribed_topics);
-                            }
                         }
                     }
                 }
@@ -464,7 +474,7 @@ fn spawn_service<T: BeaconChainTypes>(
                                     });
 
                             }
-                            BehaviourEvent::RPCFailed{id, peer_id} => {
+                            BehaviourEvent::RPCFailed{id, peer_id, ..} => {
                                 let _ = service
                                     .router_send
                                     .send(RouterMessage::RPCFailed{ peer_id, request_id: id})
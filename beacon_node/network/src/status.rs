@@ -1,5 +1,5 @@
 use beacon_chain::{BeaconChain, BeaconChainError, BeaconChainTypes};
-use types::ChainSpec;
+use types::{ChainSpec, Hash256};
 
 use eth2_libp2p::rpc::StatusMessage;
 /// Trait to produce a `StatusMessage` representing the state of the given `beacon_chain`.
@@ -8,6 +8,21 @@ use eth2_libp2p::rpc::StatusMessage;
 /// polluting/coupling the type with RPC concepts.
 pub trait ToStatusMessage {
     fn status_message(&self) -> Result<StatusMessage, BeaconChainError>;
+
+    /// Produce a `StatusMessage` for the current head, noting whether the head has re-orged away
+    /// from `previous_head_root`.
+    fn head_status(&self, previous_head_root: Hash256) -> Result<HeadStatus, BeaconChainError>;
+}
+
+/// A `StatusMessage` for a new head, along with its relationship to the previous head.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadStatus {
+    pub status: StatusMessage,
+    /// `true` if the new head does not descend from the previous head.
+    ///
+    /// When this is `false` the head has simply advanced along the same chain, so peers which
+    /// already know our previous status do not need to re-sync from our finalized checkpoint.
+    pub is_reorg: bool,
 }
 
 impl<T: BeaconChainTypes> ToStatusMessage for BeaconChain<T> {
@@ -26,4 +41,88 @@ impl<T: BeaconChainTypes> ToStatusMessage for BeaconChain<T> {
             head_slot: head_info.slot,
         })
     }
+
+    fn head_status(&self, previous_head_root: Hash256) -> Result<HeadStatus, BeaconChainError> {
+        let status = self.status_message()?;
+        // A previous head which has been pruned from fork choice (i.e., it conflicts with
+        // finalization) is always considered a re-org.
+        let is_reorg = !self
+            .fork_choice
+            .read()
+            .is_descendant(previous_head_root, status.head_root);
+
+        Ok(HeadStatus { status, is_reorg })
+    }
+}
+
+#[cfg(not(debug_assertions))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use beacon_chain::test_utils::{AttestationStrategy, BeaconChainHarness, BlockStrategy};
+    use types::{test_utils::generate_deterministic_keypairs, EthSpec, MinimalEthSpec};
+
+    const VALIDATOR_COUNT: usize = 24;
+
+    #[test]
+    fn head_status_across_forks() {
+        let harness = BeaconChainHarness::new(
+            MinimalEthSpec,
+            generate_deterministic_keypairs(VALIDATOR_COUNT),
+        );
+        harness.advance_slot();
+
+        let two_thirds = (VALIDATOR_COUNT / 3) * 2;
+        let delay = MinimalEthSpec::default_spec().min_attestation_inclusion_delay as usize;
+        let honest_validators: Vec<usize> = (0..two_thirds).collect();
+        let faulty_validators: Vec<usize> = (two_thirds..VALIDATOR_COUNT).collect();
+
+        let common_head = harness.extend_chain(
+            delay + 1,
+            BlockStrategy::OnCanonicalHead,
+            AttestationStrategy::AllValidators,
+        );
+
+        let (honest_head, faulty_head) = harness.generate_two_forks_by_skipping_a_block(
+            &honest_validators,
+            &faulty_validators,
+            delay + 1,
+            delay + 2,
+        );
+
+        let chain = &harness.chain;
+        let head_info = chain.head_info().unwrap();
+        assert_eq!(head_info.block_root, honest_head);
+
+        // Advancing from the common ancestor is not a re-org.
+        let from_common = chain.head_status(common_head).unwrap();
+        assert!(!from_common.is_reorg);
+        assert_eq!(from_common.status, chain.status_message().unwrap());
+        assert_eq!(from_common.status.head_root, honest_head);
+        assert_eq!(from_common.status.head_slot, head_info.slot);
+        assert_eq!(
+            from_common.status.finalized_root,
+            head_info.finalized_checkpoint.root
+        );
+        assert_eq!(
+            from_common.status.finalized_epoch,
+            head_info.finalized_checkpoint.epoch
+        );
+
+        // An unchanged head is not a re-org.
+        assert!(!chain.head_status(honest_head).unwrap().is_reorg);
+
+        // Switching from the other branch is a re-org, but reports the same status.
+        let from_faulty = chain.head_status(faulty_head).unwrap();
+        assert!(from_faulty.is_reorg);
+        assert_eq!(from_faulty.status, from_common.status);
+
+        // An unknown previous head is treated as a re-org.
+        assert!(
+            chain
+                .head_status(Hash256::repeat_byte(42))
+                .unwrap()
+                .is_reorg
+        );
+    }
 }
@@ -61,6 +61,14 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 .default_value("0.0.0.0")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("listen-address-v6")
+                .long("listen-address-v6")
+                .value_name("ADDRESS")
+                .help("An additional IPv6 address lighthouse will listen on for TCP connections, \
+                allowing the node to accept connections over both IPv4 and IPv6.")
+                .takes_value(true)
+        )
         .arg(
             Arg::with_name("port")
                 .long("port")
@@ -130,6 +138,14 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 .requires("enr-udp-port")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("enr-address-v6")
+                .long("enr-address-v6")
+                .value_name("ADDRESS")
+                .help("An IPv6 address to broadcast to other peers in addition to the enr-address. \
+                Set this only if you are sure other nodes can connect to your local node on this address.")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("enr-match")
                 .short("e")
@@ -165,6 +181,45 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 .help("One or more comma-delimited trusted peer ids which always have the highest score according to the peer scoring system.")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("allow-private-addresses")
+                .long("allow-private-addresses")
+                .help("Accept private and loopback listen addresses identified by peers. \
+                       Useful for local testnets where peers are not publicly reachable.")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("beacon-processor-attestation-queue-len")
+                .long("beacon-processor-attestation-queue-len")
+                .value_name("LENGTH")
+                .help("The maximum number of unaggregated gossip attestations which may be queued \
+                    for processing. Nodes subscribed to many subnets may wish to increase this.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("beacon-processor-aggregate-queue-len")
+                .long("beacon-processor-aggregate-queue-len")
+                .value_name("LENGTH")
+                .help("The maximum number of aggregated gossip attestations which may be queued \
+                    for processing.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("beacon-processor-attestations-per-peer")
+                .long("beacon-processor-attestations-per-peer")
+                .value_name("COUNT")
+                .help("The maximum number of gossip attestations and aggregates which will be \
+                    processed from a single peer within each rate limit period. Further messages \
+                    from that peer are ignored and the peer is penalized.")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("beacon-processor-rate-limit-period")
+                .long("beacon-processor-rate-limit-period")
+                .value_name("SECONDS")
+                .help("The period over which --beacon-processor-attestations-per-peer applies.")
+                .takes_value(true),
+        )
         /* REST API related arguments */
         .arg(
             Arg::with_name("http")
@@ -376,6 +431,28 @@ pub fn cli_app<'a, 'b>() -> App<'a, 'b> {
                 .value_name("NUM_SLOTS")
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("maximum-gossip-clock-disparity")
+                .long("maximum-gossip-clock-disparity")
+                .help(
+                    "The accepted clock drift (in milliseconds) for blocks and attestations \
+                    received on gossip. Increasing this value may help nodes with poor clock \
+                    synchronization, at the cost of accepting messages from further in the future."
+                )
+                .value_name("MILLISECONDS")
+                .takes_value(true)
+        )
+        .arg(
+            Arg::with_name("fork-choice-prune-retain-slots")
+                .long("fork-choice-prune-retain-slots")
+                .help(
+                    "Retain blocks in fork choice which are within this many slots of the \
+                    finalized block when pruning, rather than pruning all blocks prior to it. \
+                    Useful for debugging and slashing detection."
+                )
+                .value_name("NUM_SLOTS")
+                .takes_value(true)
+        )
         /*
          * Slasher.
          */
@@ -13,6 +13,7 @@ use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs};
 use std::net::{TcpListener, UdpSocket};
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 use types::{ChainSpec, Checkpoint, Epoch, EthSpec, Hash256, PublicKeyBytes, GRAFFITI_BYTES_LEN};
 
 /// Gets the fully-initialized global client.
@@ -363,6 +364,17 @@ pub fn get_config<E: EthSpec>(
         };
     }
 
+    if let Some(disparity) = clap_utils::parse_optional(cli_args, "maximum-gossip-clock-disparity")?
+    {
+        client_config.chain.maximum_gossip_clock_disparity_millis = disparity;
+    }
+
+    if let Some(retain_slots) =
+        clap_utils::parse_optional(cli_args, "fork-choice-prune-retain-slots")?
+    {
+        client_config.chain.fork_choice_prune_retain_slots = retain_slots;
+    }
+
     if cli_args.is_present("slasher") {
         let slasher_dir = if let Some(slasher_dir) = cli_args.value_of("slasher-dir") {
             PathBuf::from(slasher_dir)
@@ -463,6 +475,10 @@ pub fn set_network_config(
         config.import_all_attestations = true;
     }
 
+    if cli_args.is_present("allow-private-addresses") {
+        config.allow_private_addresses = true;
+    }
+
     if let Some(listen_address_str) = cli_args.value_of("listen-address") {
         let listen_address = listen_address_str
             .parse()
@@ -470,6 +486,13 @@ pub fn set_network_config(
         config.listen_address = listen_address;
     }
 
+    if let Some(listen_address_str) = cli_args.value_of("listen-address-v6") {
+        let listen_address = listen_address_str
+            .parse()
+            .map_err(|_| format!("Invalid IPv6 listen address: {:?}", listen_address_str))?;
+        config.listen_address_v6 = Some(listen_address);
+    }
+
     if let Some(target_peers_str) = cli_args.value_of("target-peers") {
         config.target_peers = target_peers_str
             .parse::<usize>()
@@ -538,6 +561,30 @@ pub fn set_network_config(
             .collect::<Result<Vec<PeerIdSerialized>, _>>()?;
     }
 
+    if let Some(queue_len) =
+        clap_utils::parse_optional(cli_args, "beacon-processor-attestation-queue-len")?
+    {
+        config.beacon_processor_attestation_queue_len = Some(queue_len);
+    }
+
+    if let Some(queue_len) =
+        clap_utils::parse_optional(cli_args, "beacon-processor-aggregate-queue-len")?
+    {
+        config.beacon_processor_aggregate_queue_len = Some(queue_len);
+    }
+
+    if let Some(count) =
+        clap_utils::parse_optional(cli_args, "beacon-processor-attestations-per-peer")?
+    {
+        config.beacon_processor_attestations_per_peer = Some(count);
+    }
+
+    if let Some(period) =
+        clap_utils::parse_optional(cli_args, "beacon-processor-rate-limit-period")?
+    {
+        config.beacon_processor_rate_limit_period = Some(Duration::from_secs(period));
+    }
+
     if let Some(enr_udp_port_str) = cli_args.value_of("enr-udp-port") {
         config.enr_udp_port = Some(
             enr_udp_port_str
@@ -604,6 +651,13 @@ pub fn set_network_config(
         config.enr_address = Some(resolved_addr);
     }
 
+    if let Some(enr_address_str) = cli_args.value_of("enr-address-v6") {
+        let enr_address = enr_address_str
+            .parse()
+            .map_err(|_| format!("Invalid IPv6 enr address: {:?}", enr_address_str))?;
+        config.enr_address_v6 = Some(enr_address);
+    }
+
     if cli_args.is_present("disable-enr-auto-update") {
         config.discv5_config.enr_update = false;
     }
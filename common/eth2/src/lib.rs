@@ -21,7 +21,7 @@ use reqwest::{IntoUrl, Response};
 pub use reqwest::{StatusCode, Url};
 use sensitive_url::SensitiveUrl;
 use serde::{de::DeserializeOwned, Serialize};
-use ssz::Decode;
+use ssz::{Decode, Encode};
 use std::convert::TryFrom;
 use std::fmt;
 use std::iter::Iterator;
@@ -321,6 +321,47 @@ impl BeaconNodeHttpClient {
         ids: Option<&[ValidatorId]>,
         statuses: Option<&[ValidatorStatus]>,
     ) -> Result<Option<GenericResponse<Vec<ValidatorData>>>, Error> {
+        let path = self.beacon_states_validators_path(state_id, ids, statuses)?;
+
+        self.get_opt(path).await
+    }
+
+    /// `GET beacon/states/{state_id}/validators?id,status,offset,limit`
+    ///
+    /// The `offset` and `limit` parameters and the `total` in the response are Lighthouse
+    /// extensions to the standard API.
+    ///
+    /// Returns `Ok(None)` on a 404 error.
+    pub async fn get_beacon_states_validators_paginated(
+        &self,
+        state_id: StateId,
+        ids: Option<&[ValidatorId]>,
+        statuses: Option<&[ValidatorStatus]>,
+        offset: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Option<PaginatedResponse<Vec<ValidatorData>>>, Error> {
+        let mut path = self.beacon_states_validators_path(state_id, ids, statuses)?;
+
+        if let Some(offset) = offset {
+            path.query_pairs_mut()
+                .append_pair("offset", &offset.to_string());
+        }
+
+        if let Some(limit) = limit {
+            path.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
+
+        self.get_opt(path).await
+    }
+
+    /// Returns the path for `GET beacon/states/{state_id}/validators?id,status`.
+    fn beacon_states_validators_path(
+        &self,
+        state_id: StateId,
+        ids: Option<&[ValidatorId]>,
+        statuses: Option<&[ValidatorStatus]>,
+    ) -> Result<Url, Error> {
         let mut path = self.eth_path()?;
 
         path.path_segments_mut()
@@ -348,7 +389,7 @@ impl BeaconNodeHttpClient {
             path.query_pairs_mut().append_pair("status", &status_string);
         }
 
-        self.get_opt(path).await
+        Ok(path)
     }
 
     /// `GET beacon/states/{state_id}/committees?slot,index,epoch`
@@ -474,6 +515,31 @@ impl BeaconNodeHttpClient {
         Ok(())
     }
 
+    /// `POST beacon/blocks`, with the block encoded as SSZ.
+    pub async fn post_beacon_blocks_ssz<T: EthSpec>(
+        &self,
+        block: &SignedBeaconBlock<T>,
+    ) -> Result<(), Error> {
+        let mut path = self.eth_path()?;
+
+        path.path_segments_mut()
+            .map_err(|()| Error::InvalidUrl(self.server.clone()))?
+            .push("beacon")
+            .push("blocks");
+
+        let response = self
+            .client
+            .post(path)
+            .header(CONTENT_TYPE, ContentType::Ssz.to_string())
+            .body(block.as_ssz_bytes())
+            .send()
+            .await
+            .map_err(Error::Reqwest)?;
+        ok_or_error(response).await?;
+
+        Ok(())
+    }
+
     /// `GET beacon/blocks`
     ///
     /// Returns `Ok(None)` on a 404 error.
@@ -2,11 +2,17 @@
 //! required for the HTTP API.
 
 use crate::Error as ServerError;
-use eth2_libp2p::{ConnectionDirection, Enr, Multiaddr, PeerConnectionStatus};
-pub use reqwest::header::ACCEPT;
-use serde::{Deserialize, Serialize};
+use eth2_libp2p::{
+    multiaddr::Protocol, ConnectionDirection, Enr, EnrExt, Multiaddr, PeerConnectionStatus, PeerId,
+    PeerInfo,
+};
+pub use reqwest::header::{ACCEPT, CONTENT_TYPE};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use ssz::Decode;
+use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::fmt;
+use std::hash::Hash;
 use std::str::{from_utf8, FromStr};
 pub use types::*;
 
@@ -27,6 +33,37 @@ pub struct IndexedErrorMessage {
     pub failures: Vec<Failure>,
 }
 
+impl IndexedErrorMessage {
+    /// Returns the indices of the submitted items which failed, in the order reported by the
+    /// server.
+    pub fn failed_indices(&self) -> Vec<u64> {
+        self.failures.iter().map(|failure| failure.index).collect()
+    }
+
+    /// Splits `submitted` into the items which succeeded and the items which failed, along with
+    /// the message the server gave for each failure.
+    ///
+    /// If the server reports several failures for the same index, the first message is used.
+    /// Failures with indices outside of `submitted` are ignored.
+    pub fn partition<'a, T>(&'a self, submitted: &'a [T]) -> (Vec<&'a T>, Vec<(&'a T, &'a str)>) {
+        let mut succeeded = vec![];
+        let mut failed = vec![];
+
+        for (i, item) in submitted.iter().enumerate() {
+            match self
+                .failures
+                .iter()
+                .find(|failure| failure.index == i as u64)
+            {
+                Some(failure) => failed.push((item, failure.message.as_str())),
+                None => succeeded.push(item),
+            }
+        }
+
+        (succeeded, failed)
+    }
+}
+
 /// A single failure in an index of API errors, serializable to JSON.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Failure {
@@ -59,6 +96,8 @@ pub enum BlockId {
     Finalized,
     Justified,
     Slot(Slot),
+    /// The start slot of the given epoch, formatted as `epoch:<N>`.
+    Epoch(Epoch),
     Root(Hash256),
 }
 
@@ -76,6 +115,11 @@ impl FromStr for BlockId {
                     Hash256::from_str(&s[2..])
                         .map(BlockId::Root)
                         .map_err(|e| format!("{} cannot be parsed as a root", e))
+                } else if let Some(epoch) = other.strip_prefix("epoch:") {
+                    u64::from_str(epoch)
+                        .map(Epoch::new)
+                        .map(BlockId::Epoch)
+                        .map_err(|_| format!("{} cannot be parsed as an epoch", s))
                 } else {
                     u64::from_str(s)
                         .map(Slot::new)
@@ -95,6 +139,7 @@ impl fmt::Display for BlockId {
             BlockId::Finalized => write!(f, "finalized"),
             BlockId::Justified => write!(f, "justified"),
             BlockId::Slot(slot) => write!(f, "{}", slot),
+            BlockId::Epoch(epoch) => write!(f, "epoch:{}", epoch),
             BlockId::Root(root) => write!(f, "{:?}", root),
         }
     }
@@ -107,6 +152,8 @@ pub enum StateId {
     Finalized,
     Justified,
     Slot(Slot),
+    /// The start slot of the given epoch, formatted as `epoch:<N>`.
+    Epoch(Epoch),
     Root(Hash256),
 }
 
@@ -124,6 +171,11 @@ impl FromStr for StateId {
                     Hash256::from_str(&s[2..])
                         .map(StateId::Root)
                         .map_err(|e| format!("{} cannot be parsed as a root", e))
+                } else if let Some(epoch) = other.strip_prefix("epoch:") {
+                    u64::from_str(epoch)
+                        .map(Epoch::new)
+                        .map(StateId::Epoch)
+                        .map_err(|_| format!("{} cannot be parsed as an epoch", s))
                 } else {
                     u64::from_str(s)
                         .map(Slot::new)
@@ -143,6 +195,7 @@ impl fmt::Display for StateId {
             StateId::Finalized => write!(f, "finalized"),
             StateId::Justified => write!(f, "justified"),
             StateId::Slot(slot) => write!(f, "{}", slot),
+            StateId::Epoch(epoch) => write!(f, "epoch:{}", epoch),
             StateId::Root(root) => write!(f, "{:?}", root),
         }
     }
@@ -161,6 +214,22 @@ pub struct GenericResponse<T: Serialize + serde::de::DeserializeOwned> {
     pub data: T,
 }
 
+/// A response containing a single page of `data`, along with the `total` number of items across
+/// all pages.
+///
+/// This is a Lighthouse extension to the standard `GenericResponse`. Clients which are unaware of
+/// it will ignore the `total` field.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Serialize + serde::de::DeserializeOwned")]
+pub struct PaginatedResponse<T: Serialize + serde::de::DeserializeOwned> {
+    pub data: T,
+    /// The number of items matching the query, prior to applying `offset` and `limit`.
+    ///
+    /// Not part of the standard API; specific to Lighthouse.
+    #[serde(with = "serde_utils::quoted_u64")]
+    pub total: u64,
+}
+
 impl<T: Serialize + serde::de::DeserializeOwned> From<T> for GenericResponse<T> {
     fn from(data: T) -> Self {
         Self { data }
@@ -197,7 +266,7 @@ pub struct FinalityCheckpointsData {
     pub finalized: Checkpoint,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum ValidatorId {
     PublicKey(PublicKeyBytes),
     Index(u64),
@@ -254,7 +323,7 @@ pub struct ValidatorBalanceData {
 // this proposal:
 //
 // https://hackmd.io/bQxMDRt1RbS1TLno8K4NPg?view
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ValidatorStatus {
     PendingInitialized,
@@ -326,6 +395,14 @@ impl ValidatorStatus {
             | ValidatorStatus::Withdrawal => *self,
         }
     }
+
+    /// Returns `true` if `self` should be returned for a query on the `queried` status.
+    ///
+    /// A queried superstatus (e.g. `active`) matches all of its sub-statuses (e.g.
+    /// `active_ongoing`), whilst a queried sub-status only matches itself.
+    pub fn matches_query(&self, queried: ValidatorStatus) -> bool {
+        *self == queried || self.superstatus() == queried
+    }
 }
 
 impl FromStr for ValidatorStatus {
@@ -378,16 +455,62 @@ pub struct CommitteesQuery {
     pub epoch: Option<Epoch>,
 }
 
+/// Filters the attestations in the pool.
+///
+/// `slot` selects a single slot, whilst `min_slot` and `max_slot` select an inclusive range of
+/// slots. `slot` cannot be combined with `min_slot` or `max_slot`.
 #[derive(Serialize, Deserialize)]
 pub struct AttestationPoolQuery {
     pub slot: Option<Slot>,
     pub committee_index: Option<u64>,
+    pub min_slot: Option<Slot>,
+    pub max_slot: Option<Slot>,
+}
+
+impl AttestationPoolQuery {
+    /// Returns an error if the query contains conflicting fields.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.slot.is_some() && (self.min_slot.is_some() || self.max_slot.is_some()) {
+            Err("slot cannot be combined with min_slot or max_slot".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns `true` if an attestation with the given `slot` and `committee_index` matches the
+    /// query.
+    pub fn matches(&self, slot: Slot, committee_index: u64) -> bool {
+        self.slot.map_or(true, |query_slot| query_slot == slot)
+            && self.min_slot.map_or(true, |min_slot| slot >= min_slot)
+            && self.max_slot.map_or(true, |max_slot| slot <= max_slot)
+            && self
+                .committee_index
+                .map_or(true, |index| index == committee_index)
+    }
 }
 
 #[derive(Deserialize)]
 pub struct ValidatorsQuery {
-    pub id: Option<QueryVec<ValidatorId>>,
-    pub status: Option<QueryVec<ValidatorStatus>>,
+    pub id: Option<QueryVecUnique<ValidatorId>>,
+    pub status: Option<QueryVecUnique<ValidatorStatus>>,
+    /// The number of matching validators to skip. Not part of the standard API.
+    pub offset: Option<u64>,
+    /// The maximum number of matching validators to return. Not part of the standard API.
+    pub limit: Option<u64>,
+}
+
+impl ValidatorsQuery {
+    /// Returns the page of `items` selected by `self.offset` and `self.limit`.
+    ///
+    /// An `offset` at or beyond the end of `items` results in an empty page.
+    pub fn paginate<T>(&self, items: Vec<T>) -> Vec<T> {
+        let to_usize = |n: u64| usize::try_from(n).unwrap_or(usize::MAX);
+        items
+            .into_iter()
+            .skip(self.offset.map_or(0, to_usize))
+            .take(self.limit.map_or(usize::MAX, to_usize))
+            .collect()
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -440,6 +563,49 @@ pub struct IdentityData {
     pub metadata: MetaData,
 }
 
+impl IdentityData {
+    /// Builds the identity of the node with the given `enr`.
+    ///
+    /// The p2p (TCP) and discovery (UDP) addresses are those advertised in the ENR, with the ENR's
+    /// peer id appended. If the ENR does not advertise a TCP address, the TCP addresses in
+    /// `listen` are used instead, so that the addresses always refer to the ENR's peer id.
+    /// Unspecified (e.g., `0.0.0.0`) and loopback listen addresses are omitted, since they are not
+    /// reachable by other nodes.
+    pub fn from_enr_and_listen_addrs(enr: &Enr, listen: &[Multiaddr], metadata: MetaData) -> Self {
+        let peer_id = enr.peer_id();
+
+        let mut p2p_addresses = enr.multiaddr_p2p_tcp();
+        if p2p_addresses.is_empty() {
+            p2p_addresses = listen
+                .iter()
+                .filter(|addr| addr.iter().any(|p| matches!(p, Protocol::Tcp(_))))
+                .filter(|addr| {
+                    addr.iter().all(|p| match p {
+                        Protocol::Ip4(ip) => !ip.is_unspecified() && !ip.is_loopback(),
+                        Protocol::Ip6(ip) => !ip.is_unspecified() && !ip.is_loopback(),
+                        _ => true,
+                    })
+                })
+                .map(|addr| {
+                    let mut addr = addr.clone();
+                    if !addr.iter().any(|p| matches!(p, Protocol::P2p(_))) {
+                        addr.push(Protocol::P2p(peer_id.clone().into()));
+                    }
+                    addr
+                })
+                .collect();
+        }
+
+        IdentityData {
+            peer_id: peer_id.to_base58(),
+            enr: enr.clone(),
+            p2p_addresses,
+            discovery_addresses: enr.multiaddr_p2p_udp(),
+            metadata,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MetaData {
     #[serde(with = "serde_utils::quoted_u64")]
@@ -479,9 +645,39 @@ impl<T: FromStr> TryFrom<String> for QueryVec<T> {
     }
 }
 
+/// A `QueryVec` which removes duplicate values, preserving the order in which each value was
+/// first seen.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "String", bound = "T: FromStr + Hash + Eq")]
+pub struct QueryVecUnique<T: FromStr + Hash + Eq>(pub Vec<T>);
+
+impl<T: FromStr + Hash + Eq> TryFrom<String> for QueryVecUnique<T> {
+    type Error = String;
+
+    fn try_from(string: String) -> Result<Self, Self::Error> {
+        let values = QueryVec::<T>::try_from(string)?.0;
+
+        let is_first_occurrence = {
+            let mut seen = HashSet::with_capacity(values.len());
+            values
+                .iter()
+                .map(|value| seen.insert(value))
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Self(
+            values
+                .into_iter()
+                .zip(is_first_occurrence)
+                .filter_map(|(value, is_first)| if is_first { Some(value) } else { None })
+                .collect(),
+        ))
+    }
+}
+
 #[derive(Clone, Deserialize)]
 pub struct ValidatorBalancesQuery {
-    pub id: Option<QueryVec<ValidatorId>>,
+    pub id: Option<QueryVecUnique<ValidatorId>>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -542,10 +738,88 @@ pub struct BeaconCommitteeSubscription {
     pub is_aggregator: bool,
 }
 
+impl BeaconCommitteeSubscription {
+    /// Returns an error if the subscription is malformed or is not for a slot between the current
+    /// slot and the end of the next epoch (inclusive).
+    ///
+    /// To allow for clock disparity, the current slot may be anywhere between
+    /// `earliest_current_slot` and `latest_current_slot` (inclusive).
+    pub fn validate<E: EthSpec>(
+        &self,
+        earliest_current_slot: Slot,
+        latest_current_slot: Slot,
+        spec: &ChainSpec,
+    ) -> Result<(), String> {
+        if self.committees_at_slot == 0
+            || self.committees_at_slot > spec.max_committees_per_slot as u64
+        {
+            return Err(format!(
+                "committees_at_slot {} is not between 1 and {}",
+                self.committees_at_slot, spec.max_committees_per_slot
+            ));
+        }
+
+        if self.committee_index >= self.committees_at_slot {
+            return Err(format!(
+                "committee_index {} is not less than committees_at_slot {}",
+                self.committee_index, self.committees_at_slot
+            ));
+        }
+
+        if self.slot < earliest_current_slot {
+            return Err(format!(
+                "slot {} is prior to the current slot {}",
+                self.slot, earliest_current_slot
+            ));
+        }
+
+        let max_slot = (latest_current_slot.epoch(E::slots_per_epoch()) + 2)
+            .start_slot(E::slots_per_epoch())
+            - 1;
+        if self.slot > max_slot {
+            return Err(format!(
+                "slot {} is later than the end of the next epoch {}",
+                self.slot, max_slot
+            ));
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Deserialize)]
 pub struct PeersQuery {
     pub state: Option<QueryVec<PeerState>>,
     pub direction: Option<QueryVec<PeerDirection>>,
+    /// Selects peers whose agent version contains any of these strings, ignoring case.
+    pub agent: Option<QueryVec<String>>,
+}
+
+impl PeersQuery {
+    /// Returns `true` if `peer` matches all of the filters in the query.
+    ///
+    /// Peers without a known agent version never match an `agent` filter.
+    pub fn matches(&self, peer: &PeerData) -> bool {
+        let state_matches = self
+            .state
+            .as_ref()
+            .map_or(true, |states| states.0.contains(&peer.state));
+        let direction_matches = self
+            .direction
+            .as_ref()
+            .map_or(true, |directions| directions.0.contains(&peer.direction));
+        let agent_matches = self.agent.as_ref().map_or(true, |agents| {
+            peer.agent_version.as_ref().map_or(false, |agent_version| {
+                let agent_version = agent_version.to_lowercase();
+                agents
+                    .0
+                    .iter()
+                    .any(|agent| agent_version.contains(&agent.to_lowercase()))
+            })
+        });
+
+        state_matches && direction_matches && agent_matches
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -555,6 +829,55 @@ pub struct PeerData {
     pub last_seen_p2p_address: String,
     pub state: PeerState,
     pub direction: PeerDirection,
+    /// The agent version reported by the peer via identify, if known.
+    pub agent_version: Option<String>,
+    /// The score of the peer, which determines whether it is disconnected or banned.
+    #[serde(default)]
+    pub score: f64,
+    /// Whether the peer is banned. This is a Lighthouse extension, since the standard `state` of a
+    /// banned peer is `disconnected`.
+    #[serde(default)]
+    pub banned: bool,
+    /// Unix timestamp (in seconds) of when the current connection to the peer was established.
+    #[serde(default)]
+    pub connected_since: Option<u64>,
+    /// Unix timestamp (in seconds) of the last RPC or identify message received from the peer.
+    #[serde(default)]
+    pub last_rpc_at: Option<u64>,
+}
+
+impl PeerData {
+    /// Builds the API representation of the peer with `peer_id` and `peer_info`.
+    ///
+    /// Returns `None` if we have never been connected to the peer, since the API spec implies
+    /// only such peers should be included.
+    pub fn from_peer_info<E: EthSpec>(peer_id: &PeerId, peer_info: &PeerInfo<E>) -> Option<Self> {
+        let direction = peer_info.connection_direction.as_ref()?;
+
+        let last_seen_p2p_address =
+            if let Some(socket_addr) = peer_info.seen_addresses.iter().next() {
+                let mut addr = Multiaddr::from(socket_addr.ip());
+                addr.push(Protocol::Tcp(socket_addr.port()));
+                addr.to_string()
+            } else if let Some(addr) = peer_info.listening_addresses.first() {
+                addr.to_string()
+            } else {
+                String::new()
+            };
+
+        Some(PeerData {
+            peer_id: peer_id.to_string(),
+            enr: peer_info.enr.as_ref().map(|enr| enr.to_base64()),
+            last_seen_p2p_address,
+            state: PeerState::from_peer_connection_status(peer_info.connection_status()),
+            direction: PeerDirection::from_connection_direction(direction),
+            agent_version: peer_info.client.agent_string.clone(),
+            score: peer_info.score().score(),
+            banned: peer_info.is_banned(),
+            connected_since: peer_info.connected_since(),
+            last_rpc_at: peer_info.last_rpc_at(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -670,6 +993,10 @@ pub struct PeerCount {
 pub struct SseBlock {
     pub slot: Slot,
     pub block: Hash256,
+    #[serde(with = "serde_utils::quoted_u64")]
+    pub proposer_index: u64,
+    /// Always `false` prior to the merge, since there is no execution payload to verify.
+    pub execution_optimistic: bool,
 }
 
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
@@ -679,6 +1006,13 @@ pub struct SseFinalizedCheckpoint {
     pub epoch: Epoch,
 }
 
+/// A Lighthouse-specific event, emitted when fork choice updates its justified checkpoint.
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct SseJustifiedCheckpoint {
+    pub block: Hash256,
+    pub epoch: Epoch,
+}
+
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
 pub struct SseHead {
     pub slot: Slot,
@@ -689,14 +1023,28 @@ pub struct SseHead {
     pub epoch_transition: bool,
 }
 
+#[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
+pub struct SseChainReorg {
+    pub slot: Slot,
+    #[serde(with = "serde_utils::quoted_u64")]
+    pub depth: u64,
+    pub old_head_block: Hash256,
+    pub old_head_state: Hash256,
+    pub new_head_block: Hash256,
+    pub new_head_state: Hash256,
+    pub epoch: Epoch,
+}
+
 #[derive(PartialEq, Debug, Serialize, Clone)]
 #[serde(bound = "T: EthSpec", untagged)]
 pub enum EventKind<T: EthSpec> {
     Attestation(Attestation<T>),
     Block(SseBlock),
     FinalizedCheckpoint(SseFinalizedCheckpoint),
+    JustifiedCheckpoint(SseJustifiedCheckpoint),
     Head(SseHead),
     VoluntaryExit(SignedVoluntaryExit),
+    ChainReorg(SseChainReorg),
 }
 
 impl<T: EthSpec> EventKind<T> {
@@ -707,9 +1055,17 @@ impl<T: EthSpec> EventKind<T> {
             EventKind::Attestation(_) => "attestation",
             EventKind::VoluntaryExit(_) => "voluntary_exit",
             EventKind::FinalizedCheckpoint(_) => "finalized_checkpoint",
+            EventKind::JustifiedCheckpoint(_) => "justified_checkpoint",
+            EventKind::ChainReorg(_) => "chain_reorg",
         }
     }
 
+    /// Encodes `self` as a server-sent event, in the format expected by `Self::from_sse_bytes`.
+    pub fn to_sse_bytes(&self) -> Result<Vec<u8>, ServerError> {
+        let data = serde_json::to_string(self).map_err(ServerError::InvalidJson)?;
+        Ok(format!("event:{}\ndata:{}\n\n", self.topic_name(), data).into_bytes())
+    }
+
     pub fn from_sse_bytes(message: &[u8]) -> Result<Self, ServerError> {
         let s = from_utf8(message)
             .map_err(|e| ServerError::InvalidServerSentEvent(format!("{:?}", e)))?;
@@ -735,11 +1091,19 @@ impl<T: EthSpec> EventKind<T> {
             "block" => Ok(EventKind::Block(serde_json::from_str(data).map_err(
                 |e| ServerError::InvalidServerSentEvent(format!("Block: {:?}", e)),
             )?)),
+            "chain_reorg" => Ok(EventKind::ChainReorg(serde_json::from_str(data).map_err(
+                |e| ServerError::InvalidServerSentEvent(format!("Chain Reorg: {:?}", e)),
+            )?)),
             "finalized_checkpoint" => Ok(EventKind::FinalizedCheckpoint(
                 serde_json::from_str(data).map_err(|e| {
                     ServerError::InvalidServerSentEvent(format!("Finalized Checkpoint: {:?}", e))
                 })?,
             )),
+            "justified_checkpoint" => Ok(EventKind::JustifiedCheckpoint(
+                serde_json::from_str(data).map_err(|e| {
+                    ServerError::InvalidServerSentEvent(format!("Justified Checkpoint: {:?}", e))
+                })?,
+            )),
             "head" => Ok(EventKind::Head(serde_json::from_str(data).map_err(
                 |e| ServerError::InvalidServerSentEvent(format!("Head: {:?}", e)),
             )?)),
@@ -768,6 +1132,8 @@ pub enum EventTopic {
     Attestation,
     VoluntaryExit,
     FinalizedCheckpoint,
+    JustifiedCheckpoint,
+    ChainReorg,
 }
 
 impl FromStr for EventTopic {
@@ -780,6 +1146,8 @@ impl FromStr for EventTopic {
             "attestation" => Ok(EventTopic::Attestation),
             "voluntary_exit" => Ok(EventTopic::VoluntaryExit),
             "finalized_checkpoint" => Ok(EventTopic::FinalizedCheckpoint),
+            "justified_checkpoint" => Ok(EventTopic::JustifiedCheckpoint),
+            "chain_reorg" => Ok(EventTopic::ChainReorg),
             _ => Err("event topic cannot be parsed.".to_string()),
         }
     }
@@ -793,6 +1161,8 @@ impl fmt::Display for EventTopic {
             EventTopic::Attestation => write!(f, "attestation"),
             EventTopic::VoluntaryExit => write!(f, "voluntary_exit"),
             EventTopic::FinalizedCheckpoint => write!(f, "finalized_checkpoint"),
+            EventTopic::JustifiedCheckpoint => write!(f, "justified_checkpoint"),
+            EventTopic::ChainReorg => write!(f, "chain_reorg"),
         }
     }
 }
@@ -814,19 +1184,102 @@ impl fmt::Display for Accept {
     }
 }
 
+impl Accept {
+    /// Parses a single media type (e.g., `application/json`), returning `None` if it is not
+    /// supported.
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "application/octet-stream" => Some(Accept::Ssz),
+            "application/json" => Some(Accept::Json),
+            "application/*" | "*/*" => Some(Accept::Any),
+            _ => None,
+        }
+    }
+}
+
+/// Parses an `Accept` header, which may be a comma-separated list of media types with optional
+/// `;q=` weights (e.g., `application/json;q=0.9, application/octet-stream`).
+///
+/// The supported media type with the highest weight is returned, preferring the earliest listed
+/// type when weights are equal. Types with a weight of zero are never selected. If no supported
+/// media type is present, `Accept::Json` is returned.
 impl FromStr for Accept {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "application/octet-stream" => Ok(Accept::Ssz),
-            "application/json" => Ok(Accept::Json),
-            "*/*" => Ok(Accept::Any),
-            _ => Err("accept header cannot be parsed.".to_string()),
+        let mut best: Option<(Accept, f32)> = None;
+
+        for media_range in s.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let media_type = parts.next().unwrap_or_default();
+
+            let accept = match Accept::from_media_type(media_type) {
+                Some(accept) => accept,
+                None => continue,
+            };
+
+            // The weight defaults to 1 if it is absent or invalid.
+            let q = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse::<f32>().ok())
+                .filter(|q| (0.0..=1.0).contains(q))
+                .unwrap_or(1.0);
+
+            if q > 0.0 && best.map_or(true, |(_, best_q)| q > best_q) {
+                best = Some((accept, q));
+            }
+        }
+
+        Ok(best.map_or(Accept::Json, |(accept, _)| accept))
+    }
+}
+
+/// The media type of a request or response body.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContentType {
+    Json,
+    Ssz,
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContentType::Ssz => write!(f, "application/octet-stream"),
+            ContentType::Json => write!(f, "application/json"),
         }
     }
 }
 
+/// Parses a `Content-Type` header, ignoring any parameters (e.g., `; charset=utf-8`).
+///
+/// Unlike `Accept`, there is no fallback: any media type other than JSON or SSZ is an error.
+impl FromStr for ContentType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let media_type = s.split(';').next().unwrap_or_default().trim();
+
+        if media_type.eq_ignore_ascii_case("application/json") {
+            Ok(ContentType::Json)
+        } else if media_type.eq_ignore_ascii_case("application/octet-stream") {
+            Ok(ContentType::Ssz)
+        } else {
+            Err(format!("unsupported content type: {}", s))
+        }
+    }
+}
+
+/// Decodes a request or response `bytes` body which has the given `content_type`.
+pub fn decode_body<T: Decode + DeserializeOwned>(
+    content_type: ContentType,
+    bytes: &[u8],
+) -> Result<T, ServerError> {
+    match content_type {
+        ContentType::Ssz => T::from_ssz_bytes(bytes).map_err(ServerError::InvalidSsz),
+        ContentType::Json => serde_json::from_slice(bytes).map_err(ServerError::InvalidJson),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -838,4 +1291,718 @@ mod tests {
             QueryVec(vec![0_u64, 1, 2])
         );
     }
+
+    #[test]
+    fn query_vec_unique() {
+        assert_eq!(
+            QueryVecUnique::try_from("1,1,2".to_string()).unwrap(),
+            QueryVecUnique(vec![1_u64, 2])
+        );
+        assert_eq!(
+            QueryVecUnique::try_from("3,1,3,2,1".to_string()).unwrap(),
+            QueryVecUnique(vec![3_u64, 1, 2]),
+            "should preserve the order in which values were first seen"
+        );
+        assert_eq!(
+            QueryVecUnique::try_from("".to_string()).unwrap(),
+            QueryVecUnique(Vec::<u64>::new())
+        );
+        assert!(QueryVecUnique::<u64>::try_from("1,one".to_string()).is_err());
+
+        let query: ValidatorsQuery =
+            serde_urlencoded::from_str("id=1,1,2&status=active,active").unwrap();
+        assert_eq!(
+            query.id,
+            Some(QueryVecUnique(vec![
+                ValidatorId::Index(1),
+                ValidatorId::Index(2)
+            ]))
+        );
+        assert_eq!(
+            query.status,
+            Some(QueryVecUnique(vec![ValidatorStatus::Active]))
+        );
+    }
+
+    #[test]
+    fn attestation_pool_query() {
+        let query: AttestationPoolQuery =
+            serde_urlencoded::from_str("committee_index=1&min_slot=2&max_slot=4").unwrap();
+        assert_eq!(query.slot, None);
+        assert_eq!(query.committee_index, Some(1));
+        assert_eq!(query.min_slot, Some(Slot::new(2)));
+        assert_eq!(query.max_slot, Some(Slot::new(4)));
+        assert_eq!(query.validate(), Ok(()));
+        assert!(!query.matches(Slot::new(1), 1));
+        assert!(query.matches(Slot::new(2), 1));
+        assert!(query.matches(Slot::new(4), 1));
+        assert!(!query.matches(Slot::new(5), 1));
+        assert!(!query.matches(Slot::new(3), 0));
+
+        let query: AttestationPoolQuery = serde_urlencoded::from_str("min_slot=2").unwrap();
+        assert_eq!(query.validate(), Ok(()));
+        assert!(!query.matches(Slot::new(1), 0));
+        assert!(query.matches(Slot::new(u64::MAX), 0));
+
+        let query: AttestationPoolQuery = serde_urlencoded::from_str("slot=3").unwrap();
+        assert_eq!(query.validate(), Ok(()));
+        assert!(query.matches(Slot::new(3), 0));
+        assert!(!query.matches(Slot::new(4), 0));
+
+        let query: AttestationPoolQuery = serde_urlencoded::from_str("").unwrap();
+        assert_eq!(query.validate(), Ok(()));
+        assert!(query.matches(Slot::new(0), 0));
+
+        for conflicting in &["slot=3&min_slot=2", "slot=3&max_slot=4"] {
+            let query: AttestationPoolQuery = serde_urlencoded::from_str(conflicting).unwrap();
+            assert!(
+                query.validate().is_err(),
+                "{} should be invalid",
+                conflicting
+            );
+        }
+
+        assert!(serde_urlencoded::from_str::<AttestationPoolQuery>("min_slot=two").is_err());
+    }
+
+    #[test]
+    fn beacon_committee_subscription_validate() {
+        type E = MainnetEthSpec;
+        let spec = ChainSpec::mainnet();
+        let slots_per_epoch = E::slots_per_epoch();
+        let current_slot = Slot::new(slots_per_epoch * 10 + 5);
+        let subscription = BeaconCommitteeSubscription {
+            validator_index: 0,
+            committee_index: 3,
+            committees_at_slot: 4,
+            slot: current_slot,
+            is_aggregator: false,
+        };
+        assert_eq!(
+            subscription.validate::<E>(current_slot, current_slot, &spec),
+            Ok(())
+        );
+
+        // The last slot of the next epoch is acceptable, but not beyond it.
+        let last_slot = Slot::new(slots_per_epoch * 12 - 1);
+        let at_last_slot = BeaconCommitteeSubscription {
+            slot: last_slot,
+            ..subscription.clone()
+        };
+        assert_eq!(
+            at_last_slot.validate::<E>(current_slot, current_slot, &spec),
+            Ok(())
+        );
+        let too_late = BeaconCommitteeSubscription {
+            slot: last_slot + 1,
+            ..subscription.clone()
+        };
+        assert!(too_late
+            .validate::<E>(current_slot, current_slot, &spec)
+            .is_err());
+
+        // Subscriptions for past slots are rejected.
+        let past = BeaconCommitteeSubscription {
+            slot: current_slot - 1,
+            ..subscription.clone()
+        };
+        assert!(past
+            .validate::<E>(current_slot, current_slot, &spec)
+            .is_err());
+
+        // A subscription for the previous slot is permitted if the clock may be behind.
+        assert_eq!(
+            past.validate::<E>(current_slot - 1, current_slot, &spec),
+            Ok(())
+        );
+
+        // A subscription beyond the next epoch is permitted if the clock may be ahead.
+        let next_epoch_start = Slot::new(slots_per_epoch * 11);
+        assert_eq!(
+            too_late.validate::<E>(current_slot, next_epoch_start, &spec),
+            Ok(())
+        );
+
+        // The committee index must be less than the committee count.
+        let out_of_range = BeaconCommitteeSubscription {
+            committee_index: 4,
+            ..subscription.clone()
+        };
+        assert!(out_of_range
+            .validate::<E>(current_slot, current_slot, &spec)
+            .is_err());
+
+        // The committee count must be in range.
+        for committees_at_slot in &[0, spec.max_committees_per_slot as u64 + 1] {
+            let bad_count = BeaconCommitteeSubscription {
+                committee_index: 0,
+                committees_at_slot: *committees_at_slot,
+                ..subscription.clone()
+            };
+            assert!(bad_count
+                .validate::<E>(current_slot, current_slot, &spec)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn peers_query() {
+        let query: PeersQuery =
+            serde_urlencoded::from_str("state=connected&direction=inbound&agent=lighthouse,Prysm")
+                .unwrap();
+        assert_eq!(query.state, Some(QueryVec(vec![PeerState::Connected])));
+        assert_eq!(
+            query.direction,
+            Some(QueryVec(vec![PeerDirection::Inbound]))
+        );
+        assert_eq!(
+            query.agent,
+            Some(QueryVec(vec![
+                "lighthouse".to_string(),
+                "Prysm".to_string()
+            ]))
+        );
+
+        let query: PeersQuery = serde_urlencoded::from_str("").unwrap();
+        assert!(query.state.is_none());
+        assert!(query.direction.is_none());
+        assert!(query.agent.is_none());
+
+        let peer = |state, direction, agent_version: Option<&str>| PeerData {
+            peer_id: String::new(),
+            enr: None,
+            last_seen_p2p_address: String::new(),
+            state,
+            direction,
+            agent_version: agent_version.map(String::from),
+            score: 0.0,
+            banned: false,
+            connected_since: None,
+            last_rpc_at: None,
+        };
+        let lighthouse = peer(
+            PeerState::Connected,
+            PeerDirection::Inbound,
+            Some("Lighthouse/v1.2.0/x86_64-linux"),
+        );
+        let prysm = peer(
+            PeerState::Connected,
+            PeerDirection::Outbound,
+            Some("Prysm/v1.3.0/abcdef"),
+        );
+        let teku = peer(
+            PeerState::Disconnected,
+            PeerDirection::Inbound,
+            Some("teku/v21.3.0"),
+        );
+        let unknown = peer(PeerState::Connected, PeerDirection::Inbound, None);
+        let peers = vec![lighthouse, prysm, teku, unknown];
+
+        let filter = |query: &str| -> Vec<usize> {
+            let query: PeersQuery = serde_urlencoded::from_str(query).unwrap();
+            peers
+                .iter()
+                .enumerate()
+                .filter(|(_, peer)| query.matches(peer))
+                .map(|(i, _)| i)
+                .collect()
+        };
+
+        assert_eq!(filter(""), vec![0, 1, 2, 3]);
+        assert_eq!(filter("agent=lighthouse"), vec![0]);
+        assert_eq!(filter("agent=PRYSM,Teku"), vec![1, 2]);
+        assert_eq!(filter("agent=nimbus"), Vec::<usize>::new());
+        assert_eq!(filter("agent=lighthouse,prysm&direction=outbound"), vec![1]);
+        assert_eq!(filter("state=connected&direction=inbound"), vec![0, 3]);
+    }
+
+    #[test]
+    fn peer_data_from_banned_peer() {
+        use eth2_libp2p::PeerAction;
+
+        let peer_id = PeerId::random();
+        let mut peer_info = PeerInfo::<MainnetEthSpec>::default();
+        assert_eq!(
+            PeerData::from_peer_info(&peer_id, &peer_info),
+            None,
+            "never connected peers are excluded"
+        );
+
+        peer_info.connection_direction = Some(ConnectionDirection::Outgoing);
+        peer_info.apply_peer_action_to_score(PeerAction::Fatal);
+        peer_info.ban();
+
+        let peer = PeerData::from_peer_info(&peer_id, &peer_info).unwrap();
+        assert_eq!(peer.peer_id, peer_id.to_string());
+        assert_eq!(
+            peer.state,
+            PeerState::Disconnected,
+            "banned peers have the standard disconnected state"
+        );
+        assert!(peer.banned);
+        assert_eq!(peer.direction, PeerDirection::Outbound);
+        assert_eq!(peer.score, peer_info.score().score());
+        assert!(peer.score < 0.0);
+
+        let json = serde_json::to_value(&peer).unwrap();
+        assert_eq!(json["state"], "disconnected");
+        assert_eq!(json["banned"], true);
+        assert_eq!(json["score"], peer.score);
+
+        assert!(PeerState::from_str("banned").is_err());
+    }
+
+    #[test]
+    fn decode_body_ssz_and_json() {
+        use ssz::Encode;
+
+        let checkpoint = Checkpoint {
+            epoch: Epoch::new(7),
+            root: Hash256::from_low_u64_be(42),
+        };
+
+        let ssz_bytes = checkpoint.as_ssz_bytes();
+        assert_eq!(
+            decode_body::<Checkpoint>(ContentType::Ssz, &ssz_bytes).unwrap(),
+            checkpoint
+        );
+
+        let json_bytes = serde_json::to_vec(&checkpoint).unwrap();
+        assert_eq!(
+            decode_body::<Checkpoint>(ContentType::Json, &json_bytes).unwrap(),
+            checkpoint
+        );
+
+        assert!(matches!(
+            decode_body::<Checkpoint>(ContentType::Ssz, &json_bytes),
+            Err(ServerError::InvalidSsz(_))
+        ));
+        assert!(matches!(
+            decode_body::<Checkpoint>(ContentType::Json, &ssz_bytes),
+            Err(ServerError::InvalidJson(_))
+        ));
+    }
+
+    #[test]
+    fn content_type_header() {
+        let parse = |s: &str| ContentType::from_str(s);
+
+        assert_eq!(parse("application/json"), Ok(ContentType::Json));
+        assert_eq!(
+            parse("application/json; charset=utf-8"),
+            Ok(ContentType::Json)
+        );
+        assert_eq!(parse("application/octet-stream"), Ok(ContentType::Ssz));
+        assert_eq!(parse("Application/Octet-Stream"), Ok(ContentType::Ssz));
+
+        // Unlike `Accept`, unsupported types and wildcards are rejected rather than defaulting
+        // to JSON.
+        for s in &[
+            "text/plain",
+            "application/*",
+            "*/*",
+            "",
+            "application/json, text/plain",
+        ] {
+            assert!(parse(s).is_err(), "{} should be rejected", s);
+        }
+
+        for content_type in &[ContentType::Json, ContentType::Ssz] {
+            assert_eq!(parse(&content_type.to_string()), Ok(*content_type));
+        }
+    }
+
+    #[test]
+    fn accept_header() {
+        let parse = |s: &str| Accept::from_str(s).unwrap();
+
+        // Single types.
+        assert_eq!(parse("application/json"), Accept::Json);
+        assert_eq!(parse("application/octet-stream"), Accept::Ssz);
+        assert_eq!(parse("*/*"), Accept::Any);
+
+        // Weighted lists.
+        assert_eq!(
+            parse("application/json;q=0.9, application/octet-stream;q=1.0"),
+            Accept::Ssz
+        );
+        assert_eq!(
+            parse("application/octet-stream;q=0.5,application/json"),
+            Accept::Json
+        );
+        assert_eq!(
+            parse("application/octet-stream; q=0.8, */*; q=0.1"),
+            Accept::Ssz
+        );
+        assert_eq!(
+            parse("application/octet-stream;q=0,application/json;q=0.1"),
+            Accept::Json
+        );
+
+        // Equal weights prefer the first listed type.
+        assert_eq!(
+            parse("application/octet-stream, application/json"),
+            Accept::Ssz
+        );
+
+        // Wildcards.
+        assert_eq!(parse("text/html, */*;q=0.8"), Accept::Any);
+        assert_eq!(parse("application/*"), Accept::Any);
+
+        // Unknown types, empty headers and zero weights fall back to JSON.
+        assert_eq!(parse("text/html"), Accept::Json);
+        assert_eq!(parse(""), Accept::Json);
+        assert_eq!(parse("application/octet-stream;q=0"), Accept::Json);
+
+        // Display is unchanged and round-trips.
+        for accept in &[Accept::Json, Accept::Ssz, Accept::Any] {
+            assert_eq!(parse(&accept.to_string()), *accept);
+        }
+    }
+
+    #[test]
+    fn validators_query() {
+        let query: ValidatorsQuery =
+            serde_urlencoded::from_str("id=0,1,2&status=active&offset=1&limit=2").unwrap();
+        assert_eq!(
+            query.id,
+            Some(QueryVecUnique(vec![
+                ValidatorId::Index(0),
+                ValidatorId::Index(1),
+                ValidatorId::Index(2)
+            ]))
+        );
+        assert_eq!(
+            query.status,
+            Some(QueryVecUnique(vec![ValidatorStatus::Active]))
+        );
+        assert_eq!(query.offset, Some(1));
+        assert_eq!(query.limit, Some(2));
+
+        let query: ValidatorsQuery = serde_urlencoded::from_str("").unwrap();
+        assert_eq!(query.id, None);
+        assert_eq!(query.status, None);
+        assert_eq!(query.offset, None);
+        assert_eq!(query.limit, None);
+
+        assert!(serde_urlencoded::from_str::<ValidatorsQuery>("offset=-1").is_err());
+        assert!(serde_urlencoded::from_str::<ValidatorsQuery>("limit=many").is_err());
+    }
+
+    #[test]
+    fn validators_query_pagination() {
+        let paginate = |offset, limit| {
+            ValidatorsQuery {
+                id: None,
+                status: None,
+                offset,
+                limit,
+            }
+            .paginate((0..5).collect::<Vec<u64>>())
+        };
+
+        assert_eq!(paginate(None, None), vec![0, 1, 2, 3, 4]);
+        assert_eq!(paginate(Some(2), None), vec![2, 3, 4]);
+        assert_eq!(paginate(None, Some(2)), vec![0, 1]);
+        assert_eq!(paginate(Some(1), Some(2)), vec![1, 2]);
+        assert_eq!(paginate(Some(4), Some(2)), vec![4]);
+        assert_eq!(paginate(None, Some(0)), Vec::<u64>::new());
+        assert_eq!(paginate(Some(5), None), Vec::<u64>::new());
+        assert_eq!(paginate(Some(u64::MAX), Some(u64::MAX)), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn validator_status_round_trip() {
+        for status in &[
+            ValidatorStatus::PendingInitialized,
+            ValidatorStatus::PendingQueued,
+            ValidatorStatus::ActiveOngoing,
+            ValidatorStatus::ActiveExiting,
+            ValidatorStatus::ActiveSlashed,
+            ValidatorStatus::ExitedUnslashed,
+            ValidatorStatus::ExitedSlashed,
+            ValidatorStatus::WithdrawalPossible,
+            ValidatorStatus::WithdrawalDone,
+            ValidatorStatus::Active,
+            ValidatorStatus::Pending,
+            ValidatorStatus::Exited,
+            ValidatorStatus::Withdrawal,
+        ] {
+            assert_eq!(
+                ValidatorStatus::from_str(&format!("{}", status)),
+                Ok(*status),
+                "{} should round-trip",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn validator_status_matches_query() {
+        for status in &[
+            ValidatorStatus::ActiveOngoing,
+            ValidatorStatus::ActiveExiting,
+            ValidatorStatus::ActiveSlashed,
+        ] {
+            assert!(status.matches_query(ValidatorStatus::Active));
+            assert!(status.matches_query(*status));
+        }
+        assert!(!ValidatorStatus::PendingQueued.matches_query(ValidatorStatus::Active));
+        assert!(ValidatorStatus::PendingQueued.matches_query(ValidatorStatus::Pending));
+        assert!(!ValidatorStatus::ActiveOngoing.matches_query(ValidatorStatus::ActiveExiting));
+        // A superstatus does not match one of its sub-statuses.
+        assert!(!ValidatorStatus::Active.matches_query(ValidatorStatus::ActiveOngoing));
+    }
+
+    #[test]
+    fn identity_data_addresses_from_enr() {
+        use eth2_libp2p::discv5::enr::{CombinedKey, EnrBuilder};
+
+        let key = CombinedKey::generate_secp256k1();
+        let metadata = MetaData {
+            seq_number: 1,
+            attnets: "0x0000000000000000".to_string(),
+        };
+        let listen: Multiaddr = "/ip4/192.168.0.1/tcp/9000".parse().unwrap();
+
+        let enr: Enr = EnrBuilder::new("v4")
+            .ip("10.0.0.1".parse().unwrap())
+            .tcp(9000)
+            .udp(9001)
+            .build(&key)
+            .unwrap();
+        let peer_id = enr.peer_id();
+        let identity =
+            IdentityData::from_enr_and_listen_addrs(&enr, &[listen.clone()], metadata.clone());
+
+        assert_eq!(identity.peer_id, peer_id.to_base58());
+        assert_eq!(identity.enr, enr);
+        assert_eq!(
+            identity.p2p_addresses,
+            vec![format!("/ip4/10.0.0.1/tcp/9000/p2p/{}", peer_id)
+                .parse::<Multiaddr>()
+                .unwrap()]
+        );
+        assert_eq!(
+            identity.discovery_addresses,
+            vec![format!("/ip4/10.0.0.1/udp/9001/p2p/{}", peer_id)
+                .parse::<Multiaddr>()
+                .unwrap()]
+        );
+        assert_eq!(identity.metadata, metadata);
+
+        // Without an advertised IP the p2p addresses fall back to the listen addresses.
+        let enr: Enr = EnrBuilder::new("v4").tcp(9000).build(&key).unwrap();
+        let identity = IdentityData::from_enr_and_listen_addrs(&enr, &[listen], metadata.clone());
+        assert_eq!(
+            identity.p2p_addresses,
+            vec![format!("/ip4/192.168.0.1/tcp/9000/p2p/{}", peer_id)
+                .parse::<Multiaddr>()
+                .unwrap()]
+        );
+        assert!(identity.discovery_addresses.is_empty());
+
+        // Unspecified and loopback listen addresses are not reachable, so they are omitted.
+        let unreachable = [
+            "/ip4/0.0.0.0/tcp/9000",
+            "/ip4/127.0.0.1/tcp/9000",
+            "/ip6/::/tcp/9000",
+        ]
+        .iter()
+        .map(|addr| addr.parse::<Multiaddr>().unwrap())
+        .collect::<Vec<_>>();
+        let identity = IdentityData::from_enr_and_listen_addrs(&enr, &unreachable, metadata);
+        assert!(identity.p2p_addresses.is_empty());
+    }
+
+    #[test]
+    fn block_event_round_trip() {
+        let block = SseBlock {
+            slot: Slot::new(42),
+            block: Hash256::from_low_u64_be(1),
+            proposer_index: 7,
+            execution_optimistic: false,
+        };
+        let event = EventKind::<MainnetEthSpec>::Block(block.clone());
+
+        let json = serde_json::to_string(&event).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["slot"], "42");
+        assert_eq!(value["block"], format!("{:?}", block.block));
+        assert_eq!(value["proposer_index"], "7");
+        assert_eq!(value["execution_optimistic"], false);
+        assert_eq!(serde_json::from_str::<SseBlock>(&json).unwrap(), block);
+
+        let sse = format!("event:{}\ndata:{}", event.topic_name(), json);
+        assert_eq!(
+            EventKind::<MainnetEthSpec>::from_sse_bytes(sse.as_bytes()).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn chain_reorg_event_round_trip() {
+        let reorg = SseChainReorg {
+            slot: Slot::new(42),
+            depth: 2,
+            old_head_block: Hash256::from_low_u64_be(1),
+            old_head_state: Hash256::from_low_u64_be(2),
+            new_head_block: Hash256::from_low_u64_be(3),
+            new_head_state: Hash256::from_low_u64_be(4),
+            epoch: Epoch::new(1),
+        };
+        let event = EventKind::<MainnetEthSpec>::ChainReorg(reorg.clone());
+
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains("\"depth\":\"2\""), "depth should be quoted");
+        assert_eq!(serde_json::from_str::<SseChainReorg>(&json).unwrap(), reorg);
+
+        let sse = format!("event:{}\ndata:{}", event.topic_name(), json);
+        assert_eq!(
+            EventKind::<MainnetEthSpec>::from_sse_bytes(sse.as_bytes()).unwrap(),
+            event
+        );
+
+        let topic = EventTopic::ChainReorg;
+        assert_eq!(topic.to_string(), event.topic_name());
+        assert_eq!(EventTopic::from_str(&topic.to_string()), Ok(topic));
+    }
+
+    #[test]
+    fn sse_bytes_round_trip() {
+        type E = MainnetEthSpec;
+        let checkpoint = Checkpoint {
+            epoch: Epoch::new(1),
+            root: Hash256::from_low_u64_be(2),
+        };
+        let events = vec![
+            EventKind::<E>::Attestation(Attestation {
+                aggregation_bits: BitList::with_capacity(4).unwrap(),
+                data: AttestationData {
+                    slot: Slot::new(42),
+                    index: 1,
+                    beacon_block_root: Hash256::from_low_u64_be(3),
+                    source: checkpoint,
+                    target: checkpoint,
+                },
+                signature: AggregateSignature::empty(),
+            }),
+            EventKind::Block(SseBlock {
+                slot: Slot::new(42),
+                block: Hash256::from_low_u64_be(1),
+                proposer_index: 7,
+                execution_optimistic: false,
+            }),
+            EventKind::FinalizedCheckpoint(SseFinalizedCheckpoint {
+                block: Hash256::from_low_u64_be(1),
+                state: Hash256::from_low_u64_be(2),
+                epoch: Epoch::new(3),
+            }),
+            EventKind::JustifiedCheckpoint(SseJustifiedCheckpoint {
+                block: Hash256::from_low_u64_be(1),
+                epoch: Epoch::new(4),
+            }),
+            EventKind::Head(SseHead {
+                slot: Slot::new(42),
+                block: Hash256::from_low_u64_be(1),
+                state: Hash256::from_low_u64_be(2),
+                current_duty_dependent_root: Hash256::from_low_u64_be(3),
+                previous_duty_dependent_root: Hash256::from_low_u64_be(4),
+                epoch_transition: true,
+            }),
+            EventKind::VoluntaryExit(SignedVoluntaryExit {
+                message: VoluntaryExit {
+                    epoch: Epoch::new(1),
+                    validator_index: 2,
+                },
+                signature: Signature::empty(),
+            }),
+            EventKind::ChainReorg(SseChainReorg {
+                slot: Slot::new(42),
+                depth: 2,
+                old_head_block: Hash256::from_low_u64_be(1),
+                old_head_state: Hash256::from_low_u64_be(2),
+                new_head_block: Hash256::from_low_u64_be(3),
+                new_head_state: Hash256::from_low_u64_be(4),
+                epoch: Epoch::new(1),
+            }),
+        ];
+
+        for event in events {
+            let bytes = event.to_sse_bytes().unwrap();
+            let sse = from_utf8(&bytes).unwrap();
+            assert!(sse.starts_with(&format!("event:{}\ndata:", event.topic_name())));
+            assert!(sse.ends_with("\n\n"));
+            assert_eq!(EventKind::<E>::from_sse_bytes(&bytes).unwrap(), event);
+        }
+    }
+
+    #[test]
+    fn block_and_state_id_epoch() {
+        assert_eq!(
+            BlockId::from_str("epoch:3"),
+            Ok(BlockId::Epoch(Epoch::new(3)))
+        );
+        assert_eq!(
+            StateId::from_str("epoch:0"),
+            Ok(StateId::Epoch(Epoch::new(0)))
+        );
+        assert_eq!(BlockId::Epoch(Epoch::new(3)).to_string(), "epoch:3");
+        assert_eq!(StateId::Epoch(Epoch::new(7)).to_string(), "epoch:7");
+
+        for invalid in &["epoch:", "epoch:-1", "epoch:0x01", "epoch: 1", "epoch3"] {
+            assert!(BlockId::from_str(invalid).is_err(), "{}", invalid);
+            assert!(StateId::from_str(invalid).is_err(), "{}", invalid);
+        }
+
+        for id in &[
+            StateId::Head,
+            StateId::Slot(Slot::new(32)),
+            StateId::Epoch(Epoch::new(1)),
+            StateId::Root(Hash256::from_low_u64_be(1)),
+        ] {
+            assert_eq!(StateId::from_str(&id.to_string()), Ok(*id));
+        }
+        for id in &[
+            BlockId::Genesis,
+            BlockId::Slot(Slot::new(32)),
+            BlockId::Epoch(Epoch::new(1)),
+            BlockId::Root(Hash256::from_low_u64_be(1)),
+        ] {
+            assert_eq!(BlockId::from_str(&id.to_string()), Ok(*id));
+        }
+    }
+
+    #[test]
+    fn indexed_error_message_partition() {
+        let error = IndexedErrorMessage {
+            code: 400,
+            message: "error: some attestations failed".to_string(),
+            failures: vec![
+                Failure::new(3, "unknown head block".to_string()),
+                Failure::new(1, "invalid signature".to_string()),
+                Failure::new(1, "duplicate".to_string()),
+                Failure::new(9, "out of range".to_string()),
+            ],
+        };
+
+        assert_eq!(error.failed_indices(), vec![3, 1, 1, 9]);
+
+        let submitted = vec!["a", "b", "c", "d", "e"];
+        let (succeeded, failed) = error.partition(&submitted);
+        assert_eq!(succeeded, vec![&"a", &"c", &"e"]);
+        assert_eq!(
+            failed,
+            vec![(&"b", "invalid signature"), (&"d", "unknown head block")]
+        );
+
+        let no_failures = IndexedErrorMessage {
+            failures: vec![],
+            ..error
+        };
+        assert!(no_failures.failed_indices().is_empty());
+        let (succeeded, failed) = no_failures.partition(&submitted);
+        assert_eq!(succeeded.len(), submitted.len());
+        assert!(failed.is_empty());
+    }
 }
@@ -1,7 +1,7 @@
-use std::fs::File;
+use std::fs::{self, File, OpenOptions};
 use std::io;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 #[cfg(windows)]
 use winapi::um::winnt::{FILE_GENERIC_READ, FILE_GENERIC_WRITE, STANDARD_RIGHTS_ALL};
 
@@ -38,6 +38,12 @@ pub enum Error {
     UnableToRetrieveMetadata(io::Error),
     /// Failed to write bytes to file
     UnableToWriteFile(io::Error),
+    /// Failed to flush file contents to disk
+    UnableToSyncFile(io::Error),
+    /// Failed to read the contents of a directory
+    UnableToReadDir(io::Error),
+    /// A symlink was encountered where a regular file or directory was expected
+    UnexpectedSymlink(PathBuf),
     /// Failed to obtain file path
     UnableToObtainFilePath,
     /// Failed to convert string to SID
@@ -54,7 +60,26 @@ pub enum Error {
 
 /// Creates a file with `600 (-rw-------)` permissions.
 pub fn create_with_600_perms<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<(), Error> {
-    let path = path.as_ref();
+    create_with_perms(path.as_ref(), bytes, 0o600)
+}
+
+/// Creates a file with `640 (-rw-r-----)` permissions.
+///
+/// This is intended for non-secret files which need to be readable by other processes in the
+/// owner's group (e.g., a monitoring sidecar).
+///
+/// ## Windows
+///
+/// Windows has no clean equivalent of Unix group permissions, so on Windows this function
+/// restricts the file to its owner, exactly like `create_with_600_perms`.
+pub fn create_with_640_perms<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<(), Error> {
+    create_with_perms(path.as_ref(), bytes, 0o640)
+}
+
+/// Creates a file with the given Unix `mode`. On Windows the `mode` is ignored and the file is
+/// restricted to its owner.
+#[cfg_attr(windows, allow(unused_variables))]
+fn create_with_perms(path: &Path, bytes: &[u8], mode: u32) -> Result<(), Error> {
     let mut file = File::create(&path).map_err(Error::UnableToCreateFile)?;
 
     #[cfg(unix)]
@@ -64,7 +89,7 @@ pub fn create_with_600_perms<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<()
             .metadata()
             .map_err(Error::UnableToRetrieveMetadata)?
             .permissions();
-        perm.set_mode(0o600);
+        perm.set_mode(mode);
         file.set_permissions(perm)
             .map_err(Error::UnableToSetPermissions)?;
     }
@@ -78,6 +103,56 @@ pub fn create_with_600_perms<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<()
     Ok(())
 }
 
+/// Atomically writes `bytes` to `path` with `600 (-rw-------)` permissions.
+///
+/// The bytes are first written to a sibling temporary file which is restricted, synced to disk
+/// and then renamed over `path`. Since the rename happens within a single directory, readers
+/// will observe either the old or the new contents of `path`, never a partial write.
+///
+/// The temporary file is removed if any step fails.
+pub fn atomic_write_with_600_perms<P: AsRef<Path>>(path: P, bytes: &[u8]) -> Result<(), Error> {
+    atomic_write_with_600_perms_using(path.as_ref(), |file| file.write_all(bytes))
+}
+
+/// Implementation of `atomic_write_with_600_perms` with a configurable `write` function, allowing
+/// write failures to be simulated in tests.
+fn atomic_write_with_600_perms_using<F>(path: &Path, write: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let file_name = path.file_name().ok_or(Error::UnableToObtainFilePath)?;
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(&temp_path)
+        .map_err(Error::UnableToCreateFile)?;
+
+    let result = (|| {
+        // `OpenOptions::mode` does not apply to an existing file, so always set the permissions.
+        restrict_file_permissions(&temp_path)?;
+        write(&mut file).map_err(Error::UnableToWriteFile)?;
+        file.sync_all().map_err(Error::UnableToSyncFile)?;
+        drop(file);
+        fs::rename(&temp_path, path).map_err(Error::UnableToRenameFile)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+
+    result
+}
+
 pub fn restrict_file_permissions<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     #[cfg(unix)]
     {
@@ -142,3 +217,213 @@ pub fn restrict_file_permissions<P: AsRef<Path>>(path: P) -> Result<(), Error> {
 
     Ok(())
 }
+
+/// Returns `true` if the file at `path` is only accessible by its owner.
+///
+/// On Unix this requires the permissions to be exactly `600 (-rw-------)`. On Windows this
+/// requires the ACL to contain a single `AccessAllow` entry, belonging to the file owner.
+pub fn verify_file_permissions<P: AsRef<Path>>(path: P) -> Result<bool, Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(path.as_ref())
+            .map_err(Error::UnableToRetrieveMetadata)?
+            .permissions()
+            .mode();
+        Ok(mode & 0o777 == 0o600)
+    }
+
+    #[cfg(windows)]
+    {
+        use winapi::um::winnt::PSID;
+        use windows_acl::acl::{AceType, ACL};
+        use windows_acl::helper::sid_to_string;
+
+        let path_str = path
+            .as_ref()
+            .to_str()
+            .ok_or(Error::UnableToObtainFilePath)?;
+        let acl = ACL::from_file_path(&path_str, false).map_err(Error::UnableToRetrieveACL)?;
+        let entries = acl.all().map_err(Error::UnableToEnumerateACLEntries)?;
+
+        let mut allow_entries = entries
+            .iter()
+            .filter(|entry| entry.entry_type == AceType::AccessAllow);
+
+        let only_owner = match (allow_entries.next(), allow_entries.next()) {
+            (Some(entry), None) => entry
+                .sid
+                .as_ref()
+                .and_then(|sid| sid_to_string(sid.as_ptr() as PSID).ok())
+                .map_or(false, |sid_str| sid_str == OWNER_SID_STR),
+            _ => false,
+        };
+
+        Ok(only_owner)
+    }
+}
+
+/// Recursively restricts the permissions of `path` and everything beneath it.
+///
+/// On Unix, directories are set to `700 (drwx------)` and files to `600 (-rw-------)`. On Windows,
+/// the ACL of each entry is reduced to a single entry for the owner (see
+/// `restrict_file_permissions`).
+///
+/// Symlinks are never followed; encountering one returns `Error::UnexpectedSymlink`.
+pub fn restrict_dir_permissions<P: AsRef<Path>>(path: P) -> Result<(), Error> {
+    let path = path.as_ref();
+    let file_type = fs::symlink_metadata(path)
+        .map_err(Error::UnableToRetrieveMetadata)?
+        .file_type();
+
+    if file_type.is_symlink() {
+        return Err(Error::UnexpectedSymlink(path.to_path_buf()));
+    }
+
+    if !file_type.is_dir() {
+        return restrict_file_permissions(path);
+    }
+
+    // Lock down the directory itself before descending into it.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))
+            .map_err(Error::UnableToSetPermissions)?;
+    }
+
+    #[cfg(windows)]
+    {
+        restrict_file_permissions(path)?;
+    }
+
+    for entry in fs::read_dir(path).map_err(Error::UnableToReadDir)? {
+        let entry = entry.map_err(Error::UnableToReadDir)?;
+        restrict_dir_permissions(entry.path())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[cfg(unix)]
+    fn mode<P: AsRef<Path>>(path: P) -> u32 {
+        use std::os::unix::fs::PermissionsExt;
+        fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn atomic_write_with_600_perms_writes_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+
+        atomic_write_with_600_perms(&path, b"first").unwrap();
+        atomic_write_with_600_perms(&path, b"second").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"second");
+        assert!(!dir.path().join(".secret.json.tmp").exists());
+        #[cfg(unix)]
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn atomic_write_with_600_perms_cleans_up_on_write_failure() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+        fs::write(&path, b"original").unwrap();
+
+        let result = atomic_write_with_600_perms_using(&path, |file| {
+            file.write_all(b"partial")?;
+            Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+        });
+
+        assert!(matches!(result, Err(Error::UnableToWriteFile(_))));
+        assert!(!dir.path().join(".secret.json.tmp").exists());
+        assert_eq!(fs::read(&path).unwrap(), b"original");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn create_with_640_perms_mode() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("metrics.json");
+
+        create_with_640_perms(&path, b"public").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"public");
+        assert_eq!(mode(&path), 0o640);
+        assert!(!verify_file_permissions(&path).unwrap());
+    }
+
+    #[test]
+    fn verify_file_permissions_restricted() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+
+        create_with_600_perms(&path, b"secret").unwrap();
+
+        assert!(verify_file_permissions(&path).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn verify_file_permissions_widened() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("secret.json");
+
+        create_with_600_perms(&path, b"secret").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        assert!(!verify_file_permissions(&path).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn restrict_dir_permissions_recursive() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("keys");
+        let nested = root.join("a").join("b");
+        fs::create_dir_all(&nested).unwrap();
+
+        let files = vec![
+            root.join("root.json"),
+            root.join("a").join("a.json"),
+            nested.join("b.json"),
+        ];
+        for file in &files {
+            fs::write(file, b"secret").unwrap();
+        }
+
+        restrict_dir_permissions(&root).unwrap();
+
+        for dir in &[root.clone(), root.join("a"), nested] {
+            assert_eq!(mode(dir), 0o700, "{:?}", dir);
+        }
+        for file in &files {
+            assert_eq!(mode(file), 0o600, "{:?}", file);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn restrict_dir_permissions_rejects_symlink() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("keys");
+        fs::create_dir(&root).unwrap();
+
+        let target = dir.path().join("target.json");
+        fs::write(&target, b"secret").unwrap();
+        std::os::unix::fs::symlink(&target, root.join("link.json")).unwrap();
+
+        match restrict_dir_permissions(&root) {
+            Err(Error::UnexpectedSymlink(path)) => assert_eq!(path, root.join("link.json")),
+            other => panic!("expected UnexpectedSymlink, got {:?}", other),
+        }
+    }
+}
@@ -127,6 +127,15 @@ pub fn invalid_auth(msg: String) -> warp::reject::Rejection {
     warp::reject::custom(InvalidAuthorization(msg))
 }
 
+#[derive(Debug)]
+pub struct UnsupportedMediaType(pub String);
+
+impl Reject for UnsupportedMediaType {}
+
+pub fn unsupported_media_type(msg: String) -> warp::reject::Rejection {
+    warp::reject::custom(UnsupportedMediaType(msg))
+}
+
 #[derive(Debug)]
 pub struct IndexedBadRequestErrors {
     pub message: String,
@@ -204,6 +213,9 @@ pub async fn handle_rejection(err: warp::Rejection) -> Result<impl warp::Reply,
     } else if let Some(e) = err.find::<crate::reject::InvalidAuthorization>() {
         code = StatusCode::FORBIDDEN;
         message = format!("FORBIDDEN: Invalid auth token: {}", e.0);
+    } else if let Some(e) = err.find::<crate::reject::UnsupportedMediaType>() {
+        code = StatusCode::UNSUPPORTED_MEDIA_TYPE;
+        message = format!("UNSUPPORTED_MEDIA_TYPE: {}", e.0);
     } else if let Some(e) = err.find::<warp::reject::MissingHeader>() {
         code = StatusCode::BAD_REQUEST;
         message = format!("BAD_REQUEST: missing {} header", e.name());
@@ -1,20 +1,41 @@
 use std::marker::PhantomData;
 
-use proto_array::{Block as ProtoBlock, ProtoArrayForkChoice};
+use proto_array::{
+    compute_balance_changes, Block as ProtoBlock, ProposerBoost, ProtoArrayForkChoice,
+};
 use ssz_derive::{Decode, Encode};
 use types::{
-    AttestationShufflingId, BeaconBlock, BeaconState, BeaconStateError, Checkpoint, Epoch, EthSpec,
-    Hash256, IndexedAttestation, RelativeEpoch, Slot,
+    AttestationShufflingId, BeaconBlock, BeaconState, BeaconStateError, ChainSpec, Checkpoint,
+    Epoch, EthSpec, Hash256, IndexedAttestation, RelativeEpoch, Slot,
 };
 
 use crate::ForkChoiceStore;
 use std::cmp::Ordering;
+use std::time::Duration;
 
 /// Defined here:
 ///
 /// https://github.com/ethereum/eth2.0-specs/blob/v0.12.1/specs/phase0/fork-choice.md#configuration
 pub const SAFE_SLOTS_TO_UPDATE_JUSTIFIED: u64 = 8;
 
+/// Defined here:
+///
+/// https://github.com/ethereum/consensus-specs/blob/v1.1.0/specs/phase0/fork-choice.md#configuration
+pub const INTERVALS_PER_SLOT: u64 = 3;
+
+/// The percentage of a single slot's committee weight which is added to a timely block.
+///
+/// Defined here:
+///
+/// https://github.com/ethereum/consensus-specs/blob/v1.1.0/specs/phase0/fork-choice.md#configuration
+pub const PROPOSER_SCORE_BOOST: u64 = 70;
+
+/// The default maximum number of attestations which may be queued for processing in a later slot.
+///
+/// Attestations are only queued for the current slot, so this only needs to exceed the number of
+/// attestations we might reasonably expect to see within a single slot.
+pub const DEFAULT_MAX_QUEUED_ATTESTATIONS: usize = 65_536;
+
 #[derive(Debug)]
 pub enum Error<T> {
     InvalidAttestation(InvalidAttestation),
@@ -38,6 +59,10 @@ pub enum Error<T> {
     ForkChoiceStoreError(T),
     UnableToSetJustifiedCheckpoint(T),
     AfterBlockFailed(T),
+    InvalidAnchor {
+        block_root: Hash256,
+        finalized_checkpoint_root: Hash256,
+    },
 }
 
 impl<T> From<InvalidAttestation> for Error<T> {
@@ -93,6 +118,17 @@ pub enum InvalidAttestation {
     /// The attestation is attesting to a state that is later than itself. (Viz., attesting to the
     /// future).
     AttestsToFutureBlock { block: Slot, attestation: Slot },
+    /// The attestation source epoch does not match the justified epoch of the target block's
+    /// state.
+    InvalidFfgSource { attestation: Epoch, local: Epoch },
+    /// The attestation target is prior to the finalized checkpoint, so it can no longer influence
+    /// fork choice.
+    FinalizedTarget {
+        target_epoch: Epoch,
+        finalized_epoch: Epoch,
+    },
+    /// The attestation would be queued for processing in a later slot, however the queue is full.
+    TooManyQueuedAttestations { max_queued_attestations: usize },
 }
 
 impl<T> From<String> for Error<T> {
@@ -125,6 +161,21 @@ fn compute_start_slot_at_epoch<E: EthSpec>(epoch: Epoch) -> Slot {
     epoch.start_slot(E::slots_per_epoch())
 }
 
+/// Calculate the score which is added to a timely block by the proposer boost.
+///
+/// ## Specification
+///
+/// Equivalent to the `proposer_score` calculation in:
+///
+/// https://github.com/ethereum/consensus-specs/blob/v1.1.0/specs/phase0/fork-choice.md#get_latest_attesting_balance
+fn compute_proposer_score<E: EthSpec>(justified_balances: &[u64]) -> u64 {
+    let total_active_balance = justified_balances
+        .iter()
+        .fold(0_u64, |total, balance| total.saturating_add(*balance));
+    let committee_weight = total_active_balance / E::slots_per_epoch();
+    committee_weight.saturating_mul(PROPOSER_SCORE_BOOST) / 100
+}
+
 /// Called whenever the current time increases.
 ///
 /// ## Specification
@@ -150,6 +201,12 @@ where
     store.set_current_slot(time);
 
     let current_slot = store.get_current_slot();
+
+    // Reset the proposer boost if this is a new slot.
+    if current_slot > previous_slot {
+        store.set_proposer_boost_root(None);
+    }
+
     if !(current_slot > previous_slot && compute_slots_since_epoch_start::<E>(current_slot) == 0) {
         return Ok(());
     }
@@ -200,6 +257,46 @@ fn dequeue_attestations(
     std::mem::replace(queued_attestations, remaining)
 }
 
+/// Describes a re-org which occurred between two consecutive calls to `ForkChoice::get_head`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReorgInfo {
+    /// The head returned by the previous call to `ForkChoice::get_head`.
+    pub old_head_root: Hash256,
+    /// The slot of the most recent block shared by the old and new heads.
+    pub common_ancestor_slot: Slot,
+    /// The number of slots between the common ancestor and the old head.
+    pub depth: u64,
+}
+
+/// The result of running the fork choice rule via `ForkChoice::get_head_with_reorg_info`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForkChoiceHead {
+    /// The root of the head block.
+    pub root: Hash256,
+    /// Set if `root` does not descend from the previously returned head.
+    pub reorg: Option<ReorgInfo>,
+}
+
+/// Two votes from the same validator for different blocks with the same target epoch, as
+/// detected by `ForkChoice::on_attestation`. This is a slashable offence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConflictingVote {
+    pub validator_index: u64,
+    pub target_epoch: Epoch,
+    /// The block root of the attestation which revealed the conflict.
+    pub block_root: Hash256,
+    /// The block root of the vote previously recorded in the `ForkChoiceStore`.
+    pub conflicting_root: Hash256,
+}
+
+/// A change to the checkpoints of `ForkChoice` which occurred whilst advancing the time via
+/// `ForkChoice::update_time`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ForkChoiceEvent {
+    JustifiedCheckpointUpdated { old: Checkpoint, new: Checkpoint },
+    FinalizedCheckpointUpdated { old: Checkpoint, new: Checkpoint },
+}
+
 /// Provides an implementation of "Ethereum 2.0 Phase 0 -- Beacon Chain Fork Choice":
 ///
 /// https://github.com/ethereum/eth2.0-specs/blob/v0.12.1/specs/phase0/fork-choice.md#ethereum-20-phase-0----beacon-chain-fork-choice
@@ -217,6 +314,26 @@ pub struct ForkChoice<T, E> {
     proto_array: ProtoArrayForkChoice,
     /// Attestations that arrived at the current slot and must be queued for later processing.
     queued_attestations: Vec<QueuedAttestation>,
+    /// The maximum length of `queued_attestations`. This is not persisted to disk.
+    max_queued_attestations: usize,
+    /// The justified checkpoint whose balances were most recently supplied to `proto_array`.
+    ///
+    /// This is used to avoid supplying the full list of justified balances to `proto_array`
+    /// whilst the justified checkpoint is unchanged. It is not persisted to disk.
+    balances_checkpoint: Option<Checkpoint>,
+    /// The head returned by the most recent call to `Self::get_head`, used for re-org detection.
+    ///
+    /// This is not persisted to disk, so no re-org will be reported by the first call to
+    /// `Self::get_head` after a restart.
+    head_root: Option<Hash256>,
+    /// If `true`, each vote is recorded in the `fc_store` and conflicting votes are collected in
+    /// `conflicting_votes`. This is not persisted to disk.
+    track_conflicting_votes: bool,
+    /// Conflicting votes detected since the last call to `Self::drain_conflicting_votes`.
+    conflicting_votes: Vec<ConflictingVote>,
+    /// Events produced whilst other methods (e.g., `Self::get_head`) advanced the time, since the
+    /// last call to `Self::drain_events`. This is not persisted to disk.
+    events: Vec<ForkChoiceEvent>,
     _phantom: PhantomData<E>,
 }
 
@@ -238,19 +355,60 @@ where
     E: EthSpec,
 {
     /// Instantiates `Self` from the genesis parameters.
+    ///
+    /// The `prune_threshold` is passed to the underlying `ProtoArrayForkChoice`, see
+    /// `ProtoArrayForkChoice::new` for details.
     pub fn from_genesis(
         fc_store: T,
         genesis_block_root: Hash256,
         genesis_block: &BeaconBlock<E>,
         genesis_state: &BeaconState<E>,
+        prune_threshold: usize,
+    ) -> Result<Self, Error<T::Error>> {
+        Self::from_anchor(
+            fc_store,
+            genesis_block_root,
+            genesis_block,
+            genesis_state,
+            prune_threshold,
+        )
+    }
+
+    /// Instantiates `Self` from some finalized `anchor_block` (and its state), which need not be
+    /// the genesis block. This allows starting from a weak subjectivity checkpoint without any
+    /// knowledge of the blocks prior to it.
+    ///
+    /// The `anchor_block` becomes the sole node in the DAG, which is treated in the same way as
+    /// the genesis block would be. The `fc_store` must have been initialized from the same anchor,
+    /// such that its finalized checkpoint root is `anchor_block_root`.
+    ///
+    /// The `anchor_state` may have been advanced through skip slots beyond the `anchor_block`
+    /// (e.g., to the start of an epoch).
+    ///
+    /// The `prune_threshold` is passed to the underlying `ProtoArrayForkChoice`, see
+    /// `ProtoArrayForkChoice::new` for details.
+    pub fn from_anchor(
+        fc_store: T,
+        anchor_block_root: Hash256,
+        anchor_block: &BeaconBlock<E>,
+        anchor_state: &BeaconState<E>,
+        prune_threshold: usize,
     ) -> Result<Self, Error<T::Error>> {
-        let finalized_block_slot = genesis_block.slot;
-        let finalized_block_state_root = genesis_block.state_root;
+        let finalized_checkpoint_root = fc_store.finalized_checkpoint().root;
+        if anchor_block_root != finalized_checkpoint_root {
+            return Err(Error::InvalidAnchor {
+                block_root: anchor_block_root,
+                finalized_checkpoint_root,
+            });
+        }
+
+        let finalized_block_slot = anchor_block.slot;
+        let finalized_block_state_root = anchor_block.state_root;
         let current_epoch_shuffling_id =
-            AttestationShufflingId::new(genesis_block_root, genesis_state, RelativeEpoch::Current)
+            AttestationShufflingId::new(anchor_block_root, anchor_state, RelativeEpoch::Current)
                 .map_err(Error::BeaconStateError)?;
         let next_epoch_shuffling_id =
-            AttestationShufflingId::new(genesis_block_root, genesis_state, RelativeEpoch::Next)
+            AttestationShufflingId::new(anchor_block_root, anchor_state, RelativeEpoch::Next)
                 .map_err(Error::BeaconStateError)?;
 
         let proto_array = ProtoArrayForkChoice::new(
@@ -261,12 +419,19 @@ where
             fc_store.finalized_checkpoint().root,
             current_epoch_shuffling_id,
             next_epoch_shuffling_id,
+            prune_threshold,
         )?;
 
         Ok(Self {
             fc_store,
             proto_array,
             queued_attestations: vec![],
+            max_queued_attestations: DEFAULT_MAX_QUEUED_ATTESTATIONS,
+            balances_checkpoint: None,
+            head_root: None,
+            track_conflicting_votes: false,
+            conflicting_votes: vec![],
+            events: vec![],
             _phantom: PhantomData,
         })
     }
@@ -284,6 +449,12 @@ where
             fc_store,
             proto_array,
             queued_attestations,
+            max_queued_attestations: DEFAULT_MAX_QUEUED_ATTESTATIONS,
+            balances_checkpoint: None,
+            head_root: None,
+            track_conflicting_votes: false,
+            conflicting_votes: vec![],
+            events: vec![],
             _phantom: PhantomData,
         }
     }
@@ -341,18 +512,96 @@ where
     ///
     /// https://github.com/ethereum/eth2.0-specs/blob/v0.12.1/specs/phase0/fork-choice.md#get_head
     pub fn get_head(&mut self, current_slot: Slot) -> Result<Hash256, Error<T::Error>> {
-        self.update_time(current_slot)?;
+        self.get_head_with_reorg_info(current_slot)
+            .map(|head| head.root)
+    }
 
+    /// Run the fork choice rule to determine the head, as per `Self::get_head`.
+    ///
+    /// Additionally, returns a `ReorgInfo` if the new head does not descend from the head that
+    /// was returned by the previous call to `Self::get_head` or this function.
+    pub fn get_head_with_reorg_info(
+        &mut self,
+        current_slot: Slot,
+    ) -> Result<ForkChoiceHead, Error<T::Error>> {
+        let head_root = self.find_head(current_slot)?;
+
+        Ok(self.record_head(head_root))
+    }
+
+    /// Stores `head_root` as the most recent head, returning it alongside a `ReorgInfo` if it does
+    /// not descend from the previous head.
+    fn record_head(&mut self, head_root: Hash256) -> ForkChoiceHead {
+        let reorg = match self.head_root.replace(head_root) {
+            Some(old_head_root) if !self.proto_array.is_descendant(old_head_root, head_root) => {
+                self.compute_reorg_info(old_head_root, head_root)
+            }
+            _ => None,
+        };
+
+        ForkChoiceHead {
+            root: head_root,
+            reorg,
+        }
+    }
+
+    /// Returns a `ReorgInfo` describing the switch from `old_head_root` to `new_head_root`.
+    ///
+    /// Returns `None` if either block is no longer known to fork choice (e.g., due to pruning).
+    fn compute_reorg_info(
+        &self,
+        old_head_root: Hash256,
+        new_head_root: Hash256,
+    ) -> Option<ReorgInfo> {
+        let old_head_slot = self.proto_array.get_block(&old_head_root)?.slot;
+        let (_, common_ancestor_slot) = self
+            .proto_array
+            .find_common_ancestor(old_head_root, new_head_root)?;
+
+        Some(ReorgInfo {
+            old_head_root,
+            common_ancestor_slot,
+            depth: old_head_slot.saturating_sub(common_ancestor_slot).as_u64(),
+        })
+    }
+
+    /// Run the fork choice rule against `self.proto_array`, without re-org detection.
+    fn find_head(&mut self, current_slot: Slot) -> Result<Hash256, Error<T::Error>> {
+        self.advance_time(current_slot)?;
+        self.find_head_at_current_time()
+    }
+
+    /// Run the fork choice rule against `self.proto_array` without first advancing the time of
+    /// `self.fc_store`.
+    fn find_head_at_current_time(&mut self) -> Result<Hash256, Error<T::Error>> {
         let store = &mut self.fc_store;
 
-        self.proto_array
-            .find_head(
-                store.justified_checkpoint().epoch,
-                store.justified_checkpoint().root,
-                store.finalized_checkpoint().epoch,
-                store.justified_balances(),
-            )
-            .map_err(Into::into)
+        let proposer_boost = store.proposer_boost_root().map(|root| ProposerBoost {
+            root,
+            score: compute_proposer_score::<E>(store.justified_balances()),
+        });
+
+        // The justified balances only change alongside the justified checkpoint, so there is no
+        // need to compare them against the balances already in `proto_array` unless the
+        // checkpoint has changed.
+        let justified_checkpoint = *store.justified_checkpoint();
+        let balance_changes = if self.balances_checkpoint == Some(justified_checkpoint) {
+            vec![]
+        } else {
+            compute_balance_changes(self.proto_array.balances(), store.justified_balances())
+        };
+
+        let head = self.proto_array.find_head_with_balance_changes(
+            justified_checkpoint.epoch,
+            justified_checkpoint.root,
+            store.finalized_checkpoint().epoch,
+            &balance_changes,
+            proposer_boost,
+        )?;
+
+        self.balances_checkpoint = Some(justified_checkpoint);
+
+        Ok(head)
     }
 
     /// Returns `true` if the given `store` should be updated to set
@@ -368,7 +617,7 @@ where
         current_slot: Slot,
         state: &BeaconState<E>,
     ) -> Result<bool, Error<T::Error>> {
-        self.update_time(current_slot)?;
+        self.advance_time(current_slot)?;
 
         let new_justified_checkpoint = &state.current_justified_checkpoint;
 
@@ -409,6 +658,8 @@ where
     /// Add `block` to the fork choice DAG.
     ///
     /// - `block_root` is the root of `block.
+    /// - `block_delay` is the time between the start of `block.slot` and when the block was
+    ///   received.
     /// - The root of `state` matches `block.state_root`.
     ///
     /// ## Specification
@@ -429,9 +680,12 @@ where
         current_slot: Slot,
         block: &BeaconBlock<E>,
         block_root: Hash256,
+        block_delay: Duration,
         state: &BeaconState<E>,
+        spec: &ChainSpec,
     ) -> Result<(), Error<T::Error>> {
-        let current_slot = self.update_time(current_slot)?;
+        self.advance_time(current_slot)?;
+        let current_slot = self.fc_store.get_current_slot();
 
         // Parent block must be known.
         if !self.proto_array.contains_block(&block.parent_root) {
@@ -480,6 +734,13 @@ where
             }));
         }
 
+        // Add the proposer score boost if the block is timely.
+        let is_before_attesting_interval =
+            block_delay < Duration::from_secs(spec.seconds_per_slot / INTERVALS_PER_SLOT);
+        if current_slot == block.slot && is_before_attesting_interval {
+            self.fc_store.set_proposer_boost_root(Some(block_root));
+        }
+
         // Update justified checkpoint.
         if state.current_justified_checkpoint.epoch > self.fc_store.justified_checkpoint().epoch {
             if state.current_justified_checkpoint.epoch
@@ -563,6 +824,30 @@ where
         Ok(())
     }
 
+    /// Add `block` to the fork choice DAG as per `Self::on_block`, then run the fork choice rule
+    /// and return the new head as per `Self::get_head`.
+    ///
+    /// ## Notes
+    ///
+    /// The time is only advanced to `current_slot` once, by `Self::on_block`. Queued attestations
+    /// are still respected: any which became eligible at `current_slot` are applied before the
+    /// head is found, whilst those from `current_slot` remain queued.
+    pub fn on_block_and_get_head(
+        &mut self,
+        current_slot: Slot,
+        block: &BeaconBlock<E>,
+        block_root: Hash256,
+        block_delay: Duration,
+        state: &BeaconState<E>,
+        spec: &ChainSpec,
+    ) -> Result<Hash256, Error<T::Error>> {
+        self.on_block(current_slot, block, block_root, block_delay, state, spec)?;
+
+        let head_root = self.find_head_at_current_time()?;
+
+        Ok(self.record_head(head_root).root)
+    }
+
     /// Validates the `indexed_attestation` for application to fork choice.
     ///
     /// ## Specification
@@ -583,9 +868,24 @@ where
             return Err(InvalidAttestation::EmptyAggregationBitfield);
         }
 
+        let target = indexed_attestation.data.target;
+
+        // An attestation with a target prior to the finalized checkpoint cannot be for a block in
+        // `proto_array`, since such blocks are either pruned or conflict with finalization. Reject
+        // it before doing any work, so it is not reported as an unknown target root.
+        //
+        // Targets in the finalized epoch are not rejected, since the finalized block itself (e.g.,
+        // the genesis or anchor block) may legitimately be the target.
+        let finalized_epoch = self.fc_store.finalized_checkpoint().epoch;
+        if target.epoch < finalized_epoch {
+            return Err(InvalidAttestation::FinalizedTarget {
+                target_epoch: target.epoch,
+                finalized_epoch,
+            });
+        }
+
         let slot_now = self.fc_store.get_current_slot();
         let epoch_now = slot_now.epoch(E::slots_per_epoch());
-        let target = indexed_attestation.data.target;
 
         // Attestation must be from the current or previous epoch.
         if target.epoch > epoch_now {
@@ -647,6 +947,32 @@ where
             });
         }
 
+        // The attestation source must match the justified checkpoint of the state at the
+        // attestation slot.
+        //
+        // Justification only changes at epoch boundaries, so this is equal to the justified epoch
+        // of the target block if it is from the target epoch. Otherwise an epoch transition may
+        // lie between the target block and the attestation slot and the source cannot be checked
+        // without loading a state. We also skip the check for the block which fork choice was
+        // initialized with, since its justified epoch is that of the anchor checkpoint.
+        //
+        // This check is not in the specification, however it is implied by `is_valid_attestation`
+        // in `process_attestation`.
+        let target_block = self
+            .proto_array
+            .get_block(&target.root)
+            .ok_or(InvalidAttestation::UnknownTargetRoot(target.root))?;
+
+        if target_block.slot.epoch(E::slots_per_epoch()) == target.epoch
+            && target_block.parent_root.is_some()
+            && target_block.justified_epoch != indexed_attestation.data.source.epoch
+        {
+            return Err(InvalidAttestation::InvalidFfgSource {
+                attestation: indexed_attestation.data.source.epoch,
+                local: target_block.justified_epoch,
+            });
+        }
+
         // Attestations must not be for blocks in the future. If this is the case, the attestation
         // should not be considered.
         if block.slot > indexed_attestation.data.slot {
@@ -659,6 +985,25 @@ where
         Ok(())
     }
 
+    /// Returns `true` if `validator_index` has already voted for a block other than `block_root`
+    /// with the same `target_epoch`. This is a slashable offence.
+    ///
+    /// Votes from equivocating validators are not applied to fork choice. The rest of the
+    /// attestation is still applied, so that the votes of honest validators in an aggregate are
+    /// not lost.
+    fn is_equivocating(
+        &self,
+        validator_index: u64,
+        target_epoch: Epoch,
+        block_root: Hash256,
+    ) -> bool {
+        self.proto_array
+            .latest_message(validator_index as usize)
+            .map_or(false, |(latest_root, latest_epoch)| {
+                latest_epoch == target_epoch && latest_root != block_root
+            })
+    }
+
     /// Register `attestation` with the fork choice DAG so that it may influence future calls to
     /// `Self::get_head`.
     ///
@@ -682,7 +1027,7 @@ where
         attestation: &IndexedAttestation<E>,
     ) -> Result<(), Error<T::Error>> {
         // Ensure the store is up-to-date.
-        self.update_time(current_slot)?;
+        self.advance_time(current_slot)?;
 
         // Ignore any attestations to the zero hash.
         //
@@ -701,10 +1046,49 @@ where
             return Ok(());
         }
 
+        // Check for conflicting votes before validating the attestation. It has already been
+        // signature-verified upstream, so the conflict is slashable regardless.
+        if self.track_conflicting_votes {
+            let target_epoch = attestation.data.target.epoch;
+            let block_root = attestation.data.beacon_block_root;
+
+            for validator_index in attestation.attesting_indices.iter() {
+                if let Some(conflicting_root) =
+                    self.fc_store
+                        .conflicting_vote(*validator_index, target_epoch, block_root)
+                {
+                    self.conflicting_votes.push(ConflictingVote {
+                        validator_index: *validator_index,
+                        target_epoch,
+                        block_root,
+                        conflicting_root,
+                    });
+                }
+            }
+        }
+
         self.validate_on_attestation(attestation)?;
 
+        if self.track_conflicting_votes {
+            for validator_index in attestation.attesting_indices.iter() {
+                self.fc_store.record_vote(
+                    *validator_index,
+                    attestation.data.target.epoch,
+                    attestation.data.beacon_block_root,
+                );
+            }
+        }
+
         if attestation.data.slot < self.fc_store.get_current_slot() {
             for validator_index in attestation.attesting_indices.iter() {
+                if self.is_equivocating(
+                    *validator_index,
+                    attestation.data.target.epoch,
+                    attestation.data.beacon_block_root,
+                ) {
+                    continue;
+                }
+
                 self.proto_array.process_attestation(
                     *validator_index as usize,
                     attestation.data.beacon_block_root,
@@ -718,6 +1102,16 @@ where
             // Attestations can only affect the fork choice of subsequent slots.
             // Delay consideration in the fork choice until their slot is in the past.
             // ```
+            //
+            // The queue is bounded to prevent a flood of current-slot attestations from consuming
+            // an unbounded amount of memory.
+            if self.queued_attestations.len() >= self.max_queued_attestations {
+                return Err(InvalidAttestation::TooManyQueuedAttestations {
+                    max_queued_attestations: self.max_queued_attestations,
+                }
+                .into());
+            }
+
             self.queued_attestations
                 .push(QueuedAttestation::from(attestation));
         }
@@ -726,19 +1120,57 @@ where
     }
 
     /// Call `on_tick` for all slots between `fc_store.get_current_slot()` and the provided
-    /// `current_slot`. Returns the value of `self.fc_store.get_current_slot`.
-    pub fn update_time(&mut self, current_slot: Slot) -> Result<Slot, Error<T::Error>> {
+    /// `current_slot`. Returns a `ForkChoiceEvent` for each checkpoint change made by `on_tick`,
+    /// in the order in which they occurred.
+    ///
+    /// ## Notes
+    ///
+    /// Other methods (e.g., `Self::get_head`) also advance the time. Any events produced whilst
+    /// doing so are not returned by a later call to this function, instead they are buffered
+    /// until `Self::drain_events` is called.
+    pub fn update_time(
+        &mut self,
+        current_slot: Slot,
+    ) -> Result<Vec<ForkChoiceEvent>, Error<T::Error>> {
+        let mut events = vec![];
+
         while self.fc_store.get_current_slot() < current_slot {
             let previous_slot = self.fc_store.get_current_slot();
+            let previous_justified = *self.fc_store.justified_checkpoint();
+            let previous_finalized = *self.fc_store.finalized_checkpoint();
+
             // Note: we are relying upon `on_tick` to update `fc_store.time` to ensure we don't
             // get stuck in a loop.
-            on_tick(&mut self.fc_store, previous_slot + 1)?
+            on_tick(&mut self.fc_store, previous_slot + 1)?;
+
+            let justified = *self.fc_store.justified_checkpoint();
+            if justified != previous_justified {
+                events.push(ForkChoiceEvent::JustifiedCheckpointUpdated {
+                    old: previous_justified,
+                    new: justified,
+                });
+            }
+
+            let finalized = *self.fc_store.finalized_checkpoint();
+            if finalized != previous_finalized {
+                events.push(ForkChoiceEvent::FinalizedCheckpointUpdated {
+                    old: previous_finalized,
+                    new: finalized,
+                });
+            }
         }
 
         // Process any attestations that might now be eligible.
         self.process_attestation_queue()?;
 
-        Ok(self.fc_store.get_current_slot())
+        Ok(events)
+    }
+
+    /// Advance the time via `Self::update_time`, buffering any events for `Self::drain_events`.
+    fn advance_time(&mut self, current_slot: Slot) -> Result<(), Error<T::Error>> {
+        let events = self.update_time(current_slot)?;
+        self.events.extend(events);
+        Ok(())
     }
 
     /// Processes and removes from the queue any queued attestations which may now be eligible for
@@ -749,6 +1181,14 @@ where
             &mut self.queued_attestations,
         ) {
             for validator_index in attestation.attesting_indices.iter() {
+                if self.is_equivocating(
+                    *validator_index,
+                    attestation.target_epoch,
+                    attestation.block_root,
+                ) {
+                    continue;
+                }
+
                 self.proto_array.process_attestation(
                     *validator_index as usize,
                     attestation.block_root,
@@ -780,6 +1220,32 @@ where
             .is_descendant(self.fc_store.finalized_checkpoint().root, block_root)
     }
 
+    /// Returns `true` if `descendant` is equal to, or a descendant of, `ancestor`.
+    ///
+    /// Always returns `false` if either block is unknown or does not descend from the finalized
+    /// root.
+    pub fn is_descendant(&self, ancestor: Hash256, descendant: Hash256) -> bool {
+        self.is_descendant_of_finalized(ancestor)
+            && self.proto_array.is_descendant(ancestor, descendant)
+    }
+
+    /// Returns the root of the block at `slot` in the chain ending at `head`. If `slot` is a skip
+    /// slot, the root of the most recent block prior to `slot` is returned.
+    ///
+    /// Returns `None` if `head` is unknown or does not descend from the finalized root, or if
+    /// `slot` is prior to the earliest block in fork choice.
+    pub fn ancestor_root_at_slot(&self, head: Hash256, slot: Slot) -> Option<Hash256> {
+        if !self.is_descendant_of_finalized(head) {
+            return None;
+        }
+
+        self.proto_array
+            .core_proto_array()
+            .iter_block_roots(&head)
+            .find(|(_, block_slot)| *block_slot <= slot)
+            .map(|(root, _)| root)
+    }
+
     /// Return the current finalized checkpoint.
     pub fn finalized_checkpoint(&self) -> Checkpoint {
         *self.fc_store.finalized_checkpoint()
@@ -807,6 +1273,41 @@ where
         &self.fc_store
     }
 
+    /// Sets the maximum number of attestations which may be queued for processing in a later slot.
+    /// Once this limit is reached, `Self::on_attestation` will reject any attestation which would
+    /// otherwise be queued with `InvalidAttestation::TooManyQueuedAttestations`.
+    pub fn set_max_queued_attestations(&mut self, max_queued_attestations: usize) {
+        self.max_queued_attestations = max_queued_attestations;
+    }
+
+    /// Sets the number of slots before the finalized slot for which blocks are retained when
+    /// pruning. See `ProtoArrayForkChoice::set_prune_retain_slots`.
+    ///
+    /// This is not persisted, so it must be set again after `Self::from_persisted`.
+    pub fn set_prune_retain_slots(&mut self, prune_retain_slots: u64) {
+        self.proto_array.set_prune_retain_slots(prune_retain_slots);
+    }
+
+    /// Enables or disables the recording of votes in the `fc_store` for the detection of
+    /// conflicting (i.e., slashable) votes. Disabled by default, so that nodes which do not report
+    /// slashings do not pay the memory cost of recording every vote.
+    ///
+    /// This has no effect on the head.
+    pub fn set_track_conflicting_votes(&mut self, track_conflicting_votes: bool) {
+        self.track_conflicting_votes = track_conflicting_votes;
+    }
+
+    /// Returns all the conflicting votes detected since the last call to this function.
+    pub fn drain_conflicting_votes(&mut self) -> Vec<ConflictingVote> {
+        std::mem::take(&mut self.conflicting_votes)
+    }
+
+    /// Returns all the events produced whilst advancing the time since the last call to this
+    /// function, excluding those already returned by `Self::update_time`.
+    pub fn drain_events(&mut self) -> Vec<ForkChoiceEvent> {
+        std::mem::take(&mut self.events)
+    }
+
     /// Returns a reference to the currently queued attestations.
     pub fn queued_attestations(&self) -> &[QueuedAttestation] {
         &self.queued_attestations
@@ -827,13 +1328,49 @@ where
         persisted: PersistedForkChoice,
         fc_store: T,
     ) -> Result<Self, Error<T::Error>> {
-        let proto_array = ProtoArrayForkChoice::from_bytes(&persisted.proto_array_bytes)
+        Self::from_persisted_with_diffs(persisted, &[], fc_store)
+    }
+
+    /// Instantiate `Self` from some `PersistedForkChoice` generated by an earlier call to
+    /// `Self::to_persisted`, then replay each of the `diffs` (in order) on top of it.
+    ///
+    /// Each diff must have been generated by `Self::persisted_diff_since` with the node count of
+    /// the state produced by the snapshot and all prior diffs.
+    pub fn from_persisted_with_diffs(
+        persisted: PersistedForkChoice,
+        diffs: &[PersistedForkChoiceDiff],
+        mut fc_store: T,
+    ) -> Result<Self, Error<T::Error>> {
+        let mut proto_array = ProtoArrayForkChoice::from_bytes(&persisted.proto_array_bytes)
             .map_err(Error::InvalidProtoArrayBytes)?;
+        let mut queued_attestations = persisted.queued_attestations;
+
+        for diff in diffs {
+            proto_array
+                .apply_diff_bytes(&diff.proto_array_diff_bytes)
+                .map_err(Error::InvalidProtoArrayBytes)?;
+
+            if *fc_store.justified_checkpoint() != diff.justified_checkpoint {
+                fc_store
+                    .set_justified_checkpoint(diff.justified_checkpoint)
+                    .map_err(Error::UnableToSetJustifiedCheckpoint)?;
+            }
+            fc_store.set_best_justified_checkpoint(diff.best_justified_checkpoint);
+            fc_store.set_finalized_checkpoint(diff.finalized_checkpoint);
+
+            queued_attestations = diff.queued_attestations.clone();
+        }
 
         Ok(Self {
             fc_store,
             proto_array,
-            queued_attestations: persisted.queued_attestations,
+            queued_attestations,
+            max_queued_attestations: DEFAULT_MAX_QUEUED_ATTESTATIONS,
+            balances_checkpoint: None,
+            head_root: None,
+            track_conflicting_votes: false,
+            conflicting_votes: vec![],
+            events: vec![],
             _phantom: PhantomData,
         })
     }
@@ -846,6 +1383,22 @@ where
             queued_attestations: self.queued_attestations().to_vec(),
         }
     }
+
+    /// Returns the changes to `Self` since the underlying DAG contained `last_node_count` nodes
+    /// (as per `ProtoArrayForkChoice::len`).
+    ///
+    /// This is cheaper to encode than `Self::to_persisted` since only the nodes added since then
+    /// are included in full. The diff should be persisted alongside an earlier snapshot and
+    /// replayed with `Self::from_persisted_with_diffs`. A new snapshot must be taken after a prune.
+    pub fn persisted_diff_since(&self, last_node_count: usize) -> PersistedForkChoiceDiff {
+        PersistedForkChoiceDiff {
+            proto_array_diff_bytes: self.proto_array().diff_bytes_since(last_node_count),
+            justified_checkpoint: *self.fc_store.justified_checkpoint(),
+            best_justified_checkpoint: *self.fc_store.best_justified_checkpoint(),
+            finalized_checkpoint: *self.fc_store.finalized_checkpoint(),
+            queued_attestations: self.queued_attestations().to_vec(),
+        }
+    }
 }
 
 /// Helper struct that is used to encode/decode the state of the `ForkChoice` as SSZ bytes.
@@ -853,7 +1406,20 @@ where
 /// This is used when persisting the state of the fork choice to disk.
 #[derive(Encode, Decode, Clone)]
 pub struct PersistedForkChoice {
-    proto_array_bytes: Vec<u8>,
+    pub proto_array_bytes: Vec<u8>,
+    queued_attestations: Vec<QueuedAttestation>,
+}
+
+/// Helper struct that is used to encode/decode the changes to `ForkChoice` since some earlier
+/// `PersistedForkChoice` as SSZ bytes.
+///
+/// This is used when journaling the state of the fork choice to disk.
+#[derive(Encode, Decode, Clone)]
+pub struct PersistedForkChoiceDiff {
+    proto_array_diff_bytes: Vec<u8>,
+    justified_checkpoint: Checkpoint,
+    best_justified_checkpoint: Checkpoint,
+    finalized_checkpoint: Checkpoint,
     queued_attestations: Vec<QueuedAttestation>,
 }
 
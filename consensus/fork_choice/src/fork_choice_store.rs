@@ -1,4 +1,4 @@
-use types::{BeaconBlock, BeaconState, Checkpoint, EthSpec, Hash256, Slot};
+use types::{BeaconBlock, BeaconState, Checkpoint, Epoch, EthSpec, Hash256, Slot};
 
 /// Approximates the `Store` in "Ethereum 2.0 Phase 0 -- Beacon Chain Fork Choice":
 ///
@@ -42,6 +42,11 @@ pub trait ForkChoiceStore<T: EthSpec>: Sized {
     fn justified_checkpoint(&self) -> &Checkpoint;
 
     /// Returns balances from the `state` identified by `justified_checkpoint.root`.
+    ///
+    /// ## Notes
+    ///
+    /// `ForkChoice` assumes that these balances only change when the `justified_checkpoint`
+    /// changes.
     fn justified_balances(&self) -> &[u64];
 
     /// Returns the `best_justified_checkpoint`.
@@ -58,4 +63,39 @@ pub trait ForkChoiceStore<T: EthSpec>: Sized {
 
     /// Sets the `best_justified_checkpoint`.
     fn set_best_justified_checkpoint(&mut self, checkpoint: Checkpoint);
+
+    /// Returns the `proposer_boost_root`, if any.
+    fn proposer_boost_root(&self) -> Option<Hash256>;
+
+    /// Sets the `proposer_boost_root`.
+    ///
+    /// ## Notes
+    ///
+    /// This should only ever be called from within `ForkChoice::on_block` and
+    /// `ForkChoice::on_tick`.
+    fn set_proposer_boost_root(&mut self, proposer_boost_root: Option<Hash256>);
+
+    /// Returns the block root of a previously recorded vote from `validator_index` which has the
+    /// same `target_epoch` as, but a different root to, `block_root`.
+    ///
+    /// ## Notes
+    ///
+    /// Votes are only recorded whilst `ForkChoice::set_track_conflicting_votes` is enabled. The
+    /// default implementation records nothing and always returns `None`.
+    fn conflicting_vote(
+        &self,
+        _validator_index: u64,
+        _target_epoch: Epoch,
+        _block_root: Hash256,
+    ) -> Option<Hash256> {
+        None
+    }
+
+    /// Record that `validator_index` voted for `block_root` in `target_epoch`, for later use by
+    /// `Self::conflicting_vote`.
+    ///
+    /// ## Notes
+    ///
+    /// This should only ever be called from within `ForkChoice::on_attestation`.
+    fn record_vote(&mut self, _validator_index: u64, _target_epoch: Epoch, _block_root: Hash256) {}
 }
@@ -2,7 +2,9 @@ mod fork_choice;
 mod fork_choice_store;
 
 pub use crate::fork_choice::{
-    Error, ForkChoice, InvalidAttestation, InvalidBlock, PersistedForkChoice, QueuedAttestation,
+    ConflictingVote, Error, ForkChoice, ForkChoiceEvent, ForkChoiceHead, InvalidAttestation,
+    InvalidBlock, PersistedForkChoice, PersistedForkChoiceDiff, QueuedAttestation, ReorgInfo,
+    DEFAULT_MAX_QUEUED_ATTESTATIONS, INTERVALS_PER_SLOT, PROPOSER_SCORE_BOOST,
     SAFE_SLOTS_TO_UPDATE_JUSTIFIED,
 };
 pub use fork_choice_store::ForkChoiceStore;
@@ -1,16 +1,21 @@
 #![cfg(not(debug_assertions))]
 
 use beacon_chain::{
+    events::{EventKind, SseJustifiedCheckpoint},
+    slot_clock::SlotClock,
     test_utils::{AttestationStrategy, BeaconChainHarness, BlockStrategy, EphemeralHarnessType},
-    BeaconChain, BeaconChainError, BeaconForkChoiceStore, ChainConfig, ForkChoiceError,
-    StateSkipConfig, WhenSlotSkipped,
+    validator_monitor::timestamp_now,
+    BeaconChain, BeaconChainError, BeaconForkChoiceStore, BeaconSnapshot, ChainConfig,
+    ForkChoiceError, StateSkipConfig, WhenSlotSkipped,
 };
 use fork_choice::{
-    ForkChoiceStore, InvalidAttestation, InvalidBlock, QueuedAttestation,
-    SAFE_SLOTS_TO_UPDATE_JUSTIFIED,
+    ConflictingVote, ForkChoice, ForkChoiceEvent, ForkChoiceStore, InvalidAttestation,
+    InvalidBlock, QueuedAttestation, ReorgInfo, SAFE_SLOTS_TO_UPDATE_JUSTIFIED,
 };
+use proto_array::DEFAULT_PRUNE_THRESHOLD;
 use std::fmt;
 use std::sync::Mutex;
+use std::time::Duration;
 use store::{MemoryStore, StoreConfig};
 use types::{
     test_utils::{generate_deterministic_keypair, generate_deterministic_keypairs},
@@ -138,6 +143,58 @@ impl ForkChoiceTest {
         self
     }
 
+    /// Assert that the proposer boost root is (or is not) set.
+    pub fn assert_proposer_boost_root_is_some(self, is_some: bool) -> Self {
+        self.harness
+            .chain
+            .fork_choice
+            .write()
+            .update_time(self.harness.chain.slot().unwrap())
+            .unwrap();
+        assert_eq!(
+            self.get(|fc_store| fc_store.proposer_boost_root().is_some()),
+            is_some,
+            "proposer_boost_root"
+        );
+        self
+    }
+
+    /// Set the maximum number of attestations which may be queued in fork choice.
+    pub fn set_max_queued_attestations(self, max_queued_attestations: usize) -> Self {
+        self.harness
+            .chain
+            .fork_choice
+            .write()
+            .set_max_queued_attestations(max_queued_attestations);
+        self
+    }
+
+    /// Enable or disable the tracking of conflicting votes in fork choice.
+    pub fn set_track_conflicting_votes(self, track_conflicting_votes: bool) -> Self {
+        self.harness
+            .chain
+            .fork_choice
+            .write()
+            .set_track_conflicting_votes(track_conflicting_votes);
+        self
+    }
+
+    /// Drain and inspect the conflicting votes detected by fork choice.
+    pub fn inspect_conflicting_votes<F>(self, mut func: F) -> Self
+    where
+        F: FnMut(&[ConflictingVote]),
+    {
+        func(
+            &self
+                .harness
+                .chain
+                .fork_choice
+                .write()
+                .drain_conflicting_votes(),
+        );
+        self
+    }
+
     /// Inspect the queued attestations in fork choice.
     pub fn inspect_queued_attestations<F>(self, mut func: F) -> Self
     where
@@ -271,7 +328,14 @@ impl ForkChoiceTest {
             .chain
             .fork_choice
             .write()
-            .on_block(current_slot, &block.message, block.canonical_root(), &state)
+            .on_block(
+                current_slot,
+                &block.message,
+                block.canonical_root(),
+                Duration::from_secs(0),
+                &state,
+                &self.harness.chain.spec,
+            )
             .unwrap();
         self
     }
@@ -305,7 +369,14 @@ impl ForkChoiceTest {
             .chain
             .fork_choice
             .write()
-            .on_block(current_slot, &block.message, block.canonical_root(), &state)
+            .on_block(
+                current_slot,
+                &block.message,
+                block.canonical_root(),
+                Duration::from_secs(0),
+                &state,
+                &self.harness.chain.spec,
+            )
             .err()
             .expect("on_block did not return an error");
         comparison_func(err);
@@ -584,6 +655,140 @@ fn justified_checkpoint_updates_with_non_descendent_outside_safe_slots_with_fina
         .assert_best_justified_epoch(3);
 }
 
+/// Returns a test where the best justified checkpoint is ahead of the justified checkpoint, which
+/// will be updated at the next epoch boundary.
+fn justified_checkpoint_update_pending() -> ForkChoiceTest {
+    ForkChoiceTest::new()
+        .apply_blocks_while(|_, state| state.current_justified_checkpoint.epoch == 0)
+        .unwrap()
+        .apply_blocks(1)
+        .move_to_next_unsafe_period()
+        .assert_justified_epoch(2)
+        .apply_block_directly_to_fork_choice(|_, state| {
+            // The finalized checkpoint should not change.
+            state.finalized_checkpoint.epoch = Epoch::new(0);
+
+            // The justified checkpoint has changed.
+            state.current_justified_checkpoint.epoch = Epoch::new(3);
+            // The new block should **not** include the current justified block as an ancestor.
+            state.current_justified_checkpoint.root = *state
+                .get_block_root(Epoch::new(1).start_slot(E::slots_per_epoch()))
+                .unwrap();
+        })
+        .assert_justified_epoch(2)
+        .assert_best_justified_epoch(3)
+}
+
+/// - The best justified checkpoint is ahead of the justified checkpoint.
+/// - Advancing across an epoch boundary produces a single event for the justified checkpoint.
+#[test]
+fn justified_checkpoint_update_on_tick_produces_event() {
+    let test = justified_checkpoint_update_pending();
+
+    let old = test.get(|fc_store| *fc_store.justified_checkpoint());
+    let new = test.get(|fc_store| *fc_store.best_justified_checkpoint());
+
+    let current_slot = test.harness.get_current_slot();
+    let next_epoch_start_slot =
+        (current_slot.epoch(E::slots_per_epoch()) + 1).start_slot(E::slots_per_epoch());
+
+    let mut events = vec![];
+    for slot in current_slot.as_u64()..=next_epoch_start_slot.as_u64() + 1 {
+        events.extend(
+            test.harness
+                .chain
+                .fork_choice
+                .write()
+                .update_time(Slot::new(slot))
+                .unwrap(),
+        );
+    }
+
+    assert_eq!(
+        events,
+        vec![ForkChoiceEvent::JustifiedCheckpointUpdated { old, new }]
+    );
+    assert!(
+        test.harness
+            .chain
+            .fork_choice
+            .write()
+            .drain_events()
+            .is_empty(),
+        "events returned by update_time should not be buffered"
+    );
+    test.assert_justified_epoch(3);
+}
+
+/// - The best justified checkpoint is ahead of the justified checkpoint.
+/// - Advancing across an epoch boundary via `get_head` buffers the event until it is drained.
+#[test]
+fn justified_checkpoint_update_in_get_head_is_buffered() {
+    let test = justified_checkpoint_update_pending();
+
+    let old = test.get(|fc_store| *fc_store.justified_checkpoint());
+    let new = test.get(|fc_store| *fc_store.best_justified_checkpoint());
+
+    let current_slot = test.harness.get_current_slot();
+    let next_epoch_start_slot =
+        (current_slot.epoch(E::slots_per_epoch()) + 1).start_slot(E::slots_per_epoch());
+
+    {
+        let mut fork_choice = test.harness.chain.fork_choice.write();
+        // The justified checkpoint is artificial and may not be viable for the head, so only the
+        // time update performed by `get_head` is of interest here.
+        let _ = fork_choice.get_head(next_epoch_start_slot + 1);
+
+        assert_eq!(
+            fork_choice.drain_events(),
+            vec![ForkChoiceEvent::JustifiedCheckpointUpdated { old, new }]
+        );
+        assert!(
+            fork_choice.drain_events().is_empty(),
+            "events should only be drained once"
+        );
+    }
+    test.assert_justified_epoch(3);
+}
+
+/// - The best justified checkpoint is ahead of the justified checkpoint.
+/// - Running fork choice across an epoch boundary forwards the update to SSE subscribers.
+#[test]
+fn justified_checkpoint_update_emits_sse_event() {
+    let test = justified_checkpoint_update_pending();
+
+    let new = test.get(|fc_store| *fc_store.best_justified_checkpoint());
+
+    let current_slot = test.harness.get_current_slot();
+    let next_epoch_start_slot =
+        (current_slot.epoch(E::slots_per_epoch()) + 1).start_slot(E::slots_per_epoch());
+
+    let mut justified = test
+        .harness
+        .chain
+        .event_handler
+        .as_ref()
+        .expect("harness should have an event handler")
+        .subscribe_justified();
+
+    test.harness.set_current_slot(next_epoch_start_slot + 1);
+    // The justified checkpoint is artificial and may not be viable for the head.
+    let _ = test.harness.chain.fork_choice();
+
+    assert_eq!(
+        justified.try_recv().expect("should emit a justified event"),
+        EventKind::JustifiedCheckpoint(SseJustifiedCheckpoint {
+            block: new.root,
+            epoch: new.epoch,
+        })
+    );
+    assert!(
+        justified.try_recv().is_err(),
+        "there should only be one justified event"
+    );
+    test.assert_justified_epoch(3);
+}
+
 /// Check that the balances are obtained correctly.
 #[test]
 fn justified_balances() {
@@ -791,6 +996,39 @@ fn invalid_attestation_past_epoch() {
         );
 }
 
+/// Attestations with a target prior to the finalized checkpoint are rejected before their target
+/// root is looked up.
+#[test]
+fn invalid_attestation_finalized_target() {
+    let test = ForkChoiceTest::new()
+        .apply_blocks_while(|_, state| state.finalized_checkpoint.epoch == 0)
+        .unwrap()
+        .apply_blocks(1);
+    let finalized_epoch = test
+        .harness
+        .chain
+        .fork_choice
+        .read()
+        .finalized_checkpoint()
+        .epoch;
+    let target_epoch = finalized_epoch - 1;
+
+    test.apply_attestation_to_chain(
+        MutationDelay::NoDelay,
+        |attestation, _| {
+            attestation.data.target.epoch = target_epoch;
+            attestation.data.target.root = Hash256::from_low_u64_be(42);
+        },
+        |result| {
+            assert_invalid_attestation!(
+                result,
+                InvalidAttestation::FinalizedTarget { target_epoch: target, finalized_epoch: finalized }
+                if target == target_epoch && finalized == finalized_epoch
+            )
+        },
+    );
+}
+
 /// Specification v0.12.1:
 ///
 /// assert target.epoch == compute_epoch_at_slot(attestation.data.slot)
@@ -926,6 +1164,44 @@ fn invalid_attestation_inconsistent_ffg_vote() {
         );
 }
 
+/// The attestation source matches the justified epoch of a target block from the target epoch.
+#[test]
+fn valid_attestation_ffg_source() {
+    ForkChoiceTest::new()
+        .apply_blocks_without_new_attestations(E::slots_per_epoch() as usize + 1)
+        .apply_attestation_to_chain(
+            MutationDelay::NoDelay,
+            |attestation, _| {
+                assert!(
+                    attestation.data.target.epoch > 0,
+                    "precondition: target should not be genesis"
+                );
+            },
+            |result| assert_eq!(result.unwrap(), ()),
+        );
+}
+
+/// The attestation source does not match the justified epoch of a target block from the target
+/// epoch.
+#[test]
+fn invalid_attestation_ffg_source() {
+    ForkChoiceTest::new()
+        .apply_blocks_without_new_attestations(E::slots_per_epoch() as usize + 1)
+        .apply_attestation_to_chain(
+            MutationDelay::NoDelay,
+            |attestation, _| {
+                attestation.data.source.epoch = Epoch::new(1);
+            },
+            |result| {
+                assert_invalid_attestation!(
+                    result,
+                    InvalidAttestation::InvalidFfgSource { attestation, local }
+                    if attestation == Epoch::new(1) && local == Epoch::new(0)
+                )
+            },
+        );
+}
+
 /// Specification v0.12.1:
 ///
 /// assert get_current_slot(store) >= attestation.data.slot + 1
@@ -944,6 +1220,196 @@ fn invalid_attestation_delayed_slot() {
         .inspect_queued_attestations(|queue| assert_eq!(queue.len(), 0));
 }
 
+/// Ensures that the vote of a validator which has already voted for a different block in the same
+/// target epoch is ignored, whilst the votes of the other validators in the attestation are still
+/// applied.
+#[test]
+fn equivocating_validator_vote_is_ignored() {
+    let mut votes = None;
+
+    let test = ForkChoiceTest::new()
+        .apply_blocks_without_new_attestations(2)
+        .apply_attestation_to_chain(
+            MutationDelay::Blocks(1),
+            |attestation, chain| {
+                let target_epoch = attestation.data.target.epoch;
+                let block_root = attestation.data.beacon_block_root;
+                let parent_root = chain
+                    .fork_choice
+                    .read()
+                    .get_block(&block_root)
+                    .and_then(|block| block.parent_root)
+                    .unwrap();
+
+                // Apply a conflicting vote for the parent block in the same target epoch.
+                let mut conflicting = attestation.clone();
+                conflicting.data.beacon_block_root = parent_root;
+                chain
+                    .fork_choice
+                    .write()
+                    .on_attestation(chain.slot().unwrap(), &conflicting)
+                    .unwrap();
+
+                // Add an honest validator which has not yet voted in the target epoch, as if the
+                // attestation were an aggregate.
+                let equivocating_index = attestation.attesting_indices[0];
+                let honest_index = (0..VALIDATOR_COUNT as u64)
+                    .find(|i| {
+                        *i != equivocating_index
+                            && chain
+                                .fork_choice
+                                .read()
+                                .latest_message(*i as usize)
+                                .map_or(true, |(_, epoch)| epoch < target_epoch)
+                    })
+                    .expect("should find a validator which has not voted");
+                attestation.attesting_indices.push(honest_index).unwrap();
+
+                votes = Some((equivocating_index, parent_root, honest_index, block_root));
+            },
+            |result| assert_eq!(result.unwrap(), ()),
+        );
+
+    let (equivocating_index, parent_root, honest_index, block_root) = votes.unwrap();
+    let fork_choice = test.harness.chain.fork_choice.read();
+    assert_eq!(
+        fork_choice
+            .latest_message(equivocating_index as usize)
+            .map(|(root, _)| root),
+        Some(parent_root),
+        "the equivocating vote should be ignored"
+    );
+    assert_eq!(
+        fork_choice
+            .latest_message(honest_index as usize)
+            .map(|(root, _)| root),
+        Some(block_root),
+        "the honest vote should be applied"
+    );
+}
+
+/// Applies a vote for the parent of the attested block, followed by the conflicting attestation.
+/// Returns the `ConflictingVote` that fork choice is expected to report.
+fn apply_conflicting_votes(test: ForkChoiceTest) -> (ForkChoiceTest, ConflictingVote) {
+    let mut expected = None;
+
+    let test = test
+        .apply_blocks_without_new_attestations(2)
+        .apply_attestation_to_chain(
+            MutationDelay::Blocks(1),
+            |attestation, chain| {
+                let parent_root = chain
+                    .fork_choice
+                    .read()
+                    .get_block(&attestation.data.beacon_block_root)
+                    .and_then(|block| block.parent_root)
+                    .unwrap();
+
+                let mut conflicting = attestation.clone();
+                conflicting.data.beacon_block_root = parent_root;
+                chain
+                    .fork_choice
+                    .write()
+                    .on_attestation(chain.slot().unwrap(), &conflicting)
+                    .unwrap();
+
+                expected = Some(ConflictingVote {
+                    validator_index: attestation.attesting_indices[0],
+                    target_epoch: attestation.data.target.epoch,
+                    block_root: attestation.data.beacon_block_root,
+                    conflicting_root: parent_root,
+                });
+            },
+            // The conflicting vote is ignored rather than rejected.
+            |result| assert_eq!(result.unwrap(), ()),
+        );
+
+    (test, expected.unwrap())
+}
+
+#[test]
+fn conflicting_votes_are_reported() {
+    let (test, expected) =
+        apply_conflicting_votes(ForkChoiceTest::new().set_track_conflicting_votes(true));
+
+    assert_eq!(
+        test.get(|fc_store| fc_store.conflicting_vote(
+            expected.validator_index,
+            expected.target_epoch,
+            expected.block_root
+        )),
+        Some(expected.conflicting_root)
+    );
+    assert_eq!(
+        test.get(|fc_store| fc_store.conflicting_vote(
+            expected.validator_index,
+            expected.target_epoch,
+            expected.conflicting_root
+        )),
+        None,
+        "the recorded vote does not conflict with itself"
+    );
+    assert_eq!(
+        test.get(|fc_store| fc_store.conflicting_vote(
+            expected.validator_index,
+            expected.target_epoch + 1,
+            expected.block_root
+        )),
+        None,
+        "a vote in a later epoch does not conflict"
+    );
+
+    test.inspect_conflicting_votes(|votes| assert_eq!(votes, &[expected]))
+        .inspect_conflicting_votes(|votes| assert!(votes.is_empty()));
+}
+
+#[test]
+fn conflicting_votes_are_not_tracked_by_default() {
+    let (test, expected) = apply_conflicting_votes(ForkChoiceTest::new());
+
+    assert_eq!(
+        test.get(|fc_store| fc_store.conflicting_vote(
+            expected.validator_index,
+            expected.target_epoch,
+            expected.block_root
+        )),
+        None
+    );
+
+    test.inspect_conflicting_votes(|votes| assert!(votes.is_empty()));
+}
+
+/// Ensures that attestations are rejected once the queue is full, rather than growing it without
+/// bound.
+#[test]
+fn invalid_attestation_queue_full() {
+    ForkChoiceTest::new()
+        .apply_blocks_without_new_attestations(1)
+        .set_max_queued_attestations(1)
+        .apply_attestation_to_chain(
+            MutationDelay::NoDelay,
+            |attestation, chain| {
+                // Fill the queue with a copy of the attestation before it is applied.
+                chain
+                    .fork_choice
+                    .write()
+                    .on_attestation(chain.slot().unwrap(), attestation)
+                    .unwrap();
+            },
+            |result| {
+                assert_invalid_attestation!(
+                    result,
+                    InvalidAttestation::TooManyQueuedAttestations {
+                        max_queued_attestations: 1
+                    }
+                )
+            },
+        )
+        .inspect_queued_attestations(|queue| assert_eq!(queue.len(), 1))
+        .skip_slot()
+        .inspect_queued_attestations(|queue| assert_eq!(queue.len(), 0));
+}
+
 /// Tests that the correct target root is used when the attested-to block is in a prior epoch to
 /// the attestation.
 #[test]
@@ -963,6 +1429,34 @@ fn valid_attestation_skip_across_epoch() {
         );
 }
 
+/// Ensures that the head returned when applying a block matches a subsequent call to `get_head`.
+#[test]
+fn on_block_and_get_head() {
+    let test = ForkChoiceTest::new().apply_blocks(2);
+    let harness = &test.harness;
+
+    let head = harness.chain.head().unwrap();
+    let current_slot = head.beacon_block.slot() + 1;
+    harness.set_current_slot(current_slot);
+    let (block, state) = harness.make_block(head.beacon_state, current_slot);
+    let block_root = block.canonical_root();
+
+    let mut fork_choice = harness.chain.fork_choice.write();
+    let head = fork_choice
+        .on_block_and_get_head(
+            current_slot,
+            &block.message,
+            block_root,
+            Duration::from_secs(0),
+            &state,
+            &harness.chain.spec,
+        )
+        .unwrap();
+
+    assert_eq!(head, block_root, "the new block should become the head");
+    assert_eq!(head, fork_choice.get_head(current_slot).unwrap());
+}
+
 #[test]
 fn can_read_finalized_block() {
     ForkChoiceTest::new()
@@ -981,6 +1475,7 @@ fn weak_subjectivity_fail_on_startup() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(Checkpoint { epoch, root }),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config);
@@ -994,6 +1489,7 @@ fn weak_subjectivity_pass_on_startup() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(Checkpoint { epoch, root }),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config)
@@ -1001,6 +1497,26 @@ fn weak_subjectivity_pass_on_startup() {
         .assert_shutdown_signal_not_sent();
 }
 
+#[test]
+fn prune_retain_slots_from_chain_config() {
+    let chain_config = ChainConfig {
+        fork_choice_prune_retain_slots: 8,
+        ..ChainConfig::default()
+    };
+
+    let test = ForkChoiceTest::new_with_chain_config(chain_config);
+    assert_eq!(
+        test.harness
+            .chain
+            .fork_choice
+            .read()
+            .proto_array()
+            .core_proto_array()
+            .prune_retain_slots,
+        8
+    );
+}
+
 #[test]
 fn weak_subjectivity_check_passes() {
     let setup_harness = ForkChoiceTest::new()
@@ -1019,6 +1535,7 @@ fn weak_subjectivity_check_passes() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config.clone())
@@ -1049,6 +1566,7 @@ fn weak_subjectivity_check_fails_early_epoch() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config.clone())
@@ -1078,6 +1596,7 @@ fn weak_subjectivity_check_fails_late_epoch() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config.clone())
@@ -1107,6 +1626,7 @@ fn weak_subjectivity_check_fails_incorrect_root() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     ForkChoiceTest::new_with_chain_config(chain_config.clone())
@@ -1143,6 +1663,7 @@ fn weak_subjectivity_check_epoch_boundary_is_skip_slot() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     // recreate the chain exactly
@@ -1184,6 +1705,7 @@ fn weak_subjectivity_check_epoch_boundary_is_skip_slot_failure() {
     let chain_config = ChainConfig {
         weak_subjectivity_checkpoint: Some(checkpoint),
         import_max_skip_slots: None,
+        ..ChainConfig::default()
     };
 
     // recreate the chain exactly
@@ -1196,3 +1718,362 @@ fn weak_subjectivity_check_epoch_boundary_is_skip_slot_failure() {
         .assert_finalized_epoch_is_less_than(checkpoint.epoch)
         .assert_shutdown_signal_sent();
 }
+
+/// Ensures that a timely block receives the proposer boost and that the boost is reset at the
+/// start of the next slot.
+#[test]
+fn proposer_boost_reset_on_new_slot() {
+    ForkChoiceTest::new()
+        .apply_blocks(1)
+        .apply_block_directly_to_fork_choice(|_, _| {})
+        .assert_proposer_boost_root_is_some(true)
+        .skip_slot()
+        .assert_proposer_boost_root_is_some(false);
+}
+
+/// Ensures that the proposer boost is determined by the time a block was first seen, rather than
+/// the time it was imported.
+#[test]
+fn proposer_boost_measured_from_seen_timestamp() {
+    let test = ForkChoiceTest::new().apply_blocks(1);
+    let harness = &test.harness;
+    let slot_clock = &harness.chain.slot_clock;
+
+    let head = harness.chain.head().unwrap();
+
+    // A block which was seen at the start of its slot is timely.
+    let timely_slot = head.beacon_block.slot() + 1;
+    harness.set_current_slot(timely_slot);
+    let (timely_block, timely_state) = harness.make_block(head.beacon_state, timely_slot);
+    let timely_root = timely_block.canonical_root();
+    harness
+        .chain
+        .process_block(timely_block, slot_clock.start_of(timely_slot).unwrap())
+        .unwrap();
+    assert_eq!(
+        test.get(|fc_store| fc_store.proposer_boost_root()),
+        Some(timely_root),
+        "timely block should be boosted"
+    );
+
+    // A block which was seen a whole slot late is not timely.
+    let late_slot = timely_slot + 1;
+    harness.set_current_slot(late_slot);
+    let (late_block, _) = harness.make_block(timely_state, late_slot);
+    harness
+        .chain
+        .process_block(
+            late_block,
+            slot_clock.start_of(late_slot).unwrap() + slot_clock.slot_duration(),
+        )
+        .unwrap();
+    assert_eq!(
+        test.get(|fc_store| fc_store.proposer_boost_root()),
+        None,
+        "late block should not be boosted"
+    );
+}
+
+/// Builds two competing branches from a common ancestor and ensures that switching between them
+/// is reported as a re-org.
+#[test]
+fn reorg_is_reported_by_get_head() {
+    let test = ForkChoiceTest::new().apply_blocks(2);
+    let harness = &test.harness;
+
+    let ancestor = harness.chain.head().unwrap();
+    let ancestor_slot = ancestor.beacon_block.slot();
+    let all_validators = (0..VALIDATOR_COUNT).collect::<Vec<_>>();
+
+    // Import a block on the first branch, without running fork choice via the beacon chain.
+    let first_slot = ancestor_slot + 1;
+    harness.set_current_slot(first_slot);
+    let (first_block, _) = harness.make_block(ancestor.beacon_state.clone(), first_slot);
+    let first_root = first_block.canonical_root();
+    harness
+        .chain
+        .process_block(first_block, timestamp_now())
+        .unwrap();
+
+    let head = harness
+        .chain
+        .fork_choice
+        .write()
+        .get_head_with_reorg_info(first_slot)
+        .unwrap();
+    assert_eq!(head.root, first_root, "first branch should be the head");
+    assert_eq!(head.reorg, None, "extending the head is not a re-org");
+
+    // Import a competing block which skips `first_slot`, then attest to it.
+    let second_slot = first_slot + 1;
+    harness.set_current_slot(second_slot);
+    let (second_block, second_state) = harness.make_block(ancestor.beacon_state, second_slot);
+    let second_root = second_block.canonical_root();
+    let second_state_root = second_block.message.state_root;
+    let second_hash = harness
+        .chain
+        .process_block(second_block.clone(), timestamp_now())
+        .unwrap();
+    harness.attest_block(
+        &second_state,
+        second_state_root,
+        second_hash.into(),
+        &second_block,
+        &all_validators,
+    );
+
+    // Advance a slot so the attestations are dequeued and the proposer boost is cleared.
+    let head = harness
+        .chain
+        .fork_choice
+        .write()
+        .get_head_with_reorg_info(second_slot + 1)
+        .unwrap();
+    assert_eq!(head.root, second_root, "second branch should be the head");
+    assert_eq!(
+        head.reorg,
+        Some(ReorgInfo {
+            old_head_root: first_root,
+            common_ancestor_slot: ancestor_slot,
+            depth: 1,
+        })
+    );
+
+    // Running fork choice again without any changes should not report another re-org.
+    let head = harness
+        .chain
+        .fork_choice
+        .write()
+        .get_head_with_reorg_info(second_slot + 1)
+        .unwrap();
+    assert_eq!(head.root, second_root);
+    assert_eq!(head.reorg, None);
+}
+
+/// Journals fork choice as a snapshot followed by a series of diffs whilst the chain justifies and
+/// finalizes, then ensures that replaying the diffs matches a full snapshot.
+#[test]
+fn persisted_diffs_match_full_snapshot() {
+    let mut test = ForkChoiceTest::new();
+    let store = test.harness.chain.store.clone();
+
+    let (base, base_fc_store, mut node_count) = {
+        let fork_choice = test.harness.chain.fork_choice.read();
+        (
+            fork_choice.to_persisted(),
+            fork_choice.fc_store().to_persisted(),
+            fork_choice.proto_array().len(),
+        )
+    };
+
+    let mut diffs = vec![];
+    for _ in 0..3 {
+        test = test.apply_blocks(E::slots_per_epoch() as usize * 2);
+
+        let fork_choice = test.harness.chain.fork_choice.read();
+        diffs.push(fork_choice.persisted_diff_since(node_count));
+        node_count = fork_choice.proto_array().len();
+    }
+
+    let fork_choice = test.harness.chain.fork_choice.read();
+
+    let full = ForkChoice::<_, E>::from_persisted(
+        fork_choice.to_persisted(),
+        BeaconForkChoiceStore::from_persisted(fork_choice.fc_store().to_persisted(), store.clone())
+            .unwrap(),
+    )
+    .unwrap();
+    let replayed = ForkChoice::<_, E>::from_persisted_with_diffs(
+        base,
+        &diffs,
+        BeaconForkChoiceStore::from_persisted(base_fc_store, store).unwrap(),
+    )
+    .unwrap();
+
+    assert!(
+        replayed.proto_array() == full.proto_array(),
+        "replayed proto_array should match the full snapshot"
+    );
+    assert!(replayed.queued_attestations() == full.queued_attestations());
+    assert!(
+        full.finalized_checkpoint().epoch > 0,
+        "chain should finalize"
+    );
+    assert_eq!(
+        replayed.fc_store().justified_checkpoint(),
+        full.fc_store().justified_checkpoint(),
+        "justified_checkpoint"
+    );
+    assert_eq!(
+        replayed.finalized_checkpoint(),
+        full.finalized_checkpoint(),
+        "finalized_checkpoint"
+    );
+    assert_eq!(
+        replayed.fc_store().best_justified_checkpoint(),
+        full.fc_store().best_justified_checkpoint(),
+        "best_justified_checkpoint"
+    );
+    assert_eq!(
+        replayed.fc_store().justified_balances(),
+        full.fc_store().justified_balances(),
+        "justified_balances"
+    );
+}
+
+/// Builds a linear chain with some skip slots and checks the ancestry queries against the block
+/// roots known to the beacon chain.
+#[test]
+fn ancestry_queries_with_skip_slots() {
+    let test = ForkChoiceTest::new()
+        .apply_blocks(2)
+        .skip_slots(2)
+        .apply_blocks(2)
+        .skip_slots(1)
+        .apply_blocks(1);
+    let chain = &test.harness.chain;
+
+    let head = chain.head().unwrap();
+    let head_root = head.beacon_block_root;
+    let head_slot = head.beacon_block.slot();
+
+    let blocks = (0..=head_slot.as_u64())
+        .map(Slot::new)
+        .filter_map(|slot| {
+            chain
+                .block_root_at_slot(slot, WhenSlotSkipped::None)
+                .unwrap()
+                .map(|root| (root, slot))
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        blocks.len() < head_slot.as_usize() + 1,
+        "chain should have skip slots"
+    );
+
+    let fork_choice = chain.fork_choice.read();
+
+    for slot in (0..=head_slot.as_u64() + 2).map(Slot::new) {
+        let expected = chain
+            .block_root_at_slot(std::cmp::min(slot, head_slot), WhenSlotSkipped::Prev)
+            .unwrap();
+        assert_eq!(
+            fork_choice.ancestor_root_at_slot(head_root, slot),
+            expected,
+            "ancestor at slot {}",
+            slot
+        );
+    }
+
+    for (ancestor_root, ancestor_slot) in &blocks {
+        for (descendant_root, descendant_slot) in &blocks {
+            assert_eq!(
+                fork_choice.is_descendant(*ancestor_root, *descendant_root),
+                ancestor_slot <= descendant_slot,
+                "is_descendant({}, {})",
+                ancestor_slot,
+                descendant_slot
+            );
+        }
+    }
+
+    let unknown_root = Hash256::from_low_u64_be(42);
+    assert!(!fork_choice.is_descendant(unknown_root, head_root));
+    assert!(!fork_choice.is_descendant(head_root, unknown_root));
+    assert_eq!(
+        fork_choice.ancestor_root_at_slot(unknown_root, head_slot),
+        None
+    );
+}
+
+/// Builds a fork choice from a finalized checkpoint (rather than genesis) and replays the
+/// subsequent blocks, ensuring it arrives at the same head as the chain.
+#[test]
+fn fork_choice_from_finalized_anchor() {
+    let test = ForkChoiceTest::new().apply_blocks(E::slots_per_epoch() as usize * 5);
+    let chain = &test.harness.chain;
+
+    let head_info = chain.head_info().unwrap();
+    let anchor_root = head_info.finalized_checkpoint.root;
+    assert!(
+        head_info.finalized_checkpoint.epoch > 0,
+        "chain should finalize"
+    );
+
+    let anchor_block = chain.get_block(&anchor_root).unwrap().unwrap();
+    let anchor_slot = anchor_block.slot();
+    let anchor_state = chain
+        .get_state(&anchor_block.state_root(), Some(anchor_slot))
+        .unwrap()
+        .unwrap();
+    assert!(anchor_slot > 0, "anchor should not be genesis");
+
+    let anchor = BeaconSnapshot {
+        beacon_block: anchor_block,
+        beacon_block_root: anchor_root,
+        beacon_state: anchor_state,
+    };
+
+    // The fork choice store must be built from the same anchor.
+    let genesis_fc_store =
+        BeaconForkChoiceStore::get_forkchoice_store(chain.store.clone(), &chain.head().unwrap());
+    assert!(matches!(
+        ForkChoice::from_anchor(
+            genesis_fc_store,
+            anchor_root,
+            &anchor.beacon_block.message,
+            &anchor.beacon_state,
+            DEFAULT_PRUNE_THRESHOLD,
+        ),
+        Err(ForkChoiceError::InvalidAnchor { .. })
+    ));
+
+    let fc_store = BeaconForkChoiceStore::get_forkchoice_store(chain.store.clone(), &anchor);
+    let mut fork_choice = ForkChoice::from_anchor(
+        fc_store,
+        anchor_root,
+        &anchor.beacon_block.message,
+        &anchor.beacon_state,
+        DEFAULT_PRUNE_THRESHOLD,
+    )
+    .unwrap();
+
+    assert_eq!(fork_choice.proto_array().len(), 1);
+    assert_eq!(
+        fork_choice.get_block(&anchor_root).map(|block| block.slot),
+        Some(anchor_slot)
+    );
+    assert_eq!(fork_choice.get_head(anchor_slot).unwrap(), anchor_root);
+
+    for slot in (anchor_slot.as_u64() + 1..=head_info.slot.as_u64()).map(Slot::new) {
+        let block_root = chain
+            .block_root_at_slot(slot, WhenSlotSkipped::None)
+            .unwrap()
+            .unwrap();
+        let block = chain.get_block(&block_root).unwrap().unwrap();
+        let state = chain
+            .get_state(&block.state_root(), Some(slot))
+            .unwrap()
+            .unwrap();
+
+        fork_choice
+            .on_block(
+                slot,
+                &block.message,
+                block_root,
+                Duration::from_secs(0),
+                &state,
+                &chain.spec,
+            )
+            .unwrap();
+    }
+
+    assert_eq!(
+        fork_choice.get_head(head_info.slot).unwrap(),
+        head_info.block_root
+    );
+    assert_eq!(
+        fork_choice.finalized_checkpoint(),
+        head_info.finalized_checkpoint
+    );
+}
@@ -0,0 +1,121 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use proto_array::{compute_balance_changes, Block, ProtoArrayForkChoice, DEFAULT_PRUNE_THRESHOLD};
+use types::{AttestationShufflingId, Epoch, Hash256, Slot};
+
+const VALIDATOR_COUNT: usize = 500_000;
+const BLOCK_COUNT: usize = 64;
+
+fn get_hash(i: u64) -> Hash256 {
+    Hash256::from_low_u64_be(i + 1)
+}
+
+/// Returns a fork choice with a single chain of `BLOCK_COUNT` blocks and a vote from every
+/// validator, along with the balances which have already been applied to it.
+fn get_fork_choice() -> (ProtoArrayForkChoice, Vec<u64>) {
+    let junk_shuffling_id = AttestationShufflingId::from_components(Epoch::new(0), Hash256::zero());
+    let mut fork_choice = ProtoArrayForkChoice::new(
+        Slot::new(0),
+        Hash256::zero(),
+        Epoch::new(0),
+        Epoch::new(0),
+        get_hash(0),
+        junk_shuffling_id.clone(),
+        junk_shuffling_id.clone(),
+        DEFAULT_PRUNE_THRESHOLD,
+    )
+    .expect("should create fork choice");
+
+    for i in 1..BLOCK_COUNT as u64 {
+        fork_choice
+            .process_block(Block {
+                slot: Slot::new(i),
+                root: get_hash(i),
+                parent_root: Some(get_hash(i - 1)),
+                state_root: Hash256::zero(),
+                target_root: get_hash(0),
+                current_epoch_shuffling_id: junk_shuffling_id.clone(),
+                next_epoch_shuffling_id: junk_shuffling_id.clone(),
+                justified_epoch: Epoch::new(0),
+                finalized_epoch: Epoch::new(0),
+            })
+            .expect("should process block");
+    }
+
+    for validator_index in 0..VALIDATOR_COUNT {
+        let block = get_hash((validator_index % BLOCK_COUNT) as u64);
+        fork_choice
+            .process_attestation(validator_index, block, Epoch::new(1))
+            .expect("should process attestation");
+    }
+
+    let balances = vec![32_000_000_000; VALIDATOR_COUNT];
+    fork_choice
+        .find_head(Epoch::new(0), get_hash(0), Epoch::new(0), &balances, None)
+        .expect("should find head");
+
+    (fork_choice, balances)
+}
+
+fn find_head(c: &mut Criterion) {
+    let (fork_choice, balances) = get_fork_choice();
+    let fork_choice_bytes = fork_choice.as_bytes();
+    let new_fork_choice = || ProtoArrayForkChoice::from_bytes(&fork_choice_bytes).unwrap();
+
+    c.bench_function("find_head with full balances", |b| {
+        b.iter_batched_ref(
+            new_fork_choice,
+            |fork_choice| {
+                black_box(
+                    fork_choice
+                        .find_head(Epoch::new(0), get_hash(0), Epoch::new(0), &balances, None)
+                        .unwrap(),
+                )
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("find_head with balance changes", |b| {
+        b.iter_batched_ref(
+            new_fork_choice,
+            |fork_choice| {
+                let balance_changes = compute_balance_changes(fork_choice.balances(), &balances);
+                black_box(
+                    fork_choice
+                        .find_head_with_balance_changes(
+                            Epoch::new(0),
+                            get_hash(0),
+                            Epoch::new(0),
+                            &balance_changes,
+                            None,
+                        )
+                        .unwrap(),
+                )
+            },
+            BatchSize::LargeInput,
+        )
+    });
+
+    c.bench_function("find_head with no balance changes", |b| {
+        b.iter_batched_ref(
+            new_fork_choice,
+            |fork_choice| {
+                black_box(
+                    fork_choice
+                        .find_head_with_balance_changes(
+                            Epoch::new(0),
+                            get_hash(0),
+                            Epoch::new(0),
+                            &[],
+                            None,
+                        )
+                        .unwrap(),
+                )
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, find_head);
+criterion_main!(benches);
@@ -6,6 +6,8 @@ fn main() {
     write_test_def_to_yaml("no_votes.yaml", get_no_votes_test_definition());
     write_test_def_to_yaml("ffg_01.yaml", get_ffg_case_01_test_definition());
     write_test_def_to_yaml("ffg_02.yaml", get_ffg_case_02_test_definition());
+    write_test_def_to_yaml("proposer_boost.yaml", get_proposer_boost_test_definition());
+    write_test_def_to_yaml("invalid_blocks.yaml", get_invalid_blocks_test_definition());
 }
 
 fn write_test_def_to_yaml(filename: &str, def: ForkChoiceTestDefinition) {
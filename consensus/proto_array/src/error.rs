@@ -4,12 +4,19 @@ use types::{Epoch, Hash256};
 pub enum Error {
     FinalizedNodeUnknown(Hash256),
     JustifiedNodeUnknown(Hash256),
+    NodeUnknown(Hash256),
     InvalidFinalizedRootChange,
     InvalidNodeIndex(usize),
     InvalidParentIndex(usize),
     InvalidBestChildIndex(usize),
     InvalidJustifiedIndex(usize),
     InvalidBestDescendant(usize),
+    NodeIsOwnParent(usize),
+    NodeIndexMismatch {
+        root: Hash256,
+        node_index: usize,
+        indices_entry: Option<usize>,
+    },
     InvalidParentDelta(usize),
     InvalidNodeDelta(usize),
     DeltaOverflow(usize),
@@ -18,6 +25,10 @@ pub enum Error {
         deltas: usize,
         indices: usize,
     },
+    InvalidIndicesLen {
+        indices: usize,
+        nodes: usize,
+    },
     RevertedFinalizedEpoch {
         current_finalized_epoch: Epoch,
         new_finalized_epoch: Epoch,
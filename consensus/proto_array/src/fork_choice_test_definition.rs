@@ -1,13 +1,19 @@
 mod ffg_updates;
+mod invalid_blocks;
 mod no_votes;
+mod proposer_boost;
 mod votes;
 
-use crate::proto_array_fork_choice::{Block, ProtoArrayForkChoice};
+use crate::proto_array_fork_choice::{
+    Block, ProposerBoost, ProtoArrayForkChoice, DEFAULT_PRUNE_THRESHOLD,
+};
 use serde_derive::{Deserialize, Serialize};
 use types::{AttestationShufflingId, Epoch, Hash256, Slot};
 
 pub use ffg_updates::*;
+pub use invalid_blocks::*;
 pub use no_votes::*;
+pub use proposer_boost::*;
 pub use votes::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +31,15 @@ pub enum Operation {
         finalized_epoch: Epoch,
         justified_state_balances: Vec<u64>,
     },
+    ProposerBoostFindHead {
+        justified_epoch: Epoch,
+        justified_root: Hash256,
+        finalized_epoch: Epoch,
+        justified_state_balances: Vec<u64>,
+        proposer_boost_root: Hash256,
+        proposer_boost_score: u64,
+        expected_head: Hash256,
+    },
     ProcessBlock {
         slot: Slot,
         root: Hash256,
@@ -42,6 +57,9 @@ pub enum Operation {
         prune_threshold: usize,
         expected_len: usize,
     },
+    InvalidateBlock {
+        block_root: Hash256,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -65,6 +83,7 @@ impl ForkChoiceTestDefinition {
             self.finalized_root,
             junk_shuffling_id.clone(),
             junk_shuffling_id,
+            DEFAULT_PRUNE_THRESHOLD,
         )
         .expect("should create fork choice struct");
 
@@ -83,6 +102,7 @@ impl ForkChoiceTestDefinition {
                             justified_root,
                             finalized_epoch,
                             &justified_state_balances,
+                            None,
                         )
                         .unwrap_or_else(|_| {
                             panic!("find_head op at index {} returned error", op_index)
@@ -106,6 +126,7 @@ impl ForkChoiceTestDefinition {
                         justified_root,
                         finalized_epoch,
                         &justified_state_balances,
+                        None,
                     );
 
                     assert!(
@@ -116,6 +137,40 @@ impl ForkChoiceTestDefinition {
                     );
                     check_bytes_round_trip(&fork_choice);
                 }
+                Operation::ProposerBoostFindHead {
+                    justified_epoch,
+                    justified_root,
+                    finalized_epoch,
+                    justified_state_balances,
+                    proposer_boost_root,
+                    proposer_boost_score,
+                    expected_head,
+                } => {
+                    let head = fork_choice
+                        .find_head(
+                            justified_epoch,
+                            justified_root,
+                            finalized_epoch,
+                            &justified_state_balances,
+                            Some(ProposerBoost {
+                                root: proposer_boost_root,
+                                score: proposer_boost_score,
+                            }),
+                        )
+                        .unwrap_or_else(|_| {
+                            panic!(
+                                "proposer_boost_find_head op at index {} returned error",
+                                op_index
+                            )
+                        });
+
+                    assert_eq!(
+                        head, expected_head,
+                        "Operation at index {} failed checks. Operation: {:?}",
+                        op_index, op
+                    );
+                    check_bytes_round_trip(&fork_choice);
+                }
                 Operation::ProcessBlock {
                     slot,
                     root,
@@ -183,6 +238,17 @@ impl ForkChoiceTestDefinition {
                         expected_len
                     );
                 }
+                Operation::InvalidateBlock { block_root } => {
+                    fork_choice
+                        .on_invalid_block(block_root)
+                        .unwrap_or_else(|e| {
+                            panic!(
+                                "invalidate_block op at index {} returned error: {:?}",
+                                op_index, e
+                            )
+                        });
+                    check_bytes_round_trip(&fork_choice);
+                }
             }
         }
     }
@@ -0,0 +1,160 @@
+use super::*;
+
+pub fn get_invalid_blocks_test_definition() -> ForkChoiceTestDefinition {
+    let balances = vec![10; 3];
+    let mut ops = vec![];
+
+    // Build the following tree.
+    //
+    //          0
+    //         / \
+    //        1   4
+    //       / \
+    //      2   5
+    //      |
+    //      3
+    for (slot, root, parent_root) in &[(1, 1, 0), (1, 4, 0), (2, 2, 1), (2, 5, 1), (3, 3, 2)] {
+        ops.push(Operation::ProcessBlock {
+            slot: Slot::new(*slot),
+            root: get_hash(*root),
+            parent_root: get_hash(*parent_root),
+            justified_epoch: Epoch::new(1),
+            finalized_epoch: Epoch::new(1),
+        });
+    }
+
+    // Add votes so that the branch containing 1 is heavier than 4.
+    //
+    //          0
+    //         / \
+    //        1   4 <- +10
+    //       / \
+    //      2   5
+    //      |
+    //      3 <- +20
+    for validator_index in 0..2 {
+        ops.push(Operation::ProcessAttestation {
+            validator_index,
+            block_root: get_hash(3),
+            target_epoch: Epoch::new(2),
+        });
+    }
+    ops.push(Operation::ProcessAttestation {
+        validator_index: 2,
+        block_root: get_hash(4),
+        target_epoch: Epoch::new(2),
+    });
+
+    // Ensure that the head is 3.
+    //
+    //          0
+    //         / \
+    //        1   4
+    //       / \
+    //      2   5
+    //      |
+    //      3 <- head
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        expected_head: get_hash(3),
+    });
+
+    // Invalidate 2, which also invalidates 3. The head moves to the sibling of 2, since 1 still
+    // carries the votes for 3.
+    //
+    //          0
+    //         / \
+    //        1   4
+    //       / \
+    //     x2   5 <- head
+    //      |
+    //     x3
+    ops.push(Operation::InvalidateBlock {
+        block_root: get_hash(2),
+    });
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        expected_head: get_hash(5),
+    });
+
+    // Add a block with a hash of 6 on top of the invalid block 3. It is also invalid, so the head
+    // does not change, even with all the votes.
+    //
+    //          0
+    //         / \
+    //        1   4
+    //       / \
+    //     x2   5 <- head
+    //      |
+    //     x3
+    //      |
+    //     x6 <- +30
+    ops.push(Operation::ProcessBlock {
+        slot: Slot::new(4),
+        root: get_hash(6),
+        parent_root: get_hash(3),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+    });
+    for validator_index in 0..3 {
+        ops.push(Operation::ProcessAttestation {
+            validator_index,
+            block_root: get_hash(6),
+            target_epoch: Epoch::new(3),
+        });
+    }
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        expected_head: get_hash(5),
+    });
+
+    // Invalidate 1. The head falls back to the only remaining valid branch.
+    //
+    //          0
+    //         / \
+    //       x1   4 <- head
+    //       / \
+    //     x2  x5
+    //      |
+    //     x3
+    //      |
+    //     x6
+    ops.push(Operation::InvalidateBlock {
+        block_root: get_hash(1),
+    });
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances,
+        expected_head: get_hash(4),
+    });
+
+    ForkChoiceTestDefinition {
+        finalized_block_slot: Slot::new(0),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+        finalized_root: get_hash(0),
+        operations: ops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test = get_invalid_blocks_test_definition();
+        test.run();
+    }
+}
@@ -0,0 +1,159 @@
+use super::*;
+
+pub fn get_proposer_boost_test_definition() -> ForkChoiceTestDefinition {
+    let balances = vec![10; 3];
+    let mut ops = vec![];
+
+    // Add a block with a hash of 1, which will become the "late" fork.
+    //
+    //          0
+    //         /
+    //        1
+    ops.push(Operation::ProcessBlock {
+        slot: Slot::new(1),
+        root: get_hash(1),
+        parent_root: get_hash(0),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+    });
+
+    // Add a block with a hash of 2, which will become the "timely" fork.
+    //
+    //          0
+    //         / \
+    //        1   2
+    ops.push(Operation::ProcessBlock {
+        slot: Slot::new(2),
+        root: get_hash(2),
+        parent_root: get_hash(0),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+    });
+
+    // Add votes so that block 1 is slightly heavier than block 2.
+    //
+    //          0
+    //         / \
+    //  +20-> 1   2 <-+10
+    for validator_index in 0..2 {
+        ops.push(Operation::ProcessAttestation {
+            validator_index,
+            block_root: get_hash(1),
+            target_epoch: Epoch::new(2),
+        });
+    }
+    ops.push(Operation::ProcessAttestation {
+        validator_index: 2,
+        block_root: get_hash(2),
+        target_epoch: Epoch::new(2),
+    });
+
+    // Ensure that the head is 1, the heavier block.
+    //
+    //          0
+    //         / \
+    // head-> 1   2
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        expected_head: get_hash(1),
+    });
+
+    // Boost block 2, which is enough to outweigh block 1.
+    //
+    //          0
+    //         / \
+    //        1   2 <- head (+15 boost)
+    ops.push(Operation::ProposerBoostFindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        proposer_boost_root: get_hash(2),
+        proposer_boost_score: 15,
+        expected_head: get_hash(2),
+    });
+
+    // Ensure that the head reverts to 1 once the boost is no longer supplied.
+    //
+    //          0
+    //         / \
+    // head-> 1   2
+    ops.push(Operation::FindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        expected_head: get_hash(1),
+    });
+
+    // Add a block with a hash of 3, as a child of 2.
+    //
+    //          0
+    //         / \
+    //        1   2
+    //            |
+    //            3
+    ops.push(Operation::ProcessBlock {
+        slot: Slot::new(3),
+        root: get_hash(3),
+        parent_root: get_hash(2),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+    });
+
+    // Boost block 3. The boost applies to its ancestor 2, which is enough to outweigh block 1.
+    //
+    //          0
+    //         / \
+    //        1   2
+    //            |
+    //            3 <- head (+15 boost)
+    ops.push(Operation::ProposerBoostFindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances.clone(),
+        proposer_boost_root: get_hash(3),
+        proposer_boost_score: 15,
+        expected_head: get_hash(3),
+    });
+
+    // Ensure that a boost which is too small does not outweigh block 1.
+    //
+    //          0
+    //         / \
+    // head-> 1   2
+    //            |
+    //            3 (+5 boost)
+    ops.push(Operation::ProposerBoostFindHead {
+        justified_epoch: Epoch::new(1),
+        justified_root: get_hash(0),
+        finalized_epoch: Epoch::new(1),
+        justified_state_balances: balances,
+        proposer_boost_root: get_hash(3),
+        proposer_boost_score: 5,
+        expected_head: get_hash(1),
+    });
+
+    ForkChoiceTestDefinition {
+        finalized_block_slot: Slot::new(0),
+        justified_epoch: Epoch::new(1),
+        finalized_epoch: Epoch::new(1),
+        finalized_root: get_hash(0),
+        operations: ops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let test = get_proposer_boost_test_definition();
+        test.run();
+    }
+}
@@ -4,7 +4,10 @@ mod proto_array;
 mod proto_array_fork_choice;
 mod ssz_container;
 
-pub use crate::proto_array_fork_choice::{Block, ProtoArrayForkChoice};
+pub use crate::proto_array_fork_choice::{
+    compute_balance_changes, BalanceChange, Block, ProposerBoost, ProtoArrayForkChoice,
+    DEFAULT_PRUNE_THRESHOLD,
+};
 pub use error::Error;
 
 pub mod core {
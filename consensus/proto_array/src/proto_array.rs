@@ -24,9 +24,12 @@ pub struct ProtoNode {
     pub parent: Option<usize>,
     pub justified_epoch: Epoch,
     pub finalized_epoch: Epoch,
-    weight: u64,
-    best_child: Option<usize>,
-    best_descendant: Option<usize>,
+    pub(crate) weight: u64,
+    pub(crate) best_child: Option<usize>,
+    pub(crate) best_descendant: Option<usize>,
+    /// Set if the block (or one of its ancestors) has been found to be invalid, in which case it
+    /// is never viable for the head. See `ProtoArray::on_invalid_block`.
+    pub(crate) invalid: bool,
 }
 
 #[derive(PartialEq, Debug, Serialize, Deserialize, Clone)]
@@ -34,6 +37,12 @@ pub struct ProtoArray {
     /// Do not attempt to prune the tree unless it has at least this many nodes. Small prunes
     /// simply waste time.
     pub prune_threshold: usize,
+    /// When pruning, retain any node preceding the finalized node whose slot is less than this
+    /// many slots before the finalized slot. Zero retains nothing.
+    ///
+    /// This is not persisted, it is reset to zero when decoding from SSZ.
+    #[serde(default)]
+    pub prune_retain_slots: u64,
     pub justified_epoch: Epoch,
     pub finalized_epoch: Epoch,
     pub nodes: Vec<ProtoNode>,
@@ -54,12 +63,16 @@ impl ProtoArray {
     /// - Compare the current node with the parents best-child, updating it if the current node
     /// should become the best child.
     /// - If required, update the parents best-descendant with the current node or its best-descendant.
+    ///
+    /// Returns the roots of all nodes whose best-child is different at the end of the pass, in
+    /// the order they appear in `self.nodes`. A change in the best-child of any ancestor of the
+    /// head indicates that the head may have changed.
     pub fn apply_score_changes(
         &mut self,
         mut deltas: Vec<i64>,
         justified_epoch: Epoch,
         finalized_epoch: Epoch,
-    ) -> Result<(), Error> {
+    ) -> Result<Vec<Hash256>, Error> {
         if deltas.len() != self.indices.len() {
             return Err(Error::InvalidDeltaLen {
                 deltas: deltas.len(),
@@ -124,6 +137,12 @@ impl ProtoArray {
             }
         }
 
+        let previous_best_children = self
+            .nodes
+            .iter()
+            .map(|node| node.best_child)
+            .collect::<Vec<_>>();
+
         // A second time, iterate backwards through all indices in `self.nodes`.
         //
         // We _must_ perform these functions separate from the weight-updating loop above to ensure
@@ -141,6 +160,42 @@ impl ProtoArray {
             }
         }
 
+        // A node's best-child may be modified several times during the pass (e.g., removed and
+        // then replaced by a sibling), so only compare the final value.
+        Ok(self
+            .nodes
+            .iter()
+            .zip(previous_best_children)
+            .filter(|(node, previous_best_child)| node.best_child != *previous_best_child)
+            .map(|(node, _)| node.root)
+            .collect())
+    }
+
+    /// Subtract `weight` from the node at `node_index` and all of its ancestors.
+    ///
+    /// This is used to revert a temporary weight (e.g., a proposer boost) which was applied via
+    /// `Self::apply_score_changes`. The best-child and best-descendant links are not modified; they
+    /// will be corrected by the next call to `Self::apply_score_changes`.
+    pub fn remove_weight(&mut self, node_index: usize, weight: u64) -> Result<(), Error> {
+        let mut next_index = Some(node_index);
+
+        while let Some(index) = next_index {
+            let node = self
+                .nodes
+                .get_mut(index)
+                .ok_or(Error::InvalidNodeIndex(index))?;
+
+            // The zero hash never has weight applied, see `Self::apply_score_changes`.
+            if node.root != Hash256::zero() {
+                node.weight = node
+                    .weight
+                    .checked_sub(weight)
+                    .ok_or(Error::DeltaOverflow(index))?;
+            }
+
+            next_index = node.parent;
+        }
+
         Ok(())
     }
 
@@ -154,6 +209,14 @@ impl ProtoArray {
         }
 
         let node_index = self.nodes.len();
+        let parent = block
+            .parent_root
+            .and_then(|parent| self.indices.get(&parent).copied());
+
+        // A block which descends from an invalid block is also invalid.
+        let invalid = parent
+            .and_then(|parent_index| self.nodes.get(parent_index))
+            .map_or(false, |parent| parent.invalid);
 
         let node = ProtoNode {
             slot: block.slot,
@@ -162,14 +225,13 @@ impl ProtoArray {
             current_epoch_shuffling_id: block.current_epoch_shuffling_id,
             next_epoch_shuffling_id: block.next_epoch_shuffling_id,
             state_root: block.state_root,
-            parent: block
-                .parent_root
-                .and_then(|parent| self.indices.get(&parent).copied()),
+            parent,
             justified_epoch: block.justified_epoch,
             finalized_epoch: block.finalized_epoch,
             weight: 0,
             best_child: None,
             best_descendant: None,
+            invalid,
         };
 
         self.indices.insert(node.root, node_index);
@@ -182,6 +244,53 @@ impl ProtoArray {
         Ok(())
     }
 
+    /// Mark the block with `block_root`, and all of its descendants, as invalid so that none of
+    /// them may become the head.
+    ///
+    /// ## Notes
+    ///
+    /// The best-child/best-descendant links are not modified; they will be corrected by the next
+    /// call to `Self::apply_score_changes`.
+    pub fn on_invalid_block(&mut self, block_root: Hash256) -> Result<(), Error> {
+        let block_index = *self
+            .indices
+            .get(&block_root)
+            .ok_or(Error::NodeUnknown(block_root))?;
+
+        self.nodes
+            .get_mut(block_index)
+            .ok_or(Error::InvalidNodeIndex(block_index))?
+            .invalid = true;
+
+        // Children always have a higher index than their parent, so a single forward pass will
+        // reach every descendant.
+        for node_index in block_index + 1..self.nodes.len() {
+            let parent_is_invalid = match self
+                .nodes
+                .get(node_index)
+                .ok_or(Error::InvalidNodeIndex(node_index))?
+                .parent
+            {
+                Some(parent_index) => {
+                    self.nodes
+                        .get(parent_index)
+                        .ok_or(Error::InvalidParentIndex(parent_index))?
+                        .invalid
+                }
+                None => false,
+            };
+
+            if parent_is_invalid {
+                self.nodes
+                    .get_mut(node_index)
+                    .ok_or(Error::InvalidNodeIndex(node_index))?
+                    .invalid = true;
+            }
+        }
+
+        Ok(())
+    }
+
     /// Follows the best-descendant links to find the best-block (i.e., head-block).
     ///
     /// ## Notes
@@ -230,6 +339,10 @@ impl ProtoArray {
     /// - The supplied finalized epoch and root are different to the current values.
     /// - The number of nodes in `self` is at least `self.prune_threshold`.
     ///
+    /// Nodes preceding the finalized node are removed, unless their slot is within
+    /// `self.prune_retain_slots` slots of the finalized slot. Retained nodes keep their relative
+    /// order, so the finalized node may no longer be the first node.
+    ///
     /// # Errors
     ///
     /// Returns errors if:
@@ -248,49 +361,80 @@ impl ProtoArray {
             return Ok(());
         }
 
+        let finalized_slot = self
+            .nodes
+            .get(finalized_index)
+            .ok_or(Error::InvalidNodeIndex(finalized_index))?
+            .slot;
+        let retain = |node: &ProtoNode| {
+            node.slot.as_u64().saturating_add(self.prune_retain_slots) > finalized_slot.as_u64()
+        };
+
+        // The new index of each node, or `None` if the node is to be removed.
+        let mut num_retained = 0;
+        let new_indices = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(node_index, node)| {
+                if node_index >= finalized_index || retain(node) {
+                    num_retained += 1;
+                    Some(num_retained - 1)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let new_index = |index: usize, field: &'static str| {
+            new_indices
+                .get(index)
+                .copied()
+                .ok_or(Error::InvalidNodeIndex(index))?
+                .ok_or(Error::IndexOverflow(field))
+        };
+
         // Remove the `self.indices` key/values for all the to-be-deleted nodes.
-        for node_index in 0..finalized_index {
-            let root = &self
-                .nodes
-                .get(node_index)
-                .ok_or(Error::InvalidNodeIndex(node_index))?
-                .root;
-            self.indices.remove(root);
+        for (node, index) in self.nodes.iter().zip(new_indices.iter()) {
+            if index.is_none() {
+                self.indices.remove(&node.root);
+            }
         }
 
-        // Drop all the nodes prior to finalization.
-        self.nodes = self.nodes.split_off(finalized_index);
-
         // Adjust the indices map.
         for (_root, index) in self.indices.iter_mut() {
-            *index = index
-                .checked_sub(finalized_index)
-                .ok_or(Error::IndexOverflow("indices"))?;
+            *index = new_index(*index, "indices")?;
         }
 
-        // Iterate through all the existing nodes and adjust their indices to match the new layout
+        // Iterate through all the retained nodes and adjust their indices to match the new layout
         // of `self.nodes`.
-        for node in self.nodes.iter_mut() {
+        //
+        // Descendants of a retained node are always retained since they have a higher slot, so
+        // only the parent of a node may have been removed.
+        for (node, _index) in self
+            .nodes
+            .iter_mut()
+            .zip(new_indices.iter())
+            .filter(|(_node, index)| index.is_some())
+        {
             if let Some(parent) = node.parent {
-                // If `node.parent` is less than `finalized_index`, set it to `None`.
-                node.parent = parent.checked_sub(finalized_index);
+                // If the parent has been removed, set it to `None`.
+                node.parent = new_indices
+                    .get(parent)
+                    .copied()
+                    .ok_or(Error::InvalidNodeIndex(parent))?;
             }
             if let Some(best_child) = node.best_child {
-                node.best_child = Some(
-                    best_child
-                        .checked_sub(finalized_index)
-                        .ok_or(Error::IndexOverflow("best_child"))?,
-                );
+                node.best_child = Some(new_index(best_child, "best_child")?);
             }
             if let Some(best_descendant) = node.best_descendant {
-                node.best_descendant = Some(
-                    best_descendant
-                        .checked_sub(finalized_index)
-                        .ok_or(Error::IndexOverflow("best_descendant"))?,
-                );
+                node.best_descendant = Some(new_index(best_descendant, "best_descendant")?);
             }
         }
 
+        // Drop all the nodes prior to finalization which are not retained.
+        let mut retained = new_indices.iter().map(Option::is_some);
+        self.nodes.retain(|_node| retained.next().unwrap_or(true));
+
         Ok(())
     }
 
@@ -417,13 +561,92 @@ impl ProtoArray {
     /// https://github.com/ethereum/eth2.0-specs/blob/v0.10.0/specs/phase0/fork-choice.md#filter_block_tree
     ///
     /// Any node that has a different finalized or justified epoch should not be viable for the
-    /// head.
+    /// head. Nor should any node which has been marked invalid.
     fn node_is_viable_for_head(&self, node: &ProtoNode) -> bool {
-        (node.justified_epoch == self.justified_epoch || self.justified_epoch == Epoch::new(0))
+        !node.invalid
+            && (node.justified_epoch == self.justified_epoch
+                || self.justified_epoch == Epoch::new(0))
             && (node.finalized_epoch == self.finalized_epoch
                 || self.finalized_epoch == Epoch::new(0))
     }
 
+    /// Verifies the internal consistency of `self`, returning an error describing the first
+    /// inconsistency found. Specifically:
+    ///
+    /// - `self.indices` maps the root of each node to its position in `self.nodes`, and nothing
+    /// else.
+    /// - The parent of each node precedes it in `self.nodes` (so no node is its own parent).
+    /// - The best-child of each node is one of its children.
+    /// - The best-descendant of each node is a valid index.
+    ///
+    /// This is intended to detect bugs (e.g., in the index adjustments of `Self::maybe_prune`)
+    /// rather than to be called during normal operation.
+    pub fn check_invariants(&self) -> Result<(), Error> {
+        if self.indices.len() != self.nodes.len() {
+            return Err(Error::InvalidIndicesLen {
+                indices: self.indices.len(),
+                nodes: self.nodes.len(),
+            });
+        }
+
+        for (node_index, node) in self.nodes.iter().enumerate() {
+            let indices_entry = self.indices.get(&node.root).copied();
+            if indices_entry != Some(node_index) {
+                return Err(Error::NodeIndexMismatch {
+                    root: node.root,
+                    node_index,
+                    indices_entry,
+                });
+            }
+
+            if let Some(parent_index) = node.parent {
+                if parent_index == node_index {
+                    return Err(Error::NodeIsOwnParent(node_index));
+                } else if parent_index > node_index {
+                    return Err(Error::InvalidParentIndex(parent_index));
+                }
+            }
+
+            if let Some(best_child_index) = node.best_child {
+                let best_child = self
+                    .nodes
+                    .get(best_child_index)
+                    .ok_or(Error::InvalidBestChildIndex(best_child_index))?;
+                if best_child.parent != Some(node_index) {
+                    return Err(Error::InvalidBestChildIndex(best_child_index));
+                }
+            }
+
+            if let Some(best_descendant_index) = node.best_descendant {
+                if best_descendant_index >= self.nodes.len() {
+                    return Err(Error::InvalidBestDescendant(best_descendant_index));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the root and weight of every node which is not the parent of any other node.
+    ///
+    /// These are the tips of all the chains currently tracked, some of which may not be viable
+    /// for the head (e.g., due to conflicting justification).
+    pub fn leaf_nodes(&self) -> Vec<(Hash256, u64)> {
+        let mut has_child = vec![false; self.nodes.len()];
+        for parent_index in self.nodes.iter().filter_map(|node| node.parent) {
+            if let Some(has_child) = has_child.get_mut(parent_index) {
+                *has_child = true;
+            }
+        }
+
+        self.nodes
+            .iter()
+            .zip(has_child)
+            .filter(|(_, has_child)| !has_child)
+            .map(|(node, _)| (node.root, node.weight))
+            .collect()
+    }
+
     /// Return a reverse iterator over the nodes which comprise the chain ending at `block_root`.
     pub fn iter_nodes<'a>(&'a self, block_root: &Hash256) -> Iter<'a> {
         let next_node_index = self.indices.get(block_root).copied();
@@ -1,6 +1,6 @@
 use crate::error::Error;
 use crate::proto_array::ProtoArray;
-use crate::ssz_container::SszContainer;
+use crate::ssz_container::{LegacySszContainer, SszContainer, SszContainerDiff};
 use ssz::{Decode, Encode};
 use ssz_derive::{Decode, Encode};
 use std::collections::HashMap;
@@ -31,6 +31,46 @@ pub struct Block {
     pub finalized_epoch: Epoch,
 }
 
+/// A boost to the weight of a single block, applied during `ProtoArrayForkChoice::find_head`.
+///
+/// The `score` is added to the block identified by `root` (and therefore also to all of its
+/// ancestors) for the duration of a single `find_head` call. It is never persisted in the weights
+/// of the tree.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ProposerBoost {
+    pub root: Hash256,
+    pub score: u64,
+}
+
+/// The new balance of a single validator, as used by
+/// `ProtoArrayForkChoice::find_head_with_balance_changes`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BalanceChange {
+    pub validator_index: usize,
+    pub balance: u64,
+}
+
+/// Returns a `BalanceChange` for each validator with a different balance in `new_balances` to
+/// `old_balances`. Validators which are missing from either list are assumed to have a balance of
+/// zero.
+pub fn compute_balance_changes(old_balances: &[u64], new_balances: &[u64]) -> Vec<BalanceChange> {
+    (0..std::cmp::max(old_balances.len(), new_balances.len()))
+        .filter_map(|validator_index| {
+            let old_balance = old_balances.get(validator_index).copied().unwrap_or(0);
+            let new_balance = new_balances.get(validator_index).copied().unwrap_or(0);
+
+            if old_balance != new_balance {
+                Some(BalanceChange {
+                    validator_index,
+                    balance: new_balance,
+                })
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
 /// A Vec-wrapper which will grow to match any request.
 ///
 /// E.g., a `get` or `insert` to an out-of-bounds element will cause the Vec to grow (using
@@ -66,6 +106,13 @@ pub struct ProtoArrayForkChoice {
 }
 
 impl ProtoArrayForkChoice {
+    /// Instantiate `Self` with the finalized block as the sole node in the tree.
+    ///
+    /// The `prune_threshold` is the minimum number of nodes which must precede the finalized
+    /// block before `Self::maybe_prune` will remove them (see `DEFAULT_PRUNE_THRESHOLD`). A very
+    /// low threshold keeps the tree small, at the cost of pruning (and therefore re-indexing the
+    /// tree) more frequently and using more CPU.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         finalized_block_slot: Slot,
         finalized_block_state_root: Hash256,
@@ -74,9 +121,11 @@ impl ProtoArrayForkChoice {
         finalized_root: Hash256,
         current_epoch_shuffling_id: AttestationShufflingId,
         next_epoch_shuffling_id: AttestationShufflingId,
+        prune_threshold: usize,
     ) -> Result<Self, String> {
         let mut proto_array = ProtoArray {
-            prune_threshold: DEFAULT_PRUNE_THRESHOLD,
+            prune_threshold,
+            prune_retain_slots: 0,
             justified_epoch,
             finalized_epoch,
             nodes: Vec::with_capacity(1),
@@ -134,12 +183,28 @@ impl ProtoArrayForkChoice {
             .map_err(|e| format!("process_block_error: {:?}", e))
     }
 
+    /// Mark the block with `block_root`, and all of its descendants, as invalid (e.g., because
+    /// the execution layer has rejected its payload) so that none of them may become the head.
+    ///
+    /// Takes effect from the next call to `Self::find_head`.
+    pub fn on_invalid_block(&mut self, block_root: Hash256) -> Result<(), String> {
+        self.proto_array
+            .on_invalid_block(block_root)
+            .map_err(|e| format!("on_invalid_block failed: {:?}", e))
+    }
+
+    /// Apply any pending votes and balance changes to the tree and return the head.
+    ///
+    /// If a `proposer_boost` is supplied, its score is temporarily added to the weight of the
+    /// boosted block whilst the head is determined. The boost is removed before this function
+    /// returns, so it only ever affects the result of a single call.
     pub fn find_head(
         &mut self,
         justified_epoch: Epoch,
         justified_root: Hash256,
         finalized_epoch: Epoch,
         justified_state_balances: &[u64],
+        proposer_boost: Option<ProposerBoost>,
     ) -> Result<Hash256, String> {
         let old_balances = &mut self.balances;
 
@@ -153,27 +218,121 @@ impl ProtoArrayForkChoice {
         )
         .map_err(|e| format!("find_head compute_deltas failed: {:?}", e))?;
 
+        *old_balances = new_balances.to_vec();
+
+        self.find_head_with_deltas(
+            deltas,
+            justified_epoch,
+            justified_root,
+            finalized_epoch,
+            proposer_boost,
+        )
+    }
+
+    /// Equivalent to `Self::find_head`, except that the justified balances are provided as a list
+    /// of changes to the balances supplied to the previous call, rather than as a full list.
+    ///
+    /// This avoids copying the full list of balances when they have not changed, which is the
+    /// case for every call except those that follow a change to the justified checkpoint. See
+    /// `compute_balance_changes` for producing `balance_changes`.
+    pub fn find_head_with_balance_changes(
+        &mut self,
+        justified_epoch: Epoch,
+        justified_root: Hash256,
+        finalized_epoch: Epoch,
+        balance_changes: &[BalanceChange],
+        proposer_boost: Option<ProposerBoost>,
+    ) -> Result<Hash256, String> {
+        let deltas = compute_deltas_from_balance_changes(
+            &self.proto_array.indices,
+            &mut self.votes,
+            &mut self.balances,
+            balance_changes,
+        )
+        .map_err(|e| format!("find_head compute_deltas failed: {:?}", e))?;
+
+        self.find_head_with_deltas(
+            deltas,
+            justified_epoch,
+            justified_root,
+            finalized_epoch,
+            proposer_boost,
+        )
+    }
+
+    /// Apply `deltas` (and the `proposer_boost`, if any) to the tree and return the head.
+    fn find_head_with_deltas(
+        &mut self,
+        mut deltas: Vec<i64>,
+        justified_epoch: Epoch,
+        justified_root: Hash256,
+        finalized_epoch: Epoch,
+        proposer_boost: Option<ProposerBoost>,
+    ) -> Result<Hash256, String> {
+        // We ignore the boost if the block is not known to fork choice.
+        let proposer_boost = proposer_boost.and_then(|boost| {
+            self.proto_array
+                .indices
+                .get(&boost.root)
+                .map(|index| (*index, boost.score))
+        });
+
+        if let Some((boost_index, boost_score)) = proposer_boost {
+            let delta = deltas
+                .get_mut(boost_index)
+                .ok_or_else(|| format!("find_head invalid boost index: {}", boost_index))?;
+            *delta = delta
+                .checked_add(boost_score as i64)
+                .ok_or_else(|| format!("find_head boost overflow: {}", boost_index))?;
+        }
+
         self.proto_array
             .apply_score_changes(deltas, justified_epoch, finalized_epoch)
             .map_err(|e| format!("find_head apply_score_changes failed: {:?}", e))?;
 
-        *old_balances = new_balances.to_vec();
-
-        self.proto_array
+        let head = self
+            .proto_array
             .find_head(&justified_root)
-            .map_err(|e| format!("find_head failed: {:?}", e))
+            .map_err(|e| format!("find_head failed: {:?}", e));
+
+        if let Some((boost_index, boost_score)) = proposer_boost {
+            self.proto_array
+                .remove_weight(boost_index, boost_score)
+                .map_err(|e| format!("find_head remove_weight failed: {:?}", e))?;
+        }
+
+        head
     }
 
     pub fn maybe_prune(&mut self, finalized_root: Hash256) -> Result<(), String> {
         self.proto_array
             .maybe_prune(finalized_root)
-            .map_err(|e| format!("find_head maybe_prune failed: {:?}", e))
+            .map_err(|e| format!("find_head maybe_prune failed: {:?}", e))?;
+
+        debug_assert_eq!(self.check_invariants(), Ok(()));
+
+        Ok(())
     }
 
+    /// Verifies the internal consistency of the tree. See `ProtoArray::check_invariants`.
+    pub fn check_invariants(&self) -> Result<(), String> {
+        self.proto_array
+            .check_invariants()
+            .map_err(|e| format!("check_invariants failed: {:?}", e))
+    }
+
+    /// Sets the minimum number of nodes which must precede the finalized block before pruning
+    /// occurs. See `Self::new` for the tradeoffs involved.
     pub fn set_prune_threshold(&mut self, prune_threshold: usize) {
         self.proto_array.prune_threshold = prune_threshold;
     }
 
+    /// Sets the number of slots before the finalized slot for which nodes are retained when
+    /// pruning. See `ProtoArray::prune_retain_slots`.
+    pub fn set_prune_retain_slots(&mut self, prune_retain_slots: u64) {
+        self.proto_array.prune_retain_slots = prune_retain_slots;
+    }
+
     pub fn len(&self) -> usize {
         self.proto_array.nodes.len()
     }
@@ -207,6 +366,51 @@ impl ProtoArrayForkChoice {
         })
     }
 
+    /// Returns the root and weight of every block which has no children. See
+    /// `ProtoArray::leaf_nodes`.
+    pub fn leaf_nodes(&self) -> Vec<(Hash256, u64)> {
+        self.proto_array.leaf_nodes()
+    }
+
+    /// Returns the weight attributed directly to `block_root`, excluding the weight of any of its
+    /// descendants. Returns `None` if the block is unknown.
+    ///
+    /// ## Notes
+    ///
+    /// Weights are only updated during `Self::find_head`, so this reflects the votes and balances
+    /// supplied to the most recent call of that function.
+    pub fn block_weight(&self, block_root: &Hash256) -> Option<u64> {
+        let block_index = *self.proto_array.indices.get(block_root)?;
+        let block = self.proto_array.nodes.get(block_index)?;
+
+        // Children always have a higher index than their parent.
+        let children_weight = self
+            .proto_array
+            .nodes
+            .iter()
+            .skip(block_index + 1)
+            .filter(|node| node.parent == Some(block_index))
+            .fold(0_u64, |total, child| total.saturating_add(child.weight));
+
+        Some(block.weight.saturating_sub(children_weight))
+    }
+
+    /// Returns the total weight of `block_root` and all of its descendants. This is the value
+    /// which is compared when choosing between competing children during `Self::find_head`.
+    /// Returns `None` if the block is unknown.
+    ///
+    /// ## Notes
+    ///
+    /// Weights are only updated during `Self::find_head`, so this reflects the votes and balances
+    /// supplied to the most recent call of that function.
+    pub fn subtree_weight(&self, block_root: &Hash256) -> Option<u64> {
+        let block_index = self.proto_array.indices.get(block_root)?;
+        self.proto_array
+            .nodes
+            .get(*block_index)
+            .map(|block| block.weight)
+    }
+
     /// Returns `true` if the `descendant_root` has an ancestor with `ancestor_root`. Always
     /// returns `false` if either input roots are unknown.
     ///
@@ -228,6 +432,70 @@ impl ProtoArrayForkChoice {
             .unwrap_or(false)
     }
 
+    /// Returns the root and slot of the most recent block which is an ancestor of both `a_root`
+    /// and `b_root`. Returns `None` if either input root is unknown or if the two chains do not
+    /// share an ancestor in the DAG (e.g., one of them does not descend from the finalized block).
+    ///
+    /// ## Notes
+    ///
+    /// A block is considered to be an ancestor of itself, so `Some(a_root)` is returned if `b_root`
+    /// descends from `a_root`.
+    pub fn find_common_ancestor(
+        &self,
+        a_root: Hash256,
+        b_root: Hash256,
+    ) -> Option<(Hash256, Slot)> {
+        let mut a_iter = self.proto_array.iter_block_roots(&a_root).peekable();
+        let mut b_iter = self.proto_array.iter_block_roots(&b_root).peekable();
+
+        loop {
+            let (a_root, a_slot) = *a_iter.peek()?;
+            let (b_root, b_slot) = *b_iter.peek()?;
+
+            if a_root == b_root {
+                return Some((a_root, a_slot));
+            }
+
+            // Always step back along the chain with the higher slot, so that both iterators
+            // converge upon the same block if there is one.
+            if a_slot >= b_slot {
+                a_iter.next();
+            } else {
+                b_iter.next();
+            }
+        }
+    }
+
+    /// Returns the slot and root of `head` and each of its ancestors, in order from `head` back
+    /// to the oldest block in the tree.
+    ///
+    /// ## Notes
+    ///
+    /// The oldest block in the tree is the finalized block as of the most recent prune (or the
+    /// block supplied to `Self::new`). Since pruning only occurs periodically, the result may
+    /// include some blocks prior to the current finalized block.
+    ///
+    /// ## Errors
+    ///
+    /// Returns an error if `head` is unknown.
+    pub fn chain_to_finalized(&self, head: Hash256) -> Result<Vec<(Slot, Hash256)>, String> {
+        if !self.contains_block(&head) {
+            return Err(format!("chain_to_finalized unknown head: {:?}", head));
+        }
+
+        Ok(self
+            .proto_array
+            .iter_block_roots(&head)
+            .map(|(root, slot)| (slot, root))
+            .collect())
+    }
+
+    /// Returns the balances which were supplied to the most recent call to `Self::find_head` (or
+    /// `Self::find_head_with_balance_changes`).
+    pub fn balances(&self) -> &[u64] {
+        &self.balances
+    }
+
     pub fn latest_message(&self, validator_index: usize) -> Option<(Hash256, Epoch)> {
         if validator_index < self.votes.0.len() {
             let vote = &self.votes.0[validator_index];
@@ -252,6 +520,40 @@ impl ProtoArrayForkChoice {
             .map_err(|e| format!("Failed to decode ProtoArrayForkChoice: {:?}", e))
     }
 
+    /// Decode bytes which were produced by `Self::as_bytes` prior to the addition of the invalid
+    /// block flag (i.e., database schema v3 and earlier). No block is considered invalid.
+    pub fn from_legacy_bytes(bytes: &[u8]) -> Result<Self, String> {
+        LegacySszContainer::from_ssz_bytes(bytes)
+            .map(SszContainer::from)
+            .map(Into::into)
+            .map_err(|e| format!("Failed to decode legacy ProtoArrayForkChoice: {:?}", e))
+    }
+
+    /// Returns the SSZ encoding of the changes to `self` since it held `base_node_count` nodes
+    /// (as per `Self::len`).
+    ///
+    /// This is smaller than `Self::as_bytes` since the nodes which existed at that time are only
+    /// partially encoded. The result may be applied to a copy of `self` from that time using
+    /// `Self::apply_diff_bytes`, as long as `self` has not been pruned in the meantime.
+    pub fn diff_bytes_since(&self, base_node_count: usize) -> Vec<u8> {
+        SszContainerDiff::new(self, base_node_count).as_ssz_bytes()
+    }
+
+    /// Applies some bytes generated by `Self::diff_bytes_since` to `self`.
+    ///
+    /// ## Errors
+    ///
+    /// - The bytes cannot be decoded.
+    /// - `self` does not match the `base_node_count` of the diff.
+    /// - The tree was pruned between `self` and the diff.
+    ///
+    /// `self` is not modified if an error is returned.
+    pub fn apply_diff_bytes(&mut self, bytes: &[u8]) -> Result<(), String> {
+        SszContainerDiff::from_ssz_bytes(bytes)
+            .map_err(|e| format!("Failed to decode ProtoArrayForkChoice diff: {:?}", e))?
+            .apply(self)
+    }
+
     /// Returns a read-lock to core `ProtoArray` struct.
     ///
     /// Should only be used when encoding/decoding during troubleshooting.
@@ -276,6 +578,79 @@ fn compute_deltas(
     old_balances: &[u64],
     new_balances: &[u64],
 ) -> Result<Vec<i64>, Error> {
+    compute_deltas_with_balances(
+        indices,
+        votes,
+        // If the validator was not included in the _old_ balances (i.e., it did not exist yet)
+        // then say its balance was zero.
+        |val_index| old_balances.get(val_index).copied().unwrap_or(0),
+        // If the validators vote is not known in the _new_ balances, then use a balance of zero.
+        //
+        // It is possible that there is a vote for an unknown validator if we change our justified
+        // state to a new state with a higher epoch that is on a different fork because that fork may have
+        // on-boarded less validators than the prior fork.
+        |val_index| new_balances.get(val_index).copied().unwrap_or(0),
+    )
+}
+
+/// Equivalent to `compute_deltas`, except that the new balances are formed by applying
+/// `balance_changes` to `balances`. Upon success, `balances` is updated to hold the new balances.
+///
+/// ## Errors
+///
+/// As per `compute_deltas`.
+fn compute_deltas_from_balance_changes(
+    indices: &HashMap<Hash256, usize>,
+    votes: &mut ElasticList<VoteTracker>,
+    balances: &mut Vec<u64>,
+    balance_changes: &[BalanceChange],
+) -> Result<Vec<i64>, Error> {
+    // Apply the changes whilst retaining the prior balance of each changed validator.
+    let mut changed_old_balances = HashMap::with_capacity(balance_changes.len());
+    for change in balance_changes {
+        if balances.len() <= change.validator_index {
+            balances.resize(change.validator_index + 1, 0);
+        }
+        let old_balance = std::mem::replace(&mut balances[change.validator_index], change.balance);
+        changed_old_balances
+            .entry(change.validator_index)
+            .or_insert(old_balance);
+    }
+
+    let new_balances: &[u64] = balances;
+
+    compute_deltas_with_balances(
+        indices,
+        votes,
+        |val_index| {
+            changed_old_balances
+                .get(&val_index)
+                .copied()
+                .unwrap_or_else(|| new_balances.get(val_index).copied().unwrap_or(0))
+        },
+        |val_index| new_balances.get(val_index).copied().unwrap_or(0),
+    )
+}
+
+/// Returns a list of `deltas`, where there is one delta for each of the indices in
+/// `0..indices.len()`.
+///
+/// The deltas are formed by a change between the balances returned by `old_balance` and
+/// `new_balance`, and/or a change of vote in `votes`.
+///
+/// ## Errors
+///
+/// As per `compute_deltas`.
+fn compute_deltas_with_balances<F, G>(
+    indices: &HashMap<Hash256, usize>,
+    votes: &mut ElasticList<VoteTracker>,
+    old_balance: F,
+    new_balance: G,
+) -> Result<Vec<i64>, Error>
+where
+    F: Fn(usize) -> u64,
+    G: Fn(usize) -> u64,
+{
     let mut deltas = vec![0_i64; indices.len()];
 
     for (val_index, vote) in votes.iter_mut().enumerate() {
@@ -285,16 +660,8 @@ fn compute_deltas(
             continue;
         }
 
-        // If the validator was not included in the _old_ balances (i.e., it did not exist yet)
-        // then say its balance was zero.
-        let old_balance = old_balances.get(val_index).copied().unwrap_or(0);
-
-        // If the validators vote is not known in the _new_ balances, then use a balance of zero.
-        //
-        // It is possible that there is a vote for an unknown validator if we change our justified
-        // state to a new state with a higher epoch that is on a different fork because that fork may have
-        // on-boarded less validators than the prior fork.
-        let new_balance = new_balances.get(val_index).copied().unwrap_or(0);
+        let old_balance = old_balance(val_index);
+        let new_balance = new_balance(val_index);
 
         if vote.current_root != vote.next_root || old_balance != new_balance {
             // We ignore the vote if it is not known in `indices`. We assume that it is outside
@@ -339,6 +706,43 @@ mod test_compute_deltas {
         Hash256::from_low_u64_be(i as u64 + 1)
     }
 
+    fn junk_shuffling_id() -> AttestationShufflingId {
+        AttestationShufflingId::from_components(Epoch::new(0), Hash256::zero())
+    }
+
+    /// Returns a `ProtoArrayForkChoice` containing only the finalized block, `hash_from_index(0)`,
+    /// at slot zero.
+    fn fork_choice_fixture(prune_threshold: usize) -> ProtoArrayForkChoice {
+        let genesis_epoch = Epoch::new(0);
+
+        ProtoArrayForkChoice::new(
+            Slot::new(0),
+            Hash256::zero(),
+            genesis_epoch,
+            genesis_epoch,
+            hash_from_index(0),
+            junk_shuffling_id(),
+            junk_shuffling_id(),
+            prune_threshold,
+        )
+        .unwrap()
+    }
+
+    /// Returns a block which may be added to the fork choice returned by `fork_choice_fixture`.
+    fn block_fixture(slot: u64, root: Hash256, parent_root: Option<Hash256>) -> Block {
+        Block {
+            slot: Slot::new(slot),
+            root,
+            parent_root,
+            state_root: Hash256::zero(),
+            target_root: hash_from_index(0),
+            current_epoch_shuffling_id: junk_shuffling_id(),
+            next_epoch_shuffling_id: junk_shuffling_id(),
+            justified_epoch: Epoch::new(0),
+            finalized_epoch: Epoch::new(0),
+        }
+    }
+
     #[test]
     fn finalized_descendant() {
         let genesis_slot = Slot::new(0);
@@ -360,6 +764,7 @@ mod test_compute_deltas {
             finalized_root,
             junk_shuffling_id.clone(),
             junk_shuffling_id.clone(),
+            DEFAULT_PRUNE_THRESHOLD,
         )
         .unwrap();
 
@@ -414,6 +819,731 @@ mod test_compute_deltas {
         assert!(!fc.is_descendant(not_finalized_desc, unknown));
     }
 
+    #[test]
+    fn common_ancestor() {
+        let finalized_root = hash_from_index(0);
+        let fork_root = hash_from_index(1);
+        let short_tip = hash_from_index(2);
+        let long_middle = hash_from_index(3);
+        let long_tip = hash_from_index(4);
+        let orphan = hash_from_index(5);
+        let unknown = hash_from_index(6);
+
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        // Build the following tree (slots in brackets):
+        //
+        //           finalized (0)
+        //                |
+        //              fork (1)         orphan (2)
+        //             /      \
+        //   short_tip (2)   long_middle (3)
+        //                        |
+        //                   long_tip (5)
+        let blocks = vec![
+            (1, fork_root, Some(finalized_root)),
+            (2, short_tip, Some(fork_root)),
+            (3, long_middle, Some(fork_root)),
+            (5, long_tip, Some(long_middle)),
+            (2, orphan, None),
+        ];
+
+        for (slot, root, parent_root) in blocks {
+            fc.proto_array
+                .on_block(block_fixture(slot, root, parent_root))
+                .unwrap();
+        }
+
+        let fork = Some((fork_root, Slot::new(1)));
+
+        assert_eq!(fc.find_common_ancestor(short_tip, long_tip), fork);
+        assert_eq!(fc.find_common_ancestor(long_tip, short_tip), fork);
+        assert_eq!(fc.find_common_ancestor(short_tip, long_middle), fork);
+        assert_eq!(
+            fc.find_common_ancestor(long_middle, long_tip),
+            Some((long_middle, Slot::new(3)))
+        );
+        assert_eq!(
+            fc.find_common_ancestor(long_tip, long_tip),
+            Some((long_tip, Slot::new(5)))
+        );
+        assert_eq!(
+            fc.find_common_ancestor(finalized_root, long_tip),
+            Some((finalized_root, Slot::new(0)))
+        );
+
+        assert_eq!(fc.find_common_ancestor(orphan, short_tip), None);
+        assert_eq!(fc.find_common_ancestor(unknown, short_tip), None);
+        assert_eq!(fc.find_common_ancestor(short_tip, unknown), None);
+    }
+
+    #[test]
+    fn chain_to_finalized() {
+        let finalized_root = hash_from_index(0);
+        let fork_root = hash_from_index(1);
+        let short_tip = hash_from_index(2);
+        let long_middle = hash_from_index(3);
+        let long_tip = hash_from_index(4);
+        let orphan = hash_from_index(5);
+        let unknown = hash_from_index(6);
+
+        let mut fc = fork_choice_fixture(0);
+
+        // Build the following tree (slots in brackets):
+        //
+        //           finalized (0)
+        //                |
+        //              fork (1)         orphan (2)
+        //             /      \
+        //   short_tip (2)   long_middle (3)
+        //                        |
+        //                   long_tip (5)
+        let blocks = vec![
+            (1, fork_root, Some(finalized_root)),
+            (2, short_tip, Some(fork_root)),
+            (3, long_middle, Some(fork_root)),
+            (5, long_tip, Some(long_middle)),
+            (2, orphan, None),
+        ];
+
+        for (slot, root, parent_root) in blocks {
+            fc.proto_array
+                .on_block(block_fixture(slot, root, parent_root))
+                .unwrap();
+        }
+
+        assert_eq!(
+            fc.chain_to_finalized(long_tip),
+            Ok(vec![
+                (Slot::new(5), long_tip),
+                (Slot::new(3), long_middle),
+                (Slot::new(1), fork_root),
+                (Slot::new(0), finalized_root),
+            ])
+        );
+        assert_eq!(
+            fc.chain_to_finalized(short_tip),
+            Ok(vec![
+                (Slot::new(2), short_tip),
+                (Slot::new(1), fork_root),
+                (Slot::new(0), finalized_root),
+            ])
+        );
+        assert_eq!(
+            fc.chain_to_finalized(finalized_root),
+            Ok(vec![(Slot::new(0), finalized_root)])
+        );
+        assert_eq!(
+            fc.chain_to_finalized(orphan),
+            Ok(vec![(Slot::new(2), orphan)])
+        );
+        assert!(fc.chain_to_finalized(unknown).is_err());
+
+        // After pruning, the chain ends at the new finalized block.
+        fc.maybe_prune(fork_root).unwrap();
+        assert_eq!(
+            fc.chain_to_finalized(long_tip),
+            Ok(vec![
+                (Slot::new(5), long_tip),
+                (Slot::new(3), long_middle),
+                (Slot::new(1), fork_root),
+            ])
+        );
+        assert!(fc.chain_to_finalized(finalized_root).is_err());
+    }
+
+    #[test]
+    fn block_and_subtree_weights() {
+        let genesis_epoch = Epoch::new(0);
+
+        let finalized_root = hash_from_index(0);
+        let parent_root = hash_from_index(1);
+        let left_root = hash_from_index(2);
+        let right_root = hash_from_index(3);
+        let unknown = hash_from_index(4);
+
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        // Build the following tree:
+        //
+        //     finalized
+        //         |
+        //       parent
+        //       /    \
+        //    left    right
+        let blocks = vec![
+            (1, parent_root, finalized_root),
+            (2, left_root, parent_root),
+            (2, right_root, parent_root),
+        ];
+
+        for (slot, root, parent_root) in blocks {
+            fc.process_block(block_fixture(slot, root, Some(parent_root)))
+                .unwrap();
+        }
+
+        // Apply deltas in the same order as the nodes: finalized, parent, left, right.
+        fc.proto_array
+            .apply_score_changes(vec![0, 5, 10, 3], genesis_epoch, genesis_epoch)
+            .unwrap();
+
+        assert_eq!(fc.block_weight(&finalized_root), Some(0));
+        assert_eq!(fc.block_weight(&parent_root), Some(5));
+        assert_eq!(fc.block_weight(&left_root), Some(10));
+        assert_eq!(fc.block_weight(&right_root), Some(3));
+        assert_eq!(fc.block_weight(&unknown), None);
+
+        assert_eq!(fc.subtree_weight(&finalized_root), Some(18));
+        assert_eq!(fc.subtree_weight(&parent_root), Some(18));
+        assert_eq!(fc.subtree_weight(&left_root), Some(10));
+        assert_eq!(fc.subtree_weight(&right_root), Some(3));
+        assert_eq!(fc.subtree_weight(&unknown), None);
+    }
+
+    #[test]
+    fn best_child_changes() {
+        let genesis_epoch = Epoch::new(0);
+
+        let finalized_root = hash_from_index(0);
+        let parent_root = hash_from_index(1);
+        let left_root = hash_from_index(2);
+        let right_root = hash_from_index(3);
+
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        // Build the following tree:
+        //
+        //     finalized
+        //         |
+        //       parent
+        //       /    \
+        //    left    right
+        let blocks = vec![
+            (1, parent_root, finalized_root),
+            (2, left_root, parent_root),
+            (2, right_root, parent_root),
+        ];
+
+        for (slot, root, parent_root) in blocks {
+            fc.process_block(block_fixture(slot, root, Some(parent_root)))
+                .unwrap();
+        }
+
+        let best_child = |fc: &ProtoArrayForkChoice, root: &Hash256| {
+            let node = &fc.proto_array.nodes[fc.proto_array.indices[root]];
+            node.best_child.map(|i| fc.proto_array.nodes[i].root)
+        };
+
+        // Both children have equal weight when imported, so the tie is broken by root.
+        assert_eq!(best_child(&fc, &parent_root), Some(right_root));
+        assert_eq!(best_child(&fc, &finalized_root), Some(parent_root));
+
+        // Apply deltas in the same order as the nodes: finalized, parent, left, right.
+        //
+        // Giving the left block more weight flips the best-child of the parent, but not of the
+        // finalized block.
+        let changes = fc
+            .proto_array
+            .apply_score_changes(vec![0, 0, 10, 0], genesis_epoch, genesis_epoch)
+            .unwrap();
+        assert_eq!(changes, vec![parent_root]);
+        assert_eq!(best_child(&fc, &parent_root), Some(left_root));
+        assert_eq!(best_child(&fc, &finalized_root), Some(parent_root));
+
+        // Applying no change leaves every best-child as it was.
+        let changes = fc
+            .proto_array
+            .apply_score_changes(vec![0; 4], genesis_epoch, genesis_epoch)
+            .unwrap();
+        assert!(changes.is_empty());
+
+        // Shifting the weight back to the right block flips the best-child of the parent again.
+        let changes = fc
+            .proto_array
+            .apply_score_changes(vec![0, 0, -10, 15], genesis_epoch, genesis_epoch)
+            .unwrap();
+        assert_eq!(changes, vec![parent_root]);
+        assert_eq!(best_child(&fc, &parent_root), Some(right_root));
+        assert_eq!(best_child(&fc, &finalized_root), Some(parent_root));
+    }
+
+    #[test]
+    fn leaf_nodes() {
+        let genesis_epoch = Epoch::new(0);
+
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        assert_eq!(fc.leaf_nodes(), vec![(hash_from_index(0), 0)]);
+
+        // Build the following tree:
+        //
+        //         0
+        //        / \
+        //       1   2
+        //      / \   \
+        //     3   4   5
+        //             |
+        //             6
+        let blocks = vec![(1, 0), (2, 0), (3, 1), (4, 1), (5, 2), (6, 5)];
+
+        for (index, parent_index) in blocks {
+            fc.process_block(block_fixture(
+                index as u64,
+                hash_from_index(index),
+                Some(hash_from_index(parent_index)),
+            ))
+            .unwrap();
+        }
+
+        fc.proto_array
+            .apply_score_changes(vec![0, 0, 0, 1, 2, 0, 3], genesis_epoch, genesis_epoch)
+            .unwrap();
+
+        let mut leaves = fc.leaf_nodes();
+        leaves.sort();
+
+        let mut expected = vec![
+            (hash_from_index(3), 1),
+            (hash_from_index(4), 2),
+            (hash_from_index(6), 3),
+        ];
+        expected.sort();
+
+        assert_eq!(leaves, expected);
+    }
+
+    #[test]
+    fn balance_changes_match_full_balances() {
+        let genesis_epoch = Epoch::new(0);
+
+        let new_fork_choice = || {
+            let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+            // Build two competing branches:
+            //
+            //      0
+            //     / \
+            //    1   2
+            //    |
+            //    3
+            for (index, parent_index) in vec![(1, 0), (2, 0), (3, 1)] {
+                fc.process_block(block_fixture(
+                    index as u64,
+                    hash_from_index(index),
+                    Some(hash_from_index(parent_index)),
+                ))
+                .unwrap();
+            }
+
+            fc
+        };
+
+        let mut full = new_fork_choice();
+        let mut sparse = new_fork_choice();
+
+        // Each step provides a list of votes and the justified balances to use.
+        let steps: Vec<(Vec<(usize, usize)>, Vec<u64>)> = vec![
+            // Validators 0 and 1 vote for 3, validator 2 votes for 2.
+            (vec![(0, 3), (1, 3), (2, 2)], vec![1, 1, 1]),
+            // Validator 2 becomes heavy enough to move the head to 2.
+            (vec![], vec![1, 1, 3]),
+            // A new validator appears and votes for 3.
+            (vec![(3, 3)], vec![1, 1, 3, 2]),
+            // Validator 1 moves its vote to 2 and the new validator disappears.
+            (vec![(1, 2)], vec![1, 1, 3]),
+            // All balances change.
+            (vec![], vec![4, 0, 2, 0]),
+        ];
+
+        let mut old_balances = vec![];
+        for (i, (votes, balances)) in steps.into_iter().enumerate() {
+            let target_epoch = Epoch::new(i as u64 + 1);
+            for (validator_index, block_index) in votes {
+                for fc in &mut [&mut full, &mut sparse] {
+                    fc.process_attestation(
+                        validator_index,
+                        hash_from_index(block_index),
+                        target_epoch,
+                    )
+                    .unwrap();
+                }
+            }
+
+            let full_head = full
+                .find_head(
+                    genesis_epoch,
+                    hash_from_index(0),
+                    genesis_epoch,
+                    &balances,
+                    None,
+                )
+                .unwrap();
+            let sparse_head = sparse
+                .find_head_with_balance_changes(
+                    genesis_epoch,
+                    hash_from_index(0),
+                    genesis_epoch,
+                    &compute_balance_changes(&old_balances, &balances),
+                    None,
+                )
+                .unwrap();
+
+            assert_eq!(full_head, sparse_head, "heads should match at step {}", i);
+            for block_index in 0..4 {
+                let root = hash_from_index(block_index);
+                assert_eq!(
+                    full.subtree_weight(&root),
+                    sparse.subtree_weight(&root),
+                    "weights should match at step {}",
+                    i
+                );
+            }
+
+            old_balances = balances;
+        }
+    }
+
+    #[test]
+    fn compute_balance_changes_handles_length_changes() {
+        assert_eq!(compute_balance_changes(&[], &[]), vec![]);
+        assert_eq!(compute_balance_changes(&[1, 2], &[1, 2]), vec![]);
+        assert_eq!(
+            compute_balance_changes(&[1, 2], &[1, 3, 4]),
+            vec![
+                BalanceChange {
+                    validator_index: 1,
+                    balance: 3
+                },
+                BalanceChange {
+                    validator_index: 2,
+                    balance: 4
+                }
+            ]
+        );
+        assert_eq!(
+            compute_balance_changes(&[1, 2, 0], &[1]),
+            vec![BalanceChange {
+                validator_index: 1,
+                balance: 0
+            }]
+        );
+    }
+
+    #[test]
+    fn zero_prune_threshold() {
+        let finalized_root = hash_from_index(0);
+
+        let mut fc = fork_choice_fixture(0);
+
+        // Build a chain of blocks descending from the finalized block.
+        let mut parent_root = finalized_root;
+        for i in 1..4 {
+            let root = hash_from_index(i);
+            fc.process_block(block_fixture(i as u64, root, Some(parent_root)))
+                .unwrap();
+            parent_root = root;
+        }
+
+        assert_eq!(fc.len(), 4);
+
+        // A single finalization should prune all prior nodes.
+        let new_finalized_root = hash_from_index(2);
+        fc.maybe_prune(new_finalized_root).unwrap();
+
+        assert_eq!(fc.len(), 2);
+        assert!(!fc.contains_block(&finalized_root));
+        assert!(fc.contains_block(&new_finalized_root));
+        assert!(fc.contains_block(&parent_root));
+    }
+
+    #[test]
+    fn prune_retain_slots() {
+        let genesis_epoch = Epoch::new(0);
+        let block = |root: usize, parent: usize, slot: u64| {
+            block_fixture(slot, hash_from_index(root), Some(hash_from_index(parent)))
+        };
+
+        let mut fc = fork_choice_fixture(0);
+
+        // Build the following tree, where block 4 is a sibling of block 2 and is inserted before
+        // block 3.
+        //
+        // slot:  0   1   2   3
+        //
+        //        0 - 1 - 2 - 3
+        //             \
+        //              4
+        for &(root, parent, slot) in &[(1, 0, 1), (2, 1, 2), (4, 1, 2), (3, 2, 3)] {
+            fc.process_block(block(root, parent, slot)).unwrap();
+        }
+        fc.proto_array
+            .apply_score_changes(vec![1; fc.len()], genesis_epoch, genesis_epoch)
+            .unwrap();
+        let finalized_root = hash_from_index(3);
+
+        // By default, all nodes before the finalized node are pruned.
+        let mut aggressive = ProtoArrayForkChoice::from_bytes(&fc.as_bytes()).unwrap();
+        aggressive.maybe_prune(finalized_root).unwrap();
+        assert_eq!(aggressive.check_invariants(), Ok(()));
+        assert_eq!(aggressive.len(), 1);
+        assert!(aggressive.contains_block(&finalized_root));
+
+        // With a grace window of two slots, the nodes at slot 2 are retained.
+        fc.set_prune_retain_slots(2);
+        fc.maybe_prune(finalized_root).unwrap();
+        assert_eq!(fc.check_invariants(), Ok(()));
+        assert_eq!(fc.len(), 3);
+        for &root in &[2, 4, 3] {
+            assert!(fc.contains_block(&hash_from_index(root)));
+        }
+        for &root in &[0, 1] {
+            assert!(!fc.contains_block(&hash_from_index(root)));
+        }
+        assert_eq!(
+            fc.chain_to_finalized(finalized_root),
+            Ok(vec![
+                (Slot::new(3), hash_from_index(3)),
+                (Slot::new(2), hash_from_index(2)),
+            ]),
+            "the finalized block should keep its retained parent"
+        );
+        assert_eq!(
+            fc.get_block(&hash_from_index(4)).unwrap().parent_root,
+            None,
+            "the retained sibling should lose its pruned parent"
+        );
+
+        // Retained nodes are pruned once they fall outside the window.
+        fc.process_block(block(5, 3, 4)).unwrap();
+        fc.maybe_prune(hash_from_index(5)).unwrap();
+        assert_eq!(fc.check_invariants(), Ok(()));
+        assert_eq!(fc.len(), 2);
+        assert!(fc.contains_block(&finalized_root));
+        assert!(fc.contains_block(&hash_from_index(5)));
+    }
+
+    #[test]
+    fn check_invariants_detects_corruption() {
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        // Build a chain of blocks descending from the finalized block.
+        for i in 1..4 {
+            fc.process_block(block_fixture(
+                i as u64,
+                hash_from_index(i),
+                Some(hash_from_index(i - 1)),
+            ))
+            .unwrap();
+        }
+
+        assert_eq!(fc.proto_array.check_invariants(), Ok(()));
+
+        let corrupt = |mutate: fn(&mut ProtoArray)| {
+            let mut corrupted = fc.proto_array.clone();
+            mutate(&mut corrupted);
+            corrupted.check_invariants()
+        };
+
+        assert_eq!(
+            corrupt(|pa| pa.nodes[2].parent = Some(2)),
+            Err(Error::NodeIsOwnParent(2))
+        );
+        assert_eq!(
+            corrupt(|pa| pa.nodes[2].parent = Some(3)),
+            Err(Error::InvalidParentIndex(3))
+        );
+        assert_eq!(
+            corrupt(|pa| pa.nodes[2].parent = Some(42)),
+            Err(Error::InvalidParentIndex(42))
+        );
+        assert_eq!(
+            corrupt(|pa| pa.nodes[1].best_child = Some(42)),
+            Err(Error::InvalidBestChildIndex(42))
+        );
+        assert_eq!(
+            corrupt(|pa| pa.nodes[0].best_child = Some(2)),
+            Err(Error::InvalidBestChildIndex(2))
+        );
+        assert_eq!(
+            corrupt(|pa| pa.nodes[0].best_descendant = Some(4)),
+            Err(Error::InvalidBestDescendant(4))
+        );
+        assert_eq!(
+            corrupt(|pa| {
+                pa.indices.insert(hash_from_index(3), 0);
+            }),
+            Err(Error::NodeIndexMismatch {
+                root: hash_from_index(3),
+                node_index: 3,
+                indices_entry: Some(0),
+            })
+        );
+        assert_eq!(
+            corrupt(|pa| {
+                pa.indices.remove(&hash_from_index(0));
+            }),
+            Err(Error::InvalidIndicesLen {
+                indices: 3,
+                nodes: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn check_invariants_after_random_blocks_and_prunes() {
+        let genesis_epoch = Epoch::new(0);
+
+        // A deterministic xorshift generator, so that failures are reproducible.
+        let mut seed = 0x2545_f491_4f6c_dd1d_u64;
+        let mut random = move |bound: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % bound as u64) as usize
+        };
+
+        for _ in 0..32 {
+            let mut fc = fork_choice_fixture(random(8));
+            fc.set_prune_retain_slots(random(4) as u64);
+            let mut next_index = 1;
+
+            for _ in 0..128 {
+                match random(8) {
+                    // Prune at some random block.
+                    0 => {
+                        let finalized = &fc.proto_array.nodes[random(fc.len())];
+                        let finalized_root = finalized.root;
+                        fc.maybe_prune(finalized_root).unwrap();
+                        assert!(fc.contains_block(&finalized_root));
+                    }
+                    // Apply some random (positive) score changes.
+                    1 => {
+                        let deltas = (0..fc.len()).map(|_| random(10) as i64).collect();
+                        fc.proto_array
+                            .apply_score_changes(deltas, genesis_epoch, genesis_epoch)
+                            .unwrap();
+                    }
+                    // Add a block to some random parent.
+                    _ => {
+                        let parent = &fc.proto_array.nodes[random(fc.len())];
+                        let slot = parent.slot.as_u64() + 1 + random(3) as u64;
+                        let parent_root = parent.root;
+                        fc.process_block(block_fixture(
+                            slot,
+                            hash_from_index(next_index),
+                            Some(parent_root),
+                        ))
+                        .unwrap();
+                        next_index += 1;
+                    }
+                }
+
+                assert_eq!(fc.proto_array.check_invariants(), Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn diff_bytes_round_trip() {
+        let genesis_epoch = Epoch::new(0);
+        let mut fc = fork_choice_fixture(DEFAULT_PRUNE_THRESHOLD);
+
+        let base_bytes = fc.as_bytes();
+        let mut diffs = vec![];
+        let mut node_count = fc.len();
+
+        // Each step adds some blocks (as `(index, parent_index)`) and some votes (as
+        // `(validator_index, block_index)`) before running fork choice and taking a diff.
+        let steps: Vec<(Vec<(usize, usize)>, Vec<(usize, usize)>)> = vec![
+            (vec![(1, 0), (2, 0)], vec![(0, 1), (1, 2)]),
+            (vec![(3, 1)], vec![(0, 3), (2, 3)]),
+            (vec![], vec![(1, 3)]),
+            (vec![(4, 2), (5, 4)], vec![(0, 5), (1, 5), (2, 5)]),
+        ];
+
+        for (i, (blocks, votes)) in steps.into_iter().enumerate() {
+            for (index, parent_index) in blocks {
+                fc.process_block(block_fixture(
+                    index as u64,
+                    hash_from_index(index),
+                    Some(hash_from_index(parent_index)),
+                ))
+                .unwrap();
+            }
+
+            for (validator_index, block_index) in votes {
+                fc.process_attestation(
+                    validator_index,
+                    hash_from_index(block_index),
+                    Epoch::new(i as u64 + 1),
+                )
+                .unwrap();
+            }
+
+            fc.find_head(
+                genesis_epoch,
+                hash_from_index(0),
+                genesis_epoch,
+                &[1, 2, 3],
+                None,
+            )
+            .unwrap();
+
+            diffs.push(fc.diff_bytes_since(node_count));
+            node_count = fc.len();
+        }
+
+        let mut replayed = ProtoArrayForkChoice::from_bytes(&base_bytes).unwrap();
+        for diff in &diffs {
+            replayed.apply_diff_bytes(diff).unwrap();
+        }
+
+        assert!(
+            replayed == ProtoArrayForkChoice::from_bytes(&fc.as_bytes()).unwrap(),
+            "base plus diffs should match a full snapshot"
+        );
+
+        // Diffs must be applied in order.
+        let mut replayed = ProtoArrayForkChoice::from_bytes(&base_bytes).unwrap();
+        assert!(replayed.apply_diff_bytes(&diffs[1]).is_err());
+    }
+
+    #[test]
+    fn diff_bytes_rejects_prune() {
+        let mut fc = fork_choice_fixture(0);
+
+        for index in 1..4 {
+            fc.process_block(block_fixture(
+                index as u64,
+                hash_from_index(index),
+                Some(hash_from_index(index - 1)),
+            ))
+            .unwrap();
+        }
+
+        let base_bytes = fc.as_bytes();
+        let node_count = fc.len();
+
+        // Prune a single node and then add a block, so the tree has the same length as the base.
+        fc.maybe_prune(hash_from_index(1)).unwrap();
+        fc.process_block(block_fixture(
+            4,
+            hash_from_index(4),
+            Some(hash_from_index(3)),
+        ))
+        .unwrap();
+        assert_eq!(fc.len(), node_count);
+
+        let mut replayed = ProtoArrayForkChoice::from_bytes(&base_bytes).unwrap();
+        assert!(replayed
+            .apply_diff_bytes(&fc.diff_bytes_since(node_count))
+            .is_err());
+        assert!(
+            replayed == ProtoArrayForkChoice::from_bytes(&base_bytes).unwrap(),
+            "a failed diff should not modify the base"
+        );
+    }
+
     #[test]
     fn zero_hash() {
         let validator_count: usize = 16;
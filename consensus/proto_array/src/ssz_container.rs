@@ -4,7 +4,7 @@ use crate::{
 };
 use ssz_derive::{Decode, Encode};
 use std::collections::HashMap;
-use types::{Epoch, Hash256};
+use types::{AttestationShufflingId, Epoch, Hash256, Slot};
 
 #[derive(Encode, Decode)]
 pub struct SszContainer {
@@ -37,6 +37,8 @@ impl From<SszContainer> for ProtoArrayForkChoice {
     fn from(from: SszContainer) -> Self {
         let proto_array = ProtoArray {
             prune_threshold: from.prune_threshold,
+            // Not persisted, the caller must set it again from its config after decoding.
+            prune_retain_slots: 0,
             justified_epoch: from.justified_epoch,
             finalized_epoch: from.finalized_epoch,
             nodes: from.nodes,
@@ -50,3 +52,180 @@ impl From<SszContainer> for ProtoArrayForkChoice {
         }
     }
 }
+
+/// The encoding of a `ProtoNode` prior to the addition of `ProtoNode::invalid`.
+#[derive(Encode, Decode)]
+struct LegacyProtoNode {
+    slot: Slot,
+    state_root: Hash256,
+    target_root: Hash256,
+    current_epoch_shuffling_id: AttestationShufflingId,
+    next_epoch_shuffling_id: AttestationShufflingId,
+    root: Hash256,
+    parent: Option<usize>,
+    justified_epoch: Epoch,
+    finalized_epoch: Epoch,
+    weight: u64,
+    best_child: Option<usize>,
+    best_descendant: Option<usize>,
+}
+
+impl From<LegacyProtoNode> for ProtoNode {
+    fn from(from: LegacyProtoNode) -> Self {
+        Self {
+            slot: from.slot,
+            state_root: from.state_root,
+            target_root: from.target_root,
+            current_epoch_shuffling_id: from.current_epoch_shuffling_id,
+            next_epoch_shuffling_id: from.next_epoch_shuffling_id,
+            root: from.root,
+            parent: from.parent,
+            justified_epoch: from.justified_epoch,
+            finalized_epoch: from.finalized_epoch,
+            weight: from.weight,
+            best_child: from.best_child,
+            best_descendant: from.best_descendant,
+            invalid: false,
+        }
+    }
+}
+
+/// The encoding of an `SszContainer` prior to the addition of `ProtoNode::invalid`.
+///
+/// Only used to migrate an existing database, see `ProtoArrayForkChoice::from_legacy_bytes`.
+#[derive(Encode, Decode)]
+pub struct LegacySszContainer {
+    votes: Vec<VoteTracker>,
+    balances: Vec<u64>,
+    prune_threshold: usize,
+    justified_epoch: Epoch,
+    finalized_epoch: Epoch,
+    nodes: Vec<LegacyProtoNode>,
+    indices: Vec<(Hash256, usize)>,
+}
+
+impl From<LegacySszContainer> for SszContainer {
+    fn from(from: LegacySszContainer) -> Self {
+        Self {
+            votes: from.votes,
+            balances: from.balances,
+            prune_threshold: from.prune_threshold,
+            justified_epoch: from.justified_epoch,
+            finalized_epoch: from.finalized_epoch,
+            nodes: from.nodes.into_iter().map(Into::into).collect(),
+            indices: from.indices,
+        }
+    }
+}
+
+/// The fields of a `ProtoNode` which may change after it has been added to the tree.
+#[derive(Encode, Decode)]
+struct SszNodeUpdate {
+    weight: u64,
+    best_child: Option<usize>,
+    best_descendant: Option<usize>,
+    invalid: bool,
+}
+
+/// The changes to a `ProtoArrayForkChoice` since it contained `base_node_count` nodes.
+///
+/// Nodes which existed at that time are represented by only their mutable fields, whilst nodes
+/// which have since been added are included in full. The votes and balances are always included
+/// in full.
+#[derive(Encode, Decode)]
+pub struct SszContainerDiff {
+    /// The root of the first node in the tree, used to detect a prune between the base and this
+    /// diff.
+    anchor_root: Hash256,
+    base_node_count: usize,
+    votes: Vec<VoteTracker>,
+    balances: Vec<u64>,
+    justified_epoch: Epoch,
+    finalized_epoch: Epoch,
+    node_updates: Vec<SszNodeUpdate>,
+    new_nodes: Vec<ProtoNode>,
+}
+
+impl SszContainerDiff {
+    pub fn new(from: &ProtoArrayForkChoice, base_node_count: usize) -> Self {
+        let proto_array = &from.proto_array;
+        let split = std::cmp::min(base_node_count, proto_array.nodes.len());
+        let (old_nodes, new_nodes) = proto_array.nodes.split_at(split);
+
+        Self {
+            anchor_root: proto_array
+                .nodes
+                .first()
+                .map(|node| node.root)
+                .unwrap_or_else(Hash256::zero),
+            base_node_count,
+            votes: from.votes.0.clone(),
+            balances: from.balances.clone(),
+            justified_epoch: proto_array.justified_epoch,
+            finalized_epoch: proto_array.finalized_epoch,
+            node_updates: old_nodes
+                .iter()
+                .map(|node| SszNodeUpdate {
+                    weight: node.weight,
+                    best_child: node.best_child,
+                    best_descendant: node.best_descendant,
+                    invalid: node.invalid,
+                })
+                .collect(),
+            new_nodes: new_nodes.to_vec(),
+        }
+    }
+
+    /// Apply `self` to `to`, which must be in the state from which `self` was computed (i.e., it
+    /// must hold `self.base_node_count` nodes and must not have been pruned since).
+    ///
+    /// `to` is not modified if an error is returned.
+    pub fn apply(self, to: &mut ProtoArrayForkChoice) -> Result<(), String> {
+        let proto_array = &mut to.proto_array;
+
+        if self.node_updates.len() != self.base_node_count {
+            return Err(format!(
+                "Diff was computed after a prune, {} of {} base nodes remain",
+                self.node_updates.len(),
+                self.base_node_count
+            ));
+        }
+
+        if proto_array.nodes.len() != self.base_node_count {
+            return Err(format!(
+                "Diff expects {} nodes, but proto_array has {}",
+                self.base_node_count,
+                proto_array.nodes.len()
+            ));
+        }
+
+        let anchor_root = proto_array.nodes.first().map(|node| node.root);
+        if anchor_root != Some(self.anchor_root) {
+            return Err(format!(
+                "Diff expects anchor root {:?}, but proto_array has {:?}",
+                self.anchor_root, anchor_root
+            ));
+        }
+
+        for (node, update) in proto_array.nodes.iter_mut().zip(self.node_updates) {
+            node.weight = update.weight;
+            node.best_child = update.best_child;
+            node.best_descendant = update.best_descendant;
+            node.invalid = update.invalid;
+        }
+
+        for node in self.new_nodes {
+            proto_array
+                .indices
+                .insert(node.root, proto_array.nodes.len());
+            proto_array.nodes.push(node);
+        }
+
+        proto_array.justified_epoch = self.justified_epoch;
+        proto_array.finalized_epoch = self.finalized_epoch;
+        to.votes = ElasticList(self.votes);
+        to.balances = self.balances;
+
+        Ok(())
+    }
+}
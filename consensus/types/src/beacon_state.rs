@@ -589,10 +589,31 @@ impl<T: EthSpec> BeaconState<T> {
     /// `state.current_epoch() == 1`, then `vec[0]` refers to slot `32` and `vec[1]` refers to slot
     /// `33`. It will always be the case that `vec.len() == SLOTS_PER_EPOCH`.
     pub fn get_beacon_proposer_indices(&self, spec: &ChainSpec) -> Result<Vec<usize>, Error> {
+        self.get_proposer_indices_for_epoch(self.current_epoch(), spec)
+    }
+
+    /// Returns the beacon proposer index for each slot in `epoch`, which must be either the
+    /// current or the next epoch.
+    ///
+    /// The proposers are derived from the seed and active validator set of `epoch`, both of which
+    /// are already known for the next epoch. However, the proposers for the next epoch are only a
+    /// prediction since they also depend on the effective balances of validators, which may change
+    /// at the epoch transition.
+    ///
+    /// As with `Self::get_beacon_proposer_indices`, `vec[i]` refers to the `i`th slot of `epoch`.
+    pub fn get_proposer_indices_for_epoch(
+        &self,
+        epoch: Epoch,
+        spec: &ChainSpec,
+    ) -> Result<Vec<usize>, Error> {
+        if epoch < self.current_epoch() || epoch > self.next_epoch()? {
+            return Err(Error::EpochOutOfBounds);
+        }
+
         // Not using the cached validator indices since they are shuffled.
-        let indices = self.get_active_validator_indices(self.current_epoch(), spec)?;
+        let indices = self.get_active_validator_indices(epoch, spec)?;
 
-        self.current_epoch()
+        epoch
             .slot_iter(T::slots_per_epoch())
             .map(|slot| {
                 let seed = self.get_beacon_proposer_seed(slot, spec)?;
@@ -70,6 +70,104 @@ fn beacon_proposer_index() {
     test_beacon_proposer_index::<MinimalEthSpec>();
 }
 
+fn test_beacon_proposer_indices<T: EthSpec>(validator_count: usize) {
+    let spec = T::default_spec();
+    let builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, &spec);
+    let (mut state, _keypairs) = builder.build();
+    state
+        .build_committee_cache(RelativeEpoch::Current, &spec)
+        .unwrap();
+
+    // Give the first proposer zero balance so that at least one slot skips a candidate.
+    let first_proposer = state
+        .get_beacon_proposer_index(Slot::new(0), &spec)
+        .unwrap();
+    state.validators[first_proposer].effective_balance = 0;
+
+    let indices = state.get_beacon_proposer_indices(&spec).unwrap();
+    assert_eq!(indices.len(), T::slots_per_epoch() as usize);
+
+    for (slot, index) in state
+        .current_epoch()
+        .slot_iter(T::slots_per_epoch())
+        .zip(indices)
+    {
+        assert_eq!(
+            state.get_beacon_proposer_index(slot, &spec),
+            Ok(index),
+            "batch and per-slot proposer should match at slot {}",
+            slot
+        );
+    }
+}
+
+#[test]
+fn beacon_proposer_indices() {
+    test_beacon_proposer_indices::<MinimalEthSpec>(MinimalEthSpec::slots_per_epoch() as usize);
+    test_beacon_proposer_indices::<MinimalEthSpec>(
+        (MinimalEthSpec::slots_per_epoch() as usize).mul(4),
+    );
+}
+
+fn test_proposer_indices_for_epoch<T: EthSpec>(validator_count: usize) {
+    let spec = T::default_spec();
+    let builder: TestingBeaconStateBuilder<T> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(validator_count, &spec);
+    let (mut state, _keypairs) = builder.build();
+    // Move away from genesis so that the previous epoch is distinct from the current epoch.
+    state.slot = T::slots_per_epoch().mul(2).into();
+
+    let current_epoch = state.current_epoch();
+    let indices = state
+        .get_proposer_indices_for_epoch(current_epoch, &spec)
+        .unwrap();
+    assert_eq!(indices, state.get_beacon_proposer_indices(&spec).unwrap());
+
+    for (slot, index) in current_epoch.slot_iter(T::slots_per_epoch()).zip(indices) {
+        assert_eq!(
+            state.get_beacon_proposer_index(slot, &spec),
+            Ok(index),
+            "epoch and per-slot proposer should match at slot {}",
+            slot
+        );
+    }
+
+    // The next epoch uses a different seed for every slot.
+    let next_epoch = state.next_epoch().unwrap();
+    let next_indices = state
+        .get_proposer_indices_for_epoch(next_epoch, &spec)
+        .unwrap();
+    assert_eq!(next_indices.len(), T::slots_per_epoch() as usize);
+    let active_validators = state
+        .get_active_validator_indices(next_epoch, &spec)
+        .unwrap();
+    for (slot, index) in next_epoch.slot_iter(T::slots_per_epoch()).zip(next_indices) {
+        let seed = state.get_beacon_proposer_seed(slot, &spec).unwrap();
+        assert_eq!(
+            state.compute_proposer_index(&active_validators, &seed, &spec),
+            Ok(index),
+            "next epoch proposer should match at slot {}",
+            slot
+        );
+    }
+
+    for epoch in &[state.previous_epoch(), next_epoch + 1] {
+        assert_eq!(
+            state.get_proposer_indices_for_epoch(*epoch, &spec),
+            Err(BeaconStateError::EpochOutOfBounds)
+        );
+    }
+}
+
+#[test]
+fn proposer_indices_for_epoch() {
+    test_proposer_indices_for_epoch::<MinimalEthSpec>(MinimalEthSpec::slots_per_epoch() as usize);
+    test_proposer_indices_for_epoch::<MinimalEthSpec>(
+        (MinimalEthSpec::slots_per_epoch() as usize).mul(4),
+    );
+}
+
 /// Test that
 ///
 /// 1. Using the cache before it's built fails.
@@ -256,6 +354,101 @@ fn tree_hash_cache() {
     assert_eq!(root.as_bytes(), &state.tree_hash_root()[..]);
 }
 
+#[test]
+fn canonical_root_is_tree_hash_root() {
+    use crate::test_utils::{SeedableRng, TestRandom, XorShiftRng};
+    use eth2_hashing::hash;
+    use tree_hash::TreeHash;
+
+    let mut rng = XorShiftRng::from_seed([42; 16]);
+    let mut state: FoundationBeaconState = BeaconState::random_for_test(&mut rng);
+
+    let root = state.canonical_root();
+
+    assert_eq!(root.as_bytes(), &state.tree_hash_root()[..]);
+    assert_eq!(root, state.update_tree_hash_cache().unwrap());
+    assert_ne!(
+        root,
+        Hash256::from_slice(&hash(&state.as_ssz_bytes())),
+        "canonical root must be the merkle root, not a hash of the SSZ bytes"
+    );
+}
+
+#[test]
+fn early_slot_bounds() {
+    type E = MinimalEthSpec;
+    let spec = E::default_spec();
+    let builder: TestingBeaconStateBuilder<E> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(8, &spec);
+    let (mut state, _keypairs) = builder.build();
+
+    for slot in 0..E::slots_per_epoch() * 2 {
+        state.slot = Slot::new(slot);
+
+        assert!(state.previous_epoch() <= state.current_epoch());
+        if slot < E::slots_per_epoch() {
+            assert_eq!(state.previous_epoch(), E::genesis_epoch());
+        }
+
+        // The current slot is never a valid historical root, even at genesis.
+        assert_eq!(
+            state.get_block_root(state.slot),
+            Err(BeaconStateError::SlotOutOfBounds)
+        );
+        assert_eq!(
+            state.get_state_root(state.slot),
+            Err(BeaconStateError::SlotOutOfBounds)
+        );
+
+        if slot > 0 {
+            assert!(state.get_block_root(state.slot - 1).is_ok());
+            assert!(state.get_state_root(state.slot - 1).is_ok());
+        }
+    }
+}
+
+#[test]
+fn block_roots_with_skip_slots() {
+    type E = MinimalEthSpec;
+    let spec = E::default_spec();
+    let builder: TestingBeaconStateBuilder<E> =
+        TestingBeaconStateBuilder::from_deterministic_keypairs(8, &spec);
+    let (mut state, _keypairs) = builder.build();
+
+    let window = E::slots_per_historical_root() as u64;
+    let end = window + window / 2;
+    let is_skipped = |slot: u64| slot % 3 == 2;
+
+    // Mimic `per_slot_processing`, which repeats the previous block root on a skipped slot.
+    let mut expected = vec![];
+    let mut root = Hash256::zero();
+    for slot in 0..end {
+        if !is_skipped(slot) {
+            root = Hash256::from_low_u64_be(slot + 1);
+        }
+        state.slot = Slot::new(slot + 1);
+        state.set_block_root(Slot::new(slot), root).unwrap();
+        expected.push(root);
+    }
+
+    for slot in 0..end + 1 {
+        let result = state.get_block_root(Slot::new(slot));
+        if slot < end - window || slot >= end {
+            assert_eq!(
+                result,
+                Err(BeaconStateError::SlotOutOfBounds),
+                "slot {}",
+                slot
+            );
+        } else {
+            assert_eq!(result, Ok(&expected[slot as usize]), "slot {}", slot);
+            if is_skipped(slot) && slot > end - window {
+                assert_eq!(result, state.get_block_root(Slot::new(slot - 1)));
+            }
+        }
+    }
+}
+
 /// Tests committee-specific components
 #[cfg(test)]
 mod committees {
@@ -10,6 +10,7 @@ use std::path::PathBuf;
 use std::process::{Command, Output};
 use std::str::{from_utf8, FromStr};
 use std::string::ToString;
+use std::time::Duration;
 use tempfile::TempDir;
 use types::{Checkpoint, Epoch, Hash256};
 
@@ -176,6 +177,20 @@ fn max_skip_slots_flag() {
         .run()
         .with_config(|config| assert_eq!(config.chain.import_max_skip_slots, Some(10)));
 }
+#[test]
+fn maximum_gossip_clock_disparity_flag() {
+    CommandLineTest::new()
+        .flag("maximum-gossip-clock-disparity", Some("1500"))
+        .run()
+        .with_config(|config| assert_eq!(config.chain.maximum_gossip_clock_disparity_millis, 1500));
+}
+#[test]
+fn fork_choice_prune_retain_slots_flag() {
+    CommandLineTest::new()
+        .flag("fork-choice-prune-retain-slots", Some("64"))
+        .run()
+        .with_config(|config| assert_eq!(config.chain.fork_choice_prune_retain_slots, 64));
+}
 
 #[test]
 fn freezer_dir_flag() {
@@ -308,6 +323,61 @@ fn network_import_all_attestations_flag() {
         .with_config(|config| assert!(config.network.import_all_attestations));
 }
 #[test]
+fn network_allow_private_addresses_flag() {
+    CommandLineTest::new()
+        .flag("allow-private-addresses", None)
+        .run()
+        .with_config(|config| assert!(config.network.allow_private_addresses));
+}
+#[test]
+fn beacon_processor_attestation_queue_len_flag() {
+    CommandLineTest::new()
+        .flag("beacon-processor-attestation-queue-len", Some("32768"))
+        .run()
+        .with_config(|config| {
+            assert_eq!(
+                config.network.beacon_processor_attestation_queue_len,
+                Some(32768)
+            )
+        });
+}
+#[test]
+fn beacon_processor_aggregate_queue_len_flag() {
+    CommandLineTest::new()
+        .flag("beacon-processor-aggregate-queue-len", Some("2048"))
+        .run()
+        .with_config(|config| {
+            assert_eq!(
+                config.network.beacon_processor_aggregate_queue_len,
+                Some(2048)
+            )
+        });
+}
+#[test]
+fn beacon_processor_attestations_per_peer_flag() {
+    CommandLineTest::new()
+        .flag("beacon-processor-attestations-per-peer", Some("128"))
+        .run()
+        .with_config(|config| {
+            assert_eq!(
+                config.network.beacon_processor_attestations_per_peer,
+                Some(128)
+            )
+        });
+}
+#[test]
+fn beacon_processor_rate_limit_period_flag() {
+    CommandLineTest::new()
+        .flag("beacon-processor-rate-limit-period", Some("6"))
+        .run()
+        .with_config(|config| {
+            assert_eq!(
+                config.network.beacon_processor_rate_limit_period,
+                Some(Duration::from_secs(6))
+            )
+        });
+}
+#[test]
 fn network_listen_address_flag() {
     let addr = "127.0.0.2".parse::<Ipv4Addr>().unwrap();
     CommandLineTest::new()